@@ -0,0 +1,165 @@
+//! Throughput and scheduling overhead of `TargetScanner`, to compare against when the
+//! scheduling loop, rate limiting or concurrency control change.
+//!
+//! Run with `cargo bench --bench scheduler`.
+
+use async_trait::async_trait;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use riphttp::scanner::recorder::{RecorderConfig, default_recorder_config};
+use riphttp::scanner::scanner::{ScanOptions, TargetScanner};
+use riphttp::scanner::task::Task;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+const TARGETS: usize = 2_000;
+
+/// Answers every target after its latency, drawn up front so every iteration sees the same
+/// distribution. Results are empty, so scans without a recorder print nothing; a recorder still
+/// commits every target in order.
+struct MockTask {
+    latencies: HashMap<String, Duration>,
+}
+
+impl MockTask {
+    /// Latencies spread uniformly over `min..=max` microseconds.
+    fn uniform(targets: &[String], min: u64, max: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(7);
+        let latencies = targets
+            .iter()
+            .map(|target| {
+                let micros = rng.random_range(min..=max);
+                (target.clone(), Duration::from_micros(micros))
+            })
+            .collect();
+        Self { latencies }
+    }
+
+    /// Makes the target at `index` take `latency` instead.
+    fn slow(mut self, targets: &[String], index: usize, latency: Duration) -> Self {
+        self.latencies.insert(targets[index].clone(), latency);
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for MockTask {
+    type Error = String;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        match self.latencies.get(&target) {
+            Some(latency) if !latency.is_zero() => tokio::time::sleep(*latency).await,
+            _ => tokio::task::yield_now().await,
+        }
+        Ok(String::new())
+    }
+}
+
+fn targets() -> Vec<String> {
+    (0..TARGETS).map(|index| format!("t{}", index)).collect()
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// A recorder writing to a scratch directory, so the contiguous commit is part of the run.
+fn recorder(dir: &Path) -> RecorderConfig {
+    RecorderConfig {
+        checkpoint_path: dir.join("checkpoint"),
+        ..default_recorder_config(
+            dir.join("output.txt"),
+            "targets.txt",
+            "bench",
+            0,
+            TARGETS,
+            true,
+        )
+    }
+}
+
+fn scan(
+    runtime: &Runtime,
+    concurrency: usize,
+    task: &Arc<MockTask>,
+    recorder: Option<RecorderConfig>,
+) {
+    let options = ScanOptions {
+        recorder,
+        quiet: true,
+        ..ScanOptions::default()
+    };
+    runtime
+        .block_on(TargetScanner::new(concurrency).scan_with_options(
+            targets(),
+            Arc::clone(task),
+            options,
+        ))
+        .unwrap();
+}
+
+/// Instant tasks: what the loop itself costs per target.
+fn scheduling_overhead(c: &mut Criterion) {
+    let runtime = runtime();
+    let task = Arc::new(MockTask::uniform(&targets(), 0, 0));
+    let mut group = c.benchmark_group("overhead");
+    group.throughput(Throughput::Elements(TARGETS as u64));
+    for concurrency in [1, 16, 128, 512] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            &concurrency,
+            |b, &concurrency| b.iter(|| scan(&runtime, concurrency, &task, None)),
+        );
+    }
+    group.finish();
+}
+
+/// Targets taking 0.5 to 2ms: throughput as concurrency grows.
+fn throughput(c: &mut Criterion) {
+    let runtime = runtime();
+    let task = Arc::new(MockTask::uniform(&targets(), 500, 2_000));
+    let mut group = c.benchmark_group("throughput");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TARGETS as u64));
+    for concurrency in [16, 128, 512] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            &concurrency,
+            |b, &concurrency| b.iter(|| scan(&runtime, concurrency, &task, None)),
+        );
+    }
+    group.finish();
+}
+
+/// One 200ms target at the head of the list. Without a recorder the others stream past it; with
+/// one, every later result waits in memory until it is committed in order.
+fn long_tail(c: &mut Criterion) {
+    let runtime = runtime();
+    let list = targets();
+    let task =
+        Arc::new(MockTask::uniform(&list, 500, 2_000).slow(&list, 0, Duration::from_millis(200)));
+    let dir = std::env::temp_dir().join(format!("riphttp-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut group = c.benchmark_group("long_tail");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TARGETS as u64));
+    group.bench_function("no_recorder", |b| {
+        b.iter(|| scan(&runtime, 128, &task, None))
+    });
+    group.bench_function("recorder", |b| {
+        b.iter(|| scan(&runtime, 128, &task, Some(recorder(&dir))))
+    });
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, scheduling_overhead, throughput, long_tail);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+use riphttplib::H1;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request, Response};
+
+/// UDP port of the HTTP/3 service advertised in an `Alt-Svc` value such as
+/// `h3=":443"; ma=86400, h3-29=":8443"`.
+///
+/// The final `h3` wins over drafts like `h3-29`, whatever their order. Alternatives on another
+/// host are skipped, since probes keep connecting to the target itself. Parameters (`ma=`,
+/// `persist=`) are ignored, and `clear` advertises nothing.
+pub fn extract_alt_svc_port(value: &str) -> Option<u16> {
+    let mut draft = None;
+    for entry in value.split(',') {
+        let alternative = entry.split(';').next().unwrap_or_default().trim();
+        let Some((protocol, authority)) = alternative.split_once('=') else {
+            continue;
+        };
+        let protocol = protocol.trim().to_ascii_lowercase();
+        let is_final = protocol == "h3";
+        if !is_final && !protocol.starts_with("h3-") {
+            continue;
+        }
+
+        let authority = authority.trim();
+        let authority = authority
+            .strip_prefix('"')
+            .and_then(|authority| authority.strip_suffix('"'))
+            .unwrap_or(authority);
+        let Some((host, port)) = authority.rsplit_once(':') else {
+            continue;
+        };
+        let Ok(port) = port.parse::<u16>() else {
+            continue;
+        };
+        if !host.is_empty() || port == 0 {
+            continue;
+        }
+
+        if is_final {
+            return Some(port);
+        }
+        draft.get_or_insert(port);
+    }
+    draft
+}
+
+/// Every `Alt-Svc` header of `response`, joined as a single list.
+pub fn alt_svc_header(response: &Response) -> Option<String> {
+    let values: Vec<&str> = response
+        .headers
+        .iter()
+        .filter(|header| header.name.eq_ignore_ascii_case("alt-svc"))
+        .filter_map(|header| header.value.as_deref())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+/// Sends `request` over HTTP/1.1 (or TLS, per its scheme) and returns the `Alt-Svc` header it
+/// was answered with.
+pub async fn fetch_alt_svc_header(
+    request: Request,
+    timeouts: &ClientTimeouts,
+) -> Result<Option<String>, ProtocolError> {
+    let response = H1::timeouts(timeouts.clone()).send_request(request).await?;
+    Ok(alt_svc_header(&response))
+}
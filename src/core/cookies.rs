@@ -0,0 +1,150 @@
+use riphttplib::types::Response;
+use url::Url;
+
+#[derive(Debug, Clone)]
+struct Cookie {
+    name: String,
+    value: String,
+    /// Cookies are host-only: they go back to exactly this host, not its subdomains.
+    host: String,
+    path: String,
+    secure: bool,
+}
+
+impl Cookie {
+    fn matches(&self, url: &Url) -> bool {
+        if self.secure && url.scheme() != "https" {
+            return false;
+        }
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        host == self.host && path_matches(url.path(), &self.path)
+    }
+}
+
+/// In-memory cookie store for one client invocation, following the basics of RFC 6265: cookies
+/// are scoped to the host that set them and to their path. The Domain attribute and expiry
+/// dates are not interpreted, except that `Max-Age=0` deletes a cookie.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a `name=value` cookie sent on every request to `url`'s host.
+    pub fn seed(&mut self, url: &Url, cookie: &str) -> Result<(), String> {
+        let (name, value) = cookie
+            .split_once('=')
+            .filter(|(name, _)| !name.trim().is_empty())
+            .ok_or_else(|| format!("invalid cookie '{}', expected name=value", cookie))?;
+        self.insert(Cookie {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+            host: url.host_str().unwrap_or_default().to_ascii_lowercase(),
+            path: "/".to_string(),
+            secure: false,
+        });
+        Ok(())
+    }
+
+    /// Stores every `Set-Cookie` of a response to `url`.
+    pub fn store(&mut self, url: &Url, response: &Response) {
+        for header in &response.headers {
+            if !header.name.eq_ignore_ascii_case("set-cookie") {
+                continue;
+            }
+            if let Some(value) = &header.value {
+                self.store_header(url, value);
+            }
+        }
+    }
+
+    /// The `Cookie` header value for a request to `url`, if any cookie applies.
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let mut matching: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(url))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Longer paths first, as RFC 6265 recommends.
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        Some(
+            matching
+                .iter()
+                .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    fn store_header(&mut self, url: &Url, set_cookie: &str) {
+        let mut parts = set_cookie.split(';');
+        let Some((name, value)) = parts.next().and_then(|pair| pair.split_once('=')) else {
+            return;
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            host: url.host_str().unwrap_or_default().to_ascii_lowercase(),
+            path: default_path(url.path()),
+            secure: false,
+        };
+        let mut expired = false;
+        for attribute in parts {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            match key.trim().to_ascii_lowercase().as_str() {
+                "path" if value.trim().starts_with('/') => cookie.path = value.trim().to_string(),
+                "secure" => cookie.secure = true,
+                "max-age" => expired = value.trim().parse::<i64>().is_ok_and(|age| age <= 0),
+                _ => {}
+            }
+        }
+
+        if expired {
+            self.cookies.retain(|existing| {
+                !(existing.name == cookie.name
+                    && existing.host == cookie.host
+                    && existing.path == cookie.path)
+            });
+        } else {
+            self.insert(cookie);
+        }
+    }
+
+    fn insert(&mut self, cookie: Cookie) {
+        match self.cookies.iter_mut().find(|existing| {
+            existing.name == cookie.name
+                && existing.host == cookie.host
+                && existing.path == cookie.path
+        }) {
+            Some(existing) => *existing = cookie,
+            None => self.cookies.push(cookie),
+        }
+    }
+}
+
+/// The directory of the request path, RFC 6265 section 5.1.4.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => path[..index].to_string(),
+    }
+}
+
+/// RFC 6265 section 5.1.4 path-match.
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
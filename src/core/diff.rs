@@ -0,0 +1,198 @@
+use clap::ValueEnum;
+use regex::Regex;
+use riphttplib::types::Response;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Volatile fragments stripped from bodies before they are compared.
+pub const DEFAULT_STRIP_PATTERNS: [&str; 5] = [
+    // HTTP dates, e.g. "Tue, 15 Nov 1994 08:12:31 GMT"
+    r"(Mon|Tue|Wed|Thu|Fri|Sat|Sun), \d{2} [A-Z][a-z]{2} \d{4} \d{2}:\d{2}:\d{2} GMT",
+    // ISO-8601 timestamps
+    r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?",
+    // Unix timestamps in seconds or milliseconds
+    r"\b1\d{9}(\d{3})?\b",
+    // Request ids, CSRF tokens and other long hex strings
+    r"\b[0-9a-fA-F]{16,}\b",
+    // CSP nonces
+    r#"nonce="[^"]*""#,
+];
+
+/// Which parts of a follow-up response are compared against the baseline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiffMode {
+    /// Compare status codes only
+    #[default]
+    Status,
+    /// Compare normalized bodies only
+    Body,
+    /// Report when either the status or the normalized body differs
+    Both,
+}
+
+/// A `--report-change` rule: a follow-up counts as a difference only when the baseline status
+/// and the follow-up status fall in these ranges. Written `FROM:TO`, where each side is a
+/// status (`200`), a range (`500-599`), a class (`5xx`) or `*`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusChange {
+    pub from: RangeInclusive<u16>,
+    pub to: RangeInclusive<u16>,
+}
+
+impl StatusChange {
+    pub fn matches(&self, baseline: u16, current: u16) -> bool {
+        self.from.contains(&baseline) && self.to.contains(&current)
+    }
+}
+
+impl FromStr for StatusChange {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let (from, to) = rule
+            .split_once(':')
+            .ok_or_else(|| format!("expected FROM:TO, e.g. 200:500-599, got '{}'", rule))?;
+        Ok(Self {
+            from: parse_status_range(from)?,
+            to: parse_status_range(to)?,
+        })
+    }
+}
+
+impl fmt::Display for StatusChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |range: &RangeInclusive<u16>| match (range.start(), range.end()) {
+            (100, 999) => "*".to_string(),
+            (low, high) if low == high => low.to_string(),
+            (low, high) => format!("{}-{}", low, high),
+        };
+        write!(f, "{}:{}", side(&self.from), side(&self.to))
+    }
+}
+
+fn parse_status_range(side: &str) -> Result<RangeInclusive<u16>, String> {
+    let side = side.trim();
+    let status = |text: &str| {
+        text.trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|status| (100..=999).contains(status))
+            .ok_or_else(|| format!("invalid status '{}'", text))
+    };
+    if side == "*" {
+        return Ok(100..=999);
+    }
+    if let Some(class) = side.strip_suffix("xx").or_else(|| side.strip_suffix("XX")) {
+        let first = status(&format!("{}00", class))?;
+        return Ok(first..=first + 99);
+    }
+    match side.split_once('-') {
+        Some((low, high)) => {
+            let (low, high) = (status(low)?, status(high)?);
+            if low > high {
+                return Err(format!("empty status range '{}'", side));
+            }
+            Ok(low..=high)
+        }
+        None => status(side).map(|status| status..=status),
+    }
+}
+
+/// Whether `needle` shows up in the headers or body of `response`.
+pub fn reflects(response: &Response, needle: &str) -> bool {
+    String::from_utf8_lossy(&response.body).contains(needle)
+        || response.headers.iter().any(|header| {
+            header
+                .value
+                .as_deref()
+                .is_some_and(|value| value.contains(needle))
+        })
+}
+
+/// A line of the answer to a raw payload that shows the back end split it: a second status
+/// line, or the smuggled `method` request reflected back, as TRACE does. Only what the client
+/// read as the first response's body can be searched.
+pub fn split_response_line(body: &[u8], method: &str) -> Option<String> {
+    let echo = format!("{} /", method);
+    String::from_utf8_lossy(body)
+        .lines()
+        .map(str::trim)
+        .find(|line| {
+            let status_line = line.starts_with("HTTP/1.")
+                && line
+                    .split_whitespace()
+                    .nth(1)
+                    .is_some_and(|status| status.len() == 3 && status.parse::<u16>().is_ok());
+            status_line || line.starts_with(&echo)
+        })
+        .map(str::to_string)
+}
+
+/// The parts of a response that take part in a diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseSignature {
+    pub status: u16,
+    pub body_len: usize,
+    pub body_hash: u64,
+}
+
+impl ResponseSignature {
+    pub fn summary(&self, mode: DiffMode) -> String {
+        match mode {
+            DiffMode::Status => self.status.to_string(),
+            DiffMode::Body | DiffMode::Both => format!("{}/{}B", self.status, self.body_len),
+        }
+    }
+}
+
+impl DiffMode {
+    pub fn differs(&self, baseline: &ResponseSignature, current: &ResponseSignature) -> bool {
+        let status = baseline.status != current.status;
+        let body = baseline.body_len != current.body_len || baseline.body_hash != current.body_hash;
+        match self {
+            DiffMode::Status => status,
+            DiffMode::Body => body,
+            DiffMode::Both => status || body,
+        }
+    }
+}
+
+/// Strips volatile fragments from bodies so benign differences don't look like a desync.
+#[derive(Clone, Debug, Default)]
+pub struct BodyNormalizer {
+    patterns: Vec<Regex>,
+}
+
+impl BodyNormalizer {
+    /// Builds a normalizer from [`DEFAULT_STRIP_PATTERNS`] plus `extra` patterns.
+    pub fn new(extra: &[String]) -> Result<Self, regex::Error> {
+        let patterns = DEFAULT_STRIP_PATTERNS
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .chain(extra.iter().map(|pattern| Regex::new(pattern)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    pub fn normalize(&self, body: &[u8]) -> String {
+        let mut text = String::from_utf8_lossy(body).into_owned();
+        for pattern in &self.patterns {
+            text = pattern.replace_all(&text, "").into_owned();
+        }
+        text
+    }
+
+    pub fn signature(&self, response: &Response) -> ResponseSignature {
+        let normalized = self.normalize(response.body.as_ref());
+        let mut hasher = DefaultHasher::new();
+        normalized.hash(&mut hasher);
+        ResponseSignature {
+            status: response.status,
+            body_len: normalized.len(),
+            body_hash: hasher.finish(),
+        }
+    }
+}
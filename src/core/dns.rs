@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use url::{Host, Url};
+
+/// Default lifetime of a successful lookup.
+pub const DEFAULT_DNS_CACHE_TTL: Duration = Duration::from_secs(300);
+/// Failed lookups are remembered for at most this long, so a dead domain fails fast across a
+/// port/path fan-out without being written off for the whole scan.
+const NEGATIVE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct CacheEntry {
+    /// `None` when the name didn't resolve.
+    addrs: Option<Vec<IpAddr>>,
+    expires: Instant,
+}
+
+/// What [`DnsCache::resolve`] found for the host of a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// The host is an IP literal, or the target doesn't parse; there is nothing to look up.
+    Literal,
+    /// The first address the host resolved to.
+    Address(IpAddr),
+    /// The host doesn't resolve.
+    Unresolved,
+}
+
+/// Process-wide cache of host lookups, shared by every task of a scan.
+#[derive(Debug)]
+pub struct DnsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    unresolved: AtomicUsize,
+}
+
+impl DnsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            unresolved: AtomicUsize::new(0),
+        }
+    }
+
+    /// Resolves `host`, serving repeated lookups from memory until their TTL expires.
+    pub async fn lookup(&self, host: &str) -> Option<Vec<IpAddr>> {
+        if let Some(entry) = self.entries.lock().unwrap().get(host)
+            && entry.expires > Instant::now()
+        {
+            return entry.addrs.clone();
+        }
+
+        // The port is irrelevant to the lookup itself.
+        let addrs = tokio::net::lookup_host((host, 0))
+            .await
+            .ok()
+            .map(|addrs| addrs.map(|addr: SocketAddr| addr.ip()).collect::<Vec<_>>())
+            .filter(|addrs| !addrs.is_empty());
+
+        let ttl = if addrs.is_some() {
+            self.ttl
+        } else {
+            self.ttl.min(NEGATIVE_TTL)
+        };
+        self.entries.lock().unwrap().insert(
+            host.to_string(),
+            CacheEntry {
+                addrs: addrs.clone(),
+                expires: Instant::now() + ttl,
+            },
+        );
+        addrs
+    }
+
+    /// Looks up the host of `target`. Targets that don't resolve are counted.
+    pub async fn resolve(&self, target: &str) -> Resolution {
+        let Ok(url) = Url::parse(target) else {
+            return Resolution::Literal;
+        };
+        let Some(Host::Domain(domain)) = url.host() else {
+            return Resolution::Literal;
+        };
+        match self.lookup(domain).await {
+            Some(addrs) => Resolution::Address(addrs[0]),
+            None => {
+                self.unresolved.fetch_add(1, Ordering::Relaxed);
+                Resolution::Unresolved
+            }
+        }
+    }
+
+    /// Targets `resolve` found unresolved.
+    pub fn unresolved(&self) -> usize {
+        self.unresolved.load(Ordering::Relaxed)
+    }
+}
+
+/// Parses a curl-style `host:port:ip` override.
+pub fn parse_resolve(value: &str) -> Result<(String, u16, IpAddr), String> {
+    let mut parts = value.splitn(3, ':');
+    let (Some(host), Some(port), Some(ip)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(format!(
+            "invalid resolve entry '{}', expected host:port:ip",
+            value
+        ));
+    };
+    if host.is_empty() {
+        return Err(format!("missing host in resolve entry '{}'", value));
+    }
+    let port: u16 = port
+        .parse()
+        .map_err(|_| format!("invalid port in resolve entry '{}'", value))?;
+    let ip: IpAddr = ip
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| format!("invalid IP address in resolve entry '{}'", value))?;
+
+    Ok((host.to_ascii_lowercase(), port, ip))
+}
+
+/// Parses a curl-style `host:port:connect_host:connect_port` override. The connect host may be
+/// a bracketed IPv6 address.
+pub fn parse_connect_to(value: &str) -> Result<(String, u16, String, u16), String> {
+    let invalid = || {
+        format!(
+            "invalid connect-to entry '{}', expected host:port:connect_host:connect_port",
+            value
+        )
+    };
+    let mut parts = value.splitn(3, ':');
+    let (Some(host), Some(port), Some(connect)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let (connect_host, connect_port) = connect.rsplit_once(':').ok_or_else(invalid)?;
+    if host.is_empty() || connect_host.is_empty() {
+        return Err(format!("missing host in connect-to entry '{}'", value));
+    }
+    let port: u16 = port
+        .parse()
+        .map_err(|_| format!("invalid port in connect-to entry '{}'", value))?;
+    let connect_port: u16 = connect_port
+        .parse()
+        .map_err(|_| format!("invalid connect port in connect-to entry '{}'", value))?;
+
+    Ok((
+        host.to_ascii_lowercase(),
+        port,
+        connect_host.to_ascii_lowercase(),
+        connect_port,
+    ))
+}
+
+/// `--resolve` and `--connect-to` table: connections to a host/port go to a fixed IP instead of
+/// what DNS returns, or to another host and port altogether.
+#[derive(Debug, Clone, Default)]
+pub struct ResolveOverrides {
+    entries: HashMap<(String, u16), IpAddr>,
+    connect_to: HashMap<(String, u16), (String, u16)>,
+}
+
+impl ResolveOverrides {
+    pub fn new(entries: Vec<(String, u16, IpAddr)>) -> Self {
+        Self {
+            entries: entries
+                .into_iter()
+                .map(|(host, port, ip)| ((host, port), ip))
+                .collect(),
+            connect_to: HashMap::new(),
+        }
+    }
+
+    /// Adds `--connect-to` entries, which take precedence over `--resolve` for the same
+    /// host and port.
+    pub fn with_connect_to(mut self, entries: Vec<(String, u16, String, u16)>) -> Self {
+        self.connect_to.extend(entries.into_iter().map(
+            |(host, port, connect_host, connect_port)| ((host, port), (connect_host, connect_port)),
+        ));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.connect_to.is_empty()
+    }
+
+    /// Points `target` at the overriding host and port, or IP. Returns the rewritten target
+    /// together with the original authority, which must be sent as the Host header; `None`
+    /// when nothing matches.
+    ///
+    /// An https target can't be rewritten: riphttplib takes SNI from the URL, so the handshake
+    /// would name the IP or connect host instead of the target's host. That is an error until
+    /// the client can connect somewhere else than the URL says.
+    pub fn apply(&self, target: &str) -> Result<Option<(String, String)>, String> {
+        let Some(mut url) = Url::parse(target).ok() else {
+            return Ok(None);
+        };
+        let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+            return Ok(None);
+        };
+        let host = host.to_ascii_lowercase();
+        let key = (host.clone(), port);
+
+        let authority = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        };
+        if let Some((connect_host, connect_port)) = self.connect_to.get(&key) {
+            if url.scheme() == "https" {
+                return Err(format!(
+                    "--connect-to can't be used with https target {}: riphttplib takes SNI from the URL it connects to, so the TLS handshake would send {} instead of {}",
+                    target, connect_host, authority
+                ));
+            }
+            if url.set_host(Some(connect_host)).is_err()
+                || url.set_port(Some(*connect_port)).is_err()
+            {
+                return Ok(None);
+            }
+        } else {
+            let Some(ip) = self.entries.get(&key) else {
+                return Ok(None);
+            };
+            if url.scheme() == "https" {
+                return Err(format!(
+                    "--resolve can't be used with https target {}: riphttplib takes SNI from the URL it connects to, so the TLS handshake would send {} instead of {}",
+                    target, ip, authority
+                ));
+            }
+            if url.set_ip_host(*ip).is_err() {
+                return Ok(None);
+            }
+        }
+        Ok(Some((url.to_string(), authority)))
+    }
+
+    /// Points an http `target` at `ip`, the way a `--resolve` entry would, so the connection
+    /// goes where an earlier lookup said. Returns the rewritten target and the original
+    /// authority, to be sent as Host; `None` for https targets, whose SNI would name the IP.
+    pub fn pin(target: &str, ip: IpAddr) -> Option<(String, String)> {
+        let mut url = Url::parse(target).ok()?;
+        if url.scheme() != "http" {
+            return None;
+        }
+        let host = url.host_str()?.to_ascii_lowercase();
+        let authority = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        };
+        url.set_ip_host(ip).ok()?;
+        Some((url.to_string(), authority))
+    }
+}
@@ -0,0 +1,105 @@
+use crate::core::finding::render_response;
+use riphttplib::parse_target;
+use riphttplib::types::{ProtocolError, Response};
+use std::fmt::Display;
+
+/// Makes CR and LF visible while keeping one line per header so framing mistakes stand out.
+pub fn render_payload(payload: &str) -> String {
+    payload
+        .replace("\r\n", "\\r\\n\u{0}")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n\n")
+        .replace('\u{0}', "\n")
+}
+
+/// Renders a builder-made request as the HTTP/1.1 message it corresponds to. Bodies with trailers are
+/// shown chunked, since that's the only way HTTP/1.1 can carry trailers. A `host:` entry in
+/// `headers` replaces the Host line derived from `target`.
+pub fn render_request(
+    method: &str,
+    target: &str,
+    headers: &[String],
+    body: Option<&str>,
+    trailers: &[&str],
+) -> Result<String, ProtocolError> {
+    let parsed = parse_target(target)?;
+    let authority = parsed.authority().unwrap_or("localhost".to_string());
+
+    let mut rendered = format!("{} {} HTTP/1.1\r\n", method, parsed.path());
+    let has_host = headers
+        .iter()
+        .any(|header| header.to_ascii_lowercase().starts_with("host:"));
+    if !has_host {
+        rendered.push_str(&format!("Host: {}\r\n", authority));
+    }
+    for header in headers {
+        rendered.push_str(header);
+        rendered.push_str("\r\n");
+    }
+
+    match body {
+        Some(body) if !trailers.is_empty() => {
+            rendered.push_str("Transfer-Encoding: chunked\r\n\r\n");
+            rendered.push_str(&format!("{:x}\r\n{}\r\n0\r\n", body.len(), body));
+            for trailer in trailers {
+                rendered.push_str(trailer);
+                rendered.push_str("\r\n");
+            }
+            rendered.push_str("\r\n");
+        }
+        Some(body) => {
+            rendered.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+        }
+        None => rendered.push_str("\r\n"),
+    }
+
+    Ok(rendered)
+}
+
+/// Whether `-vv` asked for every payload to be printed as it is sent.
+pub fn dumping_payloads() -> bool {
+    crate::verbosity() >= 2
+}
+
+/// Prints a payload about to be sent to `target`, with CR and LF made visible.
+pub fn dump_payload(target: &str, module: &str, label: &str, payload: &str) {
+    eprintln!(
+        "=== {} [{}] {} ===\n{}",
+        target,
+        module,
+        label,
+        render_payload(payload)
+    );
+}
+
+/// With `-vvv`, prints a response a probe of `target` got, headers and body in full.
+pub fn dump_response(target: &str, module: &str, label: impl Display, response: &Response) {
+    tracing::debug!(
+        target,
+        module,
+        probe = %label,
+        status = response.status,
+        body_len = response.body.len(),
+        "probe answered"
+    );
+    if crate::verbosity() >= 3 {
+        eprintln!(
+            "=== {} [{}] {} response ===\n{}\n",
+            target,
+            module,
+            label,
+            render_response(response)
+        );
+    }
+}
+
+/// Prints every payload a module would send to `target` as a single block.
+pub fn print_dry_run(target: &str, module: &str, payloads: &[(String, String)]) {
+    let mut block = String::new();
+    for (label, payload) in payloads {
+        block.push_str(&format!("=== {} [{}] {} ===\n", target, module, label));
+        block.push_str(&render_payload(payload));
+        block.push('\n');
+    }
+    println!("{}", block);
+}
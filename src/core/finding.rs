@@ -0,0 +1,437 @@
+use crate::core::diff::ResponseSignature;
+use clap::ValueEnum;
+use riphttplib::types::Response;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How far a finding can be trusted, judged from the probes that confirmed it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    /// Scores a difference seen on every probe in `probes`. It is high when each probe changed
+    /// the status and all of them answered alike, medium when only one of those holds.
+    pub fn from_probes(baseline: &ResponseSignature, probes: &[ResponseSignature]) -> Self {
+        let status_changed = probes.iter().all(|probe| probe.status != baseline.status);
+        let consistent = probes.windows(2).all(|pair| pair[0] == pair[1]);
+        match (status_changed, consistent) {
+            (true, true) => Confidence::High,
+            (true, false) | (false, true) => Confidence::Medium,
+            (false, false) => Confidence::Low,
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Confidence::Low => write!(f, "low"),
+            Confidence::Medium => write!(f, "medium"),
+            Confidence::High => write!(f, "high"),
+        }
+    }
+}
+
+/// A reported issue together with the exchange that triggered it.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub target: String,
+    pub module: &'static str,
+    /// One-line summary written to the scan output.
+    pub message: String,
+    /// The payload that triggered the finding, exactly as sent.
+    pub payload: Option<String>,
+    pub baseline: Option<Response>,
+    /// The response that differed from the baseline (or otherwise gave the issue away).
+    pub response: Option<Response>,
+    /// Set by modules that confirm a finding over several probes.
+    pub confidence: Option<Confidence>,
+    /// How long the triggering request took, for findings based on timing.
+    pub latency: Option<Duration>,
+    /// The operator's tag of the target in the targets file, empty when it has none.
+    pub tag: String,
+    /// Unique marker the smuggled request carried, to look for in the target's logs.
+    pub marker: Option<String>,
+    /// Headers of `response` as name and value, kept under `--capture-headers`.
+    pub headers: Option<Vec<(String, String)>>,
+}
+
+impl Finding {
+    pub fn new(target: &str, module: &'static str, message: String) -> Self {
+        Self {
+            target: target.to_string(),
+            module,
+            message,
+            payload: None,
+            baseline: None,
+            response: None,
+            confidence: None,
+            latency: None,
+            tag: String::new(),
+            marker: None,
+            headers: None,
+        }
+    }
+
+    pub fn with_payload(mut self, payload: impl Into<String>) -> Self {
+        self.payload = Some(payload.into());
+        self
+    }
+
+    pub fn with_baseline(mut self, response: &Response) -> Self {
+        self.baseline = Some(response.clone());
+        self
+    }
+
+    pub fn with_response(mut self, response: &Response) -> Self {
+        self.response = Some(response.clone());
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: Confidence) -> Self {
+        self.confidence = Some(confidence);
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    pub fn with_marker(mut self, marker: Option<&str>) -> Self {
+        self.marker = marker.map(str::to_string);
+        self
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = tag.into();
+        self
+    }
+
+    /// Keeps the headers of the finding's response, if it has one, for the output.
+    pub fn with_captured_headers(mut self) -> Self {
+        self.headers = self.response.as_ref().map(|response| {
+            response
+                .headers
+                .iter()
+                .map(|header| {
+                    (
+                        header.name.clone(),
+                        header.value.clone().unwrap_or_default(),
+                    )
+                })
+                .collect()
+        });
+        self
+    }
+
+    /// `server: nginx; via: 1.1 varnish` from the captured headers.
+    pub fn headers_line(&self) -> Option<String> {
+        let headers = self.headers.as_ref()?;
+        Some(
+            headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Renders the finding and its evidence as a plain-text report.
+    pub fn transcript(&self) -> String {
+        let mut transcript = format!(
+            "target: {}\nmodule: {}\nfinding: {}\n",
+            self.target, self.module, self.message
+        );
+        if let Some(confidence) = self.confidence {
+            transcript.push_str(&format!("confidence: {}\n", confidence));
+        }
+        if let Some(latency) = self.latency {
+            transcript.push_str(&format!("latency: {}ms\n", latency.as_millis()));
+        }
+        if !self.tag.is_empty() {
+            transcript.push_str(&format!("tag: {}\n", self.tag));
+        }
+        if let Some(marker) = &self.marker {
+            transcript.push_str(&format!("marker: {}\n", marker));
+        }
+
+        if let Some(payload) = &self.payload {
+            transcript.push_str("\n=== payload ===\n");
+            transcript.push_str(payload);
+            transcript.push('\n');
+        }
+        for (label, response) in [
+            ("baseline response", &self.baseline),
+            ("response", &self.response),
+        ] {
+            if let Some(response) = response {
+                transcript.push_str(&format!("\n=== {} ===\n", label));
+                transcript.push_str(&render_response(response));
+                transcript.push('\n');
+            }
+        }
+
+        transcript
+    }
+}
+
+/// A response as recorded in a transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// A transcript written by [`TranscriptStore`], read back for `riphttp replay`.
+#[derive(Debug, Clone)]
+pub struct SavedTranscript {
+    pub target: String,
+    pub module: String,
+    /// The finding's one-line message.
+    pub finding: String,
+    pub payload: String,
+    pub baseline: Option<RecordedResponse>,
+    pub response: Option<RecordedResponse>,
+}
+
+impl SavedTranscript {
+    /// Parses the layout of [`Finding::transcript`]. Fails when the target or payload is missing.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (head, sections) = text.split_once("\n=== ").unwrap_or((text, ""));
+        let field = |name: &str| {
+            head.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                .map(str::to_string)
+        };
+        let target = field("target").ok_or("transcript has no target")?;
+        let module = field("module").unwrap_or_default();
+        let finding = field("finding").unwrap_or_default();
+
+        let mut payload = None;
+        let mut baseline = None;
+        let mut response = None;
+        // Every section is followed by a newline and the next one starts on a blank line.
+        let sections = sections.strip_suffix('\n').unwrap_or(sections);
+        for section in sections.split("\n\n=== ") {
+            let Some((label, content)) = section.split_once(" ===\n") else {
+                continue;
+            };
+            match label {
+                "payload" => payload = Some(content.to_string()),
+                "baseline response" => baseline = RecordedResponse::parse(content),
+                "response" => response = RecordedResponse::parse(content),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            target,
+            module,
+            finding,
+            payload: payload.ok_or("transcript has no payload")?,
+            baseline,
+            response,
+        })
+    }
+}
+
+impl RecordedResponse {
+    fn parse(rendered: &str) -> Option<Self> {
+        let status_line = rendered.lines().next()?;
+        let status = status_line.split_whitespace().nth(1)?.parse().ok()?;
+        let body = rendered
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .unwrap_or_default();
+        Some(Self { status, body })
+    }
+}
+
+/// Turns a finding into the line(s) written to the scan output.
+pub trait FindingFormatter: Send + Sync + std::fmt::Debug {
+    fn format(&self, finding: &Finding) -> String;
+}
+
+/// The module's own message, unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFormatter;
+
+impl FindingFormatter for DefaultFormatter {
+    fn format(&self, finding: &Finding) -> String {
+        finding.message.clone()
+    }
+}
+
+/// `[module] [http] target message`, in the spirit of nuclei's output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NucleiFormatter;
+
+impl FindingFormatter for NucleiFormatter {
+    fn format(&self, finding: &Finding) -> String {
+        format!(
+            "[{}] [http] {} {}",
+            finding.module.to_ascii_lowercase(),
+            finding.target,
+            single_line(&finding.message)
+        )
+    }
+}
+
+/// Tab-separated `target module status message`, one finding per line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GrepableFormatter;
+
+impl FindingFormatter for GrepableFormatter {
+    fn format(&self, finding: &Finding) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            finding.target,
+            finding.module,
+            status(finding),
+            single_line(&finding.message)
+        )
+    }
+}
+
+/// `target [module] message` with line breaks escaped as `\r` and `\n`, so every finding is
+/// exactly one line and the message can still be restored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OnelineFormatter;
+
+impl FindingFormatter for OnelineFormatter {
+    fn format(&self, finding: &Finding) -> String {
+        format!(
+            "{} [{}] {}",
+            finding.target,
+            finding.module.to_ascii_lowercase(),
+            escape_line_breaks(&finding.message)
+        )
+    }
+}
+
+/// User template with `{target}`, `{module}`, `{status}`, `{confidence}`, `{tag}` and
+/// `{message}` placeholders.
+#[derive(Debug, Clone)]
+pub struct TemplateFormatter {
+    template: String,
+}
+
+impl TemplateFormatter {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+        }
+    }
+}
+
+impl FindingFormatter for TemplateFormatter {
+    fn format(&self, finding: &Finding) -> String {
+        self.template
+            .replace("{target}", &finding.target)
+            .replace("{module}", finding.module)
+            .replace("{status}", &status(finding))
+            .replace(
+                "{confidence}",
+                &finding
+                    .confidence
+                    .map_or("-".to_string(), |confidence| confidence.to_string()),
+            )
+            .replace("{tag}", &finding.tag)
+            .replace("{message}", &finding.message)
+    }
+}
+
+/// Picks a built-in formatter by name (`default`, `nuclei-like`, `grepable`, `oneline`),
+/// otherwise treats `spec` as a template.
+pub fn finding_formatter(spec: &str) -> Arc<dyn FindingFormatter> {
+    match spec {
+        "default" => Arc::new(DefaultFormatter),
+        "nuclei-like" => Arc::new(NucleiFormatter),
+        "grepable" => Arc::new(GrepableFormatter),
+        "oneline" => Arc::new(OnelineFormatter),
+        template => Arc::new(TemplateFormatter::new(template)),
+    }
+}
+
+/// Status of the response behind the finding, or `-` when there is none.
+fn status(finding: &Finding) -> String {
+    finding
+        .response
+        .as_ref()
+        .map_or("-".to_string(), |response| response.status.to_string())
+}
+
+fn single_line(message: &str) -> String {
+    message.replace(['\r', '\n'], " ")
+}
+
+fn escape_line_breaks(message: &str) -> String {
+    message
+        .replace('\\', "\\\\")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+/// A response as status line, headers and (lossily decoded) body.
+pub fn render_response(response: &Response) -> String {
+    let mut rendered = format!("{} {}\r\n", response.protocol, response.status);
+    for header in &response.headers {
+        match &header.value {
+            Some(value) => rendered.push_str(&format!("{}: {}\r\n", header.name, value)),
+            None => rendered.push_str(&format!("{}\r\n", header.name)),
+        }
+    }
+    rendered.push_str("\r\n");
+    rendered.push_str(&String::from_utf8_lossy(&response.body));
+    rendered
+}
+
+/// Writes one transcript file per finding into a directory.
+#[derive(Debug)]
+pub struct TranscriptStore {
+    dir: PathBuf,
+    next: AtomicUsize,
+}
+
+impl TranscriptStore {
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Saves `finding` as `<n>_<module>_<target>.txt` and returns the path written. Files left by
+    /// an earlier scan into the same directory are never overwritten.
+    pub fn save(&self, finding: &Finding) -> io::Result<PathBuf> {
+        let target: String = finding
+            .target
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = loop {
+            let index = self.next.fetch_add(1, Ordering::Relaxed);
+            let path = self
+                .dir
+                .join(format!("{:04}_{}_{}.txt", index, finding.module, target));
+            if !path.exists() {
+                break path;
+            }
+        };
+
+        fs::write(&path, finding.transcript())?;
+        Ok(path)
+    }
+}
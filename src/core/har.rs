@@ -0,0 +1,121 @@
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use riphttplib::types::Response;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// The request side of a HAR entry, captured before the request is handed to the client.
+pub struct HarRequest<'a> {
+    pub method: &'a str,
+    pub url: &'a str,
+    pub http_version: &'a str,
+    /// Headers as `name: value` lines.
+    pub headers: &'a [String],
+    pub body: Option<&'a [u8]>,
+}
+
+fn header_pairs(headers: &[String]) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|header| {
+            let (name, value) = header.split_once(':').unwrap_or((header, ""));
+            json!({ "name": name.trim(), "value": value.trim() })
+        })
+        .collect()
+}
+
+/// Builds a HAR 1.2 entry. Only the total time is measured, so it is reported as `wait`.
+pub fn har_entry(
+    request: &HarRequest,
+    response: &Response,
+    started: DateTime<Utc>,
+    elapsed: Duration,
+) -> Value {
+    let millis = elapsed.as_secs_f64() * 1000.0;
+
+    let mut har_request = json!({
+        "method": request.method,
+        "url": request.url,
+        "httpVersion": request.http_version,
+        "cookies": [],
+        "headers": header_pairs(request.headers),
+        "queryString": [],
+        "headersSize": -1,
+        "bodySize": request.body.map_or(0, |body| body.len() as i64),
+    });
+    if let Some(body) = request.body {
+        har_request["postData"] = json!({
+            "mimeType": "",
+            "text": String::from_utf8_lossy(body),
+        });
+    }
+
+    let response_headers: Vec<Value> = response
+        .headers
+        .iter()
+        .map(|header| json!({ "name": header.name, "value": header.value.clone().unwrap_or_default() }))
+        .collect();
+    let mime_type = response
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("content-type"))
+        .and_then(|header| header.value.clone())
+        .unwrap_or_default();
+    let content = match std::str::from_utf8(&response.body) {
+        Ok(text) => json!({ "size": response.body.len(), "mimeType": mime_type, "text": text }),
+        Err(_) => json!({
+            "size": response.body.len(),
+            "mimeType": mime_type,
+            "text": BASE64_STANDARD.encode(&response.body),
+            "encoding": "base64",
+        }),
+    };
+
+    json!({
+        "startedDateTime": started.to_rfc3339(),
+        "time": millis,
+        "request": har_request,
+        "response": {
+            "status": response.status,
+            "statusText": "",
+            "httpVersion": response.protocol.to_string(),
+            "cookies": [],
+            "headers": response_headers,
+            "content": content,
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": response.body.len(),
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": millis, "receive": 0 },
+    })
+}
+
+/// Appends `entry` to the HAR log at `path`, creating the file if it doesn't exist. An existing
+/// file that isn't a HAR log is left untouched and reported as an error.
+pub fn append_har(path: &str, entry: Value) -> Result<(), Box<dyn std::error::Error>> {
+    let mut har = if Path::new(path).exists() {
+        let existing: Value = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|err| format!("'{}' is not a valid HAR file: {}", path, err))?;
+        if !existing["log"]["entries"].is_array() {
+            return Err(format!("'{}' is not a valid HAR file: missing log.entries", path).into());
+        }
+        existing
+    } else {
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "riphttp", "version": env!("CARGO_PKG_VERSION") },
+                "entries": [],
+            }
+        })
+    };
+
+    if let Some(entries) = har["log"]["entries"].as_array_mut() {
+        entries.push(entry);
+    }
+    fs::write(path, serde_json::to_string_pretty(&har)?)?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use url::Url;
+
+/// Caps how many targets of one host are scanned at once, shared by every task of a scan.
+/// Targets are grouped by host alone, so the ports of a `--ports` fan-out share one limit.
+#[derive(Debug)]
+pub struct HostLimiter {
+    limit: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until the host of `target` has a free slot, which is held until the permit is
+    /// dropped. Targets without a host aren't limited.
+    pub async fn acquire(&self, target: &str) -> Option<OwnedSemaphorePermit> {
+        let host = Url::parse(target)
+            .ok()?
+            .host_str()
+            .map(str::to_ascii_lowercase)?;
+        let semaphore = Arc::clone(
+            self.hosts
+                .lock()
+                .unwrap()
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.limit))),
+        );
+        // The semaphore is never closed, so acquiring only fails if that changes.
+        semaphore.acquire_owned().await.ok()
+    }
+}
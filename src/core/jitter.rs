@@ -0,0 +1,60 @@
+use crate::core::seed::keyed_rng;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::time::Duration;
+
+/// Random pause added before probes so scan traffic doesn't arrive in a fixed rhythm.
+#[derive(Debug, Clone, Copy)]
+pub struct Jitter {
+    max: Duration,
+    seed: u64,
+}
+
+impl Jitter {
+    /// Pauses for up to `max`, drawn from generators derived from `seed`.
+    pub fn new(max: Duration, seed: u64) -> Self {
+        Self { max, seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The pauses for one series of probes, e.g. one target. They depend only on the seed and
+    /// `key`, so the same seed repeats them however targets are scheduled.
+    pub fn for_key(&self, key: &str) -> ProbeJitter {
+        ProbeJitter {
+            max: self.max,
+            rng: Some(keyed_rng(self.seed, key)),
+        }
+    }
+}
+
+/// Pauses of one series of probes; a no-op without `--jitter`.
+#[derive(Debug, Default)]
+pub struct ProbeJitter {
+    max: Duration,
+    rng: Option<StdRng>,
+}
+
+impl ProbeJitter {
+    /// Sleeps for a uniformly random time between zero and the maximum.
+    pub async fn sleep(&mut self) {
+        let Some(rng) = &mut self.rng else {
+            return;
+        };
+        let millis = rng.random_range(0..=self.max.as_millis() as u64);
+        if millis > 0 {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+        }
+    }
+}
+
+/// Indices `0..count` in the order the probes identified by `key` are sent under
+/// `--probe-order random`. Like jitter, the order depends only on `seed` and `key`.
+pub fn shuffled_order(seed: u64, key: &str, count: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..count).collect();
+    order.shuffle(&mut keyed_rng(seed, key));
+    order
+}
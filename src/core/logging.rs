@@ -0,0 +1,102 @@
+use crate::core::telemetry::Telemetry;
+use clap::ValueEnum;
+#[cfg(feature = "otlp")]
+use opentelemetry::trace::TracerProvider;
+use std::fs::OpenOptions;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::filter_fn;
+#[cfg(not(feature = "otlp"))]
+use tracing_subscriber::layer::Identity;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+
+/// Most detailed events written to the `--log-file`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    /// Scan start and end, per-target outcomes and errors
+    #[default]
+    Info,
+    /// Also requests about to be sent, proxy picks and skipped targets
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Sends every `tracing` event at `level` or above to the `log_file`, appended as timestamped
+/// `key=value` lines, and the target, protocol and probe spans to the `otlp_endpoint`
+/// collector. The log file only gets events, so its lines read the same with spans exported.
+/// With neither nothing is installed: events are dropped and spans cost nothing, so the
+/// console shows the same as before whatever is logged.
+///
+/// The returned exporter has to live until the scan ends; dropping it flushes the spans.
+pub fn init_tracing(
+    log_file: Option<(&str, LogLevel)>,
+    otlp_endpoint: Option<&str>,
+) -> Result<Option<Telemetry>, String> {
+    if log_file.is_none() && otlp_endpoint.is_none() {
+        return Ok(None);
+    }
+
+    let file_layer = match log_file {
+        Some((path, level)) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| format!("failed to open log file '{}': {}", path, err))?;
+            let level = LevelFilter::from(level);
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(Mutex::new(file))
+                    .with_ansi(false)
+                    .with_target(false)
+                    .with_filter(filter_fn(move |meta| {
+                        meta.is_event() && level >= *meta.level()
+                    })),
+            )
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "otlp")]
+    let telemetry = otlp_endpoint
+        .map(|endpoint| {
+            Telemetry::otlp(endpoint)
+                .map_err(|err| format!("failed to set up OTLP export to '{}': {}", endpoint, err))
+        })
+        .transpose()?;
+    #[cfg(feature = "otlp")]
+    let otlp_layer = telemetry.as_ref().map(|telemetry| {
+        tracing_opentelemetry::layer()
+            .with_tracer(telemetry.provider().tracer("riphttp"))
+            .with_filter(LevelFilter::INFO)
+    });
+    #[cfg(not(feature = "otlp"))]
+    let (telemetry, otlp_layer) = match otlp_endpoint {
+        Some(_) => {
+            return Err(
+                "--otlp-endpoint needs a build with the otlp feature (cargo build --release --features otlp)"
+                    .to_string(),
+            );
+        }
+        None => (None, None::<Identity>),
+    };
+
+    Registry::default().with(file_layer).with(otlp_layer).init();
+    Ok(telemetry)
+}
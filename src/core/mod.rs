@@ -1,2 +1,21 @@
+pub mod altsvc;
 pub mod constants;
+pub mod cookies;
+pub mod diff;
+pub mod dns;
+pub mod dryrun;
+pub mod finding;
+pub mod har;
+pub mod hostlimit;
+pub mod jitter;
+pub mod logging;
+pub mod proxy;
+pub mod ratelimit;
+pub mod scope;
+pub mod seed;
+pub mod sqlite;
+pub mod telemetry;
+pub mod tls;
+pub mod useragent;
 pub mod utils;
+pub mod webhook;
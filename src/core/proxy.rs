@@ -0,0 +1,342 @@
+use super::utils::parse_proxy;
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use riphttplib::types::{ProtocolError, Request};
+use std::env;
+use std::fs;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use url::Url;
+
+/// Consecutive failed requests after which a proxy is considered dead.
+const DEAD_AFTER_FAILURES: usize = 5;
+
+/// How proxies are handed out to targets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProxyRotation {
+    #[default]
+    RoundRobin,
+    Random,
+}
+
+#[derive(Debug)]
+struct PooledProxy {
+    url: String,
+    failures: AtomicUsize,
+}
+
+/// A set of proxies shared by all tasks, assigned per target.
+#[derive(Debug)]
+pub struct ProxyPool {
+    proxies: Vec<PooledProxy>,
+    rotation: ProxyRotation,
+    next: AtomicUsize,
+    /// Draws the picks of `ProxyRotation::Random`.
+    rng: Mutex<StdRng>,
+    skipped: AtomicUsize,
+}
+
+/// Every proxy of a pool is dead. The target is skipped instead of being scanned directly.
+#[derive(Debug)]
+pub struct NoLiveProxy;
+
+impl ProxyPool {
+    pub fn new(proxies: Vec<String>, rotation: ProxyRotation, seed: u64) -> Self {
+        Self {
+            proxies: proxies
+                .into_iter()
+                .map(|url| PooledProxy {
+                    url,
+                    failures: AtomicUsize::new(0),
+                })
+                .collect(),
+            rotation,
+            next: AtomicUsize::new(0),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            skipped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reads one proxy per line, skipping empty lines and `#` comments.
+    pub fn load(
+        path: &str,
+        rotation: ProxyRotation,
+        seed: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let proxies = content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(parse_proxy)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if proxies.is_empty() {
+            return Err(format!("no proxies found in '{}'", path).into());
+        }
+        Ok(Self::new(proxies, rotation, seed))
+    }
+
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+
+    /// Adds `credentials` to every proxy of the pool.
+    pub fn with_credentials(mut self, credentials: &ProxyCredentials) -> Result<Self, String> {
+        for proxy in &mut self.proxies {
+            proxy.url = credentials.apply(&proxy.url)?;
+        }
+        Ok(self)
+    }
+
+    /// Picks a live proxy. Once every proxy has been marked dead the target it was asked for
+    /// is counted as skipped.
+    pub fn select(&self) -> Result<String, NoLiveProxy> {
+        let len = self.proxies.len();
+        let start = match self.rotation {
+            ProxyRotation::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed),
+            ProxyRotation::Random => self.rng.lock().unwrap().random_range(0..len.max(1)),
+        };
+
+        let proxy = (0..len)
+            .map(|offset| &self.proxies[(start + offset) % len])
+            .find(|proxy| proxy.failures.load(Ordering::Relaxed) < DEAD_AFTER_FAILURES);
+        match proxy {
+            Some(proxy) => Ok(proxy.url.clone()),
+            None => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                Err(NoLiveProxy)
+            }
+        }
+    }
+
+    /// Targets skipped because every proxy was dead.
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Records the outcome of a request sent through `url`; enough failures in a row mark it dead.
+    pub fn report(&self, url: &str, ok: bool) {
+        let Some(proxy) = self.proxies.iter().find(|proxy| proxy.url == url) else {
+            return;
+        };
+
+        if ok {
+            proxy.failures.store(0, Ordering::Relaxed);
+        } else if proxy.failures.fetch_add(1, Ordering::Relaxed) + 1 == DEAD_AFTER_FAILURES {
+            eprintln!(
+                "Proxy {} failed {} times in a row, skipping it",
+                redact_proxy(url),
+                DEAD_AFTER_FAILURES
+            );
+        }
+    }
+}
+
+/// Proxies taken from the `HTTP_PROXY` / `HTTPS_PROXY` environment variables (either case).
+#[derive(Clone, Debug, Default)]
+pub struct EnvProxies {
+    http: Option<String>,
+    https: Option<String>,
+}
+
+impl EnvProxies {
+    /// Reads the variables; values that aren't a usable proxy URL are warned about and ignored.
+    pub fn from_env() -> Self {
+        Self {
+            http: env_proxy("HTTP_PROXY"),
+            https: env_proxy("HTTPS_PROXY"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none()
+    }
+
+    /// Adds `credentials` to both proxies.
+    pub fn with_credentials(self, credentials: &ProxyCredentials) -> Result<Self, String> {
+        Ok(Self {
+            http: self
+                .http
+                .map(|proxy| credentials.apply(&proxy))
+                .transpose()?,
+            https: self
+                .https
+                .map(|proxy| credentials.apply(&proxy))
+                .transpose()?,
+        })
+    }
+
+    /// The proxy for `target`: `HTTPS_PROXY` for https:// targets, `HTTP_PROXY` otherwise.
+    pub fn for_target(&self, target: &str) -> Option<String> {
+        let https = target
+            .get(..8)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"));
+        if https {
+            self.https.clone()
+        } else {
+            self.http.clone()
+        }
+    }
+}
+
+fn env_proxy(name: &str) -> Option<String> {
+    let value = env::var(name)
+        .or_else(|_| env::var(name.to_ascii_lowercase()))
+        .ok()
+        .filter(|value| !value.trim().is_empty())?;
+    match parse_proxy(value.trim()) {
+        Ok(proxy) => Some(proxy),
+        Err(err) => {
+            eprintln!("Ignoring {}: {}", name, err);
+            None
+        }
+    }
+}
+
+/// Hosts that are always reached directly, in curl's `NO_PROXY` format: a comma-separated list of
+/// host names (matching subdomains too) or IP addresses, or `*` for every host.
+#[derive(Clone, Debug, Default)]
+pub struct NoProxy {
+    hosts: Vec<String>,
+    all: bool,
+}
+
+impl NoProxy {
+    pub fn parse(list: &str) -> Self {
+        let mut no_proxy = Self::default();
+        for entry in list
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            if entry == "*" {
+                no_proxy.all = true;
+                continue;
+            }
+            let host = entry
+                .trim_start_matches('.')
+                .trim_start_matches('[')
+                .to_ascii_lowercase();
+            // A port is accepted but ignored, like curl does.
+            let host = match host.split_once(']') {
+                Some((ip, _)) => ip.to_string(),
+                None if host.matches(':').count() == 1 => {
+                    host.split(':').next().unwrap_or_default().to_string()
+                }
+                None => host,
+            };
+            no_proxy.hosts.push(host);
+        }
+        no_proxy
+    }
+
+    /// `NO_PROXY` (either case), empty when unset.
+    pub fn from_env() -> Self {
+        env::var("NO_PROXY")
+            .or_else(|_| env::var("no_proxy"))
+            .map(|list| Self::parse(&list))
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.hosts.is_empty()
+    }
+
+    /// Whether `target` must bypass the proxy.
+    pub fn matches(&self, target: &str) -> bool {
+        if self.all {
+            return true;
+        }
+        let Some(host) = Url::parse(target)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        else {
+            return false;
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.hosts.iter().any(|entry| {
+            host == entry
+                || host
+                    .strip_suffix(entry.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+pub fn apply_proxy(mut request: Request, proxy: Option<&str>) -> Result<Request, ProtocolError> {
+    if let Some(proxy) = proxy {
+        request.set_proxy(proxy)?;
+    }
+    Ok(request)
+}
+
+/// `--proxy-user`/`--proxy-pass`: proxy credentials given apart from the proxy URL, so they stay
+/// out of the URL wherever it is printed or saved.
+#[derive(Clone, Debug)]
+pub struct ProxyCredentials {
+    user: String,
+    pass: Option<String>,
+}
+
+impl ProxyCredentials {
+    /// `user` is `user` or `user:pass`; a password in both `user` and `pass` is an error.
+    pub fn new(user: &str, pass: Option<&str>) -> Result<Self, String> {
+        let (user, pass) = match (user.split_once(':'), pass) {
+            (Some(_), Some(_)) => {
+                return Err(
+                    "the proxy password was given in both --proxy-user and --proxy-pass".into(),
+                );
+            }
+            (Some((user, inline)), None) => (user, Some(inline)),
+            (None, pass) => (user, pass),
+        };
+        if user.is_empty() {
+            return Err("--proxy-user must name a user".into());
+        }
+        Ok(Self {
+            user: user.to_string(),
+            pass: pass.map(str::to_string),
+        })
+    }
+
+    /// `proxy` with the credentials as its `user:pass@`, which riphttplib turns into the
+    /// `Proxy-Authorization` header. Fails if the URL already carries credentials.
+    pub fn apply(&self, proxy: &str) -> Result<String, String> {
+        let mut url =
+            Url::parse(proxy).map_err(|err| format!("invalid proxy '{}': {}", proxy, err))?;
+        if !url.username().is_empty() || url.password().is_some() {
+            return Err(format!(
+                "proxy '{}' already has credentials in its URL; give them either there or with --proxy-user, not both",
+                redact_proxy(proxy)
+            ));
+        }
+        let invalid = || format!("proxy '{}' can't take credentials", proxy);
+        url.set_username(&self.user).map_err(|_| invalid())?;
+        url.set_password(self.pass.as_deref())
+            .map_err(|_| invalid())?;
+        let mut with_credentials = url.to_string();
+        // Url adds a `/` path that the given proxy didn't have.
+        if !proxy.ends_with('/') && url.path() == "/" {
+            with_credentials.pop();
+        }
+        Ok(with_credentials)
+    }
+}
+
+/// `proxy` without any `user:pass@`, for printing or saving it.
+pub fn redact_proxy(proxy: &str) -> String {
+    let (scheme, rest) = match proxy.split_once("://") {
+        Some((scheme, rest)) => (format!("{}://", scheme), rest),
+        None => (String::new(), proxy),
+    };
+    match rest.rsplit_once('@') {
+        Some((_, host)) => format!("{}{}", scheme, host),
+        None => proxy.to_string(),
+    }
+}
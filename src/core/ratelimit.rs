@@ -0,0 +1,45 @@
+use super::utils::header_value;
+use riphttplib::types::Response;
+use std::time::{Duration, SystemTime};
+
+/// Longest `Retry-After` we honor; anything above is clamped so one host can't stall a worker.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How long the server asked us to wait, if it is rate limiting us.
+///
+/// Only 429 and 503 responses are considered. `Retry-After` may hold either a number of seconds
+/// or an HTTP date; the result is capped at [`MAX_RETRY_AFTER`].
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    if !matches!(response.status, 429 | 503) {
+        return None;
+    }
+
+    let value = header_value(response, "retry-after")?.trim();
+    let delay = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            date.duration_since(SystemTime::now()).unwrap_or_default()
+        }
+    };
+
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+/// Sleeps for the `Retry-After` delay of `response`, if any. Returns whether it waited.
+pub async fn wait_retry_after(target: &str, response: &Response) -> bool {
+    let Some(delay) = retry_after(response) else {
+        return false;
+    };
+
+    if crate::is_verbose() {
+        eprintln!(
+            "{} is rate limiting (status {}), waiting {}s",
+            target,
+            response.status,
+            delay.as_secs()
+        );
+    }
+    tokio::time::sleep(delay).await;
+    true
+}
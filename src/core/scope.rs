@@ -0,0 +1,145 @@
+use std::fs;
+use std::net::IpAddr;
+use url::Url;
+
+/// Hosts and networks read from a `--scope` or `--exclude` file.
+#[derive(Debug, Clone, Default)]
+pub struct HostList {
+    /// Lowercase domains; each also covers its subdomains.
+    domains: Vec<String>,
+    /// Network address and prefix length; a bare IP is a full-length prefix.
+    networks: Vec<(IpAddr, u32)>,
+}
+
+impl HostList {
+    /// One entry per line: a domain (`example.com`, `*.example.com` and `.example.com` all cover
+    /// the domain and its subdomains), an IP address, or a CIDR block. A URL counts as its host,
+    /// and a port (`example.com:8443`, `[::1]:8443`) is dropped, so the entry covers every port.
+    /// Blank lines and `#` comments are skipped.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut list = Self::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((address, prefix)) = line.split_once('/')
+                && let Ok(address) = address
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+            {
+                let bits = max_prefix(&address);
+                let prefix = prefix
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|prefix| *prefix <= bits)
+                    .ok_or_else(|| format!("invalid CIDR prefix in '{}'", line))?;
+                list.networks.push((address, prefix));
+                continue;
+            }
+
+            let host = if line.contains("://") {
+                Url::parse(line)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                    .ok_or_else(|| format!("no host in '{}'", line))?
+            } else if let Some((host, port)) = line.rsplit_once(':')
+                && line.parse::<IpAddr>().is_err()
+            {
+                port.parse::<u16>()
+                    .map_err(|_| format!("invalid port in '{}'", line))?;
+                host.to_string()
+            } else {
+                line.to_string()
+            };
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            match host.parse::<IpAddr>() {
+                Ok(ip) => list.networks.push((ip, max_prefix(&ip))),
+                Err(_) => list.domains.push(
+                    host.trim_start_matches("*.")
+                        .trim_start_matches('.')
+                        .to_ascii_lowercase(),
+                ),
+            }
+        }
+        Ok(list)
+    }
+
+    /// Reads a list with [`HostList::parse`]. A file without entries is an error, so a typo'd
+    /// scope file can't silently drop every target.
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(file_path)?;
+        let list = Self::parse(&content).map_err(|err| format!("{}: {}", file_path, err))?;
+        if list.is_empty() {
+            return Err(format!("no hosts found in '{}'", file_path).into());
+        }
+        Ok(list)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty() && self.networks.is_empty()
+    }
+
+    /// Whether the host of `target` is listed: an exact or parent domain for names, a covering
+    /// network for IP addresses. Targets without a host never match.
+    pub fn contains(&self, target: &str) -> bool {
+        let Some(host) = Url::parse(target)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        else {
+            return false;
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return self
+                .networks
+                .iter()
+                .any(|(network, prefix)| in_network(ip, *network, *prefix));
+        }
+        let host = host.trim_end_matches('.');
+        self.domains.iter().any(|domain| {
+            host == domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+}
+
+/// Drops every target not in `scope` (when given) or in `exclude`, returning the rest in order
+/// and how many were dropped.
+pub fn filter_scope(
+    targets: Vec<String>,
+    scope: Option<&HostList>,
+    exclude: Option<&HostList>,
+) -> (Vec<String>, usize) {
+    let before = targets.len();
+    let targets: Vec<String> = targets
+        .into_iter()
+        .filter(|target| scope.is_none_or(|scope| scope.contains(target)))
+        .filter(|target| !exclude.is_some_and(|exclude| exclude.contains(target)))
+        .collect();
+    let dropped = before - targets.len();
+    (targets, dropped)
+}
+
+fn max_prefix(ip: &IpAddr) -> u32 {
+    match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u32) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,21 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Seed of one randomized feature of a scan, e.g. `"sample"` or `"jitter"`, derived from the
+/// scan's `--seed`. Features draw from unrelated generators, yet one seed repeats them all.
+pub fn derive_seed(seed: u64, feature: &str) -> u64 {
+    keyed_rng(seed, feature).random()
+}
+
+/// A generator that depends only on `seed` and `key`, e.g. a target, so the same seed repeats
+/// its values however targets are scheduled.
+pub fn keyed_rng(seed: u64, key: &str) -> StdRng {
+    StdRng::seed_from_u64(seed ^ fnv1a(key))
+}
+
+/// FNV-1a, so keys map to the same seeds across builds and platforms.
+fn fnv1a(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
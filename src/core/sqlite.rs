@@ -0,0 +1,209 @@
+use crate::core::finding::Finding;
+use chrono::Utc;
+#[cfg(feature = "sqlite")]
+use rusqlite::{Connection, params};
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+#[cfg(feature = "sqlite")]
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::JoinHandle;
+
+/// Most findings written in one transaction.
+#[cfg(feature = "sqlite")]
+const MAX_BATCH: usize = 500;
+
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS findings (
+    id INTEGER PRIMARY KEY,
+    target TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    baseline_status INTEGER,
+    observed_status INTEGER,
+    payload TEXT NOT NULL DEFAULT '',
+    signature TEXT NOT NULL DEFAULT '',
+    message TEXT NOT NULL,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    run_id TEXT NOT NULL,
+    UNIQUE (target, mode, signature)
+);";
+
+/// The same target, module and payload signature found again only refreshes the row, keeping
+/// the latest payload as sent.
+#[cfg(feature = "sqlite")]
+const UPSERT: &str = "
+INSERT INTO findings
+    (target, mode, baseline_status, observed_status, payload, signature, message, first_seen, last_seen, run_id)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, ?9)
+ON CONFLICT (target, mode, signature) DO UPDATE SET
+    baseline_status = excluded.baseline_status,
+    observed_status = excluded.observed_status,
+    payload = excluded.payload,
+    message = excluded.message,
+    last_seen = excluded.last_seen,
+    run_id = excluded.run_id";
+
+// Only the writer reads the rows, and there is none without the `sqlite` feature.
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+#[derive(Debug)]
+struct FindingRow {
+    target: String,
+    mode: &'static str,
+    baseline_status: Option<u16>,
+    observed_status: Option<u16>,
+    payload: String,
+    signature: String,
+    message: String,
+    seen_at: String,
+}
+
+/// Upserts findings into a SQLite database from a blocking writer task, batching rows that
+/// arrive together into one transaction.
+#[derive(Debug)]
+pub struct FindingDb {
+    sender: Mutex<Option<UnboundedSender<FindingRow>>>,
+    correlation_header: Option<String>,
+}
+
+impl FindingDb {
+    /// Opens (or creates) the database and starts the writer. Rows are tagged with `run_id`.
+    #[cfg(feature = "sqlite")]
+    pub fn open(
+        path: impl AsRef<Path>,
+        run_id: String,
+    ) -> rusqlite::Result<(Self, JoinHandle<()>)> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let writer = tokio::task::spawn_blocking(move || write_findings(conn, &run_id, receiver));
+        Ok((
+            Self {
+                sender: Mutex::new(Some(sender)),
+                correlation_header: None,
+            },
+            writer,
+        ))
+    }
+
+    /// Builds without the `sqlite` feature have no SQLite to open.
+    #[cfg(not(feature = "sqlite"))]
+    pub fn open(
+        _path: impl AsRef<Path>,
+        _run_id: String,
+    ) -> Result<(Self, JoinHandle<()>), &'static str> {
+        Err("riphttp was built without the sqlite feature (cargo build --features sqlite)")
+    }
+
+    /// Leaves the `--correlation-header`, whose id changes every run, out of payload signatures.
+    pub fn with_correlation_header(mut self, header: Option<&str>) -> Self {
+        self.correlation_header = header.map(str::to_string);
+        self
+    }
+
+    pub fn record(&self, finding: &Finding) {
+        let Some(sender) = self.sender.lock().unwrap().clone() else {
+            return;
+        };
+        let _ = sender.send(FindingRow {
+            target: finding.target.clone(),
+            mode: finding.module,
+            baseline_status: finding.baseline.as_ref().map(|response| response.status),
+            observed_status: finding.response.as_ref().map(|response| response.status),
+            payload: finding.payload.clone().unwrap_or_default(),
+            signature: payload_signature(finding, self.correlation_header.as_deref()),
+            message: finding.message.clone(),
+            seen_at: Utc::now().to_rfc3339(),
+        });
+    }
+
+    /// Stops accepting findings; the writer exits once everything queued is committed.
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+}
+
+/// The payload of `finding` without what changes from one run to the next: the smuggled
+/// marker, the request paths, which may be randomized, and the User-Agent and
+/// `correlation_header` lines. Findings of a target and module with the same signature are
+/// one row.
+pub fn payload_signature(finding: &Finding, correlation_header: Option<&str>) -> String {
+    let Some(payload) = &finding.payload else {
+        return String::new();
+    };
+    let payload = match &finding.marker {
+        Some(marker) => payload.replace(marker.as_str(), "<marker>"),
+        None => payload.clone(),
+    };
+    let volatile = |name: &str| {
+        name.eq_ignore_ascii_case("user-agent")
+            || correlation_header.is_some_and(|header| name.eq_ignore_ascii_case(header))
+    };
+    payload
+        .lines()
+        .filter(|line| {
+            !line
+                .split_once(':')
+                .is_some_and(|(name, _)| volatile(name.trim()))
+        })
+        .map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(method), Some(_), Some(version))
+                    if version.starts_with("HTTP/")
+                        && !method.is_empty()
+                        && method.chars().all(|c| c.is_ascii_uppercase()) =>
+                {
+                    format!("{} <path> {}", method, version)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(feature = "sqlite")]
+fn write_findings(mut conn: Connection, run_id: &str, mut receiver: UnboundedReceiver<FindingRow>) {
+    while let Some(first) = receiver.blocking_recv() {
+        let mut batch = vec![first];
+        while batch.len() < MAX_BATCH {
+            match receiver.try_recv() {
+                Ok(row) => batch.push(row),
+                Err(_) => break,
+            }
+        }
+
+        if let Err(err) = write_batch(&mut conn, run_id, &batch) {
+            eprintln!(
+                "Failed to store {} findings in SQLite: {}",
+                batch.len(),
+                err
+            );
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn write_batch(conn: &mut Connection, run_id: &str, batch: &[FindingRow]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for row in batch {
+        tx.execute(
+            UPSERT,
+            params![
+                row.target,
+                row.mode,
+                row.baseline_status,
+                row.observed_status,
+                row.payload,
+                row.signature,
+                row.message,
+                row.seen_at,
+                run_id
+            ],
+        )?;
+    }
+    tx.commit()
+}
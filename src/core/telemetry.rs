@@ -0,0 +1,100 @@
+#[cfg(feature = "otlp")]
+use opentelemetry::KeyValue;
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::Resource;
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use riphttplib::types::{ProtocolError, Response};
+use std::fmt::Display;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+/// Exports the scan's spans to an OTLP/HTTP collector until dropped, when whatever is still
+/// batched is flushed.
+#[cfg(feature = "otlp")]
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+/// Builds without the `otlp` feature have no exporter, so there is never one to keep alive.
+#[cfg(not(feature = "otlp"))]
+pub enum Telemetry {}
+
+#[cfg(feature = "otlp")]
+impl Telemetry {
+    /// Batches spans for `endpoint`, a collector's traces URL such as
+    /// `http://localhost:4318/v1/traces`.
+    pub fn otlp(endpoint: &str) -> Result<Self, ExporterBuildError> {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()?;
+        let resource = Resource::builder()
+            .with_service_name("riphttp")
+            .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+            .build();
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build();
+        Ok(Self { provider })
+    }
+
+    pub fn provider(&self) -> &SdkTracerProvider {
+        &self.provider
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(err) = self.provider.shutdown() {
+            eprintln!("Failed to flush spans to the OTLP collector: {}", err);
+        }
+    }
+}
+
+/// Root span of one target's scan.
+pub fn target_span(target: &str) -> Span {
+    tracing::info_span!("target", target)
+}
+
+/// Span of one module scanning a target over one protocol, e.g. `TeCl` over `HTTP/1.1`.
+pub fn protocol_span(module: &str, protocol: impl Display) -> Span {
+    tracing::info_span!("protocol", module, protocol = %protocol)
+}
+
+/// Span of protocol detection, where DNS, connect and TLS time of a fresh host shows up.
+pub fn detect_span() -> Span {
+    tracing::info_span!("detect")
+}
+
+/// Sends one request as a probe span named after `label`, e.g. `attack 2 probe 1`, that
+/// records how long it took and the status it got or the error it failed with. Without an
+/// `--otlp-endpoint` the span is disabled and this is just `send`.
+pub async fn probe(
+    label: impl Display,
+    send: impl Future<Output = Result<Response, ProtocolError>>,
+) -> Result<Response, ProtocolError> {
+    let span = tracing::info_span!(
+        "probe",
+        label = %label,
+        elapsed_ms = Empty,
+        status = Empty,
+        error = Empty
+    );
+    if span.is_disabled() {
+        return send.await;
+    }
+    let start = Instant::now();
+    let result = send.instrument(span.clone()).await;
+    span.record("elapsed_ms", start.elapsed().as_millis() as u64);
+    match &result {
+        Ok(response) => span.record("status", response.status),
+        Err(err) => span.record("error", tracing::field::display(err)),
+    };
+    result
+}
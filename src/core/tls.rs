@@ -0,0 +1,186 @@
+//! Details of a TLS handshake with a target, for verbose client output.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{
+    CryptoProvider, verify_tls12_signature, verify_tls13_signature,
+};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{self, ClientConfig, DigitallySignedStruct, SignatureScheme};
+use url::Url;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+/// Gives up on connecting and handshaking after this long.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a handshake negotiated and the leaf certificate the server presented.
+#[derive(Debug, Clone, Default)]
+pub struct TlsDetails {
+    pub version: String,
+    pub cipher_suite: String,
+    pub alpn: Option<String>,
+    pub subject: Option<String>,
+    /// DNS names and IP addresses of the subject alternative name extension.
+    pub san: Vec<String>,
+    pub not_after: Option<String>,
+}
+
+impl fmt::Display for TlsDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TLS: {}, {}, ALPN {}",
+            self.version,
+            self.cipher_suite,
+            self.alpn.as_deref().unwrap_or("none")
+        )?;
+        if let Some(subject) = &self.subject {
+            write!(f, "\nCertificate: {}", subject)?;
+            if !self.san.is_empty() {
+                write!(f, ", SAN {}", self.san.join(" "))?;
+            }
+            if let Some(not_after) = &self.not_after {
+                write!(f, ", expires {}", not_after)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Handshakes with the host of an https `target`, offering `alpn`, and reports what was
+/// negotiated. riphttplib keeps its own session to itself, so this is a separate connection made
+/// just before the request. The certificate isn't verified, only described. Plain http targets
+/// give `Ok(None)`.
+pub async fn handshake_details(target: &str, alpn: &[&str]) -> Result<Option<TlsDetails>, String> {
+    let url = Url::parse(target).map_err(|err| err.to_string())?;
+    if url.scheme() != "https" {
+        return Ok(None);
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("no host in {}", target))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+    let server_name = ServerName::try_from(host.clone()).map_err(|err| err.to_string())?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(DescribeOnly(provider)))
+        .with_no_client_auth();
+    config.alpn_protocols = alpn.iter().map(|proto| proto.as_bytes().to_vec()).collect();
+
+    let handshake = async {
+        let stream = TcpStream::connect((host.as_str(), port)).await?;
+        TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await
+    };
+    let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+        .await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|err| err.to_string())?;
+    let (_, session) = stream.get_ref();
+
+    let mut details = TlsDetails {
+        version: session
+            .protocol_version()
+            .map(|version| format!("{:?}", version))
+            .unwrap_or_default(),
+        cipher_suite: session
+            .negotiated_cipher_suite()
+            .map(|suite| format!("{:?}", suite.suite()))
+            .unwrap_or_default(),
+        alpn: session
+            .alpn_protocol()
+            .map(|proto| String::from_utf8_lossy(proto).into_owned()),
+        ..TlsDetails::default()
+    };
+    if let Some(leaf) = session.peer_certificates().and_then(|certs| certs.first())
+        && let Ok((_, cert)) = X509Certificate::from_der(leaf.as_ref())
+    {
+        details.subject = Some(cert.subject().to_string());
+        details.not_after = Some(cert.validity().not_after.to_string());
+        if let Ok(Some(san)) = cert.subject_alternative_name() {
+            details.san = san
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name) => Some(name.to_string()),
+                    GeneralName::IPAddress(bytes) => ip_from_bytes(bytes),
+                    _ => None,
+                })
+                .collect();
+        }
+    }
+    Ok(Some(details))
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => Some(std::net::Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?).to_string()),
+        16 => Some(std::net::Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).to_string()),
+        _ => None,
+    }
+}
+
+/// Accepts any certificate while still checking the handshake signatures, since the point is to
+/// describe what the server presents, including self-signed and expired certificates.
+#[derive(Debug)]
+struct DescribeOnly(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for DescribeOnly {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::core::constants::HTTP_USER_AGENT;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Pool used by `--random-ua`: current desktop and mobile browsers.
+const BUILTIN_USER_AGENTS: &[&str] = &[
+    HTTP_USER_AGENT,
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:142.0) Gecko/20100101 Firefox/142.0",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.6 Safari/605.1.15",
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Safari/537.36 Edg/140.0.0.0",
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 18_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.6 Mobile/15E148 Safari/604.1",
+    "Mozilla/5.0 (Linux; Android 15; Pixel 9) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Mobile Safari/537.36",
+];
+
+/// Hands out a User-Agent per request, picked at random from a pool.
+#[derive(Debug)]
+pub struct UserAgentProvider {
+    agents: Vec<String>,
+    seed: u64,
+    rng: Mutex<StdRng>,
+}
+
+impl UserAgentProvider {
+    /// Picks from `agents` (which must not be empty) with an RNG seeded from `seed`.
+    pub fn new(agents: Vec<String>, seed: u64) -> Self {
+        Self {
+            agents,
+            seed,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    pub fn builtin(seed: u64) -> Self {
+        Self::new(
+            BUILTIN_USER_AGENTS
+                .iter()
+                .map(|agent| agent.to_string())
+                .collect(),
+            seed,
+        )
+    }
+
+    /// Reads one User-Agent per line, skipping blank lines and `#` comments.
+    pub fn load(path: impl AsRef<Path>, seed: u64) -> io::Result<Self> {
+        let path = path.as_ref();
+        let agents: Vec<String> = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        if agents.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("no user agents in '{}'", path.display()),
+            ));
+        }
+        Ok(Self::new(agents, seed))
+    }
+
+    pub fn len(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// The seed the pool was created with, saved in the checkpoint so a resume reuses it.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn pick(&self) -> &str {
+        let index = self.rng.lock().unwrap().random_range(0..self.agents.len());
+        &self.agents[index]
+    }
+}
@@ -1,13 +1,558 @@
+use clap::ValueEnum;
+use riphttplib::parse_target;
+use riphttplib::types::Response;
+use riphttplib::types::protocol::HttpProtocol;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use url::Url;
 
-pub async fn load_targets(file_path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+/// Largest CIDR block `load_targets` expands; bigger ranges (e.g. an IPv6 /64) are rejected.
+pub const MAX_CIDR_HOSTS: u128 = 65_536;
+
+/// Header names, and JSON object fields, taken for the target column when no
+/// `--targets-column` is given.
+const TARGET_COLUMNS: [&str; 3] = ["url", "target", "host"];
+
+/// How a target file lists its targets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TargetsFormat {
+    /// One target per line.
+    #[default]
+    Lines,
+    /// One target per row, in a column picked by header name or number.
+    Csv,
+    /// An array of targets, or an object with a `targets` array; one value per line also works.
+    Json,
+}
+
+/// Where the targets of a target file are: its format and, for CSV and JSON, the column or
+/// object field holding them.
+#[derive(Clone, Debug, Default)]
+pub struct TargetsLayout {
+    pub format: TargetsFormat,
+    /// A CSV header name or 1-based column number, or a JSON object field. Without it a
+    /// `url`, `target` or `host` column or field is looked for, and CSV falls back to the first
+    /// column.
+    pub column: Option<String>,
+}
+
+/// Reads one target per entry of `layout` as a `(url, tag)` pair. Bare hosts get `http://`, and
+/// CIDR blocks such as `10.0.0.0/24` or `2001:db8::/120` are expanded into one target per
+/// address, each with the tag of its entry. Entries without a tag (see [`split_target_tag`]) get
+/// an empty one.
+pub async fn load_targets(
+    file_path: &str,
+    layout: &TargetsLayout,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let mut targets = Vec::new();
+    for (_, line) in read_target_entries(file_path, layout)? {
+        let (line, tag) = split_target_tag(&line);
+        match expand_cidr(line) {
+            Some(expanded) => targets.extend(
+                expanded?
+                    .into_iter()
+                    .map(|target| (target, tag.to_string())),
+            ),
+            None => targets.push((normalize_target(line), tag.to_string())),
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Splits the operator's tag off a target line: everything after a tab, or else after a `#`,
+/// e.g. `https://a.com#prod` or `https://b.com<TAB>team:payments`. A fragment is never sent,
+/// so `#` is free to mark the tag.
+pub fn split_target_tag(line: &str) -> (&str, &str) {
+    match line.split_once('\t').or_else(|| line.split_once('#')) {
+        Some((target, tag)) => (target.trim(), tag.trim()),
+        None => (line, ""),
+    }
+}
+
+/// The non-empty tags of `targets`, keyed by every target a scan may make of their urls: each
+/// `--ports` combination, as given and canonicalized.
+pub fn target_tags(targets: &[(String, String)], ports: &[u16]) -> HashMap<String, String> {
+    let mut tags = HashMap::new();
+    for (target, tag) in targets.iter().filter(|(_, tag)| !tag.is_empty()) {
+        for expanded in expand_ports(vec![target.clone()], ports) {
+            tags.insert(canonicalize_target(&expanded), tag.clone());
+            tags.insert(expanded, tag.clone());
+        }
+    }
+    tags
+}
+
+/// Reads every file with [`load_targets`] and concatenates their targets in the given order.
+pub async fn load_target_files(
+    file_paths: &[String],
+    layout: &TargetsLayout,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let mut targets = Vec::new();
+    for file_path in file_paths {
+        targets.extend(load_targets(file_path, layout).await?);
+    }
+    Ok(targets)
+}
+
+/// The trimmed, non-empty entries of a target file with where each one is: its 1-based line,
+/// or its 1-based position in a JSON array.
+pub fn read_target_entries(
+    file_path: &str,
+    layout: &TargetsLayout,
+) -> Result<Vec<(usize, String)>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(file_path)?;
+    let column = layout.column.as_deref();
+    let entries = match layout.format {
+        TargetsFormat::Lines => content
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.trim().to_string()))
+            .collect(),
+        TargetsFormat::Csv => csv_entries(&content, column)?,
+        TargetsFormat::Json => json_entries(&content, column)?,
+    };
+    Ok(entries
+        .into_iter()
+        .filter(|(_, entry)| !entry.is_empty())
+        .collect())
+}
+
+/// The cells of `column` in a CSV file. A header name is looked up in the first row, which is
+/// then skipped; a number picks that column of every row. Without either, a first row naming
+/// one of [`TARGET_COLUMNS`] is the header, and otherwise every row's first cell is a target.
+fn csv_entries(content: &str, column: Option<&str>) -> Result<Vec<(usize, String)>, String> {
+    let mut rows: Vec<(usize, Vec<String>)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| (number + 1, split_csv_row(line)))
+        .collect();
+    let header_index = |header: &[String], names: &[&str]| {
+        header
+            .iter()
+            .position(|cell| names.iter().any(|name| cell.eq_ignore_ascii_case(name)))
+    };
+    let index = match column.map(|column| (column, column.parse::<usize>())) {
+        Some((_, Ok(0))) => return Err("--targets-column numbers start at 1".to_string()),
+        Some((_, Ok(number))) => number - 1,
+        Some((name, Err(_))) => {
+            let header = rows.first().map(|(_, header)| header.as_slice());
+            let index = header
+                .and_then(|header| header_index(header, &[name]))
+                .ok_or_else(|| format!("no '{}' column in the CSV header", name))?;
+            rows.remove(0);
+            index
+        }
+        None => match rows
+            .first()
+            .and_then(|(_, header)| header_index(header, &TARGET_COLUMNS))
+        {
+            Some(index) => {
+                rows.remove(0);
+                index
+            }
+            None => 0,
+        },
+    };
+    Ok(rows
+        .into_iter()
+        .filter_map(|(number, mut row)| {
+            (index < row.len()).then(|| (number, row.swap_remove(index).trim().to_string()))
+        })
+        .collect())
+}
+
+/// Splits a CSV row on commas outside double quotes, unquoting quoted cells (`""` is a quote).
+fn split_csv_row(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+}
+
+/// The targets of a JSON array, or of the `targets` array of an object. A file that isn't one
+/// JSON document is read as JSON lines, one value per line, as recon tools write them. Strings
+/// are targets; objects hold theirs in `column`, or else in one of [`TARGET_COLUMNS`].
+fn json_entries(content: &str, column: Option<&str>) -> Result<Vec<(usize, String)>, String> {
+    let values: Vec<(usize, Value)> = match serde_json::from_str::<Value>(content) {
+        Ok(Value::Array(values)) => values.into_iter().enumerate().collect(),
+        Ok(Value::Object(mut object)) if object.contains_key("targets") => {
+            match object.remove("targets") {
+                Some(Value::Array(values)) => values.into_iter().enumerate().collect(),
+                _ => return Err("\"targets\" has to be an array".to_string()),
+            }
+        }
+        // A single JSON line.
+        Ok(value) => vec![(0, value)],
+        Err(_) => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                serde_json::from_str(line)
+                    .map(|value| (number, value))
+                    .map_err(|err| format!("line {}: {}", number + 1, err))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let names: Vec<&str> = match column {
+        Some(column) => vec![column],
+        None => TARGET_COLUMNS.to_vec(),
+    };
+    values
+        .into_iter()
+        .map(|(index, value)| {
+            let target = match &value {
+                Value::String(target) => Some(target.as_str()),
+                Value::Object(object) => names
+                    .iter()
+                    .find_map(|name| object.get(*name).and_then(Value::as_str)),
+                _ => None,
+            };
+            match target {
+                Some(target) => Ok((index + 1, target.trim().to_string())),
+                None => Err(format!(
+                    "entry {} has no target: expected a string or an object with a \"{}\" field",
+                    index + 1,
+                    names.join("\", \"")
+                )),
+            }
+        })
+        .collect()
+}
+
+/// An entry of a target file that wouldn't be scanned.
+#[derive(Debug, Clone)]
+pub struct RejectedTarget {
+    /// 1-based line number, or position in a JSON array.
+    pub line: usize,
+    pub target: String,
+    pub reason: String,
+}
+
+/// Checks every entry of a target file the way `load_targets` and the scan modules would read
+/// it, without expanding CIDR blocks.
+pub fn validate_targets(
+    file_path: &str,
+    layout: &TargetsLayout,
+) -> Result<Vec<RejectedTarget>, Box<dyn std::error::Error>> {
+    let mut invalid = Vec::new();
+    for (number, line) in read_target_entries(file_path, layout)? {
+        let (line, _) = split_target_tag(&line);
+        let reason = match expand_cidr(line) {
+            Some(expanded) => expanded.err(),
+            None => {
+                let target = normalize_target(line);
+                match Url::parse(&target) {
+                    Err(err) => Some(err.to_string()),
+                    Ok(_) => parse_target(&target).err().map(|err| err.to_string()),
+                }
+            }
+        };
+        if let Some(reason) = reason {
+            invalid.push(RejectedTarget {
+                line: number,
+                target: line.to_string(),
+                reason,
+            });
+        }
+    }
+    Ok(invalid)
+}
+
+/// Adds `http://` to targets without a scheme, bracketing bare IPv6 addresses on the way.
+pub fn normalize_target(target: &str) -> String {
+    if target.contains("://") {
+        target.to_string()
+    } else if let Ok(ip) = target.parse::<Ipv6Addr>() {
+        format!("http://[{}]", ip)
+    } else {
+        format!("http://{}", target)
+    }
+}
+
+/// Expands a bare CIDR block into `http://` targets. Returns `None` when `entry` isn't a CIDR.
+pub fn expand_cidr(entry: &str) -> Option<Result<Vec<String>, String>> {
+    let (address, prefix) = entry.split_once('/')?;
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let address: IpAddr = address.parse().ok()?;
+    let prefix: u32 = prefix.parse().ok()?;
+
+    let bits = match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    if prefix > bits {
+        return Some(Err(format!("invalid CIDR prefix in '{}'", entry)));
+    }
+    let hosts = 1u128.checked_shl(bits - prefix).unwrap_or(u128::MAX);
+    if hosts > MAX_CIDR_HOSTS {
+        return Some(Err(format!(
+            "CIDR block '{}' is larger than the {} addresses that are expanded",
+            entry, MAX_CIDR_HOSTS
+        )));
+    }
+
+    let targets = match address {
+        IpAddr::V4(ip) => {
+            let network = u32::from(ip) & (u32::MAX.checked_shl(32 - prefix).unwrap_or(0));
+            (0..hosts as u32)
+                .map(|offset| format!("http://{}", Ipv4Addr::from(network + offset)))
+                .collect()
+        }
+        IpAddr::V6(ip) => {
+            let network = u128::from(ip) & (u128::MAX.checked_shl(128 - prefix).unwrap_or(0));
+            (0..hosts)
+                .map(|offset| format!("http://[{}]", Ipv6Addr::from(network + offset)))
+                .collect()
+        }
+    };
+    Some(Ok(targets))
+}
+
+/// Reads a path wordlist, one path per line. Paths are made absolute and `#` lines are skipped.
+pub fn load_paths(file_path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let content = fs::read_to_string(file_path)?;
-    let targets: Vec<String> = content
+    let paths: Vec<String> = content
         .lines()
         .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
-        .map(|line| line.to_string())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if line.starts_with('/') {
+                line.to_string()
+            } else {
+                format!("/{}", line)
+            }
+        })
         .collect();
 
-    Ok(targets)
+    if paths.is_empty() {
+        return Err(format!("no paths found in '{}'", file_path).into());
+    }
+    Ok(paths)
+}
+
+/// Parses a duration such as `500ms`, `90s`, `30m`, `2h` or `1d`. A bare number is read as
+/// seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+    let multiplier = match unit.trim() {
+        "ms" => return Ok(Duration::from_millis(amount)),
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        other => return Err(format!("unknown duration unit '{}' in '{}'", other, value)),
+    };
+
+    Ok(Duration::from_secs(amount * multiplier))
+}
+
+/// Parses a byte size such as `500K`, `100M` or `2G` (binary units, optional trailing `B`).
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier: u64 = match unit.strip_suffix('B').unwrap_or(&unit) {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("unknown size unit '{}' in '{}'", unit, value)),
+    };
+
+    amount
+        .checked_mul(multiplier)
+        .filter(|size| *size > 0)
+        .ok_or_else(|| format!("invalid size '{}'", value))
+}
+
+/// Parses a header name such as `X-Scan-Id`, which has to be a non-empty HTTP token.
+pub fn parse_header_name(value: &str) -> Result<String, String> {
+    let name = value.trim();
+    let is_token = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+    if name.is_empty() || !name.chars().all(is_token) {
+        return Err(format!("invalid header name '{}'", value));
+    }
+    Ok(name.to_string())
+}
+
+/// Parses an `--echo-marker` value, which becomes a path segment of the smuggled request and
+/// so may only hold letters, digits, `-` and `_`.
+pub fn parse_marker(value: &str) -> Result<String, String> {
+    let marker = value.trim();
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if marker.is_empty() || !marker.chars().all(is_plain) {
+        return Err(format!(
+            "invalid marker '{}', use letters, digits, '-' and '_'",
+            value
+        ));
+    }
+    Ok(marker.to_string())
+}
+
+/// Parses a `--sample` ratio such as `1/10`: scan `take` of every `every` targets.
+pub fn parse_sample(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid sample '{}', expected e.g. 1/10", value);
+    let (take, every) = value.trim().split_once('/').ok_or_else(invalid)?;
+    let take: usize = take.trim().parse().map_err(|_| invalid())?;
+    let every: usize = every.trim().parse().map_err(|_| invalid())?;
+    if take == 0 || take > every {
+        return Err(invalid());
+    }
+    Ok((take, every))
+}
+
+/// Validates a proxy URL, defaulting a bare `host:port` to the http scheme. SOCKS proxies are
+/// rejected up front because the riphttplib clients only tunnel through HTTP proxies.
+pub fn parse_proxy(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let Some((scheme, rest)) = value.split_once("://") else {
+        if value.is_empty() {
+            return Err("proxy must not be empty".to_string());
+        }
+        return Ok(format!("http://{}", value));
+    };
+
+    if rest.is_empty() {
+        return Err(format!("proxy '{}' is missing a host", value));
+    }
+
+    match scheme.to_ascii_lowercase().as_str() {
+        "http" | "https" => Ok(value.to_string()),
+        "socks4" | "socks4a" | "socks5" | "socks5h" => Err(format!(
+            "SOCKS proxies are not supported ('{}'), use an HTTP proxy instead",
+            value
+        )),
+        other => Err(format!("unsupported proxy scheme '{}'", other)),
+    }
+}
+
+/// Returns the value of the first header named `name` (case-insensitive).
+pub fn header_value<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .and_then(|header| header.value.as_deref())
+}
+
+/// Says so when `response` came back over another HTTP version than the `requested` one, e.g.
+/// `requested HTTP/2, got HTTP/1.1`. h2c counts as HTTP/2, and a protocol that isn't an HTTP
+/// version isn't reported.
+pub fn protocol_mismatch(requested: &HttpProtocol, response: &Response) -> Option<String> {
+    let requested = match requested {
+        HttpProtocol::Http1 => "HTTP/1.1",
+        HttpProtocol::Http2 | HttpProtocol::H2C => "HTTP/2",
+        HttpProtocol::Http3 => "HTTP/3",
+    };
+    let major = |version: &str| {
+        let version = version.trim().strip_prefix("HTTP/")?;
+        Some(version.split('.').next()?.to_string())
+    };
+    let got = major(&response.protocol)?;
+    (major(requested)? != got)
+        .then(|| format!("requested {}, got {}", requested, response.protocol.trim()))
+}
+
+/// Whether the authority of `target` spells out a port, e.g. `https://example.com:8443/`.
+fn has_explicit_port(target: &str) -> bool {
+    let rest = target.split_once("://").map_or(target, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    // skip past an IPv6 literal so its colons aren't mistaken for a port separator
+    let after_host = host.rsplit_once(']').map_or(host, |(_, tail)| tail);
+    after_host.contains(':')
+}
+
+/// The form of `target` used to spot duplicates: lowercase host, no port when it is the
+/// scheme's default, no `.`/`..` segments, trailing slash or fragment. Other ports are kept.
+/// Targets that can't be parsed as a URL are returned unchanged.
+pub fn canonicalize_target(target: &str) -> String {
+    let Ok(mut url) = Url::parse(target) else {
+        return target.to_string();
+    };
+    // Parsing already lowercases the host, drops a default port and resolves dot segments.
+    let path = url.path().to_string();
+    if path.len() > 1 && path.ends_with('/') {
+        url.set_path(path.trim_end_matches('/'));
+    }
+    url.set_fragment(None);
+    url.to_string()
+}
+
+/// Canonicalizes every target and drops those equal to an earlier one, keeping the list order.
+/// Returns the targets left and how many were dropped.
+pub fn canonicalize_targets(targets: Vec<String>) -> (Vec<String>, usize) {
+    let total = targets.len();
+    let mut seen = HashSet::with_capacity(total);
+    let unique: Vec<String> = targets
+        .iter()
+        .map(|target| canonicalize_target(target))
+        .filter(|target| seen.insert(target.clone()))
+        .collect();
+    let merged = total - unique.len();
+    (unique, merged)
+}
+
+/// Fans every target without an explicit port out into one target per port in `ports`.
+///
+/// Targets that already carry a port, or that can't be parsed as a URL, are kept as they are.
+pub fn expand_ports(targets: Vec<String>, ports: &[u16]) -> Vec<String> {
+    if ports.is_empty() {
+        return targets;
+    }
+
+    let mut expanded = Vec::with_capacity(targets.len() * ports.len());
+    for target in targets {
+        let parsed = match Url::parse(&target) {
+            Ok(url) if !has_explicit_port(&target) => url,
+            _ => {
+                expanded.push(target);
+                continue;
+            }
+        };
+
+        for &port in ports {
+            let mut url = parsed.clone();
+            if url.set_port(Some(port)).is_ok() {
+                expanded.push(url.to_string());
+            }
+        }
+    }
+    expanded
 }
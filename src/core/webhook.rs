@@ -0,0 +1,135 @@
+use crate::core::constants::HTTP_USER_AGENT;
+use crate::core::finding::Finding;
+use riphttplib::H1;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request};
+use serde_json::{Value, json};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, Receiver, Sender, error::TrySendError};
+use tokio::task::JoinHandle;
+
+/// Findings waiting to be posted; more than this and new ones are dropped.
+const WEBHOOK_QUEUE: usize = 1024;
+/// Most findings sent in one POST.
+const MAX_BATCH: usize = 50;
+/// Pause between POSTs, so a burst of findings goes out in a few batched requests.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Posts findings to a URL as they are reported, off the scanning path.
+///
+/// Each POST carries a JSON array of `{target, mode, message}` objects: one finding normally,
+/// everything queued up since the previous POST during a burst.
+#[derive(Debug)]
+pub struct Webhook {
+    sender: Mutex<Option<Sender<Value>>>,
+}
+
+impl Webhook {
+    /// Starts the sender task. `headers` are extra `name: value` lines, e.g. for auth.
+    pub fn spawn(url: String, headers: Vec<String>) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(WEBHOOK_QUEUE);
+        let worker = tokio::spawn(post_findings(url, headers, receiver));
+        (
+            Self {
+                sender: Mutex::new(Some(sender)),
+            },
+            worker,
+        )
+    }
+
+    /// Queues `finding` without waiting. Findings are dropped if the queue is full.
+    pub fn notify(&self, finding: &Finding) {
+        let Some(sender) = self.sender.lock().unwrap().clone() else {
+            return;
+        };
+        let mut payload = json!({
+            "target": finding.target,
+            "mode": finding.module,
+            "message": finding.message,
+        });
+        if let Some(confidence) = finding.confidence {
+            payload["confidence"] = json!(confidence.to_string());
+        }
+        if let Some(latency) = finding.latency {
+            payload["latency_ms"] = json!(latency.as_millis() as u64);
+        }
+        if !finding.tag.is_empty() {
+            payload["tag"] = json!(finding.tag);
+        }
+        if let Some(marker) = &finding.marker {
+            payload["marker"] = json!(marker);
+        }
+        if let Some(headers) = &finding.headers {
+            // Repeated headers, such as set-cookie, are joined like a proxy would fold them.
+            let mut object = serde_json::Map::new();
+            for (name, value) in headers {
+                let name = name.to_ascii_lowercase();
+                match object.get_mut(&name) {
+                    Some(Value::String(joined)) => {
+                        joined.push_str(", ");
+                        joined.push_str(value);
+                    }
+                    _ => {
+                        object.insert(name, json!(value));
+                    }
+                }
+            }
+            payload["headers"] = Value::Object(object);
+        }
+        if let Err(TrySendError::Full(_)) = sender.try_send(payload) {
+            eprintln!(
+                "Webhook queue is full, dropping finding for {}",
+                finding.target
+            );
+        }
+    }
+
+    /// Stops accepting findings; the sender task exits once the queue is drained.
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+}
+
+async fn post_findings(url: String, headers: Vec<String>, mut receiver: Receiver<Value>) {
+    while let Some(first) = receiver.recv().await {
+        let mut batch = vec![first];
+        while batch.len() < MAX_BATCH {
+            match receiver.try_recv() {
+                Ok(payload) => batch.push(payload),
+                Err(_) => break,
+            }
+        }
+
+        let count = batch.len();
+        match post(&url, &headers, Value::Array(batch)).await {
+            Ok(status) if (200..300).contains(&status) => {}
+            Ok(status) => eprintln!("Webhook {} answered {} to {} findings", url, status, count),
+            Err(err) => eprintln!(
+                "Failed to post {} findings to webhook {}: {}",
+                count, url, err
+            ),
+        }
+        tokio::time::sleep(MIN_INTERVAL).await;
+    }
+}
+
+async fn post(url: &str, headers: &[String], body: Value) -> Result<u16, ProtocolError> {
+    let timeouts = ClientTimeouts {
+        connect: Some(TIMEOUT),
+        read: Some(TIMEOUT),
+        write: Some(TIMEOUT),
+    };
+    let mut request_headers = vec![
+        format!("user-agent: {}", HTTP_USER_AGENT),
+        "content-type: application/json".to_string(),
+    ];
+    request_headers.extend(headers.iter().cloned());
+
+    let request = Request::new(url, "POST")?
+        .headers(request_headers)
+        .body(body.to_string().into_bytes())
+        .timeout(timeouts.clone());
+    let response = H1::timeouts(timeouts).send_request(request).await?;
+    Ok(response.status)
+}
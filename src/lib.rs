@@ -1,15 +1,105 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+//! riphttp is an HTTP/1.1, HTTP/2 and HTTP/3 client and request smuggling scanner.
+//!
+//! The scanning engine is usable on its own: implement [`Task`] for a check and hand it to a
+//! [`TargetScanner`], which runs it over every target with bounded concurrency and, when a
+//! [`RecorderConfig`](scanner::recorder::RecorderConfig) is supplied, persists findings and a
+//! resumable checkpoint.
+//!
+//! ```
+//! use async_trait::async_trait;
+//! use riphttp::{ScanOptions, TargetScanner, Task};
+//! use std::sync::Arc;
+//!
+//! struct LengthCheck;
+//!
+//! #[async_trait(?Send)]
+//! impl Task for LengthCheck {
+//!     type Error = std::io::Error;
+//!
+//!     async fn execute(&self, target: String) -> Result<String, Self::Error> {
+//!         // An empty string means "nothing to report" for this target.
+//!         if target.len() > 20 {
+//!             Ok(format!("[!] long target {}", target))
+//!         } else {
+//!             Ok(String::new())
+//!         }
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let targets = vec![
+//!     "https://example.com".to_string(),
+//!     "https://a-much-longer-example.com".to_string(),
+//! ];
+//!
+//! let scanner = TargetScanner::new(10);
+//! let results = scanner
+//!     .scan_with_options(targets, Arc::new(LengthCheck), ScanOptions::default())
+//!     .await?;
+//!
+//! let findings: Vec<_> = results.iter().filter(|r| !r.output.is_empty()).collect();
+//! assert_eq!(findings.len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 pub mod core;
 pub mod modules;
 pub mod scanner;
 
-static VERBOSE: AtomicBool = AtomicBool::new(false);
+pub use scanner::scanner::{
+    ScanError, ScanOptions, ScanOutput, ScanResult, ScanStats, TargetScanner,
+};
+pub use scanner::task::Task;
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
 
+/// Level 1 and up (`-v`).
 pub fn is_verbose() -> bool {
-    VERBOSE.load(Ordering::Relaxed)
+    verbosity() >= 1
+}
+
+/// How many times `-v` was given: 0 is quiet, 1 adds diagnostics, 2 also dumps every payload.
+pub fn verbosity() -> u8 {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+pub fn set_verbose(level: u8) {
+    VERBOSITY.store(level, Ordering::Relaxed);
+}
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// Whether stdout carries the recorded findings (`--output -`). Findings aren't echoed to the
+/// terminal then, and status lines go to stderr.
+pub fn stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
 }
 
-pub fn set_verbose(verbose: bool) {
-    VERBOSE.store(verbose, Ordering::Relaxed);
+pub fn set_stdout_reserved(reserved: bool) {
+    STDOUT_RESERVED.store(reserved, Ordering::Relaxed);
+}
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Whether output may be styled with ANSI colors. Anything that colors its output, like the
+/// progress bar, checks this.
+pub fn use_color() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Colors are off with `--no-color`, when `NO_COLOR` is set to anything non-empty
+/// (<https://no-color.org>), or when stdout isn't a terminal.
+pub fn color_wanted(no_color_flag: bool) -> bool {
+    use std::io::IsTerminal;
+
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color_flag && !no_color_env && std::io::stdout().is_terminal()
 }
@@ -1,34 +1,93 @@
-use clap::{Parser, Subcommand, ValueEnum, CommandFactory};
-use riphttplib::types::{ProtocolError, Request, Response};
-use riphttplib::utils::{convert_escape_sequences, parse_header};
-use riphttplib::{H1, H2, H3};
-use scanner::checkpoint::{
-    Checkpoint, default_checkpoint_path, read_checkpoint, remove_checkpoint, write_checkpoint,
+// The `--show-config` json! object outgrows the default macro recursion limit.
+#![recursion_limit = "256"]
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use chrono::Utc;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use riphttp::core::cookies::CookieJar;
+use riphttp::core::diff::{BodyNormalizer, DiffMode, StatusChange};
+use riphttp::core::dns::{
+    DEFAULT_DNS_CACHE_TTL, DnsCache, ResolveOverrides, parse_connect_to, parse_resolve,
 };
-use scanner::recorder::default_recorder_config;
-use scanner::scanner::{ScanOptions, ScanOutput, TargetScanner};
-use std::fmt;
-use std::io::{self, Write};
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+use riphttp::core::finding::{Confidence, SavedTranscript, TranscriptStore, finding_formatter};
+use riphttp::core::har::{HarRequest, append_har, har_entry};
+use riphttp::core::hostlimit::HostLimiter;
+use riphttp::core::jitter::Jitter;
+use riphttp::core::logging::{LogLevel, init_tracing};
+use riphttp::core::proxy::{
+    EnvProxies, NoProxy, ProxyCredentials, ProxyPool, ProxyRotation, redact_proxy,
 };
+use riphttp::core::scope::{HostList, filter_scope};
+use riphttp::core::seed::derive_seed;
+use riphttp::core::sqlite::FindingDb;
+use riphttp::core::tls::handshake_details;
+use riphttp::core::useragent::UserAgentProvider;
+use riphttp::core::utils::{
+    TargetsFormat, TargetsLayout, canonicalize_targets, expand_ports, load_paths,
+    load_target_files, parse_duration, parse_header_name, parse_marker, parse_proxy, parse_sample,
+    parse_size, protocol_mismatch, read_target_entries, target_tags, validate_targets,
+};
+use riphttp::core::webhook::Webhook;
+use riphttp::modules::options::{
+    DEFAULT_BASELINE_RETRIES, DEFAULT_FILTER_STATUS, DEFAULT_IGNORED_DIFF_STATUS,
+    DEFAULT_MAX_CONSECUTIVE_ERRORS, DEFAULT_PROBES, DEFAULT_SKIP_BASELINE_STATUS,
+    DEFAULT_SMUGGLE_PATH, InvalidTargets, MAX_BASELINE_BODY, ModuleOptions,
+};
+use riphttp::modules::registry::{ModeRegistry, ScanMode, census_mode};
+use riphttp::modules::template::RequestTemplate;
+use riphttp::scanner::adaptive::AdaptiveConcurrency;
+use riphttp::scanner::checkpoint::{
+    Checkpoint, CheckpointFormat, CompletedSet, completed_checkpoint_path, create_parent_dir,
+    named_checkpoint_path, output_coverage, read_checkpoint, read_resumable_checkpoint,
+    remove_checkpoint, write_checkpoint,
+};
+use riphttp::scanner::compare::{diff_outputs, read_output};
+use riphttp::scanner::metrics::MetricsEndpoint;
+use riphttp::scanner::progress::ProgressFormat;
+use riphttp::scanner::recorder::{
+    DEFAULT_PENDING_WARN, DEFAULT_RECORDER_BUFFER, RecorderConfig, STDOUT_PATH, SortKey,
+    default_recorder_config, sort_output,
+};
+use riphttp::{
+    ScanError, ScanOptions, ScanOutput, ScanResult, ScanStats, TargetScanner, color_wanted,
+    is_verbose, set_color, set_stdout_reserved, set_verbose, stdout_reserved,
+};
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request, Response};
+use riphttplib::utils::{convert_escape_sequences, parse_header};
+use riphttplib::{H1, H2, H3, Protocol, parse_target};
+use serde_json::json;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
 
-mod core;
-mod modules;
-mod scanner;
-use core::utils::load_targets;
-use modules::trailmerge::TrailMergeTask;
-use modules::trailsmug::TrailSmugTask;
-
-static VERBOSE: AtomicBool = AtomicBool::new(false);
-
-pub fn is_verbose() -> bool {
-    VERBOSE.load(Ordering::Relaxed)
-}
+/// Exit status when `--fail-on-finding` is set and findings were recorded. Operational errors exit with 1.
+const EXIT_FINDINGS: u8 = 2;
+/// Exit status when findings or the checkpoint couldn't be written, e.g. on a full disk.
+const EXIT_PERSISTENCE: u8 = 3;
+/// Redirects `--location` follows unless `--max-redirects` says otherwise.
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+/// How long queued webhook notifications may take to go out once the scan is done.
+const WEBHOOK_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
 
-pub fn set_verbose(verbose: bool) {
-    VERBOSE.store(verbose, Ordering::Relaxed);
+/// `println!` for the status lines of a scan, which go to stderr while `--output -` keeps stdout
+/// for findings.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if stdout_reserved() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
 }
 
 /// RipHTTP - HTTP Protocol Scanner
@@ -42,12 +101,25 @@ pub fn set_verbose(verbose: bool) {
     // If a subcommand is used, don't require top-level required args
     subcommand_negates_reqs = true,
     // Prevent mixing top-level args with subcommands
-    args_conflicts_with_subcommands = true
+    args_conflicts_with_subcommands = true,
+    override_usage = "riphttp [OPTIONS] [URL]\n       riphttp [OPTIONS] <COMMAND>"
 )]
 struct Args {
-    /// Enable verbose output
-    #[clap(short, long, global = true)]
-    verbose: bool,
+    /// Enable verbose output; -vv also prints every payload sent by a scan, -vvv every response it gets
+    #[clap(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// Never color output (also off when NO_COLOR is set or stdout isn't a terminal)
+    #[clap(long, global = true)]
+    no_color: bool,
+    /// Append leveled diagnostics (targets, requests, errors) to this file, apart from findings
+    #[clap(long, global = true)]
+    log_file: Option<String>,
+    /// Most detailed events written to --log-file
+    #[clap(long, global = true, value_enum, default_value_t = LogLevel::Info, requires = "log_file")]
+    log_level: LogLevel,
+    /// Export per-target, per-protocol and per-probe timing spans to this OTLP/HTTP traces URL (e.g. http://localhost:4318/v1/traces; needs a build with the otlp feature)
+    #[clap(long, global = true, value_name = "URL")]
+    otlp_endpoint: Option<String>,
     /// Default client-mode arguments when no subcommand given
     #[clap(flatten)]
     client: TopClientArgs,
@@ -59,9 +131,84 @@ struct Args {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Send single HTTP request
-    Client(ClientArgs),
+    Client(Box<ClientArgs>),
     /// Mass scan multiple targets
-    Scan(ScanArgs),
+    Scan(Box<ScanArgs>),
+    /// Re-send the payload of a saved finding transcript and check it still reproduces
+    Replay(ReplayArgs),
+    /// Show or remove the checkpoint of a scan
+    Checkpoint(CheckpointArgs),
+    /// Compare a scan's findings with an earlier run's output
+    Diff(DiffArgs),
+}
+
+/// Arguments for comparing two scan outputs
+#[derive(Parser, Debug)]
+struct DiffArgs {
+    /// Output of the earlier scan
+    previous: PathBuf,
+    /// Output of the scan to compare with it
+    current: PathBuf,
+    /// Print the comparison as JSON
+    #[clap(long)]
+    json: bool,
+}
+
+/// Arguments for inspecting a scan's checkpoint
+#[derive(Parser, Debug)]
+struct CheckpointArgs {
+    #[command(subcommand)]
+    action: CheckpointAction,
+}
+
+#[derive(Subcommand, Debug)]
+enum CheckpointAction {
+    /// Print the checkpoint's progress and the settings a resume checks
+    Show(CheckpointLocation),
+    /// Remove the checkpoint, after asking for confirmation
+    Clear {
+        #[clap(flatten)]
+        location: CheckpointLocation,
+        /// Remove it without asking
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+/// Which checkpoint a `checkpoint` command reads, picked like `scan` picks it
+#[derive(Parser, Debug)]
+struct CheckpointLocation {
+    /// Checkpoint file
+    #[clap(long, default_value = "checkpoint", conflicts_with = "run_name")]
+    checkpoint: PathBuf,
+    /// Checkpoint of the scan run with this --run-name
+    #[clap(long)]
+    run_name: Option<String>,
+}
+
+impl CheckpointLocation {
+    fn path(&self) -> PathBuf {
+        match &self.run_name {
+            Some(name) => named_checkpoint_path(name),
+            None => self.checkpoint.clone(),
+        }
+    }
+}
+
+/// Arguments for replaying a transcript
+#[derive(Parser, Debug)]
+struct ReplayArgs {
+    /// Transcript written by `scan --save-transcripts`
+    transcript: PathBuf,
+    /// Give up connecting after this long (e.g. 500ms, 5s)
+    #[clap(long, value_parser = parse_duration)]
+    connect_timeout: Option<Duration>,
+    /// Give up waiting for the response after this long
+    #[clap(long, value_parser = parse_duration)]
+    read_timeout: Option<Duration>,
+    /// Give up sending the request after this long
+    #[clap(long, value_parser = parse_duration)]
+    write_timeout: Option<Duration>,
 }
 
 /// Arguments for HTTP client
@@ -72,21 +219,90 @@ struct ClientArgs {
     /// Request body
     #[clap(short, long)]
     data: Option<String>,
+    /// Read the request body from a file, sent byte for byte
+    #[clap(long, conflicts_with = "data")]
+    data_file: Option<String>,
+    /// Send --data verbatim, without converting escape sequences
+    #[clap(long)]
+    data_binary: bool,
     /// Method
     #[clap(short, long)]
     method: Option<String>,
     /// Perform a HEAD request
     #[clap(short = 'I', long)]
     head: bool,
-    /// Proxy to use
-    #[clap(short, long)]
+    /// Proxy to use (http:// is assumed when no scheme is given)
+    #[clap(short, long, value_parser = parse_proxy)]
     proxy: Option<String>,
+    /// Proxy user, or user:pass, kept out of the proxy URL
+    #[clap(long)]
+    proxy_user: Option<String>,
+    /// Proxy password, for a --proxy-user without one
+    #[clap(long, requires = "proxy_user")]
+    proxy_pass: Option<String>,
+    /// Hosts reached without a proxy, comma-separated (overrides NO_PROXY; `*` for all)
+    #[clap(long)]
+    noproxy: Option<String>,
     /// Headers (can be specified multiple times)
     #[clap(short = 'H', long)]
     header: Vec<String>,
+    /// Basic auth credentials as user:pass
+    #[clap(short, long, conflicts_with = "bearer")]
+    user: Option<String>,
+    /// Bearer token sent in the Authorization header
+    #[clap(long)]
+    bearer: Option<String>,
+    /// Print the response as one JSON object with protocol, status, headers and body
+    #[clap(long)]
+    json: bool,
+    /// Send the request this many times, printing one status line per response
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["raw", "location", "har"])]
+    repeat: u32,
+    /// Milliseconds to wait between --repeat requests
+    #[clap(long, default_value_t = 0, requires = "repeat")]
+    repeat_delay: u64,
     /// Trailers (can be specified multiple times)
     #[clap(short = 'T', long)]
     trailer: Vec<String>,
+    /// Send this file's raw HTTP/1.1 request as-is, ignoring method, body and header options
+    #[clap(long)]
+    raw: Option<String>,
+    /// Append the request and response to this HAR file
+    #[clap(long, conflicts_with = "raw")]
+    har: Option<String>,
+    /// Connect to this IP for host:port instead of resolving it, as host:port:ip; http:// targets only, since riphttplib takes SNI from the URL and an https handshake would name the IP (can be specified multiple times)
+    #[clap(long, value_parser = parse_resolve)]
+    resolve: Vec<(String, u16, IpAddr)>,
+    /// Connect to connect_host:connect_port for host:port, sending the original Host header, as host:port:connect_host:connect_port; http:// targets only, since riphttplib takes SNI from the URL and an https handshake would name the connect host (can be specified multiple times)
+    #[clap(long, value_parser = parse_connect_to)]
+    connect_to: Vec<(String, u16, String, u16)>,
+    /// With --raw, rewrite the Host line to the URL's authority
+    #[clap(long, requires = "raw")]
+    update_host: bool,
+    /// Follow redirects
+    #[clap(short = 'L', long, conflicts_with = "raw")]
+    location: bool,
+    /// Cookie sent with the request, as name=value (can be specified multiple times)
+    #[clap(short = 'b', long, conflicts_with = "raw")]
+    cookie: Vec<String>,
+    /// Keep cookies set by responses and send them on later requests (implied by -L and -b)
+    #[clap(long, conflicts_with = "raw")]
+    cookie_jar: bool,
+    /// Most redirects followed with --location before giving up
+    #[clap(long, default_value_t = DEFAULT_MAX_REDIRECTS, requires = "location")]
+    max_redirects: usize,
+    /// Let --location follow redirects from https to plain http
+    #[clap(long, requires = "location")]
+    unsafe_redirects: bool,
+    /// Give up connecting after this long (e.g. 500ms, 5s)
+    #[clap(long, value_parser = parse_duration)]
+    connect_timeout: Option<Duration>,
+    /// Give up waiting for the response after this long
+    #[clap(long, value_parser = parse_duration)]
+    read_timeout: Option<Duration>,
+    /// Give up sending the request after this long
+    #[clap(long, value_parser = parse_duration)]
+    write_timeout: Option<Duration>,
     /// use HTTP1
     #[clap(long, default_value = "false")]
     http1: bool,
@@ -96,6 +312,8 @@ struct ClientArgs {
     /// use HTTP3
     #[clap(long, default_value = "false")]
     http3: bool,
+    #[clap(flatten)]
+    tls: TlsArgs,
 }
 
 /// Default client-mode args at the top-level (URL optional so subcommands don't require it)
@@ -106,21 +324,90 @@ struct TopClientArgs {
     /// Request body
     #[clap(short, long)]
     data: Option<String>,
+    /// Read the request body from a file, sent byte for byte
+    #[clap(long, conflicts_with = "data")]
+    data_file: Option<String>,
+    /// Send --data verbatim, without converting escape sequences
+    #[clap(long)]
+    data_binary: bool,
     /// Method
     #[clap(short, long)]
     method: Option<String>,
     /// Perform a HEAD request (similar to curl -I)
     #[clap(short = 'I', long)]
     head: bool,
-    /// Proxy to use
-    #[clap(short, long)]
+    /// Proxy to use (http:// is assumed when no scheme is given)
+    #[clap(short, long, value_parser = parse_proxy)]
     proxy: Option<String>,
+    /// Proxy user, or user:pass, kept out of the proxy URL
+    #[clap(long)]
+    proxy_user: Option<String>,
+    /// Proxy password, for a --proxy-user without one
+    #[clap(long, requires = "proxy_user")]
+    proxy_pass: Option<String>,
+    /// Hosts reached without a proxy, comma-separated (overrides NO_PROXY; `*` for all)
+    #[clap(long)]
+    noproxy: Option<String>,
     /// Headers (can be specified multiple times)
     #[clap(short = 'H', long)]
     header: Vec<String>,
+    /// Basic auth credentials as user:pass
+    #[clap(short, long, conflicts_with = "bearer")]
+    user: Option<String>,
+    /// Bearer token sent in the Authorization header
+    #[clap(long)]
+    bearer: Option<String>,
+    /// Print the response as one JSON object with protocol, status, headers and body
+    #[clap(long)]
+    json: bool,
+    /// Send the request this many times, printing one status line per response
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["raw", "location", "har"])]
+    repeat: u32,
+    /// Milliseconds to wait between --repeat requests
+    #[clap(long, default_value_t = 0, requires = "repeat")]
+    repeat_delay: u64,
     /// Trailers (can be specified multiple times)
     #[clap(short = 'T', long)]
     trailer: Vec<String>,
+    /// Send this file's raw HTTP/1.1 request as-is, ignoring method, body and header options
+    #[clap(long)]
+    raw: Option<String>,
+    /// Append the request and response to this HAR file
+    #[clap(long, conflicts_with = "raw")]
+    har: Option<String>,
+    /// Connect to this IP for host:port instead of resolving it, as host:port:ip; http:// targets only, since riphttplib takes SNI from the URL and an https handshake would name the IP (can be specified multiple times)
+    #[clap(long, value_parser = parse_resolve)]
+    resolve: Vec<(String, u16, IpAddr)>,
+    /// Connect to connect_host:connect_port for host:port, sending the original Host header, as host:port:connect_host:connect_port; http:// targets only, since riphttplib takes SNI from the URL and an https handshake would name the connect host (can be specified multiple times)
+    #[clap(long, value_parser = parse_connect_to)]
+    connect_to: Vec<(String, u16, String, u16)>,
+    /// With --raw, rewrite the Host line to the URL's authority
+    #[clap(long, requires = "raw")]
+    update_host: bool,
+    /// Follow redirects
+    #[clap(short = 'L', long, conflicts_with = "raw")]
+    location: bool,
+    /// Cookie sent with the request, as name=value (can be specified multiple times)
+    #[clap(short = 'b', long, conflicts_with = "raw")]
+    cookie: Vec<String>,
+    /// Keep cookies set by responses and send them on later requests (implied by -L and -b)
+    #[clap(long, conflicts_with = "raw")]
+    cookie_jar: bool,
+    /// Most redirects followed with --location before giving up
+    #[clap(long, default_value_t = DEFAULT_MAX_REDIRECTS, requires = "location")]
+    max_redirects: usize,
+    /// Let --location follow redirects from https to plain http
+    #[clap(long, requires = "location")]
+    unsafe_redirects: bool,
+    /// Give up connecting after this long (e.g. 500ms, 5s)
+    #[clap(long, value_parser = parse_duration)]
+    connect_timeout: Option<Duration>,
+    /// Give up waiting for the response after this long
+    #[clap(long, value_parser = parse_duration)]
+    read_timeout: Option<Duration>,
+    /// Give up sending the request after this long
+    #[clap(long, value_parser = parse_duration)]
+    write_timeout: Option<Duration>,
     /// use HTTP1
     #[clap(long, default_value = "false")]
     http1: bool,
@@ -130,89 +417,1364 @@ struct TopClientArgs {
     /// use HTTP3
     #[clap(long, default_value = "false")]
     http3: bool,
+    #[clap(flatten)]
+    tls: TlsArgs,
+}
+
+/// TLS settings for the handshake. riphttplib's clients build their TLS configuration themselves
+/// and take none of these from the caller yet, so a command given any of them fails up front
+/// instead of connecting without them.
+#[derive(clap::Args, Debug, Clone, Default)]
+struct TlsArgs {
+    /// TLS server name to send instead of the target's host (not supported by riphttplib yet)
+    #[clap(long, value_name = "NAME")]
+    sni: Option<String>,
+    /// PEM client certificate presented for mutual TLS (not supported by riphttplib yet)
+    #[clap(long, value_name = "FILE", requires = "key")]
+    cert: Option<String>,
+    /// PEM private key of --cert
+    #[clap(long, value_name = "FILE", requires = "cert")]
+    key: Option<String>,
+    /// PEM CA bundle trusted in addition to the built-in roots (not supported by riphttplib yet)
+    #[clap(long, value_name = "FILE")]
+    cacert: Option<String>,
+    /// Don't verify the server's certificate (not supported by riphttplib yet)
+    #[clap(short = 'k', long)]
+    insecure: bool,
+    /// Lowest TLS version to negotiate (not supported by riphttplib yet)
+    #[clap(long, value_enum, value_name = "VERSION")]
+    tls_min_version: Option<TlsVersion>,
+    /// Highest TLS version to negotiate (not supported by riphttplib yet)
+    #[clap(long, value_enum, value_name = "VERSION")]
+    tls_max_version: Option<TlsVersion>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TlsVersion {
+    #[value(name = "1.2")]
+    Tls12,
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+impl TlsArgs {
+    fn check(&self) -> Result<(), String> {
+        if let (Some(min), Some(max)) = (self.tls_min_version, self.tls_max_version)
+            && min > max
+        {
+            return Err("--tls-min-version can't be above --tls-max-version".to_string());
+        }
+        let unsupported = [
+            (
+                "--sni",
+                self.sni.is_some(),
+                "they always send the target URL's host as the server name",
+            ),
+            (
+                "--cert",
+                self.cert.is_some(),
+                "they never present a client certificate",
+            ),
+            (
+                "--cacert",
+                self.cacert.is_some(),
+                "they and detect_protocol verify against their own root store",
+            ),
+            (
+                "--insecure",
+                self.insecure,
+                "they and detect_protocol always verify the server's certificate",
+            ),
+            (
+                "--tls-min-version",
+                self.tls_min_version.is_some(),
+                "they negotiate the TLS version on their own",
+            ),
+            (
+                "--tls-max-version",
+                self.tls_max_version.is_some(),
+                "they negotiate the TLS version on their own",
+            ),
+        ];
+        for (flag, given, reason) in unsupported {
+            if given {
+                return Err(format!(
+                    "{} isn't supported yet: riphttplib's clients don't take TLS settings from riphttp, and {}",
+                    flag, reason
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Arguments for mass scanning
 #[derive(Parser, Debug)]
 struct ScanArgs {
-    /// Target file
-    #[clap(short, long, default_value = "targets.txt")]
-    targets: String,
-    /// Output file
+    /// Target file; repeat it or separate files with commas to scan several lists in order
+    #[clap(short, long, value_delimiter = ',', default_value = "targets.txt")]
+    targets: Vec<String>,
+    /// How the target files list their targets
+    #[clap(long, value_enum, default_value_t = TargetsFormat::Lines)]
+    targets_format: TargetsFormat,
+    /// CSV header name or 1-based column number, or JSON object field, holding the targets
+    #[clap(long, value_name = "COLUMN")]
+    targets_column: Option<String>,
+    /// Output file, or - for stdout
     #[clap(short, long, default_value = "output.txt")]
     output: String,
     /// Resume from a checkpoint created during a previous scan
     #[clap(long)]
     resume: bool,
+    /// Start at this target index (0-based) whatever the checkpoint says, appending to the output
+    #[clap(long, conflicts_with = "resume")]
+    resume_from: Option<usize>,
+    /// Start a fresh scan but append to the output file instead of truncating it
+    #[clap(long, conflicts_with = "resume")]
+    append: bool,
+    /// Checkpoint file of this scan
+    #[clap(long, default_value = "checkpoint", conflicts_with = "run_name")]
+    checkpoint: PathBuf,
+    /// Name of this scan, kept in the checkpoint file checkpoint-<NAME>
+    #[clap(long)]
+    run_name: Option<String>,
+    /// Keep findings, checkpoint, summary and transcripts in a new timestamped directory under this one (with --resume: the run directory to continue)
+    #[clap(long, conflicts_with_all = ["output", "checkpoint", "run_name", "summary_json", "save_transcripts"])]
+    output_dir: Option<PathBuf>,
+    /// Resume even if the checkpoint was written by a scan through a different proxy
+    #[clap(long, requires = "resume")]
+    force: bool,
+    /// Keep the checkpoint of a completed scan as <CHECKPOINT>.done instead of deleting it
+    #[clap(long)]
+    keep_checkpoint: bool,
+    /// How the checkpoint is written; binary also kicks in by itself for very large completed sets
+    #[clap(long, value_enum, default_value_t = CheckpointFormat::Text)]
+    checkpoint_format: CheckpointFormat,
     /// Number of threads
     #[clap(long, default_value = "50")]
     threads: usize,
-    /// Proxy to use
+    /// Lower the number of threads when connection errors spike, ramping back up as they recover
     #[clap(long)]
+    adaptive_concurrency: bool,
+    /// Lowest number of threads adaptive concurrency may drop to
+    #[clap(long, default_value = "5", requires = "adaptive_concurrency")]
+    min_threads: usize,
+    /// Proxy to use (http:// is assumed when no scheme is given)
+    #[clap(long, value_parser = parse_proxy)]
     proxy: Option<String>,
-    /// Scanner mode to use
-    #[clap(long, value_enum, default_value_t = ScanMode::TrailMerge)]
-    mode: ScanMode,
+    /// File with one proxy per line, rotated across targets
+    #[clap(long, conflicts_with = "proxy")]
+    proxy_list: Option<String>,
+    /// Scan through the proxy in HTTPS_PROXY/HTTP_PROXY, which scans ignore otherwise
+    #[clap(long, conflicts_with_all = ["proxy", "proxy_list"])]
+    env_proxy: bool,
+    /// How proxies from --proxy-list are assigned to targets
+    #[clap(long, value_enum, default_value_t = ProxyRotation::RoundRobin)]
+    proxy_rotation: ProxyRotation,
+    /// Proxy user, or user:pass, kept out of the proxy URL and the checkpoint
+    #[clap(long)]
+    proxy_user: Option<String>,
+    /// Proxy password, for a --proxy-user without one
+    #[clap(long, requires = "proxy_user")]
+    proxy_pass: Option<String>,
+    /// Hosts reached without a proxy, comma-separated (overrides NO_PROXY; `*` for all)
+    #[clap(long)]
+    noproxy: Option<String>,
+    /// Scan only this share of the targets, e.g. 1/10 for one in ten
+    #[clap(long, value_parser = parse_sample, conflicts_with = "sample_count")]
+    sample: Option<(usize, usize)>,
+    /// Scan this many targets picked at random, kept in file order
+    #[clap(long)]
+    sample_count: Option<usize>,
+    /// Seed for --sample-count (saved in the checkpoint; derived from --seed by default)
+    #[clap(long, requires = "sample_count")]
+    sample_seed: Option<u64>,
+    /// Ports to scan on targets that don't specify one (e.g. 80,8080,8443)
+    #[clap(long, value_delimiter = ',')]
+    ports: Vec<u16>,
+    /// Lowercase hosts, drop default ports and trailing slashes, and scan targets that end up equal once
+    #[clap(long)]
+    canonicalize: bool,
+    /// Only scan targets whose host is listed in this file (domains with their subdomains, IPs, CIDRs)
+    #[clap(long)]
+    scope: Option<String>,
+    /// Never scan targets whose host is listed in this file (same format as --scope)
+    #[clap(long)]
+    exclude: Option<String>,
+    /// File with paths to probe on every target, one per line (default: the target's own path)
+    #[clap(long)]
+    paths: Option<String>,
+    /// Host header to send instead of the target's (connections still go to the target)
+    #[clap(long)]
+    vhost: Option<String>,
+    /// Scanner mode to use; `help` lists the available modes
+    #[clap(long, default_value = "trail-merge")]
+    mode: String,
+    /// List the available scanner modes and exit
+    #[clap(long)]
+    list_modes: bool,
+    /// Request sequence and detect rules --mode template runs (see "Request templates" in the README)
+    #[clap(long, visible_alias = "request-template")]
+    template: Option<String>,
+    /// Only send each target's baseline request and record its status, without any attack payloads
+    #[clap(long, conflicts_with = "mode")]
+    head_only: bool,
+    /// Stop scheduling new targets after this long (e.g. 90s, 30m, 2h)
+    #[clap(long, value_parser = parse_duration)]
+    max_runtime: Option<Duration>,
+    /// Stop scheduling new targets once this many have findings; --resume continues the list
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    stop_after_findings: Option<u32>,
+    /// Record a target whose scan fails and go on; `false` ends the scan on the first failure
+    #[clap(long, default_value_t = true, action = ArgAction::Set)]
+    continue_on_error: bool,
+    /// Connect timeout of every request, replacing each module's default (e.g. 500ms, 5s)
+    #[clap(long, value_parser = parse_duration)]
+    connect_timeout: Option<Duration>,
+    /// Read timeout of every request, replacing each module's default
+    #[clap(long, value_parser = parse_duration)]
+    read_timeout: Option<Duration>,
+    /// Write timeout of every request, replacing each module's default
+    #[clap(long, value_parser = parse_duration)]
+    write_timeout: Option<Duration>,
+    /// Path requested by the smuggled sub-request (trail-smug, te-cl, cl-te)
+    #[clap(long, default_value = DEFAULT_SMUGGLE_PATH)]
+    smuggle_path: String,
+    /// Smuggle a random, most likely nonexistent path for every target instead of --smuggle-path
+    #[clap(long)]
+    random_smuggle_path: bool,
+    /// Put a unique marker in the path of smuggled sub-requests and report a follow-up that echoes it; made up per target unless a value is given
+    #[clap(long, num_args = 0..=1, value_name = "MARKER", value_parser = parse_marker)]
+    echo_marker: Option<Option<String>>,
+    /// Method of the smuggled sub-request, e.g. GET (default: TRACE for trail-smug and te-cl, GET for cl-te)
+    #[clap(long)]
+    smuggle_method: Option<String>,
+    /// Skip targets whose baseline answers with one of these statuses (replaces the built-in list)
+    #[clap(long, value_delimiter = ',')]
+    skip_baseline_status: Vec<u16>,
+    /// Re-send a baseline answered with one of these statuses, backing off, and skip the target if it keeps answering them, e.g. 502,503
+    #[clap(long, value_delimiter = ',')]
+    retry_on_status: Vec<u16>,
+    /// Times --retry-on-status re-sends a baseline before skipping the target
+    #[clap(long, default_value_t = DEFAULT_BASELINE_RETRIES)]
+    baseline_retries: usize,
+    /// Re-send a target's first baseline this many times, 500ms apart, when it fails to connect or answer, before giving up on the target
+    #[clap(long, default_value_t = 0)]
+    baseline_error_retries: usize,
+    /// Throwaway baseline requests sent to each target before the real baseline, to warm caches
+    #[clap(long, default_value_t = 0)]
+    warmup: usize,
+    /// Never report a follow-up with one of these statuses as a difference (replaces the built-in list)
+    #[clap(long, value_delimiter = ',')]
+    ignore_diff_status: Vec<u16>,
+    /// Only report a difference when the status changes like this, e.g. 200:500-599 or 2xx:5xx; replaces --ignore-diff-status (can be specified multiple times)
+    #[clap(long, value_delimiter = ',', conflicts_with = "ignore_diff_status")]
+    report_change: Vec<StatusChange>,
+    /// Report a trail-merge probe answered with one of these statuses (replaces the default 100,504)
+    #[clap(long, value_delimiter = ',')]
+    filter_status: Vec<u16>,
+    /// Drop findings scored below this confidence (findings without a score are kept)
+    #[clap(long, value_enum)]
+    min_confidence: Option<Confidence>,
+    /// Methods trail-merge probes every target with, comma-separated (default: POST)
+    #[clap(long, value_delimiter = ',')]
+    methods: Vec<String>,
+    /// Report a trail-merge or te-cl attack answered after this many milliseconds as a stall (default: 90% of the read timeout)
+    #[clap(long)]
+    timeout_threshold: Option<u64>,
+    /// Check that a target accepts trailers before the trail-merge stalling probe, reporting rejected and clean targets too
+    #[clap(long)]
+    classify_trailers: bool,
+    /// Trailer the trail-merge attack probe sends instead of its built-in ones, as "name: value" (can be specified multiple times)
+    #[clap(long)]
+    trailer: Vec<String>,
+    /// Body of the trail-merge and h2-smug probes, baseline included, in place of their filler (e.g. '{"id":1}')
+    #[clap(long)]
+    baseline_body: Option<String>,
+    /// Read --baseline-body from a file, sent byte for byte
+    #[clap(long, conflicts_with = "baseline_body")]
+    baseline_body_file: Option<String>,
+    /// Keep at most this much of each response body (e.g. 64K; 0 keeps bodies whole)
+    #[clap(long, default_value = "1M", value_parser = parse_size)]
+    max_response_size: u64,
+    /// Which parts of the follow-up response are compared against the baseline
+    #[clap(long, value_enum, default_value_t = DiffMode::Status)]
+    diff_mode: DiffMode,
+    /// Also report a follow-up whose body length is off the baseline's by more than this many bytes
+    #[clap(long)]
+    length_tolerance: Option<usize>,
+    /// Extra regex stripped from bodies before diffing (can be specified multiple times)
+    #[clap(long)]
+    diff_strip: Vec<String>,
+    /// Check that every target parses, report the bad lines and exit without scanning
+    #[clap(long)]
+    validate_only: bool,
+    /// Load and filter the targets like a scan would, print how many each step leaves and exit without scanning
+    #[clap(long, conflicts_with_all = ["validate_only", "show_config", "resume"])]
+    count_only: bool,
+    /// Print the settings the scan would run with as JSON and exit without scanning
+    #[clap(long)]
+    show_config: bool,
+    /// Print the payloads each target would receive without sending anything
+    #[clap(long)]
+    dry_run: bool,
+    /// Exit with status 2 when any finding is recorded
+    #[clap(long)]
+    fail_on_finding: bool,
+    /// Connect to this IP for host:port instead of resolving it, as host:port:ip; http:// targets only, since riphttplib takes SNI from the URL and an https handshake would name the IP (can be specified multiple times)
+    #[clap(long, value_parser = parse_resolve)]
+    resolve: Vec<(String, u16, IpAddr)>,
+    /// Connect to connect_host:connect_port for host:port, sending the original Host header, as host:port:connect_host:connect_port; http:// targets only, since riphttplib takes SNI from the URL and an https handshake would name the connect host (can be specified multiple times)
+    #[clap(long, value_parser = parse_connect_to)]
+    connect_to: Vec<(String, u16, String, u16)>,
+    /// Don't resolve each target's host through the in-process cache before scanning it, which skips those that don't resolve and connects http targets to the cached address
+    #[clap(long)]
+    no_dns_cache: bool,
+    /// Seconds a DNS cache lookup is cached for (failed lookups are cached for at most 30s)
+    #[clap(long, default_value_t = DEFAULT_DNS_CACHE_TTL.as_secs(), conflicts_with = "no_dns_cache")]
+    dns_cache_ttl: u64,
+    /// Scan at most this many targets of one host at once; other hosts aren't held up
+    #[clap(long)]
+    max_connections_per_host: Option<usize>,
+    /// Give up on a target after this many attack payloads in a row fail to connect; timeouts and resets don't count
+    #[clap(long, default_value_t = DEFAULT_MAX_CONSECUTIVE_ERRORS)]
+    max_errors: usize,
+    /// Send each trail-smug, te-cl and cl-te payload this many times; every follow-up must differ to report it
+    #[clap(long, default_value_t = DEFAULT_PROBES)]
+    probes: usize,
+    /// Save the payload and responses behind every finding to a file in this directory
+    #[clap(long)]
+    save_transcripts: Option<String>,
+    /// Don't show the progress bar (it is also hidden when not running in a terminal)
+    #[clap(short, long)]
+    quiet: bool,
+    /// How progress is reported; json prints progress and finding events to stderr
+    #[clap(long, value_enum, default_value_t = ProgressFormat::Bar)]
+    progress_format: ProgressFormat,
+    /// Show a full-screen dashboard of the scan instead of the progress bar (the bar is kept when not running in a terminal; needs a build with the tui feature)
+    #[clap(long, conflicts_with = "progress_format")]
+    tui: bool,
+    /// Serve Prometheus metrics of the scan on http://<ADDR>/metrics, e.g. 127.0.0.1:9100
+    #[clap(long)]
+    metrics_addr: Option<SocketAddr>,
+    /// Only scan over HTTP/1.1
+    #[clap(long)]
+    http1: bool,
+    /// Only scan over HTTP/2 (including cleartext h2c)
+    #[clap(long)]
+    http2: bool,
+    /// Only scan over HTTP/3
+    #[clap(long)]
+    http3: bool,
+    /// Never scan over this protocol, e.g. h3 on flaky QUIC paths (can be specified multiple times)
+    #[clap(long, value_enum)]
+    exclude_protocol: Vec<ProtocolName>,
+    /// Retry failed HTTP/3 probes over HTTP/2 or HTTP/1.1 when the target isn't scanned over TCP
+    #[clap(long)]
+    h3_fallback: bool,
+    /// Results buffered for the output writer before the scan waits for it to catch up
+    #[clap(long, default_value_t = DEFAULT_RECORDER_BUFFER)]
+    recorder_buffer: usize,
+    /// Warn when this many results are held back waiting on an unfinished target
+    #[clap(long, default_value_t = DEFAULT_PENDING_WARN)]
+    pending_warn: usize,
+    /// Skip past unfinished targets once this many results are held back; theirs are appended later
+    #[clap(long)]
+    max_pending: Option<usize>,
+    /// Finding layout: default, nuclei-like, grepable, oneline, or a template using {target}, {module}, {status}, {confidence}, {tag} and {message}
+    #[clap(long, default_value = "default")]
+    format_template: String,
+    /// Write each finding on exactly one line (same as --format-template oneline)
+    #[clap(long, conflicts_with = "format_template")]
+    oneline: bool,
+    /// Follow each finding with the headers of the response that gave it away
+    #[clap(long)]
+    capture_headers: bool,
+    /// Once the scan completes, rewrite the output ordered by this key (needs --format-template grepable)
+    #[clap(long, value_enum, conflicts_with_all = ["oneline", "compress", "max_output_size"])]
+    sort: Option<SortKey>,
+    /// Gzip the output file (implied when it ends in .gz)
+    #[clap(long)]
+    compress: bool,
+    /// Roll the output over to output.1.txt, output.2.txt, ... once it grows past this size (e.g. 100M)
+    #[clap(long, value_parser = parse_size)]
+    max_output_size: Option<u64>,
+    /// Cut recorded lines longer than this many bytes, ending them in a truncation marker
+    #[clap(long, value_parser = clap::value_parser!(u32).range(32..))]
+    max_line_length: Option<u32>,
+    /// Prefix each recorded finding with the UTC time its target finished (RFC 3339)
+    #[clap(long)]
+    timestamps: bool,
+    /// Write targets that fail to parse to this file, one per line
+    #[clap(long)]
+    invalid_targets: Option<String>,
+    /// Drop findings already recorded in this run for the same host and mode
+    #[clap(long)]
+    dedup_findings: bool,
+    /// Also log every scanned target with its outcome (finding, clean or error) to this file
+    #[clap(long)]
+    all_results: Option<String>,
+    /// Write totals, error counts and timings for the run to this JSON file
+    #[clap(long)]
+    summary_json: Option<String>,
+    /// POST every finding as JSON to this URL
+    #[clap(long)]
+    webhook: Option<String>,
+    /// Header sent with webhook requests, e.g. for auth (can be specified multiple times)
+    #[clap(long, requires = "webhook")]
+    webhook_header: Vec<String>,
+    /// Also upsert findings into this SQLite database, deduplicated across runs (needs a build with the sqlite feature)
+    #[clap(long)]
+    sqlite: Option<String>,
+    /// Pick each request's User-Agent from this file, one per line
+    #[clap(long, conflicts_with = "random_ua")]
+    user_agent_list: Option<String>,
+    /// Pick each request's User-Agent from a built-in list of browsers
+    #[clap(long)]
+    random_ua: bool,
+    /// Seed for the User-Agent rotation (saved in the checkpoint; derived from --seed by default)
+    #[clap(long)]
+    ua_seed: Option<u64>,
+    /// Stamp every request with this header carrying an id unique to the run, e.g. X-Scan-Id
+    #[clap(long, value_parser = parse_header_name)]
+    correlation_header: Option<String>,
+    /// Wait a random 0 to this many milliseconds before each baseline and attack request
+    #[clap(long)]
+    jitter: Option<u64>,
+    /// Seed for --jitter (derived from --seed by default)
+    #[clap(long, requires = "jitter")]
+    jitter_seed: Option<u64>,
+    /// Order attack payloads are sent in: as listed, or shuffled per target
+    #[clap(long, value_enum, default_value_t = ProbeOrder::Fixed)]
+    probe_order: ProbeOrder,
+    /// Seed for --probe-order random (saved in the checkpoint; derived from --seed by default)
+    #[clap(long)]
+    probe_seed: Option<u64>,
+    /// Seed every randomized feature derives its own from (saved in the checkpoint and summary; random and printed by default)
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Start a new target at most once per this many milliseconds, whatever the thread count
+    #[clap(long)]
+    delay_between_targets: Option<u64>,
+    #[clap(flatten)]
+    tls: TlsArgs,
+}
+
+/// Attack orders accepted by --probe-order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ProbeOrder {
+    /// Every target gets the attacks in the same order
+    Fixed,
+    /// Each target gets its own order, drawn from --probe-seed
+    Random,
 }
 
+/// Protocol names accepted by --exclude-protocol.
 #[derive(Clone, Copy, Debug, ValueEnum)]
-enum ScanMode {
-    TrailMerge,
-    TrailSmug
+enum ProtocolName {
+    H1,
+    H2,
+    H2c,
+    H3,
 }
 
-impl fmt::Display for ScanMode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl ProtocolName {
+    fn http_protocol(self) -> HttpProtocol {
         match self {
-            ScanMode::TrailMerge => write!(f, "TrailMerge"),
-            ScanMode::TrailSmug => write!(f, "TrailSmug"),
+            ProtocolName::H1 => HttpProtocol::Http1,
+            ProtocolName::H2 => HttpProtocol::Http2,
+            ProtocolName::H2c => HttpProtocol::H2C,
+            ProtocolName::H3 => HttpProtocol::Http3,
         }
     }
 }
 
+/// Parses the command line. Global args given before the subcommand are moved after its name
+/// first: args_conflicts_with_subcommands counts them like any other top-level arg, so
+/// `riphttp -v scan` would otherwise take `scan` for the client's URL.
+fn parse_args() -> Args {
+    Args::parse_from(subcommand_first(std::env::args_os().collect()))
+}
+
+/// `args` with a run of global args right before a subcommand name moved after it, e.g.
+/// `riphttp -v --no-color scan ...` to `riphttp scan -v --no-color ...`.
+fn subcommand_first(mut args: Vec<OsString>) -> Vec<OsString> {
+    let command = Args::command();
+    let globals: Vec<&clap::Arg> = command
+        .get_arguments()
+        .filter(|arg| arg.is_global_set())
+        .collect();
+    let is_global_short = |flag: char| {
+        globals
+            .iter()
+            .any(|arg| arg.get_short() == Some(flag) && !arg.get_action().takes_values())
+    };
+
+    let mut index = 1;
+    while let Some(arg) = args.get(index).and_then(|arg| arg.to_str()) {
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (long, false),
+            };
+            let Some(global) = globals.iter().find(|arg| arg.get_long() == Some(name)) else {
+                break;
+            };
+            index += if global.get_action().takes_values() && !inline_value {
+                2
+            } else {
+                1
+            };
+        } else if let Some(shorts) = arg.strip_prefix('-')
+            && !shorts.is_empty()
+            && shorts.chars().all(is_global_short)
+        {
+            index += 1;
+        } else {
+            break;
+        }
+    }
+
+    if index > 1 && index < args.len() && command.find_subcommand(&args[index]).is_some() {
+        args[1..=index].rotate_right(1);
+    }
+    args
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let args = parse_args();
 
     // Set global verbose flag
     set_verbose(args.verbose);
+    set_color(color_wanted(args.no_color));
+    // Flushes the exported spans when main returns.
+    let _telemetry = init_tracing(
+        args.log_file.as_deref().map(|path| (path, args.log_level)),
+        args.otlp_endpoint.as_deref(),
+    )?;
 
     match args.command {
         Some(Commands::Client(client_args)) => {
-            run_protocol_command(client_args).await?;
+            run_protocol_command(*client_args).await?;
+        }
+        Some(Commands::Replay(replay_args)) => {
+            return run_replay(replay_args).await;
+        }
+        Some(Commands::Checkpoint(checkpoint_args)) => {
+            return run_checkpoint(checkpoint_args).await;
+        }
+        Some(Commands::Diff(diff_args)) => {
+            return run_diff(diff_args).await;
         }
         Some(Commands::Scan(scan_args)) => {
+            scan_args.tls.check()?;
+            // --show-config prints its JSON alone on stdout, like findings with -o -.
+            set_stdout_reserved(
+                (scan_args.output == STDOUT_PATH && !scan_args.dry_run) || scan_args.show_config,
+            );
             if is_verbose() {
-                println!("Running trailers scan in verbose mode");
+                status!("Running trailers scan in verbose mode");
             }
 
             let ScanArgs {
-                targets: targets_path,
+                targets: target_files,
+                targets_format,
+                targets_column,
                 output,
                 resume,
+                resume_from,
+                append,
+                checkpoint,
+                run_name,
+                output_dir,
+                force,
+                keep_checkpoint,
+                checkpoint_format,
                 threads,
+                adaptive_concurrency,
+                min_threads,
                 proxy,
+                proxy_list,
+                env_proxy,
+                proxy_rotation,
+                proxy_user,
+                proxy_pass,
+                noproxy,
+                sample,
+                sample_count,
+                sample_seed,
+                ports,
+                canonicalize,
+                scope,
+                exclude,
+                paths,
+                vhost,
                 mode,
-            } = scan_args;
+                list_modes,
+                head_only,
+                max_runtime,
+                stop_after_findings,
+                continue_on_error,
+                connect_timeout,
+                read_timeout,
+                write_timeout,
+                smuggle_path,
+                random_smuggle_path,
+                echo_marker,
+                smuggle_method,
+                skip_baseline_status,
+                retry_on_status,
+                baseline_retries,
+                baseline_error_retries,
+                warmup,
+                ignore_diff_status,
+                report_change,
+                filter_status,
+                min_confidence,
+                methods,
+                timeout_threshold,
+                classify_trailers,
+                trailer,
+                baseline_body,
+                baseline_body_file,
+                template,
+                max_response_size,
+                diff_mode,
+                length_tolerance,
+                diff_strip,
+                validate_only,
+                count_only,
+                show_config,
+                dry_run,
+                fail_on_finding,
+                resolve,
+                connect_to,
+                no_dns_cache,
+                dns_cache_ttl,
+                max_connections_per_host,
+                max_errors,
+                probes,
+                save_transcripts,
+                quiet,
+                progress_format,
+                tui,
+                metrics_addr,
+                http1,
+                http2,
+                http3,
+                exclude_protocol,
+                h3_fallback,
+                recorder_buffer,
+                pending_warn,
+                max_pending,
+                format_template,
+                oneline,
+                capture_headers,
+                sort,
+                compress,
+                max_output_size,
+                max_line_length,
+                timestamps,
+                invalid_targets,
+                dedup_findings,
+                all_results,
+                summary_json,
+                webhook,
+                webhook_header,
+                sqlite,
+                user_agent_list,
+                random_ua,
+                ua_seed,
+                correlation_header,
+                jitter,
+                jitter_seed,
+                probe_order,
+                probe_seed,
+                seed,
+                delay_between_targets,
+                tls: _,
+            } = *scan_args;
+
+            if output == STDOUT_PATH
+                && !dry_run
+                && (resume || resume_from.is_some() || append || max_output_size.is_some())
+            {
+                return Err("--output - can't be combined with --resume, --resume-from, --append or --max-output-size".into());
+            }
+            if tui && !cfg!(feature = "tui") {
+                return Err(
+                    "--tui needs a build with the tui feature (cargo build --release --features tui)"
+                        .into(),
+                );
+            }
+            if sort.is_some() {
+                if format_template != "grepable" {
+                    return Err("--sort needs --format-template grepable".into());
+                }
+                if output == STDOUT_PATH || output.ends_with(".gz") {
+                    return Err("--sort needs an uncompressed output file".into());
+                }
+            }
+
+            // Nothing is sent with --show-config or --count-only either, so they skip what a dry
+            // run skips: no webhook, metrics endpoint, database or run directory.
+            let dry_run = dry_run || show_config || count_only;
+
+            let registry = ModeRegistry::builtin();
+            if list_modes || mode == "help" {
+                println!("{}", registry.help());
+                return Ok(ExitCode::SUCCESS);
+            }
+            let selected = if head_only {
+                Some(census_mode())
+            } else {
+                registry.get(&mode).cloned()
+            };
+            let mode = selected.ok_or_else(|| {
+                let names: Vec<&str> = registry.modes().iter().map(|mode| mode.name).collect();
+                format!(
+                    "Unknown mode '{}', expected one of: {}",
+                    mode,
+                    names.join(", ")
+                )
+            })?;
+            let template = match (&template, mode.name) {
+                (Some(path), "template") => {
+                    Some(Arc::new(RequestTemplate::load(path).map_err(|err| {
+                        format!("failed to load template '{}': {}", path, err)
+                    })?))
+                }
+                (None, "template") => return Err("--mode template needs --template".into()),
+                (Some(_), _) => return Err("--template only applies to --mode template".into()),
+                (None, _) => None,
+            };
+
+            if targets_column.is_some() && targets_format == TargetsFormat::Lines {
+                return Err("--targets-column only applies to --targets-format csv or json".into());
+            }
+            let layout = TargetsLayout {
+                format: targets_format,
+                column: targets_column,
+            };
 
-            let targets = load_targets(&targets_path).await?;
+            // Saved in the checkpoint, so a resume has to name the same files in the same order.
+            let targets_path = target_files.join(",");
+
+            if validate_only {
+                let mut invalid = Vec::new();
+                for file in &target_files {
+                    let rejected = validate_targets(file, &layout)?;
+                    for rejected in &rejected {
+                        println!(
+                            "{}:{}: {}: {}",
+                            file, rejected.line, rejected.target, rejected.reason
+                        );
+                    }
+                    invalid.extend(rejected);
+                }
+                if invalid.is_empty() {
+                    println!("All targets in '{}' are valid", targets_path);
+                    return Ok(ExitCode::SUCCESS);
+                }
+                println!("{} invalid targets in '{}'", invalid.len(), targets_path);
+                return Ok(ExitCode::FAILURE);
+            }
+
+            let protocol = if http1 || http2 || http3 {
+                let selected = determine_protocol(http1, http2, http3)?;
+                if !matches!(selected, SelectedProtocol::Http1) && mode.http1_only {
+                    return Err(format!(
+                        "{} only speaks HTTP/1.1 and can't be restricted to {}",
+                        mode.name,
+                        selected.http_version()
+                    )
+                    .into());
+                }
+                Some(selected.http_protocol())
+            } else {
+                None
+            };
+
+            let tagged = load_target_files(&target_files, &layout).await?;
+            status!("Loaded {} targets", tagged.len());
+            // What each loading step leaves, for --count-only.
+            let mut counts = Vec::new();
+            if count_only {
+                if targets_format == TargetsFormat::Lines {
+                    let (lines, entries) = count_target_lines(&target_files)?;
+                    counts.push(("Lines", lines));
+                    counts.push(("Non-empty lines", entries));
+                } else {
+                    let mut entries = 0;
+                    for file in &target_files {
+                        entries += read_target_entries(file, &layout)?.len();
+                    }
+                    counts.push(("Entries", entries));
+                }
+                counts.push(("After CIDR expansion", tagged.len()));
+            }
+            let tags = Arc::new(target_tags(&tagged, &ports));
+            let targets = tagged.into_iter().map(|(target, _)| target).collect();
+            let targets = expand_ports(targets, &ports);
+            if !ports.is_empty() {
+                status!("Scanning {} target/port combinations", targets.len());
+                counts.push(("After --ports", targets.len()));
+            }
+            let targets = if canonicalize {
+                let (targets, merged) = canonicalize_targets(targets);
+                if merged > 0 {
+                    status!(
+                        "{} targets canonicalized to an earlier entry and were dropped, {} left",
+                        merged,
+                        targets.len()
+                    );
+                }
+                counts.push(("After --canonicalize", targets.len()));
+                targets
+            } else {
+                targets
+            };
+            let targets = if scope.is_some() || exclude.is_some() {
+                let scope = scope.as_deref().map(HostList::load).transpose()?;
+                let exclude = exclude.as_deref().map(HostList::load).transpose()?;
+                let (targets, dropped) = filter_scope(targets, scope.as_ref(), exclude.as_ref());
+                if dropped > 0 {
+                    status!(
+                        "{} targets out of scope were dropped, {} left",
+                        dropped,
+                        targets.len()
+                    );
+                }
+                counts.push(("After scope filter", targets.len()));
+                targets
+            } else {
+                targets
+            };
+            // Everything the run leaves behind goes into one directory: a new one per scan, or
+            // the given one when resuming it.
+            let (output, checkpoint, summary_json, save_transcripts) = match output_dir {
+                Some(dir) if !dry_run => {
+                    let run_dir = if resume {
+                        if !dir.is_dir() {
+                            return Err(format!(
+                                "Run directory '{}' does not exist",
+                                dir.display()
+                            )
+                            .into());
+                        }
+                        dir
+                    } else {
+                        std::fs::create_dir_all(&dir).map_err(|err| {
+                            format!("failed to create '{}': {}", dir.display(), err)
+                        })?;
+                        let run_dir = dir.join(Utc::now().format("%Y%m%dT%H%M%SZ").to_string());
+                        std::fs::create_dir(&run_dir).map_err(|err| {
+                            format!("failed to create '{}': {}", run_dir.display(), err)
+                        })?;
+                        run_dir
+                    };
+                    status!("Keeping this run in {}", run_dir.display());
+                    let artifact = |name: &str| run_dir.join(name).to_string_lossy().into_owned();
+                    (
+                        artifact("findings.txt"),
+                        run_dir.join("checkpoint"),
+                        Some(artifact("summary.json")),
+                        Some(artifact("transcripts")),
+                    )
+                }
+                _ => (output, checkpoint, summary_json, save_transcripts),
+            };
+            let checkpoint_path = match &run_name {
+                Some(name) => named_checkpoint_path(name),
+                None => checkpoint,
+            };
+            let resumed = match resume {
+                true => read_resumable_checkpoint(&checkpoint_path).await?,
+                false => None,
+            };
+            // Seeds not given on their own are derived from this one, so passing it again
+            // repeats the scan's random choices; a resume reuses the checkpoint's.
+            let seed = seed
+                .or_else(|| resumed.as_ref().and_then(|checkpoint| checkpoint.seed))
+                .unwrap_or_else(rand::random);
+            status!("Using seed {}", seed);
+            // The sample is taken before indices are assigned, so a resume sees the same list.
+            let sample_seed = sample_count.map(|_| {
+                sample_seed
+                    .or_else(|| {
+                        resumed
+                            .as_ref()
+                            .and_then(|checkpoint| checkpoint.sample_seed)
+                    })
+                    .unwrap_or_else(|| derive_seed(seed, "sample"))
+            });
+            let available = targets.len();
+            let targets = match (sample, sample_count, sample_seed) {
+                (Some((take, every)), _, _) => targets
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| index % every < take)
+                    .map(|(_, target)| target)
+                    .collect(),
+                (None, Some(count), Some(seed)) => sample_targets(targets, count, seed),
+                _ => targets,
+            };
             let total_targets = targets.len();
-            println!("Loaded {} targets", total_targets);
-            println!("Using {} threads", threads);
-            println!("Scanner mode: {:?}", mode);
+            if let Some(seed) = sample_seed {
+                status!(
+                    "Sampled {} of {} targets (seed {})",
+                    total_targets,
+                    available,
+                    seed
+                );
+            } else if sample.is_some() {
+                status!("Sampled {} of {} targets", total_targets, available);
+            }
+            if count_only {
+                if sample.is_some() || sample_count.is_some() {
+                    counts.push(("After sampling", total_targets));
+                }
+                let width = counts
+                    .iter()
+                    .map(|(label, _)| label.len())
+                    .max()
+                    .unwrap_or(0);
+                for (label, count) in counts {
+                    println!("{:<width$}  {}", label, count, width = width);
+                }
+                println!("Targets to scan: {}", total_targets);
+                return Ok(ExitCode::SUCCESS);
+            }
+            status!("Using {} threads", threads);
+            let adaptive = adaptive_concurrency.then(|| {
+                status!(
+                    "Adaptive concurrency: {} to {} threads",
+                    min_threads,
+                    threads
+                );
+                AdaptiveConcurrency {
+                    min: min_threads,
+                    max: threads,
+                }
+            });
+            status!("Scanner mode: {}", mode.label);
+            if let Some(protocol) = &protocol {
+                status!("Only scanning over {}", protocol);
+            }
+            let excluded_protocols: Vec<HttpProtocol> = exclude_protocol
+                .into_iter()
+                .map(ProtocolName::http_protocol)
+                .collect();
+            if !excluded_protocols.is_empty() {
+                let names: Vec<String> = excluded_protocols.iter().map(|p| p.to_string()).collect();
+                status!("Not scanning over {}", names.join(", "));
+            }
+
+            // Recorded in the checkpoint so a resume can tell it would change the egress.
+            let proxy_label = proxy
+                .as_deref()
+                .map(redact_proxy)
+                .or_else(|| proxy_list.as_ref().map(|path| format!("list:{}", path)));
+            let credentials = proxy_user
+                .as_deref()
+                .map(|user| ProxyCredentials::new(user, proxy_pass.as_deref()))
+                .transpose()?;
+            let proxies = match (proxy, proxy_list) {
+                (Some(proxy), _) => {
+                    status!("Using proxy: {}", redact_proxy(&proxy));
+                    Some(ProxyPool::new(
+                        vec![proxy],
+                        proxy_rotation,
+                        derive_seed(seed, "proxy-rotation"),
+                    ))
+                }
+                (None, Some(path)) => {
+                    let pool = ProxyPool::load(
+                        &path,
+                        proxy_rotation,
+                        derive_seed(seed, "proxy-rotation"),
+                    )?;
+                    status!(
+                        "Loaded {} proxies ({:?} rotation)",
+                        pool.len(),
+                        proxy_rotation
+                    );
+                    Some(pool)
+                }
+                (None, None) => None,
+            };
+            // Scans only pick up the environment's proxy when asked to, so a shell that happens to
+            // export one doesn't change where the payloads come from.
+            let env_proxies = if env_proxy {
+                EnvProxies::from_env()
+            } else {
+                EnvProxies::default()
+            };
+            if env_proxy && env_proxies.is_empty() {
+                return Err("--env-proxy needs HTTPS_PROXY or HTTP_PROXY to be set".into());
+            }
+            // Their attacks would bypass the proxy and reach the target from this host.
+            if (proxies.is_some() || env_proxy) && mode.raw_payloads {
+                return Err(format!(
+                    "--mode {} sends payloads that can't go through a proxy; scan it without --proxy, --proxy-list or --env-proxy",
+                    mode.name
+                )
+                .into());
+            }
+            // riphttplib's protocol detection opens its own connections, so the target would
+            // see this host before any proxied request.
+            if (proxies.is_some() || env_proxy) && mode.detects_protocols {
+                return Err(format!(
+                    "--mode {} detects the target's protocols over a direct connection, which can't go through a proxy; scan it without --proxy, --proxy-list or --env-proxy",
+                    mode.name
+                )
+                .into());
+            }
+            let (proxies, env_proxies) = match &credentials {
+                Some(credentials) => (
+                    proxies
+                        .map(|pool| pool.with_credentials(credentials))
+                        .transpose()?,
+                    env_proxies.with_credentials(credentials)?,
+                ),
+                None => (proxies, env_proxies),
+            };
+            if credentials.is_some() && proxies.is_none() && env_proxies.is_empty() {
+                return Err(
+                    "--proxy-user needs a proxy: --proxy, --proxy-list or --env-proxy".into(),
+                );
+            }
+            if !env_proxies.is_empty() {
+                status!("Using proxy from HTTP_PROXY/HTTPS_PROXY");
+            }
+            // Kept to report the targets skipped once every proxy of the pool is dead.
+            let proxies = proxies.map(Arc::new);
+            // A proxy resolves the targets itself, and may well see names this host can't.
+            let dns = (!no_dns_cache && proxies.is_none() && env_proxies.is_empty())
+                .then(|| Arc::new(DnsCache::new(Duration::from_secs(dns_cache_ttl))));
+            let no_proxy = noproxy
+                .map(|list| NoProxy::parse(&list))
+                .unwrap_or_else(NoProxy::from_env);
+            let paths = match paths {
+                Some(path) => {
+                    let paths = load_paths(&path)?;
+                    status!("Loaded {} paths per target", paths.len());
+                    paths
+                }
+                None => Vec::new(),
+            };
+            if let Some(ref vhost) = vhost {
+                status!("Using Host: {}", vhost);
+            }
+            if let Some(runtime) = max_runtime {
+                status!("Maximum runtime: {}s", runtime.as_secs());
+            }
+            let target_interval = delay_between_targets
+                .filter(|delay| *delay > 0)
+                .map(Duration::from_millis);
+            if let Some(interval) = target_interval {
+                status!(
+                    "Starting at most one target every {}ms",
+                    interval.as_millis()
+                );
+            }
+
+            let (webhook, webhook_worker) = match webhook {
+                Some(url) if !dry_run => {
+                    status!("Posting findings to {}", url);
+                    let (webhook, worker) =
+                        Webhook::spawn(url, parse_cli_headers(&webhook_header)?);
+                    (Some(Arc::new(webhook)), Some(worker))
+                }
+                _ => (None, None),
+            };
+
+            let metrics = match metrics_addr {
+                Some(addr) if !dry_run => {
+                    let endpoint = MetricsEndpoint::bind(addr)
+                        .await
+                        .map_err(|err| format!("Failed to serve metrics on {}: {}", addr, err))?;
+                    status!(
+                        "Serving metrics on http://{}/metrics",
+                        endpoint.local_addr()?
+                    );
+                    Some(endpoint)
+                }
+                _ => None,
+            };
+
+            let (database, database_writer) = match sqlite {
+                Some(path) if !dry_run => {
+                    let run_id = format!(
+                        "{}-{:08x}",
+                        Utc::now().format("%Y%m%dT%H%M%SZ"),
+                        rand::random::<u32>()
+                    );
+                    let (database, writer) = FindingDb::open(&path, run_id.clone())
+                        .map_err(|err| format!("failed to open '{}': {}", path, err))?;
+                    status!("Storing findings in {} (run {})", path, run_id);
+                    let database = database.with_correlation_header(correlation_header.as_deref());
+                    (Some(Arc::new(database)), Some(writer))
+                }
+                _ => (None, None),
+            };
 
-            if let Some(ref proxy) = proxy {
-                println!("Using proxy: {}", proxy);
+            let load_user_agents =
+                |seed: u64| -> Result<Option<Arc<UserAgentProvider>>, Box<dyn std::error::Error>> {
+                    Ok(match (&user_agent_list, random_ua) {
+                        (Some(path), _) => Some(Arc::new(UserAgentProvider::load(path, seed)?)),
+                        (None, true) => Some(Arc::new(UserAgentProvider::builtin(seed))),
+                        (None, false) => None,
+                    })
+                };
+            let user_agents =
+                load_user_agents(ua_seed.unwrap_or_else(|| derive_seed(seed, "user-agent")))?;
+            if let Some(pool) = &user_agents {
+                status!("Rotating {} user agents (seed {})", pool.len(), pool.seed());
+            }
+
+            let invalid_targets = Arc::new(
+                InvalidTargets::new(invalid_targets.as_deref(), (resume || append) && !dry_run)
+                    .map_err(|err| {
+                        format!(
+                            "failed to open invalid targets file '{}': {}",
+                            invalid_targets.as_deref().unwrap_or_default(),
+                            err
+                        )
+                    })?,
+            );
+
+            let baseline_body = match (baseline_body, &baseline_body_file) {
+                (Some(body), _) => Some(body),
+                (None, Some(path)) => Some(std::fs::read_to_string(path).map_err(|err| {
+                    format!("failed to read baseline body file '{}': {}", path, err)
+                })?),
+                (None, None) => None,
+            };
+            if let Some(body) = &baseline_body
+                && body.len() > MAX_BASELINE_BODY
+            {
+                return Err(format!(
+                    "--baseline-body is {} bytes, at most {} are allowed",
+                    body.len(),
+                    MAX_BASELINE_BODY
+                )
+                .into());
+            }
+
+            let mut module_options = ModuleOptions {
+                diff_mode,
+                length_tolerance,
+                normalizer: BodyNormalizer::new(&diff_strip)?,
+                dry_run,
+                proxies: proxies.clone(),
+                env_proxies: Arc::new(env_proxies),
+                no_proxy: Arc::new(no_proxy),
+                paths: Arc::new(paths),
+                vhost,
+                transcripts: match save_transcripts {
+                    Some(dir) if !dry_run => {
+                        status!("Saving finding transcripts to {}", dir);
+                        Some(Arc::new(TranscriptStore::new(&dir)?))
+                    }
+                    _ => None,
+                },
+                max_consecutive_errors: max_errors.max(1),
+                probes: probes.max(1),
+                resolve: Arc::new(ResolveOverrides::new(resolve).with_connect_to(connect_to)),
+                dns: dns.clone(),
+                host_limit: max_connections_per_host.map(|limit| Arc::new(HostLimiter::new(limit))),
+                protocol,
+                excluded_protocols,
+                h3_fallback,
+                formatter: finding_formatter(if oneline { "oneline" } else { &format_template }),
+                webhook: webhook.clone(),
+                database: database.clone(),
+                user_agents,
+                invalid_targets: Arc::clone(&invalid_targets),
+                smuggle_path,
+                random_smuggle_path,
+                marker_seed: derive_seed(seed, "markers"),
+                echo_marker,
+                smuggle_method: smuggle_method.map(|method| method.trim().to_ascii_uppercase()),
+                skip_baseline_status: Arc::new(if skip_baseline_status.is_empty() {
+                    DEFAULT_SKIP_BASELINE_STATUS.into_iter().collect()
+                } else {
+                    skip_baseline_status.into_iter().collect()
+                }),
+                retry_on_status: Arc::new(retry_on_status.into_iter().collect()),
+                baseline_retries,
+                baseline_error_retries,
+                warmup,
+                ignored_diff_status: Arc::new(if ignore_diff_status.is_empty() {
+                    DEFAULT_IGNORED_DIFF_STATUS.into_iter().collect()
+                } else {
+                    ignore_diff_status.into_iter().collect()
+                }),
+                report_changes: Arc::new(report_change),
+                filter_status: Arc::new(if filter_status.is_empty() {
+                    DEFAULT_FILTER_STATUS.into_iter().collect()
+                } else {
+                    filter_status.into_iter().collect()
+                }),
+                min_confidence,
+                methods: Arc::new(
+                    methods
+                        .iter()
+                        .map(|method| method.trim().to_ascii_uppercase())
+                        .collect(),
+                ),
+                connect_timeout,
+                read_timeout,
+                write_timeout,
+                timeout_threshold: timeout_threshold.map(Duration::from_millis),
+                max_response_size: (max_response_size > 0).then_some(max_response_size as usize),
+                classify_trailers,
+                trailers: Arc::new(parse_cli_headers(&trailer)?),
+                baseline_body: baseline_body.map(Arc::from),
+                template,
+                capture_headers,
+                tags: Arc::clone(&tags),
+                correlation: correlation_header.map(|name| {
+                    let id = format!("{:016x}", rand::random::<u64>());
+                    status!("Stamping every request with {}: {}", name, id);
+                    (name, id)
+                }),
+                jitter: jitter.filter(|max| *max > 0).map(|max| {
+                    let jitter = Jitter::new(
+                        Duration::from_millis(max),
+                        jitter_seed.unwrap_or_else(|| derive_seed(seed, "jitter")),
+                    );
+                    status!(
+                        "Jittering probes by up to {}ms (seed {})",
+                        max,
+                        jitter.seed()
+                    );
+                    jitter
+                }),
+                probe_seed: (probe_order == ProbeOrder::Random).then(|| {
+                    let seed = probe_seed.unwrap_or_else(|| derive_seed(seed, "probe-order"));
+                    status!("Shuffling attacks per target (seed {})", seed);
+                    seed
+                }),
+            };
+
+            if show_config {
+                let resolved = mode.resolve(module_options.clone());
+                let timeouts = resolved.timeouts();
+                let millis = |duration: Option<Duration>| duration.map(|d| d.as_millis() as u64);
+                let sorted = |statuses: &HashSet<u16>| {
+                    let mut statuses: Vec<u16> = statuses.iter().copied().collect();
+                    statuses.sort_unstable();
+                    statuses
+                };
+                let config = json!({
+                    "mode": mode.name,
+                    "targets": targets_path,
+                    "targets_format": format!("{:?}", layout.format).to_ascii_lowercase(),
+                    "targets_column": layout.column,
+                    "target_count": total_targets,
+                    "output": output,
+                    "max_line_length": max_line_length,
+                    "checkpoint": checkpoint_path,
+                    "checkpoint_format": format!("{:?}", checkpoint_format).to_ascii_lowercase(),
+                    "threads": threads,
+                    "max_connections_per_host": max_connections_per_host.map(|limit| limit.max(1)),
+                    "adaptive": adaptive.map(|bounds| json!({ "min": bounds.min, "max": bounds.max })),
+                    "timeouts_ms": {
+                        "connect": millis(timeouts.connect),
+                        "read": millis(timeouts.read),
+                        "write": millis(timeouts.write),
+                    },
+                    "timeout_threshold_ms": millis(resolved.timeout_threshold),
+                    "proxy": proxy_label,
+                    "env_proxy": !resolved.env_proxies.is_empty(),
+                    "dns_cache": resolved.dns.is_some(),
+                    "delay_between_targets_ms": millis(target_interval),
+                    "jitter_ms": jitter.filter(|max| *max > 0),
+                    "seed": seed,
+                    "probe_seed": resolved.probe_seed,
+                    "max_runtime_secs": max_runtime.map(|runtime| runtime.as_secs()),
+                    "stop_after_findings": stop_after_findings,
+                    "protocol": resolved.protocol.as_ref().map(|protocol| protocol.to_string()),
+                    "excluded_protocols": resolved
+                        .excluded_protocols
+                        .iter()
+                        .map(|protocol| protocol.to_string())
+                        .collect::<Vec<_>>(),
+                    "diff_mode": format!("{:?}", resolved.diff_mode).to_ascii_lowercase(),
+                    "length_tolerance": resolved.length_tolerance,
+                    "probes": resolved.probes,
+                    "max_errors": resolved.max_consecutive_errors,
+                    "skip_baseline_status": sorted(&resolved.skip_baseline_status),
+                    "ignore_diff_status": sorted(&resolved.ignored_diff_status),
+                    "report_change": resolved
+                        .report_changes
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>(),
+                    "filter_status": sorted(&resolved.filter_status),
+                    "retry_on_status": sorted(&resolved.retry_on_status),
+                    "baseline_retries": resolved.baseline_retries,
+                    "baseline_error_retries": resolved.baseline_error_retries,
+                    "warmup": resolved.warmup,
+                    "sort": sort.map(|key| format!("{:?}", key).to_ascii_lowercase()),
+                    "methods": resolved.methods.as_slice(),
+                    "trailers": resolved.trailers.as_slice(),
+                    "baseline_body": resolved.baseline_body.as_deref(),
+                    "template": resolved.template.as_ref().map(|template| &template.name),
+                    "capture_headers": resolved.capture_headers,
+                    "paths": resolved.paths.len(),
+                    "vhost": resolved.vhost,
+                    "smuggle_path": (!resolved.random_smuggle_path).then_some(&resolved.smuggle_path),
+                    "echo_marker": resolved.echo_marker.as_ref().map(|marker| marker.as_deref().unwrap_or("random")),
+                    "smuggle_method": resolved.smuggle_method,
+                    "min_confidence": resolved.min_confidence.map(|confidence| confidence.to_string()),
+                    "user_agents": resolved.user_agents.as_ref().map(|pool| pool.len()),
+                    "correlation_header": resolved.correlation.as_ref().map(|(header, _)| header),
+                });
+                println!("{}", serde_json::to_string_pretty(&config)?);
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            if dry_run {
+                // Nothing is sent, so there is nothing to record or checkpoint.
+                println!("Dry run: printing payloads without sending any requests");
+                scan_mode(
+                    &TargetScanner::new(threads),
+                    &mode,
+                    targets,
+                    ScanOptions {
+                        recorder: None,
+                        max_runtime,
+                        adaptive,
+                        quiet,
+                        progress_format,
+                        timestamps,
+                        stats: None,
+                        completed_before: 0,
+                        metrics: None,
+                        abort_on_error: !continue_on_error,
+                        target_interval,
+                        stop_after_findings: stop_after_findings.map(|limit| limit as usize),
+                    },
+                    module_options,
+                )
+                .await
+                .map_err(|err| -> Box<dyn std::error::Error> { Box::new(err) })?;
+                return Ok(ExitCode::SUCCESS);
             }
 
-            let checkpoint_path = default_checkpoint_path();
             let mut output_path = output.clone();
             let mut base_index: usize = 0;
+            // Targets past base_index an interrupted run already wrote out of order.
+            let mut finished_out_of_order: Option<CompletedSet> = None;
             let mut truncate_output = true;
-            let mode_label = mode.to_string();
+            let mode_label = mode.label.to_string();
 
             let checkpoint_to_use = if resume {
-                let checkpoint = read_checkpoint(&checkpoint_path).await?.ok_or_else(|| {
+                let checkpoint = read_resumable_checkpoint(&checkpoint_path).await?.ok_or_else(|| {
                     format!(
                         "No checkpoint found at '{}'. Run without --resume to start a fresh scan.",
                         checkpoint_path.display()
@@ -235,10 +1797,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .into());
                 }
 
+                // Checkpoints from before threads were recorded don't know their proxy either.
+                if checkpoint.threads.is_some() && checkpoint.proxy != proxy_label {
+                    let describe = |proxy: &Option<String>| {
+                        proxy.clone().unwrap_or_else(|| "no proxy".to_string())
+                    };
+                    let message = format!(
+                        "Checkpoint was scanned through '{}' but this run uses '{}'",
+                        describe(&checkpoint.proxy),
+                        describe(&proxy_label)
+                    );
+                    if !force {
+                        return Err(format!("{} (use --force to resume anyway)", message).into());
+                    }
+                    eprintln!("Warning: {}", message);
+                }
+                if let Some(previous) = checkpoint.threads
+                    && previous != threads
+                {
+                    status!(
+                        "Checkpoint was scanned with {} threads, continuing with {}",
+                        previous,
+                        threads
+                    );
+                }
+
                 if checkpoint.output_path != output_path {
-                    println!(
+                    status!(
                         "Using output file '{}' from checkpoint (overriding '{}')",
-                        checkpoint.output_path, output_path
+                        checkpoint.output_path,
+                        output_path
                     );
                     output_path = checkpoint.output_path.clone();
                 }
@@ -247,43 +1835,147 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 None
             };
 
+            // Carried over so a resume of an appended run keeps skipping the coverage check.
+            let checkpoint_append = checkpoint_to_use
+                .as_ref()
+                .is_some_and(|checkpoint| checkpoint.append);
             if let Some(checkpoint) = checkpoint_to_use {
                 base_index = checkpoint.next_index.min(total_targets);
+                finished_out_of_order = checkpoint.completed.clone();
                 truncate_output = false;
 
+                if let Some(seed) = checkpoint.ua_seed
+                    && ua_seed.is_none()
+                    && module_options
+                        .user_agents
+                        .as_ref()
+                        .is_some_and(|pool| pool.seed() != seed)
+                {
+                    status!("Reusing user agent seed {} from checkpoint", seed);
+                    module_options.user_agents = load_user_agents(seed)?;
+                }
+                if let Some(seed) = checkpoint.probe_seed
+                    && probe_seed.is_none()
+                    && module_options
+                        .probe_seed
+                        .is_some_and(|current| current != seed)
+                {
+                    status!("Reusing probe order seed {} from checkpoint", seed);
+                    module_options.probe_seed = Some(seed);
+                }
+
                 if base_index >= total_targets {
-                    println!(
+                    status!(
                         "Checkpoint indicates all {} targets were already scanned.",
                         total_targets
                     );
                     remove_checkpoint(&checkpoint_path).await?;
-                    return Ok(());
+                    return Ok(ExitCode::SUCCESS);
                 }
 
-                println!(
+                let out_of_order = finished_out_of_order
+                    .as_ref()
+                    .map_or(0, CompletedSet::count);
+                status!(
                     "Resuming from checkpoint: {} targets processed, {} remaining",
-                    base_index,
-                    total_targets - base_index
+                    base_index + out_of_order,
+                    total_targets - base_index - out_of_order
                 );
+                // An appended output holds entries of earlier runs for any index, and one with
+                // out-of-order results holds entries past the checkpoint.
+                if !(compress
+                    || output_path.ends_with(".gz")
+                    || checkpoint.append
+                    || out_of_order > 0)
+                {
+                    check_output_coverage(&output_path, &targets, base_index).await;
+                }
             } else {
                 remove_checkpoint(&checkpoint_path).await?;
+                // A fresh run must not leave an older run's record claiming it completed.
+                remove_checkpoint(completed_checkpoint_path(&checkpoint_path)).await?;
+
+                if append {
+                    truncate_output = false;
+                    status!("Appending findings to '{}'", output_path);
+                }
+
+                if let Some(index) = resume_from {
+                    if index >= total_targets {
+                        return Err(format!(
+                            "--resume-from {} is out of range: the target list has {} targets",
+                            index, total_targets
+                        )
+                        .into());
+                    }
+                    base_index = index;
+                    truncate_output = false;
+                    eprintln!(
+                        "Warning: starting at target {} and appending to '{}'; findings it already holds for targets {}..{} are kept, not replaced",
+                        index, output_path, index, total_targets
+                    );
+                }
             }
 
-            let remaining_total = total_targets.saturating_sub(base_index);
+            let done_before = base_index
+                + finished_out_of_order
+                    .as_ref()
+                    .map_or(0, CompletedSet::count);
+            let remaining_total = total_targets.saturating_sub(done_before);
             if remaining_total == 0 {
-                println!("No targets left to scan.");
+                status!("No targets left to scan.");
                 remove_checkpoint(&checkpoint_path).await?;
-                return Ok(());
+                return Ok(ExitCode::SUCCESS);
             }
 
-            let recorder_cfg = default_recorder_config(
-                output_path.clone(),
-                targets_path.clone(),
-                mode_label.clone(),
-                base_index,
-                remaining_total,
-                truncate_output,
-            );
+            let recorder_cfg = RecorderConfig {
+                buffer: recorder_buffer.max(1),
+                pending_warn,
+                max_pending,
+                compress: compress || output_path.ends_with(".gz"),
+                max_output_size,
+                max_line_length: max_line_length.map(|max| max as usize),
+                seed: Some(seed),
+                ua_seed: module_options.user_agents.as_ref().map(|pool| pool.seed()),
+                sample_seed,
+                probe_seed: module_options.probe_seed,
+                dedup: dedup_findings,
+                all_results_path: all_results.as_ref().map(PathBuf::from),
+                keep_checkpoint,
+                checkpoint_format,
+                tags: Arc::clone(&module_options.tags),
+                append: append || checkpoint_append,
+                threads: Some(threads),
+                proxy: proxy_label.clone(),
+                checkpoint_path: checkpoint_path.clone(),
+                completed: finished_out_of_order.clone(),
+                ..default_recorder_config(
+                    output_path.clone(),
+                    targets_path.clone(),
+                    mode_label.clone(),
+                    base_index,
+                    total_targets - base_index,
+                    truncate_output,
+                )
+            };
+
+            // A mistyped directory fails here, before any target is sent, rather than in the
+            // recorder once the scan is under way.
+            if output_path != STDOUT_PATH {
+                create_parent_dir(&output_path).await.map_err(|err| {
+                    format!(
+                        "failed to create the directory for '{}': {}",
+                        output_path, err
+                    )
+                })?;
+            }
+            create_parent_dir(&checkpoint_path).await.map_err(|err| {
+                format!(
+                    "failed to create the directory for '{}': {}",
+                    checkpoint_path.display(),
+                    err
+                )
+            })?;
 
             // Initialize the checkpoint so that a sudden stop before any target completes can still resume.
             let initial_checkpoint = Checkpoint::new(
@@ -291,44 +1983,110 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 targets_path.clone(),
                 output_path.clone(),
                 mode_label.clone(),
-            );
+            )
+            .with_seed(Some(seed))
+            .with_ua_seed(module_options.user_agents.as_ref().map(|pool| pool.seed()))
+            .with_sample_seed(sample_seed)
+            .with_probe_seed(module_options.probe_seed)
+            .with_append(append || checkpoint_append)
+            .with_completed(finished_out_of_order.clone())
+            .with_format(checkpoint_format)
+            .with_conditions(threads, proxy_label.clone());
             write_checkpoint(&checkpoint_path, &initial_checkpoint).await?;
 
-            println!(
+            status!(
                 "Writing findings incrementally to '{}' and tracking progress in '{}'",
                 output_path,
                 checkpoint_path.display()
             );
 
             let scanner = TargetScanner::new(threads);
+            let stats = Arc::new(ScanStats::default());
+            let started_at = Utc::now();
+            let started = Instant::now();
 
-            let results = match (mode, targets) {
-                (ScanMode::TrailMerge, targets_vec) => {
-                    let task = Arc::new(TrailMergeTask::new());
-                    scanner
-                        .scan_with_options(
-                            targets_vec.into_iter().skip(base_index),
-                            task,
-                            ScanOptions {
-                                recorder: Some(recorder_cfg.clone()),
-                            },
-                        )
-                        .await
-                }
-                (ScanMode::TrailSmug, targets_vec) => {
-                    let task = Arc::new(TrailSmugTask::new());
-                    scanner
-                        .scan_with_options(
-                            targets_vec.into_iter().skip(base_index),
-                            task,
-                            ScanOptions {
-                                recorder: Some(recorder_cfg.clone()),
-                            },
-                        )
-                        .await
+            let correlation = module_options.correlation.clone();
+            tracing::info!(
+                mode = %mode_label,
+                targets = remaining_total,
+                first_index = base_index,
+                threads,
+                output = %output_path,
+                "scan started"
+            );
+            let results = scan_mode(
+                &scanner,
+                &mode,
+                targets
+                    .into_iter()
+                    .enumerate()
+                    .skip(base_index)
+                    .filter(|(index, _)| {
+                        !finished_out_of_order
+                            .as_ref()
+                            .is_some_and(|completed| completed.contains(*index))
+                    })
+                    .map(|(_, target)| target),
+                ScanOptions {
+                    recorder: Some(recorder_cfg),
+                    max_runtime,
+                    adaptive,
+                    quiet: quiet || stdout_reserved(),
+                    progress_format: if tui {
+                        ProgressFormat::Tui
+                    } else {
+                        progress_format
+                    },
+                    timestamps,
+                    stats: Some(Arc::clone(&stats)),
+                    completed_before: done_before,
+                    metrics,
+                    abort_on_error: !continue_on_error,
+                    target_interval,
+                    stop_after_findings: stop_after_findings.map(|limit| limit as usize),
+                },
+                module_options,
+            )
+            .await;
+
+            // Findings recorded before a failed scan still go out, so both are drained first.
+            if let (Some(webhook), Some(worker)) = (webhook, webhook_worker) {
+                webhook.close();
+                if tokio::time::timeout(WEBHOOK_DRAIN_TIMEOUT, worker)
+                    .await
+                    .is_err()
+                {
+                    eprintln!("Gave up waiting for pending webhook notifications");
                 }
             }
-            .map_err(|err| -> Box<dyn std::error::Error> { Box::new(err) })?;
+
+            if let (Some(database), Some(writer)) = (database, database_writer) {
+                database.close();
+                let _ = writer.await;
+            }
+
+            let results = match results {
+                Ok(results) => results,
+                Err(ScanError::Persistence { error }) => {
+                    let resume = match read_resumable_checkpoint(&checkpoint_path).await {
+                        Ok(Some(checkpoint)) => format!(
+                            "progress is checkpointed at index {} in '{}' and can be resumed with --resume",
+                            checkpoint.next_index,
+                            checkpoint_path.display()
+                        ),
+                        _ => format!(
+                            "no checkpoint could be read from '{}'",
+                            checkpoint_path.display()
+                        ),
+                    };
+                    eprintln!(
+                        "Error: failed to write findings to '{}': {}; {}",
+                        output_path, error, resume
+                    );
+                    return Ok(ExitCode::from(EXIT_PERSISTENCE));
+                }
+                Err(err) => return Err(err.into()),
+            };
 
             let total_results = results.len();
             let findings: Vec<ScanOutput> = results
@@ -336,14 +2094,92 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .filter(|record| !record.output.trim().is_empty())
                 .collect();
 
-            let total_processed = base_index + total_results;
-            println!(
+            let total_processed = done_before + total_results;
+            tracing::info!(
+                scanned = total_results,
+                total_processed,
+                findings = findings.len(),
+                errors = stats.errors().values().sum::<usize>(),
+                elapsed_secs = started.elapsed().as_secs_f64(),
+                "scan finished"
+            );
+            let stopped_early =
+                stop_after_findings.is_some_and(|limit| stats.findings() >= limit as usize);
+            if total_results < remaining_total && stopped_early {
+                status!(
+                    "Stopped after {} targets with findings: scanned {} of {} targets. Run again with --resume to continue.",
+                    stats.findings(),
+                    total_processed,
+                    total_targets
+                );
+            } else if total_results < remaining_total {
+                status!(
+                    "Maximum runtime reached: scanned {} of {} targets. Run again with --resume to continue.",
+                    total_processed,
+                    total_targets
+                );
+            } else if let Some(key) = sort {
+                sort_output(Path::new(&output_path), key)
+                    .await
+                    .map_err(|err| format!("failed to sort '{}': {}", output_path, err))?;
+            }
+
+            let latency = stats.latency_summary();
+            if let Some(latency) = &latency {
+                status!("{}", latency.render().trim_end());
+            }
+
+            let proxy_skips = proxies.as_ref().map_or(0, |pool| pool.skipped());
+            let unresolved = dns.as_ref().map_or(0, |dns| dns.unresolved());
+            if let Some(path) = summary_json {
+                let summary = json!({
+                    "mode": mode_label,
+                    "seed": seed,
+                    "total_targets": total_targets,
+                    "scanned_this_run": total_results,
+                    "total_processed": total_processed,
+                    "completed": total_processed >= total_targets,
+                    "findings": findings.len(),
+                    "errors": stats.errors(),
+                    "invalid_targets": invalid_targets.count(),
+                    "proxy_skipped": proxy_skips,
+                    "unresolved": unresolved,
+                    "latency": latency.as_ref().map(|latency| latency.to_json()),
+                    "correlation": correlation.as_ref().map(|(header, id)| json!({
+                        "header": header,
+                        "id": id,
+                    })),
+                    "elapsed_secs": started.elapsed().as_secs_f64(),
+                    "started_at": started_at.to_rfc3339(),
+                    "finished_at": Utc::now().to_rfc3339(),
+                });
+                std::fs::write(&path, serde_json::to_string_pretty(&summary)?)
+                    .map_err(|err| format!("failed to write summary to '{}': {}", path, err))?;
+            }
+
+            if invalid_targets.count() > 0 {
+                status!("Skipped {} invalid targets", invalid_targets.count());
+            }
+            if unresolved > 0 {
+                status!("Skipped {} targets whose host doesn't resolve", unresolved);
+            }
+            if proxy_skips > 0 {
+                status!(
+                    "Skipped {} targets because every proxy was dead",
+                    proxy_skips
+                );
+            }
+
+            status!(
                 "Recorded {} findings in {} ({} targets scanned this run, {} total processed)",
                 findings.len(),
                 output_path,
                 total_results,
                 total_processed
             );
+            if fail_on_finding && !findings.is_empty() {
+                return Ok(ExitCode::from(EXIT_FINDINGS));
+            }
         }
         None => {
             // No subcommand provided; run in default client mode using top-level args
@@ -352,14 +2188,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let client_args = ClientArgs {
                     url,
                     data: top.data,
+                    data_file: top.data_file,
+                    data_binary: top.data_binary,
                     method: top.method,
                     head: top.head,
                     proxy: top.proxy,
+                    proxy_user: top.proxy_user,
+                    proxy_pass: top.proxy_pass,
+                    noproxy: top.noproxy,
                     header: top.header,
+                    user: top.user,
+                    bearer: top.bearer,
+                    json: top.json,
+                    repeat: top.repeat,
+                    repeat_delay: top.repeat_delay,
                     trailer: top.trailer,
+                    raw: top.raw,
+                    har: top.har,
+                    resolve: top.resolve,
+                    connect_to: top.connect_to,
+                    update_host: top.update_host,
+                    location: top.location,
+                    cookie: top.cookie,
+                    cookie_jar: top.cookie_jar,
+                    max_redirects: top.max_redirects,
+                    unsafe_redirects: top.unsafe_redirects,
+                    connect_timeout: top.connect_timeout,
+                    read_timeout: top.read_timeout,
+                    write_timeout: top.write_timeout,
                     http1: top.http1,
                     http2: top.http2,
                     http3: top.http3,
+                    tls: top.tls,
                 };
                 run_protocol_command(client_args).await?;
             } else {
@@ -370,10 +2230,120 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
     }
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn scan_mode<I>(
+    scanner: &TargetScanner,
+    mode: &ScanMode,
+    targets: I,
+    scan_options: ScanOptions,
+    module_options: ModuleOptions,
+) -> ScanResult
+where
+    I: IntoIterator<Item = String>,
+{
+    scanner
+        .scan_with_options(targets, mode.task(module_options), scan_options)
+        .await
+}
+
+/// Warns when the output being resumed has entries for targets the checkpoint says aren't
+/// done yet, which points at a checkpoint that fell behind or a resume that went wrong.
+async fn check_output_coverage(output_path: &str, targets: &[String], next_index: usize) {
+    let output = match tokio::fs::read_to_string(output_path).await {
+        Ok(output) => output,
+        Err(err) => {
+            if err.kind() != io::ErrorKind::NotFound {
+                eprintln!(
+                    "Could not check '{}' against the checkpoint: {}",
+                    output_path, err
+                );
+            }
+            return;
+        }
+    };
+    let Some(coverage) = output_coverage(&output, targets) else {
+        return;
+    };
+
+    if coverage.last >= next_index {
+        eprintln!(
+            "Warning: checkpoint claims {} done but output contains {} findings spanning indices {}..{}",
+            next_index, coverage.entries, coverage.first, coverage.last
+        );
+    } else if is_verbose() {
+        println!(
+            "Output matches the checkpoint: {} findings spanning indices {}..{}",
+            coverage.entries, coverage.first, coverage.last
+        );
+    }
+}
+
+/// `count` targets picked at random with `seed`, in their original order.
+/// Lines of the target files, and how many of them aren't blank.
+fn count_target_lines(files: &[String]) -> std::io::Result<(usize, usize)> {
+    let mut lines = 0;
+    let mut entries = 0;
+    for file in files {
+        let content = std::fs::read_to_string(file)?;
+        lines += content.lines().count();
+        entries += content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count();
+    }
+    Ok((lines, entries))
+}
+
+fn sample_targets(targets: Vec<String>, count: usize, seed: u64) -> Vec<String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut picked =
+        rand::seq::index::sample(&mut rng, targets.len(), count.min(targets.len())).into_vec();
+    picked.sort_unstable();
+    targets
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| picked.binary_search(index).is_ok())
+        .map(|(_, target)| target)
+        .collect()
 }
 
 async fn run_protocol_command(args: ClientArgs) -> Result<(), Box<dyn std::error::Error>> {
+    args.tls.check()?;
+
+    // Without any timeout flag riphttplib's own defaults apply.
+    let timeouts = (args.connect_timeout.is_some()
+        || args.read_timeout.is_some()
+        || args.write_timeout.is_some())
+    .then_some(ClientTimeouts {
+        connect: args.connect_timeout,
+        read: args.read_timeout,
+        write: args.write_timeout,
+    });
+
+    if let Some(path) = &args.raw {
+        return send_raw_file(&args.url, path, args.update_host, timeouts, args.json).await;
+    }
+
+    // An explicit --proxy wins over the environment; --noproxy/NO_PROXY exclude either.
+    let no_proxy = args
+        .noproxy
+        .as_deref()
+        .map(NoProxy::parse)
+        .unwrap_or_else(NoProxy::from_env);
+    let proxy = args
+        .proxy
+        .clone()
+        .or_else(|| EnvProxies::from_env().for_target(&args.url))
+        .filter(|_| !no_proxy.matches(&args.url));
+    let proxy = match (&args.proxy_user, proxy) {
+        (Some(user), Some(proxy)) => {
+            Some(ProxyCredentials::new(user, args.proxy_pass.as_deref())?.apply(&proxy)?)
+        }
+        (_, proxy) => proxy,
+    };
+
     if is_verbose() {
         println!("Sending request to: {}", args.url);
         if let Some(method) = &args.method {
@@ -384,6 +2354,9 @@ async fn run_protocol_command(args: ClientArgs) -> Result<(), Box<dyn std::error
         if let Some(body) = &args.data {
             println!("Request body: {}", body);
         }
+        if let Some(path) = &args.data_file {
+            println!("Request body from: {}", path);
+        }
         if !args.header.is_empty() {
             println!("Headers:");
             for header in &args.header {
@@ -396,22 +2369,68 @@ async fn run_protocol_command(args: ClientArgs) -> Result<(), Box<dyn std::error
                 println!("  {}", trailer);
             }
         }
-        if let Some(proxy) = &args.proxy {
-            println!("Using proxy: {}", proxy);
+        if let Some(proxy) = &proxy {
+            println!("Using proxy: {}", redact_proxy(proxy));
+        }
+    }
+
+    if is_verbose() {
+        // riphttplib doesn't expose its session, so the details come from a handshake of our own.
+        if proxy.is_some() {
+            eprintln!("TLS: not inspected through a proxy");
+        } else if args.http3 {
+            eprintln!("TLS: not inspected over QUIC");
+        } else {
+            let alpn: &[&str] = if args.http1 {
+                &["http/1.1"]
+            } else if args.http2 {
+                &["h2"]
+            } else {
+                &["h2", "http/1.1"]
+            };
+            match handshake_details(&args.url, alpn).await {
+                Ok(Some(details)) => eprintln!("{}", details),
+                Ok(None) => eprintln!("TLS: no TLS"),
+                Err(err) => eprintln!("TLS: handshake failed: {}", err),
+            }
         }
     }
 
     let ClientArgs {
         url,
         data,
+        data_file,
+        data_binary,
         method,
         head,
-        proxy,
-        header,
+        proxy: _,
+        proxy_user: _,
+        proxy_pass: _,
+        noproxy: _,
+        mut header,
         trailer,
+        raw: _,
+        update_host: _,
+        location,
+        max_redirects,
+        unsafe_redirects,
+        connect_timeout: _,
+        read_timeout: _,
+        write_timeout: _,
+        cookie,
+        cookie_jar,
+        har,
+        resolve,
+        connect_to,
         http1,
         http2,
         http3,
+        user,
+        bearer,
+        json,
+        repeat,
+        repeat_delay,
+        tls: _,
     } = args;
 
     let method = match (head, method) {
@@ -428,7 +2447,7 @@ async fn run_protocol_command(args: ClientArgs) -> Result<(), Box<dyn std::error
         (true, None) => "HEAD".to_string(),
         (false, Some(explicit)) => explicit.to_uppercase(),
         (false, None) => {
-            if data.as_ref().is_some() {
+            if data.is_some() || data_file.is_some() {
                 "POST".to_string()
             } else {
                 "GET".to_string()
@@ -438,39 +2457,500 @@ async fn run_protocol_command(args: ClientArgs) -> Result<(), Box<dyn std::error
 
     let is_head = method.eq_ignore_ascii_case("HEAD");
 
-    let headers = parse_cli_headers(&header)?;
-    let trailers = parse_cli_headers(&trailer)?;
-
-    let mut request = Request::new(&url, method.clone())?;
-    if !headers.is_empty() {
-        request = request.headers(headers);
-    }
-    if !trailers.is_empty() {
-        request = request.trailers(trailers);
+    if let Some(authorization) = authorization_header(user.as_deref(), bearer.as_deref()) {
+        if header
+            .iter()
+            .any(|item| item.to_ascii_lowercase().starts_with("authorization:"))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot combine --user/--bearer with an explicit Authorization header",
+            )
+            .into());
+        }
+        header.push(authorization);
     }
-    if let Some(body) = data {
-        if is_head {
+
+    let url = match ResolveOverrides::new(resolve)
+        .with_connect_to(connect_to)
+        .apply(&url)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+    {
+        Some((resolved, host)) => {
+            if is_verbose() {
+                println!("Resolving {} to {}", url, resolved);
+            }
+            if !header
+                .iter()
+                .any(|item| item.to_ascii_lowercase().starts_with("host:"))
+            {
+                header.push(format!("Host: {}", host));
+            }
+            resolved
+        }
+        None => url,
+    };
+
+    let body = match (data, data_file) {
+        (Some(body), _) if data_binary => Some(body.into_bytes()),
+        (Some(body), _) => Some(convert_escape_sequences(&body).into_bytes()),
+        (None, Some(path)) => Some(tokio::fs::read(&path).await?),
+        (None, None) => None,
+    };
+    let body = match body {
+        Some(_) if is_head => {
             if is_verbose() {
                 println!("Ignoring request body for HEAD request");
             }
+            None
+        }
+        body => body,
+    };
+
+    let mut jar = (cookie_jar || location || !cookie.is_empty()).then(CookieJar::new);
+    if let Some(jar) = &mut jar {
+        let seed_url = Url::parse(&url)?;
+        for cookie in &cookie {
+            jar.seed(&seed_url, cookie)?;
+        }
+    }
+
+    let selected = determine_protocol(http1, http2, http3)?;
+    let mut url = url;
+    let mut method = method;
+    let mut body = body;
+    let mut trailer = trailer;
+    let mut redirects = 0;
+
+    loop {
+        let mut headers = parse_cli_headers(&header)?;
+        let trailers = parse_cli_headers(&trailer)?;
+        if let Some(jar) = &jar
+            && let Some(cookies) = jar.header_for(&Url::parse(&url)?)
+        {
+            headers.push(format!("cookie: {}", cookies));
+        }
+
+        let har_headers = har.as_ref().map(|_| headers.clone());
+
+        let mut request = Request::new(&url, method.clone())?;
+        if !headers.is_empty() {
+            request = request.headers(headers);
+        }
+        if !trailers.is_empty() {
+            request = request.trailers(trailers);
+        }
+        if let Some(body) = &body {
+            request = request.body(body.clone());
+        }
+        if let Some(proxy) = &proxy {
+            request = apply_proxy(request, proxy)?;
+        }
+        if let Some(timeouts) = &timeouts {
+            request = request.timeout(timeouts.clone());
+        }
+        if repeat > 1 {
+            let delay = Duration::from_millis(repeat_delay);
+            return send_repeated(
+                request,
+                selected,
+                timeouts.as_ref(),
+                repeat,
+                delay,
+                &method,
+                json,
+            )
+            .await;
+        }
+
+        let started = Utc::now();
+        let start = Instant::now();
+        let response = send_with_protocol(request, selected, timeouts.as_ref())
+            .await
+            .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)?;
+        let elapsed = start.elapsed();
+        warn_on_protocol_mismatch(selected, &response);
+        if let Some(jar) = &mut jar {
+            jar.store(&Url::parse(&url)?, &response);
+        }
+
+        if let (Some(path), Some(headers)) = (&har, har_headers) {
+            let har_request = HarRequest {
+                method: &method,
+                url: &url,
+                http_version: selected.http_version(),
+                headers: &headers,
+                body: body.as_deref(),
+            };
+            append_har(path, har_entry(&har_request, &response, started, elapsed))?;
+            if is_verbose() {
+                println!("Appended HAR entry to {}", path);
+            }
+        }
+
+        let next = if location {
+            redirect_location(&url, &response)?
         } else {
-            let processed = convert_escape_sequences(&body);
-            request = request.body(processed);
+            None
+        };
+        let Some(next) = next else {
+            print_response(&response, &method, json)?;
+            return Ok(());
+        };
+
+        if redirects >= max_redirects {
+            return Err(format!("Maximum ({}) redirects followed", max_redirects).into());
+        }
+        if url.starts_with("https://") && next.scheme() == "http" && !unsafe_redirects {
+            return Err(format!(
+                "Refusing to follow redirect from https to {} (use --unsafe-redirects)",
+                next
+            )
+            .into());
+        }
+        if is_verbose() {
+            println!("< {} redirect to {}", response.status, next);
+        }
+
+        // Like browsers and curl: 303 turns into a GET, and so does a POST answered with 301/302.
+        // 307 and 308 repeat the request as it was.
+        let rewrite = (response.status == 303 && !is_head)
+            || (matches!(response.status, 301 | 302) && method == "POST");
+        if rewrite {
+            method = "GET".to_string();
+            body = None;
+            trailer.clear();
+        }
+        // Credentials and a pinned Host only apply to the host they were given for.
+        if Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            != next.host_str().map(str::to_string)
+        {
+            header.retain(|item| {
+                let name = item.to_ascii_lowercase();
+                !name.starts_with("host:") && !name.starts_with("authorization:")
+            });
         }
+
+        redirects += 1;
+        url = next.to_string();
+    }
+}
+
+/// Where a redirect response points, resolved against the URL that was requested. `None` when
+/// `response` isn't a redirect.
+fn redirect_location(
+    url: &str,
+    response: &Response,
+) -> Result<Option<Url>, Box<dyn std::error::Error>> {
+    if !matches!(response.status, 301 | 302 | 303 | 307 | 308) {
+        return Ok(None);
+    }
+    let Some(location) = response
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("location"))
+        .and_then(|header| header.value.as_deref())
+    else {
+        return Ok(None);
+    };
+    let next = Url::parse(url)?
+        .join(location.trim())
+        .map_err(|err| format!("invalid redirect location '{}': {}", location, err))?;
+    Ok(Some(next))
+}
+
+/// Sends a raw request file byte for byte over HTTP/1.1, bypassing the request builder.
+async fn send_raw_file(
+    url: &str,
+    path: &str,
+    update_host: bool,
+    timeouts: Option<ClientTimeouts>,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut raw = tokio::fs::read(path).await?;
+    if update_host {
+        let authority = parse_target(url)?
+            .authority()
+            .unwrap_or("localhost".to_string());
+        raw = rewrite_host(&raw, &authority);
     }
-    if let Some(proxy) = proxy {
-        request = apply_proxy(request, &proxy)?;
+    if is_verbose() {
+        println!("Sending {} raw bytes from {} to {}", raw.len(), path, url);
     }
 
-    let selected = determine_protocol(http1, http2, http3)?;
-    let response = send_with_protocol(request, selected)
+    // HEAD responses carry no body, so the method decides how the response is printed.
+    let method = raw
+        .split(|byte| *byte == b' ')
+        .next()
+        .map(|method| String::from_utf8_lossy(method).into_owned())
+        .unwrap_or_default();
+
+    let client = match timeouts {
+        Some(timeouts) => H1::timeouts(timeouts),
+        None => H1::new(),
+    };
+    let response = client
+        .send_raw(url, raw.into())
         .await
         .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)?;
-
-    print_response(&response, &method)?;
+    print_response(&response, &method, json)?;
     Ok(())
 }
 
+/// Modules whose transcripts hold the response to a follow-up request sent after the payload,
+/// rather than the response to the payload itself. Their split-response findings are the
+/// exception.
+const FOLLOW_UP_MODULES: [&str; 4] = ["TrailSmug", "TeCl", "ClTe", "QueuePoison"];
+
+/// Sends a transcript's payload to its target again and compares the outcome with the recorded
+/// response. Exits with 1 when the status no longer matches.
+async fn run_checkpoint(args: CheckpointArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let (location, clear, yes) = match args.action {
+        CheckpointAction::Show(location) => (location, false, false),
+        CheckpointAction::Clear { location, yes } => (location, true, yes),
+    };
+    let path = location.path();
+    // A completed run's checkpoint is only left behind, at its .done path, with --keep-checkpoint.
+    let (path, checkpoint) = match read_checkpoint(&path).await? {
+        Some(checkpoint) => (path, checkpoint),
+        None => {
+            let done = completed_checkpoint_path(&path);
+            match read_checkpoint(&done).await? {
+                Some(checkpoint) => (done, checkpoint),
+                None => {
+                    eprintln!("No checkpoint at '{}'", path.display());
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+    };
+
+    if !clear {
+        print_checkpoint(&path, &checkpoint).await;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if !yes {
+        eprint!(
+            "Remove checkpoint '{}' of the {} scan at index {}? [y/N] ",
+            path.display(),
+            checkpoint.mode,
+            checkpoint.next_index
+        );
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            eprintln!("Kept '{}'", path.display());
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    remove_checkpoint(&path).await?;
+    println!("Removed '{}'", path.display());
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints what `checkpoint show` knows about a scan. Progress is measured against the target
+/// files as they are now, read one target per line, before `--ports` and sampling, so it is left
+/// out when they can't be read or no longer match.
+async fn print_checkpoint(path: &Path, checkpoint: &Checkpoint) {
+    println!("Checkpoint: {}", path.display());
+    println!("Mode: {}", checkpoint.mode);
+    println!("Targets: {}", checkpoint.targets_path);
+    println!("Output: {}", checkpoint.output_path);
+    let files: Vec<String> = checkpoint
+        .targets_path
+        .split(',')
+        .map(str::to_string)
+        .collect();
+    match load_target_files(&files, &TargetsLayout::default()).await {
+        Ok(targets) if checkpoint.next_index <= targets.len() && !targets.is_empty() => {
+            println!(
+                "Progress: {} of {} targets ({:.1}%)",
+                checkpoint.next_index,
+                targets.len(),
+                checkpoint.next_index as f64 * 100.0 / targets.len() as f64
+            );
+        }
+        _ => println!("Progress: {} targets", checkpoint.next_index),
+    }
+    if let Some(threads) = checkpoint.threads {
+        println!("Threads: {}", threads);
+    }
+    if let Some(proxy) = &checkpoint.proxy {
+        println!("Proxy: {}", proxy);
+    }
+    for (name, seed) in [
+        ("Seed", checkpoint.seed),
+        ("User-Agent seed", checkpoint.ua_seed),
+        ("Sample seed", checkpoint.sample_seed),
+        ("Probe order seed", checkpoint.probe_seed),
+    ] {
+        if let Some(seed) = seed {
+            println!("{}: {}", name, seed);
+        }
+    }
+    if let Some(completed) = &checkpoint.completed {
+        println!(
+            "Completed out of order: {} of {} targets",
+            completed.count(),
+            completed.len()
+        );
+    }
+    println!(
+        "Format: {}",
+        format!("{:?}", checkpoint.format).to_ascii_lowercase()
+    );
+    if checkpoint.append {
+        println!("Appended to an existing output");
+    }
+}
+
+/// Prints the findings of `current` that are new, resolved or unchanged since `previous`, and
+/// exits with 1 when any are new.
+async fn run_diff(args: DiffArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let read = async |path: &Path| {
+        read_output(path)
+            .await
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    };
+    let previous = read(&args.previous).await?;
+    let current = read(&args.current).await?;
+    let diff = diff_outputs(&previous, &current);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff.to_json())?);
+    } else {
+        print!("{}", diff.to_text());
+    }
+    Ok(if diff.new.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+async fn run_replay(args: ReplayArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let text = tokio::fs::read_to_string(&args.transcript).await?;
+    let transcript = SavedTranscript::parse(&text)
+        .map_err(|err| format!("{}: {}", args.transcript.display(), err))?;
+    let target = transcript.target.as_str();
+
+    let timeouts = (args.connect_timeout.is_some()
+        || args.read_timeout.is_some()
+        || args.write_timeout.is_some())
+    .then_some(ClientTimeouts {
+        connect: args.connect_timeout,
+        read: args.read_timeout,
+        write: args.write_timeout,
+    });
+    let client = match &timeouts {
+        Some(timeouts) => H1::timeouts(timeouts.clone()),
+        None => H1::new(),
+    };
+
+    println!("Replaying {} finding against {}", transcript.module, target);
+    let mut response = client
+        .send_raw(target, transcript.payload.clone().into())
+        .await?;
+    if is_verbose() {
+        println!("payload answered {}", response.status);
+    }
+    // The poisoned connection only shows in the response to the next request.
+    if FOLLOW_UP_MODULES.contains(&transcript.module.as_str())
+        && !transcript.finding.contains("split response")
+    {
+        let mut request = Request::new(target, "GET")?.follow_redirects(false);
+        if let Some(timeouts) = &timeouts {
+            request = request.timeout(timeouts.clone());
+        }
+        response = client.send_request(request).await?;
+    }
+    if is_verbose() {
+        print_response(&response, "GET", false)?;
+    }
+
+    let Some(recorded) = &transcript.response else {
+        println!(
+            "Got status {}; the transcript has no response to compare with",
+            response.status
+        );
+        return Ok(ExitCode::SUCCESS);
+    };
+    if let Some(baseline) = &transcript.baseline {
+        println!("Recorded baseline status: {}", baseline.status);
+    }
+
+    let normalizer = BodyNormalizer::new(&[])?;
+    let same_body =
+        normalizer.normalize(recorded.body.as_bytes()) == normalizer.normalize(&response.body);
+    if response.status == recorded.status {
+        println!(
+            "Reproduced: status {} as recorded{}",
+            response.status,
+            if same_body {
+                ", same body"
+            } else {
+                ", body differs"
+            }
+        );
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!(
+            "Not reproduced: status {}, recorded {}",
+            response.status, recorded.status
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Replaces the value of the first `Host` header in the head of a raw request, keeping its line ending.
+fn rewrite_host(raw: &[u8], authority: &str) -> Vec<u8> {
+    let mut rewritten = Vec::with_capacity(raw.len() + authority.len());
+    let mut in_head = true;
+    let mut replaced = false;
+
+    for (i, line) in raw.split_inclusive(|byte| *byte == b'\n').enumerate() {
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        if i > 0 && content.is_empty() {
+            in_head = false;
+        }
+
+        if in_head
+            && !replaced
+            && i > 0
+            && content.len() >= 5
+            && content[..5].eq_ignore_ascii_case(b"host:")
+        {
+            rewritten.extend_from_slice(b"Host: ");
+            rewritten.extend_from_slice(authority.as_bytes());
+            rewritten.extend_from_slice(&line[content.len()..]);
+            replaced = true;
+        } else {
+            rewritten.extend_from_slice(line);
+        }
+    }
+
+    if !replaced && is_verbose() {
+        println!("No Host header found in the raw request, leaving it unchanged");
+    }
+    rewritten
+}
+
+/// Builds the Authorization header for `--user` (Basic) or `--bearer`.
+fn authorization_header(user: Option<&str>, bearer: Option<&str>) -> Option<String> {
+    if let Some(credentials) = user {
+        return Some(format!(
+            "Authorization: Basic {}",
+            BASE64_STANDARD.encode(credentials)
+        ));
+    }
+    bearer.map(|token| format!("Authorization: Bearer {}", token))
+}
+
 fn parse_cli_headers(items: &[String]) -> Result<Vec<String>, ProtocolError> {
     let mut headers = Vec::with_capacity(items.len());
     for item in items {
@@ -496,6 +2976,24 @@ enum SelectedProtocol {
     Http3,
 }
 
+impl SelectedProtocol {
+    fn http_version(self) -> &'static str {
+        match self {
+            SelectedProtocol::Http1 => "HTTP/1.1",
+            SelectedProtocol::Http2 => "HTTP/2",
+            SelectedProtocol::Http3 => "HTTP/3",
+        }
+    }
+
+    fn http_protocol(self) -> HttpProtocol {
+        match self {
+            SelectedProtocol::Http1 => HttpProtocol::Http1,
+            SelectedProtocol::Http2 => HttpProtocol::Http2,
+            SelectedProtocol::Http3 => HttpProtocol::Http3,
+        }
+    }
+}
+
 fn determine_protocol(
     http1: bool,
     http2: bool,
@@ -524,15 +3022,114 @@ fn determine_protocol(
 async fn send_with_protocol(
     request: Request,
     protocol: SelectedProtocol,
+    timeouts: Option<&ClientTimeouts>,
 ) -> Result<Response, ProtocolError> {
-    match protocol {
-        SelectedProtocol::Http1 => H1::new().send_request(request).await,
-        SelectedProtocol::Http2 => H2::new().send_request(request).await,
-        SelectedProtocol::Http3 => H3::new().send_request(request).await,
+    match (protocol, timeouts) {
+        (SelectedProtocol::Http1, None) => H1::new().send_request(request).await,
+        (SelectedProtocol::Http1, Some(timeouts)) => {
+            H1::timeouts(timeouts.clone()).send_request(request).await
+        }
+        (SelectedProtocol::Http2, None) => H2::new().send_request(request).await,
+        (SelectedProtocol::Http2, Some(timeouts)) => {
+            H2::timeouts(timeouts.clone()).send_request(request).await
+        }
+        (SelectedProtocol::Http3, None) => H3::new().send_request(request).await,
+        (SelectedProtocol::Http3, Some(timeouts)) => {
+            H3::timeouts(timeouts.clone()).send_request(request).await
+        }
+    }
+}
+
+/// Warns on stderr when the server answered over another HTTP version than the one asked for,
+/// e.g. an HTTP/2 request that ended up on HTTP/1.1.
+fn warn_on_protocol_mismatch(selected: SelectedProtocol, response: &Response) {
+    if let Some(mismatch) = protocol_mismatch(&selected.http_protocol(), response) {
+        eprintln!("Warning: {}", mismatch);
     }
 }
 
-fn print_response(response: &Response, method: &str) -> io::Result<()> {
+/// Sends `request` `repeat` times for `--repeat`, `delay` apart, and prints a status line per
+/// response noting whether its body differs from the first. With `json` the responses are printed
+/// as one JSON array instead.
+async fn send_repeated(
+    request: Request,
+    protocol: SelectedProtocol,
+    timeouts: Option<&ClientTimeouts>,
+    repeat: u32,
+    delay: Duration,
+    method: &str,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut first: Option<Response> = None;
+    let mut responses = Vec::new();
+    for iteration in 1..=repeat {
+        if iteration > 1 && !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        let start = Instant::now();
+        let response = send_with_protocol(request.clone(), protocol, timeouts)
+            .await
+            .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)?;
+        let elapsed = start.elapsed();
+        warn_on_protocol_mismatch(protocol, &response);
+        if json {
+            responses.push(response_json(&response, method));
+            continue;
+        }
+
+        let mut line = format!(
+            "#{} {} {} ({} bytes, {}ms",
+            iteration,
+            response.protocol,
+            response.status,
+            response.body.len(),
+            elapsed.as_millis()
+        );
+        match &first {
+            None => first = Some(response),
+            Some(first) if first.body != response.body => line.push_str(", body differs from #1"),
+            Some(_) => {}
+        }
+        line.push(')');
+        println!("{}", line);
+    }
+    if json {
+        println!("{}", serde_json::Value::Array(responses));
+    }
+    Ok(())
+}
+
+/// `response` as the JSON object `--json` prints. Bodies that aren't UTF-8 are base64-encoded,
+/// which `body_encoding` says, and HEAD responses get an empty one.
+fn response_json(response: &Response, method: &str) -> serde_json::Value {
+    let headers: Vec<_> = response
+        .headers
+        .iter()
+        .map(|header| json!({ "name": header.name, "value": header.value }))
+        .collect();
+    let (body, encoding) = match std::str::from_utf8(&response.body) {
+        _ if method.eq_ignore_ascii_case("HEAD") => (String::new(), "utf-8"),
+        Ok(text) => (text.to_string(), "utf-8"),
+        Err(_) => (BASE64_STANDARD.encode(&response.body), "base64"),
+    };
+    json!({
+        "protocol": response.protocol.to_string(),
+        "status": response.status,
+        "headers": headers,
+        "body": body,
+        "body_encoding": encoding,
+    })
+}
+
+/// Prints `response` as curl would, or as one JSON object with `json`. HEAD responses are
+/// printed without a body, and in JSON with an empty one.
+fn print_response(response: &Response, method: &str, json: bool) -> io::Result<()> {
+    let head = method.eq_ignore_ascii_case("HEAD");
+    if json {
+        println!("{}", response_json(response, method));
+        return Ok(());
+    }
+
     println!("{} {}", response.protocol, response.status);
     for header in &response.headers {
         if let Some(value) = &header.value {
@@ -543,7 +3140,7 @@ fn print_response(response: &Response, method: &str) -> io::Result<()> {
     }
     println!();
 
-    if !method.eq_ignore_ascii_case("HEAD") {
+    if !head {
         let body = response.body.as_ref();
         if let Ok(text) = std::str::from_utf8(body) {
             print!("{}", text);
@@ -0,0 +1,50 @@
+use riphttplib::types::Response;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+tokio::task_local! {
+    /// Baselines of the target `--mode all` is working on, shared by its modules.
+    static BASELINES: BaselineCache;
+}
+
+/// The plain GET baselines taken of one target, keyed by the URL requested since `--paths`
+/// scans several per target. Outside [`BaselineCache::scope`] nothing is cached and every
+/// module fetches its own.
+#[derive(Debug, Default)]
+pub struct BaselineCache {
+    responses: Mutex<HashMap<String, Response>>,
+}
+
+impl BaselineCache {
+    /// Runs `scan` with an empty cache that is dropped once it completes, so baselines never
+    /// outlive the target they were taken of.
+    pub async fn scope<F: Future>(scan: F) -> F::Output {
+        BASELINES.scope(BaselineCache::default(), scan).await
+    }
+}
+
+/// The baseline an earlier module took of `target`, if any.
+pub fn shared_baseline(target: &str) -> Option<Response> {
+    BASELINES
+        .try_with(|cache| cache.responses.lock().unwrap().get(target).cloned())
+        .ok()
+        .flatten()
+}
+
+/// Offers `response` to the modules that scan `target` next.
+pub fn share_baseline(target: &str, response: &Response) {
+    let _ = BASELINES.try_with(|cache| {
+        cache
+            .responses
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), response.clone())
+    });
+}
+
+/// Drops the baseline of `target` once a module's payloads may have poisoned a connection to it,
+/// so the next module takes a fresh one instead of comparing against the state before.
+pub fn invalidate_baseline(target: &str) {
+    let _ = BASELINES.try_with(|cache| cache.responses.lock().unwrap().remove(target));
+}
@@ -0,0 +1,107 @@
+use crate::core::dryrun::{dump_payload, dump_response, dumping_payloads, print_dry_run};
+use crate::core::telemetry::protocol_span;
+use crate::core::utils::header_value;
+use crate::modules::options::ModuleOptions;
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::H1;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request, Response};
+use tracing::Instrument;
+
+/// Sends only the baseline GET the HTTP/1.1 modules start with and reports its status, as a
+/// quick liveness pass before a full scan.
+#[derive(Clone)]
+pub struct CensusTask {
+    options: ModuleOptions,
+}
+
+impl Default for CensusTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CensusTask {
+    pub fn new() -> Self {
+        Self::with_options(ModuleOptions::default())
+    }
+
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self { options }
+    }
+
+    fn build_baseline_request(
+        target: &str,
+        timeouts: &ClientTimeouts,
+    ) -> Result<Request, ProtocolError> {
+        Ok(Request::new(target, "GET")?
+            .timeout(timeouts.clone())
+            .follow_redirects(false))
+    }
+
+    /// `status 301 HTTP/1.1 server=nginx location=https://example.com/`, leaving out headers the
+    /// response doesn't have.
+    fn describe(response: &Response) -> String {
+        let mut line = format!("status {} {}", response.status, response.protocol);
+        for name in ["server", "location"] {
+            if let Some(value) = header_value(response, name) {
+                line.push_str(&format!(" {}={}", name, value.trim()));
+            }
+        }
+        line
+    }
+
+    async fn scan_path(&self, target: String) -> Result<String, ProtocolError> {
+        if self.options.dry_run {
+            let baseline = self.options.render_baseline(&target)?;
+            print_dry_run(&target, "Census", &[("baseline".to_string(), baseline)]);
+            return Ok(String::new());
+        }
+
+        let timeouts = self.options.timeouts();
+        let Ok(proxy) = self.options.select_proxy(&target) else {
+            return Ok(String::new());
+        };
+        self.options.jitter_for(&target).sleep().await;
+        if dumping_payloads() {
+            dump_payload(
+                &target,
+                "Census",
+                "baseline",
+                &self.options.render_baseline(&target)?,
+            );
+        }
+        let client = H1::timeouts(timeouts.clone());
+        let response = self
+            .options
+            .first_baseline(&target, async || {
+                client
+                    .send_request(self.options.prepare(
+                        Self::build_baseline_request(&target, &timeouts)?,
+                        proxy.as_deref(),
+                    )?)
+                    .await
+            })
+            .await;
+        self.options.report_proxy(proxy.as_deref(), &response);
+        let response = self.options.cap_body(response?);
+        dump_response(&target, "Census", "baseline", &response);
+
+        Ok(Self::describe(&response))
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for CensusTask {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        self.options
+            .scan_paths(&target, |target| {
+                self.scan_path(target)
+                    .instrument(protocol_span("Census", HttpProtocol::Http1))
+            })
+            .await
+    }
+}
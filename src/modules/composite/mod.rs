@@ -0,0 +1,68 @@
+use crate::modules::baseline::BaselineCache;
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::types::ProtocolError;
+use std::sync::Arc;
+
+pub type ModuleTask = Arc<dyn Task<Error = ProtocolError>>;
+
+/// Runs several modules against the same target and merges their findings.
+///
+/// The modules run one after another inside the target's slot, so `--threads` still bounds how many
+/// targets are scanned at once, but every target now costs the sum of all modules' requests and takes
+/// correspondingly longer to complete. The modules share one [`BaselineCache`] per target, so the
+/// plain GET baseline is fetched once rather than by every module.
+#[derive(Clone, Default)]
+pub struct CompositeTask {
+    tasks: Vec<(String, ModuleTask)>,
+}
+
+impl CompositeTask {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a module whose findings are prefixed with `[label]`.
+    pub fn with_task(mut self, label: impl Into<String>, task: ModuleTask) -> Self {
+        self.tasks.push((label.into(), task));
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for CompositeTask {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        BaselineCache::scope(self.execute_all(target)).await
+    }
+}
+
+impl CompositeTask {
+    async fn execute_all(&self, target: String) -> Result<String, ProtocolError> {
+        let mut findings = Vec::new();
+
+        for (label, task) in &self.tasks {
+            match task.execute(target.clone()).await {
+                Ok(output) => {
+                    findings.extend(
+                        output
+                            .lines()
+                            .filter(|line| !line.trim().is_empty())
+                            .map(|line| format!("[{}] {}", label, line)),
+                    );
+                }
+                Err(err) => {
+                    if crate::is_verbose() {
+                        eprintln!("{} failed for {}: {}", label, target, err);
+                    }
+                    if matches!(&err, ProtocolError::InvalidTarget(_)) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        Ok(findings.join("\n"))
+    }
+}
@@ -0,0 +1,94 @@
+use crate::core::finding::Finding;
+use crate::core::telemetry::detect_span;
+use crate::modules::options::ModuleOptions;
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::types::ProtocolError;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::{DetectedProtocol, detect_protocol};
+use tracing::Instrument;
+use url::Url;
+
+/// Runs protocol detection alone and reports what each target speaks, sending no attack
+/// traffic, as a fast pre-pass that picks the hosts worth a full scan.
+#[derive(Clone)]
+pub struct DetectTask {
+    options: ModuleOptions,
+}
+
+impl Default for DetectTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DetectTask {
+    pub fn new() -> Self {
+        Self::with_options(ModuleOptions::default())
+    }
+
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self { options }
+    }
+
+    /// `H1:443,H2:443,H3:443`, with the target's own port for protocols detected without one.
+    /// Protocols left out by `--http1` and friends or `--exclude-protocol` aren't listed.
+    fn describe(&self, target: &str, protocols: &[DetectedProtocol]) -> String {
+        let default_port = Url::parse(target)
+            .ok()
+            .and_then(|url| url.port_or_known_default());
+        protocols
+            .iter()
+            .filter(|detected| self.options.scans_protocol(&detected.protocol))
+            .map(|detected| {
+                let port = detected
+                    .port
+                    .or(default_port)
+                    .map_or_else(|| "?".to_string(), |port| port.to_string());
+                format!("{}:{}", protocol_name(&detected.protocol), port)
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Short name of `protocol` in findings and `-v` output, e.g. `H2C`.
+pub fn protocol_name(protocol: &HttpProtocol) -> &'static str {
+    match protocol {
+        HttpProtocol::Http1 => "H1",
+        HttpProtocol::Http2 => "H2",
+        HttpProtocol::H2C => "H2C",
+        HttpProtocol::Http3 => "H3",
+    }
+}
+
+impl DetectTask {
+    async fn scan_path(&self, target: String) -> Result<String, ProtocolError> {
+        if self.options.dry_run {
+            println!("=== {} [Detect] protocol detection only ===\n", target);
+            return Ok(String::new());
+        }
+
+        let protocols = detect_protocol(&target).instrument(detect_span()).await?;
+        let detected = self.describe(&target, &protocols);
+        if detected.is_empty() {
+            if crate::is_verbose() {
+                eprintln!("{}: detected nothing", target);
+            }
+            return Ok(String::new());
+        }
+
+        let finding = Finding::new(&target, "Detect", detected);
+        Ok(self.options.report(finding).unwrap_or_default())
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for DetectTask {
+    type Error = ProtocolError;
+
+    /// Detection is per host, so `--paths` doesn't repeat it for every path.
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        self.scan_path(target).await
+    }
+}
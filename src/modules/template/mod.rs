@@ -0,0 +1,530 @@
+use crate::core::diff::reflects;
+use crate::core::dryrun::{dump_response, print_dry_run};
+use crate::core::finding::{Confidence, Finding};
+use crate::core::ratelimit::wait_retry_after;
+use crate::core::telemetry::{probe, protocol_span};
+use crate::modules::options::ModuleOptions;
+use crate::modules::smuggle::{PROBE_DELAY, TargetProbes};
+use crate::scanner::task::Task;
+use async_trait::async_trait;
+use riphttplib::types::protocol::HttpProtocol;
+use riphttplib::types::{ProtocolError, Response};
+use riphttplib::{H1, Protocol, parse_target};
+use std::cell::Cell;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+const MODULE: &str = "Template";
+
+/// Placeholders a template request may use; `length` is filled in last, once the body is known.
+const PLACEHOLDERS: [&str; 5] = ["host", "path", "user_agent", "marker", "length"];
+
+/// What makes the answer to an attack a finding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DetectRule {
+    /// `detect: status`: the checked response has another status than the baseline, one that
+    /// `--ignore-diff-status` and `--report-change` don't rule out.
+    StatusChange,
+    /// `detect: marker <text>`: the checked response's headers or body contain the text, which
+    /// may use `{{marker}}`.
+    Marker(String),
+    /// `detect: timing <ms>`: the attack took at least that long, or timed out, while the
+    /// baseline didn't.
+    Timing(Duration),
+}
+
+impl DetectRule {
+    fn parse(rule: &str) -> Result<Self, String> {
+        let (kind, argument) = rule
+            .split_once(char::is_whitespace)
+            .map_or((rule, ""), |(kind, argument)| (kind, argument.trim()));
+        match kind {
+            "status" if argument.is_empty() => Ok(Self::StatusChange),
+            "marker" if !argument.is_empty() => Ok(Self::Marker(argument.to_string())),
+            "timing" => argument
+                .strip_suffix("ms")
+                .unwrap_or(argument)
+                .parse()
+                .map(|millis| Self::Timing(Duration::from_millis(millis)))
+                .map_err(|_| format!("'timing' needs milliseconds, got '{}'", argument)),
+            _ => Err(format!(
+                "unknown detect rule '{}', expected 'status', 'marker <text>' or 'timing <ms>'",
+                rule
+            )),
+        }
+    }
+}
+
+/// One request block of a template, line breaks already turned into CRLF.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplateRequest {
+    pub label: String,
+    pub raw: String,
+}
+
+/// A `--template` file: a baseline request, the attacks sent after it, an optional follow-up
+/// sent after every attack, and the rules that make an attack a finding.
+///
+/// ```text
+/// name: cl-te-get
+/// detect: marker {{marker}}
+///
+/// [baseline]
+/// GET {{path}} HTTP/1.1
+/// Host: {{host}}
+///
+/// [attack space-before-colon]
+/// POST {{path}} HTTP/1.1
+/// Host: {{host}}
+/// Content-Length: {{length}}
+/// Transfer-Encoding : chunked
+///
+/// 0
+///
+/// GET /{{marker}} HTTP/1.1
+/// X: \
+///
+/// [follow-up]
+/// GET {{path}} HTTP/1.1
+/// Host: {{host}}
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestTemplate {
+    /// Named in findings; the file name unless a `name:` line says otherwise.
+    pub name: String,
+    pub detect: Vec<DetectRule>,
+    pub baseline: TemplateRequest,
+    pub attacks: Vec<TemplateRequest>,
+    /// Checked instead of the attack's own response for `status` and `marker` when given.
+    pub follow_up: Option<TemplateRequest>,
+}
+
+impl RequestTemplate {
+    /// `name:` and `detect:` lines (`#` comments and blank lines skipped) followed by
+    /// `[baseline]`, `[attack]` or `[attack <label>]` and `[follow-up]` blocks. A block runs
+    /// until the next one, blank lines at its end left out, and every line is sent followed by
+    /// CRLF: a line ending in `\` is sent without it, and `\r`, `\n` and `\\` escapes are
+    /// allowed anywhere. A block without an empty line gets one, ending its headers.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut name = None;
+        let mut detect = Vec::new();
+        let mut baseline = None;
+        let mut attacks = Vec::new();
+        let mut follow_up = None;
+
+        let mut blocks: Vec<(usize, &str, Vec<&str>)> = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let number = index + 1;
+            if let Some(section) = section_header(line) {
+                blocks.push((number, section, Vec::new()));
+                continue;
+            }
+            if let Some((_, _, lines)) = blocks.last_mut() {
+                lines.push(line);
+                continue;
+            }
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(':').ok_or_else(|| {
+                format!("line {}: expected 'name:', 'detect:' or a block", number)
+            })?;
+            match key.trim() {
+                "name" => name = Some(value.trim().to_string()),
+                "detect" => detect.push(
+                    DetectRule::parse(value.trim())
+                        .map_err(|err| format!("line {}: {}", number, err))?,
+                ),
+                other => return Err(format!("line {}: unknown setting '{}'", number, other)),
+            }
+        }
+
+        for (number, section, lines) in blocks {
+            let raw = request_block(&lines)
+                .map_err(|err| format!("line {}: [{}] {}", number, section, err))?;
+            match section {
+                "baseline" if baseline.is_some() => {
+                    return Err(format!("line {}: a second [baseline]", number));
+                }
+                "baseline" => {
+                    baseline = Some(TemplateRequest {
+                        label: "baseline".to_string(),
+                        raw,
+                    })
+                }
+                "follow-up" if follow_up.is_some() => {
+                    return Err(format!("line {}: a second [follow-up]", number));
+                }
+                "follow-up" => {
+                    follow_up = Some(TemplateRequest {
+                        label: "follow-up".to_string(),
+                        raw,
+                    })
+                }
+                attack => {
+                    let label = attack.strip_prefix("attack").unwrap_or_default().trim();
+                    let label = if label.is_empty() {
+                        format!("attack {}", attacks.len() + 1)
+                    } else {
+                        label.to_string()
+                    };
+                    attacks.push(TemplateRequest { label, raw });
+                }
+            }
+        }
+
+        if detect.is_empty() {
+            return Err("no 'detect:' rule".to_string());
+        }
+        if attacks.is_empty() {
+            return Err("no [attack] block".to_string());
+        }
+        Ok(Self {
+            name: name.unwrap_or_default(),
+            detect,
+            baseline: baseline.ok_or("no [baseline] block")?,
+            attacks,
+            follow_up,
+        })
+    }
+
+    /// Reads a template with [`RequestTemplate::parse`], named after the file unless it says
+    /// otherwise.
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(file_path)?;
+        let mut template = Self::parse(&content)?;
+        if template.name.is_empty() {
+            template.name = std::path::Path::new(file_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        Ok(template)
+    }
+
+    fn uses_timing(&self) -> Option<Duration> {
+        self.detect.iter().find_map(|rule| match rule {
+            DetectRule::Timing(threshold) => Some(*threshold),
+            _ => None,
+        })
+    }
+}
+
+/// `baseline`, `follow-up` or `attack ...` of a `[...]` line.
+fn section_header(line: &str) -> Option<&str> {
+    let section = line.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+    let known = section == "baseline"
+        || section == "follow-up"
+        || section == "attack"
+        || section.starts_with("attack ");
+    known.then_some(section)
+}
+
+fn request_block(lines: &[&str]) -> Result<String, String> {
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .ok_or("is empty")?;
+    let lines = &lines[..=end];
+
+    let mut raw = String::new();
+    for line in lines {
+        let (text, line_break) = unescape(line);
+        raw.push_str(&text);
+        if line_break {
+            raw.push_str("\r\n");
+        }
+    }
+    if !lines.iter().any(|line| line.trim().is_empty()) {
+        raw.push_str("\r\n");
+    }
+
+    let mut rest = raw.as_str();
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or("has an unclosed '{{'")?;
+        let name = &rest[start + 2..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "uses unknown placeholder '{{{{{}}}}}', expected one of {}",
+                name,
+                PLACEHOLDERS.join(", ")
+            ));
+        }
+        rest = &rest[start + end + 2..];
+    }
+    Ok(raw)
+}
+
+/// The line with its escapes resolved, and whether it keeps its line break.
+fn unescape(line: &str) -> (String, bool) {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            None => return (text, false),
+            Some('r') => text.push('\r'),
+            Some('n') => text.push('\n'),
+            Some('\\') => text.push('\\'),
+            Some(other) => {
+                text.push('\\');
+                text.push(other);
+            }
+        }
+    }
+    (text, true)
+}
+
+/// Runs a `--template` request sequence: the baseline, then every attack, each followed by the
+/// follow-up if there is one, reporting the attacks a detect rule matches. Requests are sent as
+/// raw HTTP/1.1, exactly as the template renders them.
+#[derive(Clone)]
+pub struct TemplateTask {
+    options: ModuleOptions,
+    template: Option<Arc<RequestTemplate>>,
+}
+
+impl Default for TemplateTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TemplateTask {
+    pub fn new() -> Self {
+        Self::with_options(ModuleOptions::default())
+    }
+
+    /// Runs `options.template`; without one the task reports nothing.
+    pub fn with_options(options: ModuleOptions) -> Self {
+        let template = options.template.clone();
+        Self { options, template }
+    }
+
+    pub fn with_template(mut self, template: RequestTemplate) -> Self {
+        self.template = Some(Arc::new(template));
+        self
+    }
+
+    /// `request` rendered for `target`. `{{length}}` becomes the byte length of what follows
+    /// the first empty line.
+    pub fn render(
+        &self,
+        request: &TemplateRequest,
+        target: &str,
+        marker: &str,
+    ) -> Result<String, ProtocolError> {
+        let parsed = parse_target(target)?;
+        let rendered = request
+            .raw
+            .replace("{{host}}", &self.options.authority(parsed.authority()))
+            .replace("{{path}}", parsed.path())
+            .replace("{{user_agent}}", &self.options.user_agent())
+            .replace("{{marker}}", marker);
+        let length = rendered
+            .split_once("\r\n\r\n")
+            .map_or(0, |(_, body)| body.len());
+        Ok(rendered.replace("{{length}}", &length.to_string()))
+    }
+
+    fn print_payloads(
+        &self,
+        template: &RequestTemplate,
+        target: &str,
+        marker: &str,
+    ) -> Result<(), ProtocolError> {
+        let mut payloads = vec![(
+            "baseline".to_string(),
+            self.render(&template.baseline, target, marker)?,
+        )];
+        for number in self.options.probe_order(target, template.attacks.len()) {
+            let attack = &template.attacks[number];
+            payloads.push((attack.label.clone(), self.render(attack, target, marker)?));
+            if let Some(follow_up) = &template.follow_up {
+                payloads.push((
+                    format!("follow-up {}", attack.label),
+                    self.render(follow_up, target, marker)?,
+                ));
+            }
+        }
+        print_dry_run(target, MODULE, &payloads);
+        Ok(())
+    }
+
+    /// Why `response`, the answer to an attack or its follow-up, matches a detect rule.
+    fn matches(
+        &self,
+        template: &RequestTemplate,
+        marker: &str,
+        baseline: &Response,
+        response: Option<&Response>,
+        elapsed: Duration,
+    ) -> Option<(String, Option<Confidence>)> {
+        template.detect.iter().find_map(|rule| match rule {
+            DetectRule::StatusChange => response
+                .filter(|response| {
+                    response.status != baseline.status
+                        && self
+                            .options
+                            .interesting_change(baseline.status, response.status)
+                })
+                .map(|response| {
+                    (
+                        format!("status {} (baseline {})", response.status, baseline.status),
+                        None,
+                    )
+                }),
+            DetectRule::Marker(text) => {
+                let text = text.replace("{{marker}}", marker);
+                response
+                    .filter(|response| reflects(response, &text))
+                    .map(|response| {
+                        (
+                            format!("reflected {:?} (status {})", text, response.status),
+                            Some(Confidence::High),
+                        )
+                    })
+            }
+            DetectRule::Timing(threshold) => {
+                (elapsed >= *threshold).then(|| (format!("took {}ms", elapsed.as_millis()), None))
+            }
+        })
+    }
+}
+
+impl TemplateTask {
+    async fn scan_path(&self, target: String) -> Result<String, ProtocolError> {
+        let Some(template) = self.template.as_deref() else {
+            return Ok(String::new());
+        };
+        let marker = self
+            .options
+            .echo_marker(&target)
+            .unwrap_or_else(|| self.options.marker("rpt", 12, &target));
+
+        if self.options.dry_run {
+            self.print_payloads(template, &target, &marker)?;
+            return Ok(String::new());
+        }
+
+        let client = H1::timeouts(self.options.timeouts());
+        let mut probes = TargetProbes::new(&self.options, MODULE, MODULE, &target, &client);
+        let timing = template.uses_timing();
+
+        let baseline_payload = self.render(&template.baseline, &target, &marker)?;
+        // Timing rules compare against the last baseline sent, not the warm-up before it.
+        let baseline_elapsed = Cell::new(Duration::ZERO);
+        let baseline = probes
+            .baseline(
+                async || {
+                    let start = Instant::now();
+                    let response = client
+                        .send_raw(&target, baseline_payload.clone().into())
+                        .await;
+                    baseline_elapsed.set(start.elapsed());
+                    response
+                },
+                || Ok(baseline_payload.clone()),
+            )
+            .await?;
+        let baseline_elapsed = baseline_elapsed.get();
+        wait_retry_after(&target, &baseline).await;
+
+        if self.options.skip_baseline_status.contains(&baseline.status)
+            || timing.is_some_and(|threshold| baseline_elapsed >= threshold)
+        {
+            return Ok(String::new());
+        }
+
+        let probe_count = self.options.probes.max(1);
+        let mut findings = Vec::new();
+        for number in self.options.probe_order(&target, template.attacks.len()) {
+            let attack = &template.attacks[number];
+            let payload = self.render(attack, &target, &marker)?;
+            for i in 0..probe_count {
+                let label = format!("{} probe {}", attack.label, i + 1);
+                let response = match probes.attack(&label, &payload).await {
+                    Ok(response) => Some(self.options.cap_body(response)),
+                    // A stalled back end is what a timing rule looks for.
+                    Err(ProtocolError::Timeout) if timing.is_some() => None,
+                    Err(err) => {
+                        if probes.failed(&err) {
+                            return Ok(findings.join("\n"));
+                        }
+                        break;
+                    }
+                };
+                let elapsed = probes.elapsed();
+
+                let checked = match &template.follow_up {
+                    Some(follow_up) => {
+                        let follow_up = self.render(follow_up, &target, &marker)?;
+                        let label = format!("follow-up {} probe {}", attack.label, i + 1);
+                        match probe(&label, client.send_raw(&target, follow_up.into())).await {
+                            Ok(res) => {
+                                probes.answered();
+                                let res = self.options.cap_body(res);
+                                dump_response(&target, MODULE, &label, &res);
+                                wait_retry_after(&target, &res).await;
+                                Some(res)
+                            }
+                            Err(_) => return Ok(findings.join("\n")),
+                        }
+                    }
+                    None => response,
+                };
+
+                let Some((reason, confidence)) =
+                    self.matches(template, &marker, &baseline, checked.as_ref(), elapsed)
+                else {
+                    break;
+                };
+                // Earlier probes all matched as well, or the loop would have stopped.
+                if i + 1 == probe_count {
+                    let mut finding = Finding::new(
+                        &target,
+                        MODULE,
+                        format!(
+                            "[!] {} template {} {}: {} payload {}",
+                            target, template.name, attack.label, reason, payload
+                        ),
+                    )
+                    .with_payload(payload.as_str())
+                    .with_marker(Some(&marker))
+                    .with_baseline(&baseline);
+                    if let Some(confidence) = confidence {
+                        finding = finding.with_confidence(confidence);
+                    }
+                    if let Some(response) = &checked {
+                        finding = finding.with_response(response);
+                    }
+                    findings.extend(self.options.report(finding));
+                } else {
+                    tokio::time::sleep(PROBE_DELAY).await;
+                }
+            }
+        }
+
+        Ok(findings.join("\n"))
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for TemplateTask {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        self.options
+            .scan_paths(&target, |target| {
+                self.scan_path(target)
+                    .instrument(protocol_span("Template", HttpProtocol::Http1))
+            })
+            .await
+    }
+}
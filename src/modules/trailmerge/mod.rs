@@ -1,63 +1,209 @@
-use crate::core::constants::HTTP_USER_AGENT;
+use crate::core::altsvc::{extract_alt_svc_port, fetch_alt_svc_header};
+use crate::core::dryrun::{
+    dump_payload, dump_response, dumping_payloads, print_dry_run, render_request,
+};
+use crate::core::finding::Finding;
+use crate::core::telemetry::{detect_span, probe, protocol_span};
+use crate::core::utils::protocol_mismatch;
+use crate::modules::detect::protocol_name;
+use crate::modules::options::{DEFAULT_IO_TIMEOUT, ModuleOptions};
+use crate::scanner::progress::print_above_bar;
 use crate::scanner::task::Task;
 use async_trait::async_trait;
+use futures::future::join_all;
 use riphttplib::types::protocol::HttpProtocol;
 use riphttplib::types::{ClientTimeouts, ProtocolError, Request, Response};
-use riphttplib::{DetectedProtocol, H1, H2, H3, detect_protocol};
-use std::time::Duration;
+use riphttplib::{DetectedProtocol, H1, H2, H3, Protocol, detect_protocol};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+use url::Url;
 
-const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
-const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// QUIC handshakes to hosts that firewall UDP never complete, so HTTP/3 gives up sooner
+/// unless `--connect-timeout` is given.
+const H3_CONNECT_TIMEOUT: Duration = Duration::from_millis(1500);
+/// Most protocol and method combinations of one target probed at the same time. Each adds its
+/// own connection on top of the `--threads` targets in flight.
+const MAX_PROBE_FANOUT: usize = 3;
+/// Method of every probe unless `--methods` lists others.
+const DEFAULT_METHOD: &str = "POST";
 
-#[derive(Clone, Copy, Default)]
-pub struct TrailMergeTask;
+/// Chunked body shared by every probe unless `--baseline-body` is given; its only job is to
+/// carry the trailers.
+const PROBE_BODY: &str = "aaaaaaaaa";
+const TEST_TRAILERS: [&str; 2] = ["test: testlongolonglonglongheader", "content-length: 0"];
+const TIMEOUT_TRAILERS: [&str; 2] = [
+    "test: testlongolonglonglongheader",
+    "content-length: 100000",
+];
+const EXPECT_TRAILERS: [&str; 1] = ["expect: 100-continue"];
+/// Harmless trailer `--classify-trailers` checks a target accepts before the stalling probe.
+const CLASSIFY_TRAILERS: [&str; 1] = ["x-trailer-check: 1"];
+/// Answers to the classify probe that mean trailers are refused outright.
+const TRAILERS_REJECTED_STATUS: [u16; 4] = [400, 411, 413, 501];
+/// Bytes the merge check sends after the chunked message; a trailer Content-Length exceeds the
+/// chunked body by exactly their length.
+const MERGE_PAD: &str = "riphttp-trailer-merge-check";
+
+#[derive(Clone)]
+pub struct TrailMergeTask {
+    options: ModuleOptions,
+}
+
+impl Default for TrailMergeTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl TrailMergeTask {
     pub fn new() -> Self {
-        Self
+        Self::with_options(ModuleOptions::default())
+    }
+
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self { options }
+    }
+
+    /// `--baseline-body`, or the built-in filler.
+    fn probe_body(&self) -> &str {
+        self.options.baseline_body.as_deref().unwrap_or(PROBE_BODY)
+    }
+
+    fn build_probe_request(
+        &self,
+        target: &str,
+        method: &str,
+        trailers: &[&str],
+        timeouts: &ClientTimeouts,
+    ) -> Result<Request, ProtocolError> {
+        let mut request = Request::new(target, method)?.body(self.probe_body().to_owned());
+        for trailer in trailers {
+            request = request.trailer(trailer);
+        }
+        Ok(request.timeout(timeouts.clone()).follow_redirects(false))
     }
 
     fn build_test_request(
+        &self,
         target: &str,
+        method: &str,
         timeouts: &ClientTimeouts,
     ) -> Result<Request, ProtocolError> {
-        Ok(Request::new(target, "POST")?
-            .header(&format!("user-agent: {}", HTTP_USER_AGENT))
-            .body("aaaaaaaaa")
-            .trailer("test: testlongolonglonglongheader")
-            .trailer("content-length: 0")
-            .timeout(timeouts.clone())
-            .follow_redirects(false))
+        self.build_probe_request(target, method, &TEST_TRAILERS, timeouts)
     }
 
     fn build_timeout_request(
+        &self,
         target: &str,
+        method: &str,
         timeouts: &ClientTimeouts,
     ) -> Result<Request, ProtocolError> {
-        Ok(Request::new(target, "POST")?
-            .header(&format!("user-agent: {}", HTTP_USER_AGENT))
-            .body("aaaaaaaaa")
-            .trailer("test: testlongolonglonglongheader")
-            .trailer("content-length: 100000")
-            // .trailer("user-agent: xxx")
-            .timeout(timeouts.clone())
-            .follow_redirects(false))
+        self.build_probe_request(target, method, &self.attack_trailers(), timeouts)
+    }
+
+    /// Trailers of the attack probe: `--trailer` when given, else the built-in pair that
+    /// promises a body the back end waits for.
+    fn attack_trailers(&self) -> Vec<&str> {
+        if self.options.trailers.is_empty() {
+            TIMEOUT_TRAILERS.to_vec()
+        } else {
+            self.options.trailers.iter().map(String::as_str).collect()
+        }
     }
 
     fn build_expect_request(
+        &self,
         target: &str,
+        method: &str,
         timeouts: &ClientTimeouts,
     ) -> Result<Request, ProtocolError> {
-        Ok(Request::new(target, "POST")?
-            .header(&format!("user-agent: {}", HTTP_USER_AGENT))
-            .body("aaaaaaaaa")
-            .trailer("expect: 100-continue")
-            .timeout(timeouts.clone())
-            .follow_redirects(false))
+        self.build_probe_request(target, method, &EXPECT_TRAILERS, timeouts)
     }
 
-    fn apply_detected_port(request: Request, detected: &DetectedProtocol) -> Request {
-        if let Some(port) = detected.port {
+    fn build_classify_request(
+        &self,
+        target: &str,
+        method: &str,
+        timeouts: &ClientTimeouts,
+    ) -> Result<Request, ProtocolError> {
+        self.build_probe_request(target, method, &CLASSIFY_TRAILERS, timeouts)
+    }
+
+    fn render_probe(
+        &self,
+        target: &str,
+        method: &str,
+        trailers: &[&str],
+    ) -> Result<String, ProtocolError> {
+        let headers = self
+            .options
+            .with_host_header(vec![format!("user-agent: {}", self.options.user_agent())]);
+        render_request(method, target, &headers, Some(self.probe_body()), trailers)
+    }
+
+    /// The HTTP/1.1 merge check: a chunked message whose trailer Content-Length is `MERGE_PAD`
+    /// longer than its body, followed by the pad itself when `padded`.
+    fn render_merge_probe(
+        &self,
+        target: &str,
+        method: &str,
+        padded: bool,
+    ) -> Result<String, ProtocolError> {
+        let trailer = format!(
+            "content-length: {}",
+            self.probe_body().len() + MERGE_PAD.len()
+        );
+        let mut probe = self.render_probe(target, method, &[trailer.as_str()])?;
+        if padded {
+            probe.push_str(MERGE_PAD);
+        }
+        Ok(probe)
+    }
+
+    /// Methods every probe is sent with, `--methods` or the default POST.
+    fn methods(&self) -> Vec<&str> {
+        if self.options.methods.is_empty() {
+            vec![DEFAULT_METHOD]
+        } else {
+            self.options.methods.iter().map(String::as_str).collect()
+        }
+    }
+
+    fn print_payloads(&self, target: &str) -> Result<(), ProtocolError> {
+        let methods = self.methods();
+        let mut payloads = Vec::with_capacity(3 * methods.len());
+        let attack_trailers = self.attack_trailers();
+        for method in methods {
+            if self.options.classify_trailers {
+                payloads.push((
+                    format!("classify {}", method),
+                    self.render_probe(target, method, &CLASSIFY_TRAILERS)?,
+                ));
+            }
+            for (label, trailers) in [
+                ("baseline", &TEST_TRAILERS[..]),
+                ("expect", &EXPECT_TRAILERS[..]),
+                ("attack", &attack_trailers[..]),
+            ] {
+                payloads.push((
+                    format!("{} {}", label, method),
+                    self.render_probe(target, method, trailers)?,
+                ));
+            }
+            // Only sent over HTTP/1.1, after an attack finding.
+            for (label, padded) in [("merge wait", false), ("merge fill", true)] {
+                payloads.push((
+                    format!("{} {}", label, method),
+                    self.render_merge_probe(target, method, padded)?,
+                ));
+            }
+        }
+        print_dry_run(target, "TrailMerge", &payloads);
+        Ok(())
+    }
+
+    fn apply_detected_port(request: Request, port: Option<u16>) -> Request {
+        if let Some(port) = port {
             request.set_port(port)
         } else {
             request
@@ -79,104 +225,400 @@ impl TrailMergeTask {
     }
 
     async fn scan_protocol(
+        &self,
         target: &str,
-        detected: &DetectedProtocol,
+        protocol: &HttpProtocol,
+        method: &str,
+        port: Option<u16>,
         timeouts: &ClientTimeouts,
+        proxy: Option<&str>,
     ) -> Result<Option<String>, ProtocolError> {
-        // let probes = 3;
+        // Probes of one target run concurrently, so each protocol and method gets its own pauses.
+        let mut jitter = self
+            .options
+            .jitter_for(&format!("{} {} {}", target, protocol, method));
+
+        if self.options.classify_trailers {
+            let classify_request = self.build_classify_request(target, method, timeouts)?;
+            let classify_request = Self::apply_detected_port(classify_request, port);
+            let classify_request = self.options.prepare(classify_request, proxy)?;
+            jitter.sleep().await;
+            if dumping_payloads() {
+                dump_payload(
+                    target,
+                    "TrailMerge",
+                    &format!("classify {} {}", protocol, method),
+                    &self.render_probe(target, method, &CLASSIFY_TRAILERS)?,
+                );
+            }
+            let response = probe(
+                format_args!("classify {}", method),
+                Self::send_with_protocol(protocol, classify_request, timeouts),
+            )
+            .await?;
+            let response = self.options.cap_body(response);
+            dump_response(
+                target,
+                "TrailMerge",
+                format_args!("classify {} {}", protocol, method),
+                &response,
+            );
+            // No point waiting out the stalling probe on a server that refuses trailers.
+            if TRAILERS_REJECTED_STATUS.contains(&response.status) {
+                return Ok(Some(format!(
+                    "[-] trailers rejected {} {} {} {:?} status {}",
+                    protocol, method, target, port, response.status
+                )));
+            }
+        }
 
         // Send baseline request first
-        let test_request = Self::build_test_request(target, timeouts)?;
-        let test_request = Self::apply_detected_port(test_request, detected);
+        let test_request = self.build_test_request(target, method, timeouts)?;
+        let test_request = Self::apply_detected_port(test_request, port);
+        let test_request = self.options.prepare(test_request, proxy)?;
 
-        let test_response =
-            match Self::send_with_protocol(&detected.protocol, test_request, timeouts).await {
-                Ok(response) => response,
-                Err(ProtocolError::Timeout) => {
-                    return Ok(None);
-                }
-                Err(err) => return Err(err),
-            };
+        jitter.sleep().await;
+        self.options
+            .warm_up(target, async || {
+                let request = self.build_test_request(target, method, timeouts)?;
+                let request = Self::apply_detected_port(request, port);
+                Self::send_with_protocol(protocol, self.options.prepare(request, proxy)?, timeouts)
+                    .await
+            })
+            .await;
+        if dumping_payloads() {
+            dump_payload(
+                target,
+                "TrailMerge",
+                &format!("baseline {} {}", protocol, method),
+                &self.render_probe(target, method, &TEST_TRAILERS)?,
+            );
+        }
+        let start = Instant::now();
+        let test_response = probe(
+            format_args!("baseline {}", method),
+            Self::send_with_protocol(protocol, test_request, timeouts),
+        )
+        .await;
+        let test_elapsed = start.elapsed();
+        self.options.report_proxy(proxy, &test_response);
+        let test_response = match test_response {
+            Ok(response) => self.options.cap_body(response),
+            Err(ProtocolError::Timeout) => {
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        if crate::is_verbose()
+            && let Some(mismatch) = protocol_mismatch(protocol, &test_response)
+        {
+            eprintln!(
+                "{} {} baseline of {}: {}",
+                protocol, method, target, mismatch
+            );
+        }
+        dump_response(
+            target,
+            "TrailMerge",
+            format_args!("baseline {} {}", protocol, method),
+            &test_response,
+        );
 
-        if Self::interpret_status(&detected, test_response.status, target).is_some() {
+        if self
+            .interpret_status(protocol, method, port, test_response.status, target)
+            .is_some()
+        {
             return Ok(None);
         }
 
         // test expect
-        let expect_req = Self::build_expect_request(target, timeouts)?;
-        let expect_req = Self::apply_detected_port(expect_req, detected);
-        match Self::send_with_protocol(&detected.protocol, expect_req, timeouts).await {
+        let expect_req = self.build_expect_request(target, method, timeouts)?;
+        let expect_req = Self::apply_detected_port(expect_req, port);
+        let expect_req = self.options.prepare(expect_req, proxy)?;
+        jitter.sleep().await;
+        if dumping_payloads() {
+            dump_payload(
+                target,
+                "TrailMerge",
+                &format!("expect {} {}", protocol, method),
+                &self.render_probe(target, method, &EXPECT_TRAILERS)?,
+            );
+        }
+        match probe(
+            format_args!("expect {}", method),
+            Self::send_with_protocol(protocol, expect_req, timeouts),
+        )
+        .await
+        {
             Ok(response) => {
+                let response = self.options.cap_body(response);
+                dump_response(
+                    target,
+                    "TrailMerge",
+                    format_args!("expect {} {}", protocol, method),
+                    &response,
+                );
                 if response.status == 100 {
-                    return Ok(Some(format!(
-                        "[!+] got expect! {} {} {:?}",
-                        detected.protocol, target, detected.port
-                    )));
+                    let message = format!(
+                        "[!+] got expect! {} {} {} {:?}",
+                        protocol, method, target, port
+                    );
+                    let finding = Finding::new(target, "TrailMerge", message)
+                        .with_payload(self.render_probe(target, method, &EXPECT_TRAILERS)?)
+                        .with_baseline(&test_response)
+                        .with_response(&response);
+                    return Ok(self.options.report(finding));
                 }
             }
             Err(ProtocolError::Timeout) => {}
             _ => {}
         };
 
-        let attack_request = Self::build_timeout_request(target, timeouts)?;
-        let attack_request = Self::apply_detected_port(attack_request, detected);
+        let attack_request = self.build_timeout_request(target, method, timeouts)?;
+        let attack_request = Self::apply_detected_port(attack_request, port);
+        let attack_request = self.options.prepare(attack_request, proxy)?;
         // let mut diff = false;
 
         // for i in 0..probes {
-            // timeout payload
-        let response =
-            Self::send_with_protocol(&detected.protocol, attack_request, timeouts).await?;
+        // timeout payload
+        jitter.sleep().await;
+        if dumping_payloads() {
+            dump_payload(
+                target,
+                "TrailMerge",
+                &format!("attack {} {}", protocol, method),
+                &self.render_probe(target, method, &self.attack_trailers())?,
+            );
+        }
+        let start = Instant::now();
+        let response = probe(
+            format_args!("attack {}", method),
+            Self::send_with_protocol(protocol, attack_request, timeouts),
+        )
+        .await?;
+        let elapsed = start.elapsed();
+        let response = self.options.cap_body(response);
+        dump_response(
+            target,
+            "TrailMerge",
+            format_args!("attack {} {}", protocol, method),
+            &response,
+        );
+
+        // Some front ends answer normally once the back end gives up waiting for the promised
+        // body, so a stall is a signal of its own. It only counts if the baseline was quick.
+        let threshold = self.timeout_threshold(timeouts);
+        let message = self
+            .interpret_status(protocol, method, port, response.status, target)
+            .or_else(|| {
+                (elapsed >= threshold && test_elapsed < threshold).then(|| {
+                    format!(
+                        "[+] stalled response! {} {} {} {:?} status {} after {}ms",
+                        protocol,
+                        method,
+                        target,
+                        port,
+                        response.status,
+                        elapsed.as_millis()
+                    )
+                })
+            });
+
+        let message = match message {
+            // Its probes are sent raw, which would bypass the proxy and reach the target directly.
+            Some(message) if *protocol == HttpProtocol::Http1 && proxy.is_some() => Some(format!(
+                "{} (merge check skipped: its raw probes can't go through the proxy)",
+                message
+            )),
+            Some(message) if *protocol == HttpProtocol::Http1 => {
+                match self.confirm_merge(target, method, port, timeouts).await {
+                    Some(extra) => Some(format!(
+                        "{} merged trailer content-length: waited for exactly {} extra bytes",
+                        message, extra
+                    )),
+                    None => Some(message),
+                }
+            }
+            message => message,
+        };
 
-        Ok(Self::interpret_status(&detected, response.status, target))
+        Ok(match message {
+            Some(message) => {
+                let finding = Finding::new(target, "TrailMerge", message)
+                    .with_payload(self.render_probe(target, method, &self.attack_trailers())?)
+                    .with_baseline(&test_response)
+                    .with_response(&response)
+                    .with_latency(elapsed);
+                self.options.report(finding)
+            }
+            None => self.options.classify_trailers.then(|| {
+                format!(
+                    "[-] trailers accepted, no desync {} {} {} {:?}",
+                    protocol, method, target, port
+                )
+            }),
+        })
         // }
     }
 
-    fn interpret_status(detected: &DetectedProtocol, status: u16, target: &str) -> Option<String> {
-        match status {
-            100 => Some(format!(
-                "[!+] got expect! {} {} {:?}",
-                detected.protocol, target, detected.port
-            )),
-            // 502 => Some(format!("[?] bad gateway {} {}", detected.protocol, target)),
-            // 503 => Some(format!(
-            //     "[?] service unavailable {} {}",
-            //     detected.protocol, target
-            // )),
-            504 => Some(format!(
-                "[+] gateway timeout! {} {} {:?}",
-                detected.protocol, target, detected.port
-            )),
-            _ => None,
+    /// Tells a merged trailer Content-Length apart from a back end that merely stalled. The same
+    /// message is sent twice, each over its own connection: on its own, a back end that merged
+    /// the trailer waits for `MERGE_PAD` more bytes, and followed by exactly those bytes it
+    /// answers in time. Returns the number of extra bytes it waited for when both held. Both
+    /// are written raw to a direct connection, so it isn't run through a proxy.
+    async fn confirm_merge(
+        &self,
+        target: &str,
+        method: &str,
+        port: Option<u16>,
+        timeouts: &ClientTimeouts,
+    ) -> Option<usize> {
+        let mut url = Url::parse(target).ok()?;
+        if port.is_some() {
+            url.set_port(port).ok()?;
         }
+        let threshold = self.timeout_threshold(timeouts);
+        let client = H1::timeouts(timeouts.clone());
+
+        let mut answered_in_time = Vec::with_capacity(2);
+        for (label, padded) in [("merge wait", false), ("merge fill", true)] {
+            let payload = self.render_merge_probe(target, method, padded).ok()?;
+            if dumping_payloads() {
+                dump_payload(
+                    target,
+                    "TrailMerge",
+                    &format!("{} {}", label, method),
+                    &payload,
+                );
+            }
+            let start = Instant::now();
+            let in_time = match probe(
+                format_args!("{} {}", label, method),
+                client.send_raw(url.as_str(), payload.into()),
+            )
+            .await
+            {
+                Ok(response) => {
+                    dump_response(
+                        target,
+                        "TrailMerge",
+                        format_args!("{} {}", label, method),
+                        &response,
+                    );
+                    response.status != 504 && start.elapsed() < threshold
+                }
+                Err(ProtocolError::Timeout) => false,
+                Err(err) => {
+                    tracing::debug!(target, error = %err, "merge check failed");
+                    return None;
+                }
+            };
+            answered_in_time.push(in_time);
+        }
+        (answered_in_time == [false, true]).then_some(MERGE_PAD.len())
+    }
+
+    /// How long the attack may take before it counts as a back-end stall: `--timeout-threshold`,
+    /// or 90% of the read timeout.
+    fn timeout_threshold(&self, timeouts: &ClientTimeouts) -> Duration {
+        self.options
+            .timeout_threshold
+            .unwrap_or_else(|| timeouts.read.unwrap_or(DEFAULT_IO_TIMEOUT) * 9 / 10)
+    }
+
+    /// A labeled line for a response whose status is in `--filter-status` (100 and 504 by default).
+    fn interpret_status(
+        &self,
+        protocol: &HttpProtocol,
+        method: &str,
+        port: Option<u16>,
+        status: u16,
+        target: &str,
+    ) -> Option<String> {
+        if !self.options.filter_status.contains(&status) {
+            return None;
+        }
+        let label = match status {
+            100 => "[!+] got expect!".to_string(),
+            502 => "[?] bad gateway".to_string(),
+            503 => "[?] service unavailable".to_string(),
+            504 => "[+] gateway timeout!".to_string(),
+            status => format!("[?] status {}", status),
+        };
+        Some(format!(
+            "{} {} {} {} {:?}",
+            label, protocol, method, target, port
+        ))
     }
 }
 
-#[async_trait(?Send)]
-impl Task for TrailMergeTask {
-    type Error = ProtocolError;
+impl TrailMergeTask {
+    async fn scan_path(&self, target: String) -> Result<String, ProtocolError> {
+        let timeouts = self.options.timeouts();
 
-    async fn execute(&self, target: String) -> Result<String, Self::Error> {
-        let timeouts = ClientTimeouts {
-            connect: Some(CONNECT_TIMEOUT),
-            read: Some(IO_TIMEOUT),
-            write: Some(IO_TIMEOUT),
+        if self.options.dry_run {
+            self.print_payloads(&target)?;
+            return Ok(String::new());
+        }
+
+        let Ok(proxy) = self.options.select_proxy(&target) else {
+            return Ok(String::new());
         };
+        let mut protocols = detect_protocol(&target).instrument(detect_span()).await?;
+        if self.options.scans_protocol(&HttpProtocol::Http3) {
+            self.discover_h3_port(&target, &mut protocols, &timeouts, proxy.as_deref())
+                .await;
+        }
+        if crate::is_verbose() {
+            print_above_bar(&describe_protocols(&target, &protocols));
+        }
+        let methods = self.methods();
+        let probes: Vec<(&DetectedProtocol, &str)> = protocols
+            .iter()
+            .filter(|detected| self.options.scans_protocol(&detected.protocol))
+            .flat_map(|detected| methods.iter().map(move |method| (detected, *method)))
+            .collect();
 
-        let protocols = detect_protocol(&target).await?;
-        let mut findings = Vec::new();
+        // The probes are independent, so they run together, a few at a time. Results come back
+        // in detection order, which keeps the findings deterministic.
+        let mut results = Vec::with_capacity(probes.len());
+        for chunk in probes.chunks(MAX_PROBE_FANOUT) {
+            results.extend(
+                join_all(chunk.iter().map(|(detected, method)| {
+                    self.scan_detected(
+                        &target,
+                        detected,
+                        method,
+                        &protocols,
+                        &timeouts,
+                        proxy.as_deref(),
+                    )
+                }))
+                .await,
+            );
+        }
 
-        // detect supported protocols for the target
-        for detected in protocols {
-            let protocol = detected.protocol.clone();
-            match Self::scan_protocol(&target, &detected, &timeouts).await {
+        let mut findings = Vec::new();
+        for ((protocol, result), (_, method)) in results.into_iter().zip(&probes) {
+            match result {
                 Ok(Some(message)) => findings.push(message),
                 Ok(None) => {}
                 Err(ProtocolError::Timeout) => {
-                    findings.push(format!("[!] timeout {} {}", protocol, target));
+                    findings.push(format!("[!] timeout {} {} {}", protocol, method, target));
                 }
                 Err(err) => {
+                    tracing::warn!(
+                        target = %target,
+                        protocol = %protocol,
+                        method = %method,
+                        error = %err,
+                        "probe failed"
+                    );
                     if crate::is_verbose() {
-                        eprintln!("Failed to scan {} using {}: {}", target, protocol, err);
+                        eprintln!(
+                            "Failed to scan {} using {} {}: {}",
+                            target, protocol, method, err
+                        );
                     }
                     if matches!(&err, ProtocolError::InvalidTarget(_)) {
                         return Err(err);
@@ -187,4 +629,152 @@ impl Task for TrailMergeTask {
 
         Ok(findings.join("\n"))
     }
+
+    /// Probes one detected protocol with `method`, retrying over TCP per `--h3-fallback`.
+    /// Returns the protocol actually used along with the result.
+    async fn scan_detected(
+        &self,
+        target: &str,
+        detected: &DetectedProtocol,
+        method: &str,
+        protocols: &[DetectedProtocol],
+        timeouts: &ClientTimeouts,
+        proxy: Option<&str>,
+    ) -> (HttpProtocol, Result<Option<String>, ProtocolError>) {
+        let protocol = detected.protocol.clone();
+        let timeouts = match protocol {
+            HttpProtocol::Http3 => ClientTimeouts {
+                connect: Some(self.options.connect_timeout.unwrap_or(H3_CONNECT_TIMEOUT)),
+                ..timeouts.clone()
+            },
+            _ => timeouts.clone(),
+        };
+        let result = self
+            .scan_protocol(target, &protocol, method, detected.port, &timeouts, proxy)
+            .instrument(protocol_span("TrailMerge", &protocol))
+            .await;
+
+        if protocol == HttpProtocol::Http3
+            && self.options.h3_fallback
+            && matches!(&result, Err(err) if !matches!(err, ProtocolError::InvalidTarget(_)))
+            && let Some(fallback) = self.h3_fallback_protocol(protocols)
+        {
+            if crate::is_verbose() {
+                eprintln!(
+                    "HTTP/3 probe of {} failed, retrying over {}",
+                    target, fallback
+                );
+            }
+            let timeouts = self.options.timeouts();
+            let result = self
+                .scan_protocol(target, &fallback, method, None, &timeouts, proxy)
+                .instrument(protocol_span("TrailMerge", &fallback))
+                .await;
+            return (fallback, result);
+        }
+
+        (protocol, result)
+    }
+
+    /// Points the HTTP/3 probe at the port the target advertises in `Alt-Svc` when detection
+    /// didn't report one, adding the probe if HTTP/3 is only advertised there.
+    async fn discover_h3_port(
+        &self,
+        target: &str,
+        protocols: &mut Vec<DetectedProtocol>,
+        timeouts: &ClientTimeouts,
+        proxy: Option<&str>,
+    ) {
+        if protocols
+            .iter()
+            .any(|detected| detected.protocol == HttpProtocol::Http3 && detected.port.is_some())
+        {
+            return;
+        }
+
+        let request = Request::new(target, "GET")
+            .map(|request| request.timeout(timeouts.clone()).follow_redirects(false))
+            .and_then(|request| self.options.prepare(request, proxy));
+        let header = match request {
+            Ok(request) => fetch_alt_svc_header(request, timeouts).await,
+            Err(err) => Err(err),
+        };
+        let port = match header {
+            Ok(header) => header.as_deref().and_then(extract_alt_svc_port),
+            Err(err) => {
+                if crate::is_verbose() {
+                    eprintln!("Failed to fetch Alt-Svc of {}: {}", target, err);
+                }
+                None
+            }
+        };
+        let Some(port) = port else {
+            return;
+        };
+
+        if crate::is_verbose() {
+            eprintln!("{} advertises HTTP/3 on UDP port {}", target, port);
+        }
+        match protocols
+            .iter_mut()
+            .find(|detected| detected.protocol == HttpProtocol::Http3)
+        {
+            Some(detected) => detected.port = Some(port),
+            None => protocols.push(DetectedProtocol {
+                protocol: HttpProtocol::Http3,
+                port: Some(port),
+            }),
+        }
+    }
+
+    /// The TCP protocol a failed HTTP/3 probe is retried over: HTTP/2, else HTTP/1.1. `None`
+    /// when the target is already being scanned over TCP anyway.
+    fn h3_fallback_protocol(&self, detected: &[DetectedProtocol]) -> Option<HttpProtocol> {
+        if detected.iter().any(|detected| {
+            detected.protocol != HttpProtocol::Http3
+                && self.options.scans_protocol(&detected.protocol)
+        }) {
+            return None;
+        }
+        [HttpProtocol::Http2, HttpProtocol::Http1]
+            .into_iter()
+            .find(|protocol| self.options.scans_protocol(protocol))
+    }
+}
+
+/// `example.com: detected H1(443), H2(443), H3(443:udp)`, with the target's own port for
+/// protocols detected without one.
+fn describe_protocols(target: &str, protocols: &[DetectedProtocol]) -> String {
+    if protocols.is_empty() {
+        return format!("{}: detected nothing, skipping", target);
+    }
+    let default_port = Url::parse(target)
+        .ok()
+        .and_then(|url| url.port_or_known_default());
+    let detected: Vec<String> = protocols
+        .iter()
+        .map(|detected| {
+            let name = protocol_name(&detected.protocol);
+            let port = detected
+                .port
+                .or(default_port)
+                .map_or_else(|| "?".to_string(), |port| port.to_string());
+            match detected.protocol {
+                HttpProtocol::Http3 => format!("{}({}:udp)", name, port),
+                _ => format!("{}({})", name, port),
+            }
+        })
+        .collect();
+    format!("{}: detected {}", target, detected.join(", "))
+}
+
+#[async_trait(?Send)]
+impl Task for TrailMergeTask {
+    type Error = ProtocolError;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        self.options
+            .scan_paths(&target, |target| self.scan_path(target))
+            .await
+    }
 }
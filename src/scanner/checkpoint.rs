@@ -2,14 +2,109 @@ use std::collections::HashMap;
 use std::io;
 use std::path::{Path, PathBuf};
 
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use clap::ValueEnum;
 use tokio::fs;
 
+/// First bytes of a binary checkpoint, followed by its format version.
+const BINARY_MAGIC: &[u8; 7] = b"RIPCKPT";
+/// Version 2 added the `--seed` of the scan; version 1 checkpoints are still read.
+const BINARY_VERSION: u8 = 2;
+/// Checkpoints whose completed set covers more targets than this are written in binary, even
+/// with the text format selected.
+pub const AUTO_BINARY_TARGETS: usize = 1 << 20;
+
+/// How checkpoints are written. Reading detects either.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CheckpointFormat {
+    /// Human-readable key=value lines
+    #[default]
+    Text,
+    /// Versioned header and packed fields, with the completed set as a bitmap
+    Binary,
+}
+
+/// Target indices finished so far, one bit each, so millions of targets take little room.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletedSet {
+    len: usize,
+    bits: Vec<u8>,
+}
+
+impl CompletedSet {
+    /// An empty set for `len` targets.
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            bits: vec![0; len.div_ceil(8)],
+        }
+    }
+
+    /// A set for `len` targets from the bitmap [`as_bytes`](Self::as_bytes) returned. `None`
+    /// when the bitmap doesn't have the size `len` needs.
+    pub fn from_bytes(len: usize, bits: Vec<u8>) -> Option<Self> {
+        (bits.len() == len.div_ceil(8)).then_some(Self { len, bits })
+    }
+
+    /// How many targets the set covers, finished or not.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Marks target `index` finished. Indices past the end are ignored.
+    pub fn insert(&mut self, index: usize) {
+        if index < self.len {
+            self.bits[index / 8] |= 1 << (index % 8);
+        }
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index < self.len && self.bits[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// How many targets are finished.
+    pub fn count(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// The bitmap, lowest index in the lowest bit of the first byte.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Checkpoint {
     pub next_index: usize,
     pub targets_path: String,
     pub output_path: String,
     pub mode: String,
+    /// `--seed` of the scan, which every other seed is derived from unless given; reused on
+    /// resume. `None` in checkpoints written before it was recorded.
+    pub seed: Option<u64>,
+    /// Seed of the User-Agent rotation, reused on resume.
+    pub ua_seed: Option<u64>,
+    /// Seed of the `--sample-count` selection, reused on resume so the same targets are picked.
+    pub sample_seed: Option<u64>,
+    /// Seed of the `--probe-order random` attack order, reused on resume.
+    pub probe_seed: Option<u64>,
+    /// Thread count of the scan. `None` in checkpoints written before it was recorded.
+    pub threads: Option<usize>,
+    /// Proxy (or proxy list) the scan went through; `None` for a direct scan.
+    pub proxy: Option<String>,
+    /// The scan appended to an output that may already hold entries of earlier runs.
+    pub append: bool,
+    /// Targets finished out of order, beyond `next_index`.
+    pub completed: Option<CompletedSet>,
+    /// How the checkpoint is written.
+    pub format: CheckpointFormat,
 }
 
 impl Checkpoint {
@@ -24,14 +119,201 @@ impl Checkpoint {
             targets_path: targets_path.into(),
             output_path: output_path.into(),
             mode: mode.into(),
+            seed: None,
+            ua_seed: None,
+            sample_seed: None,
+            probe_seed: None,
+            threads: None,
+            proxy: None,
+            append: false,
+            completed: None,
+            format: CheckpointFormat::Text,
         }
     }
 
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_ua_seed(mut self, ua_seed: Option<u64>) -> Self {
+        self.ua_seed = ua_seed;
+        self
+    }
+
+    pub fn with_sample_seed(mut self, sample_seed: Option<u64>) -> Self {
+        self.sample_seed = sample_seed;
+        self
+    }
+
+    pub fn with_probe_seed(mut self, probe_seed: Option<u64>) -> Self {
+        self.probe_seed = probe_seed;
+        self
+    }
+
+    pub fn with_append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    pub fn with_completed(mut self, completed: Option<CompletedSet>) -> Self {
+        self.completed = completed;
+        self
+    }
+
+    pub fn with_format(mut self, format: CheckpointFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_conditions(mut self, threads: usize, proxy: Option<String>) -> Self {
+        self.threads = Some(threads);
+        self.proxy = proxy;
+        self
+    }
+
     pub fn to_string(&self) -> String {
-        format!(
+        let mut data = format!(
             "next_index={}\ntargets={}\noutput={}\nmode={}\n",
             self.next_index, self.targets_path, self.output_path, self.mode
-        )
+        );
+        if let Some(seed) = self.seed {
+            data.push_str(&format!("seed={}\n", seed));
+        }
+        if let Some(seed) = self.ua_seed {
+            data.push_str(&format!("ua_seed={}\n", seed));
+        }
+        if let Some(seed) = self.sample_seed {
+            data.push_str(&format!("sample_seed={}\n", seed));
+        }
+        if let Some(seed) = self.probe_seed {
+            data.push_str(&format!("probe_seed={}\n", seed));
+        }
+        if let Some(threads) = self.threads {
+            data.push_str(&format!("threads={}\n", threads));
+        }
+        if let Some(proxy) = &self.proxy {
+            data.push_str(&format!("proxy={}\n", proxy));
+        }
+        if self.append {
+            data.push_str("append=true\n");
+        }
+        if let Some(completed) = &self.completed {
+            data.push_str(&format!(
+                "completed={}:{}\n",
+                completed.len(),
+                BASE64_STANDARD.encode(completed.as_bytes())
+            ));
+        }
+        data
+    }
+
+    /// The checkpoint in its format, or in binary once the completed set is too big to write
+    /// as text.
+    pub fn encode(&self) -> Vec<u8> {
+        let large = self
+            .completed
+            .as_ref()
+            .is_some_and(|completed| completed.len() > AUTO_BINARY_TARGETS);
+        if self.format == CheckpointFormat::Binary || large {
+            self.to_bytes()
+        } else {
+            self.to_string().into_bytes()
+        }
+    }
+
+    /// Binary layout, little-endian: magic and version, `next_index` as u64, the targets, output
+    /// and mode strings (u32 length, then UTF-8), the optional seeds (`seed`, User-Agent, sample
+    /// and probe order) and thread count (a 0/1 tag, then a u64), the optional proxy, the append
+    /// flag, and the optional completed set (its target count as u64, then the bitmap).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = BINARY_MAGIC.to_vec();
+        data.push(BINARY_VERSION);
+        data.extend((self.next_index as u64).to_le_bytes());
+        for text in [&self.targets_path, &self.output_path, &self.mode] {
+            push_string(&mut data, text);
+        }
+        for value in [
+            self.seed,
+            self.ua_seed,
+            self.sample_seed,
+            self.probe_seed,
+            self.threads.map(|threads| threads as u64),
+        ] {
+            push_optional(&mut data, value);
+        }
+        match &self.proxy {
+            Some(proxy) => {
+                data.push(1);
+                push_string(&mut data, proxy);
+            }
+            None => data.push(0),
+        }
+        data.push(self.append as u8);
+        match &self.completed {
+            Some(completed) => {
+                data.push(1);
+                data.extend((completed.len() as u64).to_le_bytes());
+                data.extend(completed.as_bytes());
+            }
+            None => data.push(0),
+        }
+        data
+    }
+
+    /// Parses a checkpoint written by [`to_bytes`](Self::to_bytes), or by version 1, which has
+    /// no `seed`. `None` when it isn't one or was written by an unknown version.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader {
+            data: data.strip_prefix(BINARY_MAGIC)?,
+        };
+        let version = reader.u8()?;
+        if !(1..=BINARY_VERSION).contains(&version) {
+            return None;
+        }
+        let next_index = reader.u64()? as usize;
+        let targets_path = reader.string()?;
+        let output_path = reader.string()?;
+        let mode = reader.string()?;
+        let seed = match version {
+            1 => None,
+            _ => reader.optional_u64()?,
+        };
+        let ua_seed = reader.optional_u64()?;
+        let sample_seed = reader.optional_u64()?;
+        let probe_seed = reader.optional_u64()?;
+        let threads = reader.optional_u64()?.map(|threads| threads as usize);
+        let proxy = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.string()?),
+            _ => return None,
+        };
+        let append = reader.u8()? != 0;
+        let completed = match reader.u8()? {
+            0 => None,
+            1 => {
+                let len = reader.u64()? as usize;
+                let bits = reader.take(len.div_ceil(8))?.to_vec();
+                Some(CompletedSet::from_bytes(len, bits)?)
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            next_index,
+            targets_path,
+            output_path,
+            mode,
+            seed,
+            ua_seed,
+            sample_seed,
+            probe_seed,
+            threads,
+            proxy,
+            append,
+            completed,
+            format: CheckpointFormat::Binary,
+        })
     }
 
     pub fn from_str(data: &str) -> Option<Self> {
@@ -46,28 +328,171 @@ impl Checkpoint {
         let targets_path = values.get("targets")?.clone();
         let output_path = values.get("output")?.clone();
         let mode = values.get("mode")?.clone();
+        let seed = values.get("seed").and_then(|seed| seed.parse().ok());
+        let ua_seed = values.get("ua_seed").and_then(|seed| seed.parse().ok());
+        let sample_seed = values.get("sample_seed").and_then(|seed| seed.parse().ok());
+        let probe_seed = values.get("probe_seed").and_then(|seed| seed.parse().ok());
+        let threads = values
+            .get("threads")
+            .and_then(|threads| threads.parse().ok());
+        let proxy = values.get("proxy").cloned();
+        let append = values.get("append").is_some_and(|append| append == "true");
+        let completed = match values.get("completed") {
+            Some(completed) => {
+                let (len, bits) = completed.split_once(':')?;
+                let bits = BASE64_STANDARD.decode(bits).ok()?;
+                Some(CompletedSet::from_bytes(len.parse().ok()?, bits)?)
+            }
+            None => None,
+        };
 
         Some(Self {
             next_index,
             targets_path,
             output_path,
             mode,
+            seed,
+            ua_seed,
+            sample_seed,
+            probe_seed,
+            threads,
+            proxy,
+            append,
+            completed,
+            format: CheckpointFormat::Text,
         })
     }
 }
 
+fn push_string(data: &mut Vec<u8>, text: &str) {
+    data.extend((text.len() as u32).to_le_bytes());
+    data.extend(text.as_bytes());
+}
+
+fn push_optional(data: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) => {
+            data.push(1);
+            data.extend(value.to_le_bytes());
+        }
+        None => data.push(0),
+    }
+}
+
+/// Reads the fields of a binary checkpoint in order; every read is `None` past the end.
+struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.data.len() < count {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(count);
+        self.data = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().ok()?) as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn optional_u64(&mut self) -> Option<Option<u64>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => Some(Some(self.u64()?)),
+            _ => None,
+        }
+    }
+}
+
+/// Where the entries of an output file fall in the target list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputCoverage {
+    /// Entries whose target is in the list.
+    pub entries: usize,
+    /// Lowest and highest target index among them.
+    pub first: usize,
+    pub last: usize,
+}
+
+/// Maps each entry of a plain-text output file back to the index of its target, to check the
+/// output against a checkpoint. Lines that don't start an entry (continuations, `#` markers)
+/// are ignored; a target listed twice counts as its first occurrence. `None` when no entry
+/// matches a target.
+pub fn output_coverage(output: &str, targets: &[String]) -> Option<OutputCoverage> {
+    let mut indices = HashMap::with_capacity(targets.len());
+    for (index, target) in targets.iter().enumerate() {
+        indices.entry(target.as_str()).or_insert(index);
+    }
+
+    let mut coverage: Option<OutputCoverage> = None;
+    for line in output.lines().filter(|line| !line.starts_with('#')) {
+        // Entries are `target\toutput`, or `timestamp\ttarget\toutput` with --timestamps.
+        let mut fields = line.split('\t');
+        let Some(index) = fields
+            .next()
+            .and_then(|field| indices.get(field))
+            .or_else(|| fields.next().and_then(|field| indices.get(field)))
+        else {
+            continue;
+        };
+        coverage = Some(match coverage {
+            None => OutputCoverage {
+                entries: 1,
+                first: *index,
+                last: *index,
+            },
+            Some(coverage) => OutputCoverage {
+                entries: coverage.entries + 1,
+                first: coverage.first.min(*index),
+                last: coverage.last.max(*index),
+            },
+        });
+    }
+    coverage
+}
+
+/// Creates the directories leading to `path`, so a file can be created there.
+pub async fn create_parent_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    match path.as_ref().parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent).await,
+        _ => Ok(()),
+    }
+}
+
 pub async fn write_checkpoint(path: impl AsRef<Path>, checkpoint: &Checkpoint) -> io::Result<()> {
-    fs::write(path, checkpoint.to_string()).await
+    fs::write(path, checkpoint.encode()).await
 }
 
 pub async fn read_checkpoint(path: impl AsRef<Path>) -> io::Result<Option<Checkpoint>> {
-    match fs::read_to_string(&path).await {
-        Ok(content) => Ok(Checkpoint::from_str(&content)),
+    match fs::read(&path).await {
+        Ok(content) if content.starts_with(BINARY_MAGIC) => Ok(Checkpoint::from_bytes(&content)),
+        Ok(content) => Ok(Checkpoint::from_str(&String::from_utf8_lossy(&content))),
         Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
         Err(err) => Err(err),
     }
 }
 
+/// A checkpoint to resume from: the one at `path`, else the one a completed run left at its
+/// `.done` path with `--keep-checkpoint`.
+pub async fn read_resumable_checkpoint(path: impl AsRef<Path>) -> io::Result<Option<Checkpoint>> {
+    match read_checkpoint(&path).await? {
+        Some(checkpoint) => Ok(Some(checkpoint)),
+        None => read_checkpoint(completed_checkpoint_path(path)).await,
+    }
+}
+
 pub async fn remove_checkpoint(path: impl AsRef<Path>) -> io::Result<()> {
     let path_ref = path.as_ref();
     match fs::remove_file(path_ref).await {
@@ -77,6 +502,26 @@ pub async fn remove_checkpoint(path: impl AsRef<Path>) -> io::Result<()> {
     }
 }
 
+/// Where `--keep-checkpoint` leaves the checkpoint of a completed run, e.g. `checkpoint.done`.
+pub fn completed_checkpoint_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".done");
+    PathBuf::from(name)
+}
+
+/// Moves the checkpoint of a completed run to its `.done` path, replacing an older one, and
+/// returns that path.
+pub async fn complete_checkpoint(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let done = completed_checkpoint_path(&path);
+    fs::rename(path, &done).await?;
+    Ok(done)
+}
+
 pub fn default_checkpoint_path() -> PathBuf {
     PathBuf::from("checkpoint")
 }
+
+/// Checkpoint of the run called `name`, so scans in one directory don't share state.
+pub fn named_checkpoint_path(name: &str) -> PathBuf {
+    PathBuf::from(format!("checkpoint-{}", name))
+}
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use async_compression::tokio::bufread::GzipDecoder;
+use chrono::DateTime;
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, BufReader};
+use url::Url;
+
+/// One finding of a scan output: the target it was recorded for and its message, with the
+/// lines of a multi-line message joined back together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputEntry {
+    pub target: String,
+    pub finding: String,
+}
+
+impl OutputEntry {
+    /// What the entry is matched on between runs: its target and its message with the parts
+    /// that change on every run taken out (latencies, random markers and `--capture-headers`
+    /// lines).
+    pub fn signature(&self) -> String {
+        let finding: Vec<String> = self
+            .finding
+            .lines()
+            .filter(|line| !line.starts_with("headers: "))
+            .flat_map(str::split_whitespace)
+            .map(normalize_word)
+            .collect();
+        format!("{}\t{}", self.target, finding.join(" "))
+    }
+}
+
+/// Findings of a scan compared against an earlier one of the same targets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputDiff {
+    /// Only in the current output.
+    pub new: Vec<OutputEntry>,
+    /// Only in the previous output.
+    pub resolved: Vec<OutputEntry>,
+    /// In both, as recorded by the current run.
+    pub unchanged: Vec<OutputEntry>,
+}
+
+impl OutputDiff {
+    pub fn to_json(&self) -> Value {
+        let entries = |entries: &[OutputEntry]| -> Vec<Value> {
+            entries
+                .iter()
+                .map(|entry| json!({ "target": entry.target, "finding": entry.finding }))
+                .collect()
+        };
+        json!({
+            "new": entries(&self.new),
+            "resolved": entries(&self.resolved),
+            "unchanged": entries(&self.unchanged),
+        })
+    }
+
+    /// `+` new, `-` resolved and `=` unchanged findings, one per line, after a count of each.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{} new, {} resolved, {} unchanged\n",
+            self.new.len(),
+            self.resolved.len(),
+            self.unchanged.len()
+        );
+        for (sign, entries) in [
+            ('+', &self.new),
+            ('-', &self.resolved),
+            ('=', &self.unchanged),
+        ] {
+            for entry in entries {
+                text.push_str(&format!(
+                    "{} {}\t{}\n",
+                    sign,
+                    entry.target,
+                    entry.finding.replace('\n', "\\n")
+                ));
+            }
+        }
+        text
+    }
+}
+
+/// The findings of a plain-text scan output, in file order. An entry starts at a line whose
+/// first field, or second after a `--timestamps` column, is a URL; lines up to the next entry
+/// belong to its message. `#` markers are skipped.
+pub fn parse_output(output: &str) -> Vec<OutputEntry> {
+    let mut entries: Vec<OutputEntry> = Vec::new();
+    for line in output.lines().filter(|line| !line.starts_with('#')) {
+        match entry_start(line) {
+            Some((target, finding)) => entries.push(OutputEntry {
+                target: target.to_string(),
+                finding: finding.to_string(),
+            }),
+            None => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.finding.push('\n');
+                    entry.finding.push_str(line);
+                }
+            }
+        }
+    }
+    entries
+}
+
+/// Matches the findings of `current` against those of `previous` by their signature. A finding
+/// recorded several times in one output counts once.
+pub fn diff_outputs(previous: &str, current: &str) -> OutputDiff {
+    let previous = dedup(parse_output(previous));
+    let current = dedup(parse_output(current));
+    let before: HashSet<String> = previous.iter().map(OutputEntry::signature).collect();
+    let after: HashSet<String> = current.iter().map(OutputEntry::signature).collect();
+
+    let mut diff = OutputDiff::default();
+    for entry in current {
+        if before.contains(&entry.signature()) {
+            diff.unchanged.push(entry);
+        } else {
+            diff.new.push(entry);
+        }
+    }
+    diff.resolved = previous
+        .into_iter()
+        .filter(|entry| !after.contains(&entry.signature()))
+        .collect();
+    diff
+}
+
+/// Reads a scan output, decompressing it when it ends in `.gz`.
+pub async fn read_output(path: &Path) -> io::Result<String> {
+    if path.extension().is_none_or(|ext| ext != "gz") {
+        return tokio::fs::read_to_string(path).await;
+    }
+    let file = tokio::fs::File::open(path).await?;
+    let mut decoder = GzipDecoder::new(BufReader::new(file));
+    // Every resume appends a gzip member of its own.
+    decoder.multiple_members(true);
+    let mut output = String::new();
+    decoder.read_to_string(&mut output).await?;
+    Ok(output)
+}
+
+fn dedup(entries: Vec<OutputEntry>) -> Vec<OutputEntry> {
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .filter(|entry| seen.insert(entry.signature()))
+        .collect()
+}
+
+/// `(target, message)` of a line that starts an entry.
+fn entry_start(line: &str) -> Option<(&str, &str)> {
+    let (first, rest) = line.split_once('\t')?;
+    if is_target(first) {
+        return Some((first, rest));
+    }
+    DateTime::parse_from_rfc3339(first).ok()?;
+    let (target, finding) = rest.split_once('\t')?;
+    is_target(target).then_some((target, finding))
+}
+
+fn is_target(field: &str) -> bool {
+    Url::parse(field).is_ok_and(|url| url.has_host())
+}
+
+/// `1234ms` and `2.5s` become `<n>ms` and `<n>s`, and a word of letters and digits at least
+/// 10 long, like the random marker of a smuggled request, becomes `<random>`.
+fn normalize_word(word: &str) -> String {
+    let core = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    for unit in ["ms", "s"] {
+        if let Some(number) = core.strip_suffix(unit)
+            && !number.is_empty()
+            && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+        {
+            return word.replace(core, &format!("<n>{}", unit));
+        }
+    }
+    if core.len() >= 10
+        && core.chars().all(|c| c.is_ascii_alphanumeric())
+        && core.chars().any(|c| c.is_ascii_digit())
+        && core.chars().any(|c| c.is_ascii_alphabetic())
+    {
+        return word.replace(core, "<random>");
+    }
+    word.to_string()
+}
@@ -0,0 +1,302 @@
+use super::executor::PauseSwitch;
+use super::scanner::ScanStats;
+#[cfg(feature = "tui")]
+use ratatui::{
+    Frame, Terminal,
+    backend::CrosstermBackend,
+    crossterm::cursor::{Hide, Show},
+    crossterm::execute,
+    crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style},
+    widgets::{Block, Gauge, List, ListItem},
+};
+#[cfg(feature = "tui")]
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "tui")]
+use std::time::Duration;
+use std::time::Instant;
+#[cfg(feature = "tui")]
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "tui")]
+type DashboardTerminal = Terminal<CrosstermBackend<io::Stderr>>;
+
+/// How often the dashboard is redrawn.
+#[cfg(feature = "tui")]
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+/// Diagnostic lines kept for the log pane.
+const LOG_LINES: usize = 200;
+/// Running targets listed as the slowest.
+#[cfg(feature = "tui")]
+const SLOWEST_RUNNING: usize = 10;
+
+/// The dashboard of the running scan, so diagnostics can go to its log pane.
+static ACTIVE_DASHBOARD: Mutex<Option<Arc<Dashboard>>> = Mutex::new(None);
+
+/// Adds `line` to the log pane of the open dashboard. Returns false, doing nothing, when none is
+/// open.
+pub(crate) fn log_to_dashboard(line: &str) -> bool {
+    match ACTIVE_DASHBOARD.lock().unwrap().as_ref() {
+        Some(dashboard) => {
+            dashboard.log(line);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Full-screen view of a scan for `--tui`: throughput, findings, errors by kind and the targets
+/// that have been running longest. Only builds with the `tui` feature can draw it.
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub struct Dashboard {
+    stats: Arc<ScanStats>,
+    pause: Arc<PauseSwitch>,
+    /// Targets done by an earlier run, left out of the rate.
+    completed_before: usize,
+    started: Instant,
+    findings: Mutex<Vec<String>>,
+    log: Mutex<VecDeque<String>>,
+    running: Mutex<HashMap<String, Instant>>,
+}
+
+impl Dashboard {
+    pub fn new(stats: Arc<ScanStats>, pause: Arc<PauseSwitch>, completed_before: usize) -> Self {
+        Self {
+            stats,
+            pause,
+            completed_before,
+            started: Instant::now(),
+            findings: Mutex::default(),
+            log: Mutex::default(),
+            running: Mutex::default(),
+        }
+    }
+
+    pub fn start(&self, target: &str) {
+        self.running
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), Instant::now());
+    }
+
+    /// Records a finished target, listing its output when it produced any.
+    pub fn finish(&self, target: &str, output: &str) {
+        self.running.lock().unwrap().remove(target);
+        if !output.trim().is_empty() {
+            self.findings.lock().unwrap().push(output.to_string());
+        }
+    }
+
+    pub fn log(&self, line: &str) {
+        let mut log = self.log.lock().unwrap();
+        if log.len() == LOG_LINES {
+            log.pop_front();
+        }
+        log.push_back(line.to_string());
+    }
+
+    /// Builds without the `tui` feature have nothing to draw the dashboard with.
+    #[cfg(not(feature = "tui"))]
+    pub fn open(self: &Arc<Self>) -> io::Result<DashboardHandle> {
+        Err(io::Error::other(
+            "riphttp was built without the tui feature (cargo build --features tui)",
+        ))
+    }
+}
+
+#[cfg(feature = "tui")]
+impl Dashboard {
+    /// Switches stderr to the dashboard and redraws it until the returned handle is closed or
+    /// dropped.
+    pub fn open(self: &Arc<Self>) -> io::Result<DashboardHandle> {
+        execute!(io::stderr(), EnterAlternateScreen, Hide)?;
+        let terminal = match Terminal::new(CrosstermBackend::new(io::stderr())) {
+            Ok(terminal) => Arc::new(Mutex::new(Some(terminal))),
+            Err(err) => {
+                let _ = execute!(io::stderr(), LeaveAlternateScreen, Show);
+                return Err(err);
+            }
+        };
+        *ACTIVE_DASHBOARD.lock().unwrap() = Some(Arc::clone(self));
+
+        let dashboard = Arc::clone(self);
+        let drawn = Arc::clone(&terminal);
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REDRAW_INTERVAL);
+            loop {
+                interval.tick().await;
+                // Taken by the handle once the terminal is given back.
+                match drawn.lock().unwrap().as_mut() {
+                    Some(terminal) => {
+                        let _ = terminal.draw(|frame| dashboard.render(frame));
+                    }
+                    None => break,
+                }
+            }
+        });
+        Ok(DashboardHandle {
+            dashboard: Arc::clone(self),
+            terminal,
+            task,
+        })
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [header, body, log] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(6),
+            Constraint::Length(8),
+        ])
+        .areas(frame.area());
+        let [findings, side] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .areas(body);
+        let [errors, slowest] =
+            Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(side);
+
+        frame.render_widget(self.progress(), header);
+
+        let lines: Vec<String> = {
+            let findings = self.findings.lock().unwrap();
+            findings
+                .iter()
+                .flat_map(|output| output.lines())
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        frame.render_widget(
+            tail_list(
+                &lines,
+                findings,
+                format!(" Findings ({}) ", self.stats.findings()),
+            ),
+            findings,
+        );
+
+        let errors_by_kind: Vec<String> = self
+            .stats
+            .errors()
+            .iter()
+            .map(|(kind, count)| format!("{:>6}  {}", count, kind))
+            .collect();
+        frame.render_widget(
+            tail_list(&errors_by_kind, errors, " Errors ".to_string()),
+            errors,
+        );
+
+        let mut running: Vec<(String, Duration)> = self
+            .running
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(target, started)| (target.clone(), started.elapsed()))
+            .collect();
+        running.sort_by_key(|(_, elapsed)| Reverse(*elapsed));
+        let running: Vec<String> = running
+            .into_iter()
+            .take(SLOWEST_RUNNING)
+            .map(|(target, elapsed)| format!("{:>5}s  {}", elapsed.as_secs(), target))
+            .collect();
+        frame.render_widget(
+            List::new(running.into_iter().map(ListItem::new))
+                .block(Block::bordered().title(" Slowest running ")),
+            slowest,
+        );
+
+        let log_lines: Vec<String> = self.log.lock().unwrap().iter().cloned().collect();
+        frame.render_widget(tail_list(&log_lines, log, " Log ".to_string()), log);
+    }
+
+    fn progress(&self) -> Gauge<'static> {
+        let done = self.stats.done();
+        let total = self.stats.targets();
+        let elapsed = self.started.elapsed();
+        let rate = done.saturating_sub(self.completed_before) as f64 / elapsed.as_secs_f64();
+        let mut label = format!(
+            "{}/{} targets | {:.1} targets/s | {} running | {} findings | {}s",
+            done,
+            total,
+            if rate.is_finite() { rate } else { 0.0 },
+            self.stats.in_flight(),
+            self.stats.findings(),
+            elapsed.as_secs()
+        );
+        if self.pause.is_paused() {
+            label.push_str(" | paused");
+        }
+        let style = if crate::use_color() {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        Gauge::default()
+            .block(Block::bordered().title(" riphttp "))
+            .gauge_style(style)
+            .ratio(if total == 0 {
+                0.0
+            } else {
+                (done as f64 / total as f64).min(1.0)
+            })
+            .label(label)
+    }
+}
+
+/// The last lines that fit in `area`, so the newest stay in view as the list grows.
+#[cfg(feature = "tui")]
+fn tail_list(lines: &[String], area: Rect, title: String) -> List<'static> {
+    let visible = area.height.saturating_sub(2) as usize;
+    let start = lines.len().saturating_sub(visible);
+    List::new(lines[start..].iter().cloned().map(ListItem::new))
+        .block(Block::bordered().title(title))
+}
+
+/// Keeps the dashboard drawn; closing or dropping it gives the terminal back.
+#[cfg(feature = "tui")]
+pub struct DashboardHandle {
+    dashboard: Arc<Dashboard>,
+    terminal: Arc<Mutex<Option<DashboardTerminal>>>,
+    task: JoinHandle<()>,
+}
+
+/// Never built without the `tui` feature, since the dashboard can't be opened.
+#[cfg(not(feature = "tui"))]
+pub enum DashboardHandle {}
+
+#[cfg(not(feature = "tui"))]
+impl DashboardHandle {
+    pub fn close(self) {
+        match self {}
+    }
+}
+
+#[cfg(feature = "tui")]
+impl DashboardHandle {
+    /// Restores the terminal, then prints the findings the dashboard showed, since they went
+    /// nowhere else on the console.
+    pub fn close(self) {
+        let findings = std::mem::take(&mut *self.dashboard.findings.lock().unwrap());
+        drop(self);
+        if !crate::stdout_reserved() {
+            for output in findings {
+                println!("{}", output);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl Drop for DashboardHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+        *ACTIVE_DASHBOARD.lock().unwrap() = None;
+        // Waits out a redraw in progress, so none lands after the screen is switched back.
+        if self.terminal.lock().unwrap().take().is_some() {
+            let _ = execute!(io::stderr(), LeaveAlternateScreen, Show);
+        }
+    }
+}
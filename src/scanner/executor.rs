@@ -1,10 +1,19 @@
+use super::adaptive::AdaptiveLimiter;
+use super::progress::print_above_bar;
+use super::scanner::ScanOutput;
 use super::task::Task;
+use chrono::Utc;
 use futures::{StreamExt, stream::FuturesUnordered};
+use std::collections::VecDeque;
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::mpsc::UnboundedSender;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::TrySendError;
 
 #[derive(Debug)]
 pub enum ExecutionError {
@@ -52,70 +61,216 @@ impl ExecutionError {
     }
 }
 
-type TaskFuture =
-    Pin<Box<dyn Future<Output = Result<(usize, String, String), ExecutionError>> + 'static>>;
+/// Holds off scheduling new targets while set; tasks already running are left to finish.
+#[derive(Debug, Default)]
+pub struct PauseSwitch {
+    paused: AtomicBool,
+    resumed: Notify,
+}
+
+impl PauseSwitch {
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Pauses a running scan or resumes a paused one. Returns whether it is now paused.
+    pub fn toggle(&self) -> bool {
+        let paused = !self.paused.fetch_xor(true, Ordering::Relaxed);
+        if !paused {
+            self.resumed.notify_waiters();
+        }
+        paused
+    }
+
+    async fn wait_resumed(&self) {
+        loop {
+            let resumed = self.resumed.notified();
+            if !self.is_paused() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+}
+
+/// Limits applied to the scheduling loop in [`execute`].
+#[derive(Debug, Clone, Default)]
+pub struct ExecutorOptions {
+    /// Stop pulling new targets once this instant has passed. In-flight tasks still finish.
+    pub deadline: Option<Instant>,
+    /// Caps the number of in-flight tasks below `concurrency` while errors are spiking.
+    pub limiter: Option<Arc<AdaptiveLimiter>>,
+    /// Stops pulling new targets while paused.
+    pub pause: Option<Arc<PauseSwitch>>,
+    /// End the scan on the first target whose task fails. Otherwise the target is passed on
+    /// with an empty output and its error, and the scan goes on.
+    pub abort_on_error: bool,
+    /// Release new targets at most once per this interval, whatever the concurrency. A target
+    /// picked up early waits in its slot until its turn.
+    pub target_interval: Option<Duration>,
+    /// Stop pulling new targets once set. In-flight tasks still finish.
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+impl ExecutorOptions {
+    fn deadline_reached(&self) -> bool {
+        self.deadline
+            .map(|deadline| Instant::now() >= deadline)
+            .unwrap_or(false)
+    }
+
+    fn paused(&self) -> bool {
+        self.pause.as_ref().is_some_and(|pause| pause.is_paused())
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn concurrency_limit(&self, concurrency: usize) -> usize {
+        self.limiter
+            .as_ref()
+            .map(|limiter| limiter.limit().min(concurrency))
+            .unwrap_or(concurrency)
+    }
+}
+
+/// The variant name of `error` from its `Debug` form, e.g. `Timeout` for
+/// `ProtocolError::Timeout` or `Io` for `Io(..)`, so messages that embed a target group together.
+pub fn error_kind<E: fmt::Debug>(error: &E) -> String {
+    format!("{:?}", error)
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .filter(|kind| !kind.is_empty())
+        .unwrap_or("Unknown")
+        .to_string()
+}
+
+type TaskFuture = Pin<Box<dyn Future<Output = ScanOutput> + 'static>>;
 
 pub async fn execute<I, T>(
     targets: I,
     concurrency: usize,
     task: Arc<T>,
-    result_tx: Option<&UnboundedSender<(usize, String, String)>>,
-) -> Result<Vec<(String, String)>, ExecutionError>
+    result_tx: &Sender<ScanOutput>,
+    options: &ExecutorOptions,
+) -> Result<(), ExecutionError>
 where
     I: IntoIterator<Item = String>,
     T: Task + 'static,
-    T::Error: fmt::Display,
+    T::Error: fmt::Debug + fmt::Display,
 {
-    let mut results = Vec::new();
     let concurrency = concurrency.max(1);
     let mut pending: FuturesUnordered<TaskFuture> = FuturesUnordered::new();
     let mut position: usize = 0;
     let mut iter = targets.into_iter();
+    // When the next target may start, with --delay-between-targets.
+    let mut next_release = tokio::time::Instant::now();
 
-    while pending.len() < concurrency {
-        if let Some(target) = iter.next() {
-            pending.push(schedule_task(Arc::clone(&task), target, position));
-            position = position.wrapping_add(1);
-        } else {
-            break;
+    let mut fill = |pending: &mut FuturesUnordered<TaskFuture>| {
+        while pending.len() < options.concurrency_limit(concurrency)
+            && !options.deadline_reached()
+            && !options.stopped()
+            && !options.paused()
+        {
+            if let Some(target) = iter.next() {
+                let release = options.target_interval.map(|interval| {
+                    let release = next_release.max(tokio::time::Instant::now());
+                    next_release = release + interval;
+                    release
+                });
+                pending.push(schedule_task(Arc::clone(&task), target, position, release));
+                position = position.wrapping_add(1);
+            } else {
+                break;
+            }
         }
-    }
+    };
 
-    while let Some(result) = pending.next().await {
-        match result {
-            Ok((index, target, output)) => {
-                if let Some(sender) = result_tx {
-                    let _ = sender.send((index, target.clone(), output.clone()));
-                }
+    fill(&mut pending);
+    // Finished outputs the consumer hasn't taken yet. In-flight tasks keep being polled while it
+    // is behind, so their timings aren't skewed; only new targets are held off.
+    let mut ready: VecDeque<ScanOutput> = VecDeque::new();
 
-                results.push((index, target, output));
-
-                if let Some(next_target) = iter.next() {
-                    pending.push(schedule_task(Arc::clone(&task), next_target, position));
-                    position = position.wrapping_add(1);
+    loop {
+        if pending.is_empty() && ready.is_empty() {
+            // Everything in flight has finished; a paused scan waits here until it is resumed.
+            match &options.pause {
+                Some(pause) if pause.is_paused() => {
+                    pause.wait_resumed().await;
+                    fill(&mut pending);
+                    if pending.is_empty() {
+                        break;
+                    }
                 }
+                _ => break,
             }
-            Err(err) => return Err(err),
+        }
+        tokio::select! {
+            permit = result_tx.reserve(), if !ready.is_empty() => {
+                // A closed channel means nobody is reading; the outputs are dropped like before.
+                let output = ready.pop_front().expect("checked non-empty");
+                if let Ok(permit) = permit {
+                    permit.send(output);
+                }
+            }
+            Some(output) = pending.next(), if !pending.is_empty() => {
+                if let Some(error) = &output.error {
+                    if options.abort_on_error {
+                        return Err(ExecutionError::task_failed(output.target, error));
+                    }
+                    if crate::is_verbose() {
+                        print_above_bar(&format!("Task failed for {}: {}", output.target, error));
+                    }
+                }
+                ready.push_back(output);
+            }
+            else => break,
+        }
+        while let Some(output) = ready.pop_front() {
+            if let Err(TrySendError::Full(output)) = result_tx.try_send(output) {
+                ready.push_front(output);
+                break;
+            }
+        }
+        // New targets wait until the consumer has caught up. The adaptive limit may have moved
+        // since the last fill; top up (or not) accordingly.
+        if ready.is_empty() {
+            fill(&mut pending);
         }
     }
 
-    results.sort_by_key(|(index, _, _)| *index);
-    Ok(results
-        .into_iter()
-        .map(|(_, target, output)| (target, output))
-        .collect())
+    Ok(())
 }
 
-fn schedule_task<T>(task: Arc<T>, target: String, index: usize) -> TaskFuture
+fn schedule_task<T>(
+    task: Arc<T>,
+    target: String,
+    index: usize,
+    release: Option<tokio::time::Instant>,
+) -> TaskFuture
 where
     T: Task + 'static,
-    T::Error: fmt::Display,
+    T::Error: fmt::Debug + fmt::Display,
 {
     Box::pin(async move {
+        if let Some(release) = release {
+            tokio::time::sleep_until(release).await;
+        }
         let stored_target = target.clone();
-        match task.execute(target).await {
-            Ok(output) => Ok((index, stored_target, output)),
-            Err(err) => Err(ExecutionError::task_failed(stored_target, err)),
+        let (output, error, error_kind) = match task.execute(target).await {
+            Ok(output) => (output, None, None),
+            Err(err) => (String::new(), Some(err.to_string()), Some(error_kind(&err))),
+        };
+        ScanOutput {
+            index,
+            target: stored_target,
+            output,
+            error,
+            error_kind,
+            completed_at: Utc::now(),
         }
     })
 }
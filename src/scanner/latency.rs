@@ -0,0 +1,143 @@
+use serde_json::{Value, json};
+use std::cmp::Reverse;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Upper bounds of the histogram buckets; slower targets land in a final open-ended bucket.
+const BUCKETS: [Duration; 8] = [
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+];
+/// Width of the longest histogram bar.
+const BAR_WIDTH: usize = 40;
+/// Slowest targets listed after a scan.
+pub const SLOWEST_TARGETS: usize = 10;
+
+/// How long the targets of a scan took, from the start of their task to its end.
+#[derive(Debug, Clone)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// Targets per bucket of `BUCKETS`, plus one for those slower than the last bound.
+    pub buckets: Vec<usize>,
+    /// The slowest targets, slowest first.
+    pub slowest: Vec<(String, Duration)>,
+}
+
+impl LatencySummary {
+    /// Summarizes `samples`, keeping the `slowest` slowest targets. `None` without samples.
+    pub fn new(mut samples: Vec<(String, Duration)>, slowest: usize) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_by_key(|(_, elapsed)| Reverse(*elapsed));
+        let sorted: Vec<Duration> = samples.iter().rev().map(|(_, elapsed)| *elapsed).collect();
+
+        let mut buckets = vec![0; BUCKETS.len() + 1];
+        for elapsed in &sorted {
+            let bucket = BUCKETS
+                .iter()
+                .position(|bound| elapsed < bound)
+                .unwrap_or(BUCKETS.len());
+            buckets[bucket] += 1;
+        }
+
+        samples.truncate(slowest);
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            p50: percentile(&sorted, 50),
+            p90: percentile(&sorted, 90),
+            p99: percentile(&sorted, 99),
+            max: sorted[sorted.len() - 1],
+            buckets,
+            slowest: samples,
+        })
+    }
+
+    /// Percentiles, a histogram and the slowest targets, as printed after a scan.
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "Target latency over {} targets: min {}, p50 {}, p90 {}, p99 {}, max {}\n",
+            self.count,
+            millis(self.min),
+            millis(self.p50),
+            millis(self.p90),
+            millis(self.p99),
+            millis(self.max)
+        );
+
+        let largest = self.buckets.iter().copied().max().unwrap_or(0).max(1);
+        for (label, count) in bucket_labels().iter().zip(&self.buckets) {
+            let bar = "#".repeat(count * BAR_WIDTH / largest);
+            let _ = writeln!(out, "  {:>13} |{:<BAR_WIDTH$}| {}", label, bar, count);
+        }
+
+        if !self.slowest.is_empty() {
+            out.push_str("Slowest targets:\n");
+            for (target, elapsed) in &self.slowest {
+                let _ = writeln!(out, "  {:>9}  {}", millis(*elapsed), target);
+            }
+        }
+        out
+    }
+
+    /// The summary for `--summary-json`, with every duration in milliseconds.
+    pub fn to_json(&self) -> Value {
+        let buckets: Vec<Value> = bucket_labels()
+            .into_iter()
+            .zip(&self.buckets)
+            .map(|(label, count)| json!({ "range": label, "targets": count }))
+            .collect();
+        let slowest: Vec<Value> = self
+            .slowest
+            .iter()
+            .map(|(target, elapsed)| json!({ "target": target, "ms": elapsed.as_millis() as u64 }))
+            .collect();
+        json!({
+            "targets": self.count,
+            "min_ms": self.min.as_millis() as u64,
+            "p50_ms": self.p50.as_millis() as u64,
+            "p90_ms": self.p90.as_millis() as u64,
+            "p99_ms": self.p99.as_millis() as u64,
+            "max_ms": self.max.as_millis() as u64,
+            "histogram": buckets,
+            "slowest": slowest,
+        })
+    }
+}
+
+/// Nearest-rank percentile of ascending `sorted`.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn millis(elapsed: Duration) -> String {
+    format!("{}ms", elapsed.as_millis())
+}
+
+/// `<100ms`, `100-250ms`, ..., `>=30000ms`.
+fn bucket_labels() -> Vec<String> {
+    let mut labels = Vec::with_capacity(BUCKETS.len() + 1);
+    let mut lower: Option<Duration> = None;
+    for bound in BUCKETS {
+        labels.push(match lower {
+            None => format!("<{}", millis(bound)),
+            Some(lower) => format!("{}-{}", lower.as_millis(), millis(bound)),
+        });
+        lower = Some(bound);
+    }
+    labels.push(format!(">={}", millis(BUCKETS[BUCKETS.len() - 1])));
+    labels
+}
@@ -0,0 +1,130 @@
+use super::scanner::ScanStats;
+use std::fmt::Write as _;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Longest request head read from a client; anything after it is ignored.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+/// A client that sends nothing for this long is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Waits after a failed accept, doubling up to `MAX_ACCEPT_BACKOFF` while the failures last,
+/// e.g. when the scan has used up its file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Serves the scan counters in the Prometheus text exposition format on `GET /metrics`.
+#[derive(Debug)]
+pub struct MetricsEndpoint {
+    listener: TcpListener,
+}
+
+impl MetricsEndpoint {
+    /// Binds before the scan starts, so a busy address fails the run instead of going unnoticed.
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Starts answering scrapes. Abort the task when the scan ends.
+    pub fn spawn(self, stats: Arc<ScanStats>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut backoff = ACCEPT_BACKOFF;
+            loop {
+                let stream = match self.listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        tracing::warn!(error = %err, "metrics endpoint failed to accept a scrape");
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                        continue;
+                    }
+                };
+                backoff = ACCEPT_BACKOFF;
+                let stats = Arc::clone(&stats);
+                tokio::spawn(async move {
+                    let _ = tokio::time::timeout(CLIENT_TIMEOUT, serve(stream, &stats)).await;
+                });
+            }
+        })
+    }
+}
+
+async fn serve(mut stream: TcpStream, stats: &ScanStats) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    let request_line = String::from_utf8_lossy(&head);
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(stats)),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: text/plain; version=0.0.4\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn render(stats: &ScanStats) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: usize| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value);
+    };
+    metric(
+        "riphttp_targets_total",
+        "gauge",
+        "Targets in the scan, including those finished by a resumed run.",
+        stats.targets(),
+    );
+    metric(
+        "riphttp_targets_done",
+        "gauge",
+        "Targets finished so far.",
+        stats.done(),
+    );
+    metric(
+        "riphttp_targets_in_flight",
+        "gauge",
+        "Targets being scanned right now.",
+        stats.in_flight(),
+    );
+    metric(
+        "riphttp_findings_total",
+        "counter",
+        "Targets that produced a finding.",
+        stats.findings(),
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP riphttp_errors_total Failed targets by error kind."
+    );
+    let _ = writeln!(out, "# TYPE riphttp_errors_total counter");
+    for (kind, count) in stats.errors() {
+        let _ = writeln!(out, "riphttp_errors_total{{kind=\"{}\"}} {}", kind, count);
+    }
+    out
+}
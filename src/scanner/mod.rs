@@ -1,5 +1,11 @@
+pub mod adaptive;
 pub mod checkpoint;
+pub mod compare;
+pub mod dashboard;
 pub mod executor;
+pub mod latency;
+pub mod metrics;
+pub mod progress;
 pub mod recorder;
 pub mod scanner;
 pub mod task;
@@ -0,0 +1,103 @@
+use super::dashboard::log_to_dashboard;
+use chrono::{SecondsFormat, Utc};
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often a `progress` event is emitted in JSON mode.
+const JSON_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The bar of the running scan, so modules can print above it.
+static ACTIVE_BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Makes `bar` the one [`print_above_bar`] prints above, or clears it with `None`.
+pub fn set_active_bar(bar: Option<ProgressBar>) {
+    *ACTIVE_BAR.lock().unwrap() = bar;
+}
+
+/// Prints a diagnostic line to stderr, above the progress bar when one is drawn so the line
+/// doesn't tear it, or to the log pane of the `--tui` dashboard.
+pub fn print_above_bar(line: &str) {
+    if log_to_dashboard(line) {
+        return;
+    }
+    match ACTIVE_BAR.lock().unwrap().as_ref() {
+        Some(bar) if !bar.is_hidden() => bar.println(line),
+        _ => eprintln!("{}", line),
+    }
+}
+
+/// How scan progress is reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    /// Interactive progress bar on stderr.
+    #[default]
+    Bar,
+    /// JSON lines on stderr, one `progress` event per interval and one `finding` event per finding.
+    Json,
+    /// Full-screen dashboard on stderr, falling back to the bar when stderr isn't a terminal.
+    Tui,
+}
+
+/// Emits progress and finding events as JSON lines on stderr.
+#[derive(Debug)]
+pub struct JsonProgress {
+    total: usize,
+    done: AtomicUsize,
+    started: Instant,
+    /// Add a `timestamp` field to finding events.
+    timestamps: bool,
+}
+
+impl JsonProgress {
+    pub fn new(total: usize, timestamps: bool) -> Self {
+        Self {
+            total,
+            done: AtomicUsize::new(0),
+            started: Instant::now(),
+            timestamps,
+        }
+    }
+
+    /// Records a finished target, emitting a `finding` event when it produced output.
+    pub fn complete(&self, target: &str, output: &str) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        if !output.trim().is_empty() {
+            let mut event = json!({ "event": "finding", "target": target, "output": output });
+            if self.timestamps {
+                event["timestamp"] = json!(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+            }
+            eprintln!("{}", event);
+        }
+    }
+
+    pub fn emit_progress(&self) {
+        let done = self.done.load(Ordering::Relaxed);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            done as f64 / elapsed
+        } else {
+            0.0
+        };
+        eprintln!(
+            "{}",
+            json!({ "event": "progress", "done": done, "total": self.total, "rate": rate })
+        );
+    }
+
+    /// Spawns the task emitting `progress` events. Abort it when the scan ends.
+    pub fn spawn_ticker(self: &Arc<Self>) -> JoinHandle<()> {
+        let progress = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(JSON_PROGRESS_INTERVAL);
+            loop {
+                interval.tick().await;
+                progress.emit_progress();
+            }
+        })
+    }
+}
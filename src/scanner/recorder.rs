@@ -1,16 +1,34 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
-use tokio::fs::OpenOptions;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use async_compression::tokio::write::GzipEncoder;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time::Interval;
+use url::Url;
 
-use super::checkpoint::{Checkpoint, default_checkpoint_path, remove_checkpoint, write_checkpoint};
-use super::scanner::ScanOutput;
+use super::checkpoint::{
+    Checkpoint, CheckpointFormat, CompletedSet, complete_checkpoint, create_parent_dir,
+    default_checkpoint_path, remove_checkpoint, write_checkpoint,
+};
+
+/// Records queued for the recorder before producers have to wait for it to catch up.
+pub const DEFAULT_RECORDER_BUFFER: usize = 1024;
+/// Destination of recorded findings: the output file, possibly behind a gzip encoder.
+type OutputWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// `--output` value that writes findings to stdout instead of a file.
+pub const STDOUT_PATH: &str = "-";
+
+/// Out-of-order results held back before the recorder warns about a stuck target.
+pub const DEFAULT_PENDING_WARN: usize = 10_000;
 
 #[derive(Debug, Clone)]
 pub struct RecorderConfig {
@@ -22,16 +40,74 @@ pub struct RecorderConfig {
     pub total_targets: usize,
     pub truncate_output: bool,
     pub flush_interval: Duration,
+    /// Capacity of the channels feeding the recorder. A full buffer makes the scan wait for the
+    /// disk instead of queueing results in memory.
+    pub buffer: usize,
+    /// Warn when this many results are waiting on an unfinished lower-index target.
+    pub pending_warn: usize,
+    /// Skip past unfinished targets once this many results are waiting on them. Their results
+    /// are appended out of order when they arrive, after a gap marker in the output; the
+    /// checkpoint moves past them, so a resumed scan doesn't retry them.
+    pub max_pending: Option<usize>,
+    /// Gzip the output. When appending on resume a new gzip member is started, which
+    /// decompressors read as one continuous stream.
+    pub compress: bool,
+    /// Roll the output over to a numbered segment once this many bytes have been written to it.
+    /// The newest segment always keeps the configured name, so a resume appends to it.
+    pub max_output_size: Option<u64>,
+    /// Prefix each line with the RFC 3339 time its target finished.
+    pub timestamps: bool,
+    /// `--seed` written to every checkpoint.
+    pub seed: Option<u64>,
+    /// User-Agent rotation seed written to every checkpoint.
+    pub ua_seed: Option<u64>,
+    /// `--sample-count` seed written to every checkpoint.
+    pub sample_seed: Option<u64>,
+    /// `--probe-order random` seed written to every checkpoint.
+    pub probe_seed: Option<u64>,
+    /// Thread count written to every checkpoint.
+    pub threads: Option<usize>,
+    /// Proxy written to every checkpoint, checked on resume.
+    pub proxy: Option<String>,
+    /// Drop finding lines already written in this run for the same host and mode.
+    pub dedup: bool,
+    /// Also log every target with its outcome here, in target order, findings or not.
+    pub all_results_path: Option<PathBuf>,
+    /// Keep the checkpoint of a completed run at its `.done` path instead of removing it.
+    pub keep_checkpoint: bool,
+    /// `--append` flag written to every checkpoint.
+    pub append: bool,
+    /// How checkpoints are written.
+    pub checkpoint_format: CheckpointFormat,
+    /// Targets an earlier run finished out of order. They aren't scanned again; the recorder
+    /// steps over them and keeps them in every checkpoint.
+    pub completed: Option<CompletedSet>,
+    /// Targets-file tags by target. When the file tagged any target, every line gets a tag
+    /// column after the target, empty for untagged ones.
+    pub tags: Arc<HashMap<String, String>>,
+    /// Cut every written line down to this many bytes, marker included.
+    pub max_line_length: Option<usize>,
 }
 
 impl RecorderConfig {
     pub fn checkpoint_template(&self, next_index: usize) -> Checkpoint {
-        Checkpoint::new(
+        let checkpoint = Checkpoint::new(
             next_index,
             self.targets_path.clone(),
             self.output_path.to_string_lossy(),
             self.mode.clone(),
         )
+        .with_seed(self.seed)
+        .with_ua_seed(self.ua_seed)
+        .with_sample_seed(self.sample_seed)
+        .with_probe_seed(self.probe_seed)
+        .with_append(self.append)
+        .with_completed(self.completed.clone())
+        .with_format(self.checkpoint_format);
+        match self.threads {
+            Some(threads) => checkpoint.with_conditions(threads, self.proxy.clone()),
+            None => checkpoint,
+        }
     }
 }
 
@@ -41,38 +117,48 @@ pub enum RecorderMessage {
         absolute_index: usize,
         target: String,
         output: String,
+        /// Why the target failed, if it did.
+        error: Option<String>,
+        completed_at: DateTime<Utc>,
     },
     Flush,
 }
 
 #[derive(Clone)]
 pub struct RecorderHandle {
-    sender: UnboundedSender<RecorderMessage>,
+    sender: Sender<RecorderMessage>,
 }
 
 impl RecorderHandle {
-    pub fn new(sender: UnboundedSender<RecorderMessage>) -> Self {
+    pub fn new(sender: Sender<RecorderMessage>) -> Self {
         Self { sender }
     }
 
-    pub fn record(
+    /// Queues a record, waiting while the recorder's buffer is full.
+    pub async fn record(
         &self,
         absolute_index: usize,
         target: String,
         output: String,
+        error: Option<String>,
+        completed_at: DateTime<Utc>,
     ) -> Result<(), RecorderError> {
         self.sender
             .send(RecorderMessage::Record {
                 absolute_index,
                 target,
                 output,
+                error,
+                completed_at,
             })
+            .await
             .map_err(|_| RecorderError::ChannelClosed)
     }
 
-    pub fn request_flush(&self) -> Result<(), RecorderError> {
+    pub async fn request_flush(&self) -> Result<(), RecorderError> {
         self.sender
             .send(RecorderMessage::Flush)
+            .await
             .map_err(|_| RecorderError::ChannelClosed)
     }
 }
@@ -110,50 +196,86 @@ impl std::error::Error for RecorderError {
 struct PendingRecord {
     target: String,
     output: String,
+    error: Option<String>,
+    completed_at: DateTime<Utc>,
 }
 
 pub struct ScanRecorder {
     cfg: RecorderConfig,
     next_expected_index: usize,
     pending: BTreeMap<usize, PendingRecord>,
+    /// Indices skipped by a forced flush whose results haven't arrived yet.
+    skipped: BTreeSet<usize>,
+    warned: bool,
+    /// Bytes in the current output segment.
+    written: u64,
+    /// Signatures of the findings written so far, when deduplicating.
+    seen: HashSet<String>,
+    duplicates: usize,
+    /// The `all_results_path` log, once opened.
+    all_results: Option<File>,
 }
 
 impl ScanRecorder {
-    pub fn new(cfg: RecorderConfig) -> (Self, RecorderHandle, UnboundedReceiver<RecorderMessage>) {
-        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    pub fn new(cfg: RecorderConfig) -> (Self, RecorderHandle, Receiver<RecorderMessage>) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(cfg.buffer.max(1));
         let recorder = Self {
             next_expected_index: cfg.base_index,
             cfg,
             pending: BTreeMap::new(),
+            skipped: BTreeSet::new(),
+            warned: false,
+            written: 0,
+            seen: HashSet::new(),
+            duplicates: 0,
+            all_results: None,
         };
         let handle = RecorderHandle::new(sender);
         (recorder, handle, receiver)
     }
 
-    async fn open_output(&self) -> io::Result<tokio::fs::File> {
+    async fn open_output(&mut self, truncate: bool) -> io::Result<OutputWriter> {
+        if self.cfg.output_path == Path::new(STDOUT_PATH) {
+            self.written = 0;
+            return Ok(if self.cfg.compress {
+                Box::new(GzipEncoder::new(tokio::io::stdout()))
+            } else {
+                Box::new(tokio::io::stdout())
+            });
+        }
+        create_parent_dir(&self.cfg.output_path).await?;
         let mut options = OpenOptions::new();
         options.create(true).write(true);
-        if self.cfg.truncate_output {
+        if truncate {
             options.truncate(true);
         } else {
             options.append(true);
         }
-        options.open(&self.cfg.output_path).await
+        let file = options.open(&self.cfg.output_path).await?;
+        self.written = file.metadata().await?.len();
+        Ok(if self.cfg.compress {
+            Box::new(GzipEncoder::new(file))
+        } else {
+            Box::new(file)
+        })
     }
 
-    async fn commit_ready(&mut self, file: &mut tokio::fs::File) -> Result<(), RecorderError> {
-        while let Some(record) = self.pending.remove(&self.next_expected_index) {
-            let output_entry = ScanOutput {
-                target: record.target,
-                output: record.output,
-            };
-
-            if !output_entry.output.trim().is_empty() {
-                file.write_all(output_entry.target.as_bytes()).await?;
-                file.write_all(b"\t").await?;
-                file.write_all(output_entry.output.as_bytes()).await?;
-                file.write_all(b"\n").await?;
+    async fn commit_ready(&mut self, file: &mut OutputWriter) -> Result<(), RecorderError> {
+        loop {
+            if self
+                .cfg
+                .completed
+                .as_ref()
+                .is_some_and(|completed| completed.contains(self.next_expected_index))
+            {
+                self.next_expected_index += 1;
+                continue;
             }
+            let Some(record) = self.pending.remove(&self.next_expected_index) else {
+                break;
+            };
+            self.write_entry(file, &record).await?;
+            self.write_result(&record).await?;
 
             self.next_expected_index += 1;
 
@@ -163,38 +285,243 @@ impl ScanRecorder {
         Ok(())
     }
 
+    /// Writes the results still waiting on an unfinished target when the scan stops early,
+    /// after a gap marker, and checkpoints them as completed so a resume doesn't scan them again.
+    async fn commit_out_of_order(&mut self, file: &mut OutputWriter) -> Result<(), RecorderError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let final_index = self.cfg.base_index + self.cfg.total_targets;
+        let mut completed = CompletedSet::new(final_index);
+        if let Some(previous) = &self.cfg.completed {
+            for index in self.next_expected_index..final_index {
+                if previous.contains(index) {
+                    completed.insert(index);
+                }
+            }
+        }
+
+        let gap = format!(
+            "stopped with the target at index {} unfinished; {} later result(s) written out of order",
+            self.next_expected_index,
+            self.pending.len()
+        );
+        let marker = format!("# {}\n", gap);
+        file.write_all(marker.as_bytes()).await?;
+        self.written += marker.len() as u64;
+        eprintln!("Recorder {}", gap);
+
+        for (index, record) in std::mem::take(&mut self.pending) {
+            self.write_entry(file, &record).await?;
+            self.write_result(&record).await?;
+            completed.insert(index);
+        }
+        let checkpoint = self
+            .cfg
+            .checkpoint_template(self.next_expected_index)
+            .with_completed(Some(completed));
+        write_checkpoint(&self.cfg.checkpoint_path, &checkpoint).await?;
+        Ok(())
+    }
+
     async fn handle_record(
         &mut self,
-        file: &mut tokio::fs::File,
+        file: &mut OutputWriter,
         index: usize,
-        target: String,
-        output: String,
+        record: PendingRecord,
     ) -> Result<(), RecorderError> {
         if index < self.next_expected_index {
-            // Already processed according to checkpoint; skip.
+            if self.skipped.remove(&index) {
+                // Skipped by a forced flush; written out of order now that it finished.
+                self.write_entry(file, &record).await?;
+                self.write_result(&record).await?;
+                self.rotate_if_full(file).await?;
+            }
+            // Otherwise already processed according to checkpoint; skip.
+            return Ok(());
+        }
+
+        self.pending.insert(index, record);
+        self.commit_ready(file).await?;
+        self.check_pending(file).await?;
+        self.rotate_if_full(file).await
+    }
+
+    async fn write_entry(
+        &mut self,
+        file: &mut OutputWriter,
+        record: &PendingRecord,
+    ) -> io::Result<()> {
+        let output = if self.cfg.dedup {
+            self.unseen_lines(record)
+        } else {
+            record.output.clone()
+        };
+        if output.trim().is_empty() {
+            return Ok(());
+        }
+        let mut line = String::new();
+        if self.cfg.timestamps {
+            line.push_str(
+                &record
+                    .completed_at
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+            );
+            line.push('\t');
+        }
+        line.push_str(&format!(
+            "{}\t{}{}\n",
+            record.target,
+            self.tag_column(record),
+            output
+        ));
+        if let Some(max) = self.cfg.max_line_length {
+            line = line
+                .split_inclusive('\n')
+                .map(|part| match part.strip_suffix('\n') {
+                    Some(part) => format!("{}\n", truncate_line(part, max)),
+                    None => truncate_line(part, max),
+                })
+                .collect();
+        }
+        file.write_all(line.as_bytes()).await?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    /// Logs `record` to the all-results file as `target\toutcome` (with the tag column in
+    /// between for tagged runs), where the outcome is
+    /// `finding`, `clean` or `error: <reason>`.
+    async fn write_result(&mut self, record: &PendingRecord) -> io::Result<()> {
+        let tag = self.tag_column(record);
+        let Some(all_results) = &mut self.all_results else {
+            return Ok(());
+        };
+        let outcome = match &record.error {
+            Some(error) => format!("error: {}", error.replace(['\n', '\r'], " ")),
+            None if record.output.trim().is_empty() => "clean".to_string(),
+            None => "finding".to_string(),
+        };
+        let mut line = String::new();
+        if self.cfg.timestamps {
+            line.push_str(
+                &record
+                    .completed_at
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+            );
+            line.push('\t');
+        }
+        line.push_str(&format!("{}\t{}{}\n", record.target, tag, outcome));
+        all_results.write_all(line.as_bytes()).await
+    }
+
+    /// `tag\t` for runs with tagged targets, nothing otherwise.
+    fn tag_column(&self, record: &PendingRecord) -> String {
+        if self.cfg.tags.is_empty() {
+            return String::new();
+        }
+        let tag = self.cfg.tags.get(&record.target).map_or("", String::as_str);
+        format!("{}\t", tag)
+    }
+
+    /// The lines of `record` whose signature hasn't been written yet in this run.
+    fn unseen_lines(&mut self, record: &PendingRecord) -> String {
+        let host = Url::parse(&record.target)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+            .unwrap_or_else(|| record.target.clone());
+        let mut lines = Vec::new();
+        for line in record.output.lines().filter(|line| !line.trim().is_empty()) {
+            let signature = format!("{}\t{}\t{}", host, self.cfg.mode, finding_signature(line));
+            if self.seen.insert(signature) {
+                lines.push(line);
+            } else {
+                self.duplicates += 1;
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Moves the output to `<name>.1.<ext>` (shifting older segments up by one) and starts a
+    /// fresh file once it has grown past `max_output_size`.
+    async fn rotate_if_full(&mut self, file: &mut OutputWriter) -> Result<(), RecorderError> {
+        match self.cfg.max_output_size {
+            Some(max) if self.written >= max => {}
+            _ => return Ok(()),
+        }
+
+        file.shutdown().await?;
+        let path = &self.cfg.output_path;
+        let mut last = 1;
+        while tokio::fs::try_exists(segment_path(path, last)).await? {
+            last += 1;
+        }
+        for segment in (1..last).rev() {
+            tokio::fs::rename(segment_path(path, segment), segment_path(path, segment + 1)).await?;
+        }
+        tokio::fs::rename(path, segment_path(path, 1)).await?;
+
+        *file = self.open_output(true).await?;
+        Ok(())
+    }
+
+    /// Warns when results pile up behind an unfinished target and, past `max_pending`, skips
+    /// ahead to the lowest finished index.
+    async fn check_pending(&mut self, file: &mut OutputWriter) -> Result<(), RecorderError> {
+        let held = self.pending.len();
+        if held < self.cfg.pending_warn {
+            self.warned = false;
+        } else if !self.warned {
+            self.warned = true;
+            eprintln!(
+                "Recorder is holding {} results while waiting for the target at index {} to finish",
+                held, self.next_expected_index
+            );
+        }
+
+        let Some(max_pending) = self.cfg.max_pending else {
+            return Ok(());
+        };
+        if held <= max_pending {
             return Ok(());
         }
+        let Some(&first_ready) = self.pending.keys().next() else {
+            return Ok(());
+        };
+
+        let gap = format!(
+            "skipped {} unfinished target(s) from index {}; their results are appended when they finish",
+            first_ready - self.next_expected_index,
+            self.next_expected_index
+        );
+        let marker = format!("# {}\n", gap);
+        file.write_all(marker.as_bytes()).await?;
+        self.written += marker.len() as u64;
+        eprintln!("Recorder {}", gap);
 
-        self.pending.insert(index, PendingRecord { target, output });
+        self.skipped.extend(self.next_expected_index..first_ready);
+        self.next_expected_index = first_ready;
+        self.warned = false;
         self.commit_ready(file).await
     }
 
-    async fn flush_if_due(&mut self, file: &mut tokio::fs::File) -> Result<(), RecorderError> {
+    async fn flush_if_due(&mut self, file: &mut OutputWriter) -> Result<(), RecorderError> {
         file.flush().await.map_err(RecorderError::from)
     }
 
     async fn finish(
         mut self,
-        mut file: tokio::fs::File,
-        mut receiver: UnboundedReceiver<RecorderMessage>,
+        mut file: OutputWriter,
+        mut receiver: Receiver<RecorderMessage>,
     ) -> Result<(), RecorderError> {
         let mut flush_timer: Interval = tokio::time::interval(self.cfg.flush_interval);
         loop {
             tokio::select! {
                 maybe_message = receiver.recv() => {
                     match maybe_message {
-                        Some(RecorderMessage::Record { absolute_index, target, output }) => {
-                            self.handle_record(&mut file, absolute_index, target, output).await?;
+                        Some(RecorderMessage::Record { absolute_index, target, output, error, completed_at }) => {
+                            let record = PendingRecord { target, output, error, completed_at };
+                            self.handle_record(&mut file, absolute_index, record).await?;
                         }
                         Some(RecorderMessage::Flush) => {
                             self.flush_if_due(&mut file).await?;
@@ -212,28 +539,148 @@ impl ScanRecorder {
 
         // After channel closed, ensure all pending entries committed.
         self.commit_ready(&mut file).await?;
-        self.flush_if_due(&mut file).await?;
+        self.commit_out_of_order(&mut file).await?;
+        if self.duplicates > 0 {
+            let note = format!("suppressed {} duplicate finding(s)", self.duplicates);
+            let marker = format!("# {}\n", note);
+            file.write_all(marker.as_bytes()).await?;
+            self.written += marker.len() as u64;
+            eprintln!("Recorder {}", note);
+        }
+        // Writes the gzip trailer; for a plain file this is just a flush.
+        file.shutdown().await?;
+        if let Some(all_results) = &mut self.all_results {
+            all_results.flush().await?;
+        }
 
         let final_index = self.cfg.base_index + self.cfg.total_targets;
 
         if self.next_expected_index >= final_index {
-            // Completed full run: remove checkpoint file.
-            remove_checkpoint(&self.cfg.checkpoint_path).await?;
+            // Completed full run: remove checkpoint file, or set it aside as a record of the run.
+            if self.cfg.keep_checkpoint {
+                let done = complete_checkpoint(&self.cfg.checkpoint_path).await?;
+                eprintln!("Kept the final checkpoint in '{}'", done.display());
+            } else {
+                remove_checkpoint(&self.cfg.checkpoint_path).await?;
+            }
         }
 
         Ok(())
     }
 
-    pub async fn run(
-        mut self,
-        receiver: UnboundedReceiver<RecorderMessage>,
-    ) -> Result<(), RecorderError> {
-        let mut file = self.open_output().await?;
+    pub async fn run(mut self, receiver: Receiver<RecorderMessage>) -> Result<(), RecorderError> {
+        let mut file = self.open_output(self.cfg.truncate_output).await?;
+        if let Some(path) = &self.cfg.all_results_path {
+            let mut options = OpenOptions::new();
+            options.create(true).write(true);
+            if self.cfg.truncate_output {
+                options.truncate(true);
+            } else {
+                options.append(true);
+            }
+            self.all_results = Some(options.open(path).await?);
+        }
         self.flush_if_due(&mut file).await?;
         self.finish(file, receiver).await
     }
 }
 
+/// What `--sort` orders the findings of a completed scan by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Response status, with findings that have none last
+    Status,
+    /// Module that reported the finding
+    Mode,
+    /// Target URL
+    Target,
+}
+
+/// Rewrites the `grepable` output at `path` with its findings ordered by `key`, keeping target
+/// order among equal keys. Lines that can't be parsed, and `#` markers, go last. The whole file
+/// is held in memory while it is sorted.
+pub async fn sort_output(path: &Path, key: SortKey) -> io::Result<()> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let mut lines: Vec<&str> = content.lines().filter(|line| !line.is_empty()).collect();
+    lines.sort_by_cached_key(|line| {
+        let field = (!line.starts_with('#'))
+            .then(|| sort_field(line, key))
+            .flatten();
+        (field.is_none(), field)
+    });
+    let mut sorted = String::with_capacity(content.len());
+    for line in lines {
+        sorted.push_str(line);
+        sorted.push('\n');
+    }
+    tokio::fs::write(path, sorted).await
+}
+
+/// The `key` field of a `grepable` line. Its last four tab-separated fields are target, module,
+/// status and message; the recorder's target and timestamp may come before them.
+fn sort_field(line: &str, key: SortKey) -> Option<(u16, String)> {
+    let mut fields = line.rsplitn(4, '\t');
+    let _message = fields.next()?;
+    let status = fields.next()?;
+    let module = fields.next()?;
+    let target = fields.next()?.rsplit('\t').next()?;
+    match key {
+        SortKey::Status => Some((status.parse().ok()?, String::new())),
+        SortKey::Mode => Some((0, module.to_string())),
+        SortKey::Target => Some((0, target.to_string())),
+    }
+}
+
+/// A finding line with what varies between ports and paths of one host taken out: the
+/// `[/path]` prefix is dropped and every URL is reduced to its host.
+fn finding_signature(line: &str) -> String {
+    let line = line.trim();
+    let line = match line.split_once("] ") {
+        Some((prefix, rest)) if prefix.starts_with("[/") => rest,
+        _ => line,
+    };
+    line.split_whitespace()
+        .map(|word| match Url::parse(word) {
+            Ok(url) if url.has_host() => url.host_str().unwrap_or(word).to_ascii_lowercase(),
+            _ => word.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `line` cut to at most `max` bytes, ending in `…[truncated N bytes]`, or unchanged when it
+/// already fits. The cut never splits a character.
+fn truncate_line(line: &str, max: usize) -> String {
+    if line.len() <= max {
+        return line.to_string();
+    }
+    let mut keep = max;
+    loop {
+        while !line.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        let marker = format!("…[truncated {} bytes]", line.len() - keep);
+        // Dropping more can add a digit to the count, so shrink until the marker fits too.
+        match (keep + marker.len()).checked_sub(max) {
+            Some(over) if over > 0 && keep > 0 => keep = keep.saturating_sub(over),
+            _ => return format!("{}{}", &line[..keep], marker),
+        }
+    }
+}
+
+/// `output.txt` -> `output.<n>.txt`; the number goes before the last extension.
+fn segment_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
 pub fn default_recorder_config(
     output_path: impl Into<PathBuf>,
     targets_path: impl Into<String>,
@@ -251,5 +698,25 @@ pub fn default_recorder_config(
         total_targets,
         truncate_output,
         flush_interval: Duration::from_secs(120),
+        buffer: DEFAULT_RECORDER_BUFFER,
+        pending_warn: DEFAULT_PENDING_WARN,
+        max_pending: None,
+        compress: false,
+        max_output_size: None,
+        timestamps: false,
+        seed: None,
+        ua_seed: None,
+        sample_seed: None,
+        probe_seed: None,
+        threads: None,
+        proxy: None,
+        dedup: false,
+        all_results_path: None,
+        keep_checkpoint: false,
+        append: false,
+        checkpoint_format: CheckpointFormat::Text,
+        completed: None,
+        tags: Arc::default(),
+        max_line_length: None,
     }
 }
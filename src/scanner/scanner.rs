@@ -1,19 +1,41 @@
-use super::executor::{self, ExecutionError};
-use super::recorder::{RecorderConfig, RecorderError, RecorderHandle, ScanRecorder};
+use super::adaptive::{AdaptiveConcurrency, AdaptiveLimiter};
+use super::dashboard::Dashboard;
+use super::executor::{self, ExecutionError, ExecutorOptions, PauseSwitch, error_kind};
+use super::latency::{LatencySummary, SLOWEST_TARGETS};
+use super::metrics::MetricsEndpoint;
+use super::progress::{JsonProgress, ProgressFormat, set_active_bar};
+use super::recorder::{
+    DEFAULT_RECORDER_BUFFER, RecorderConfig, RecorderError, RecorderHandle, ScanRecorder,
+};
 use super::task::Task;
+use crate::core::telemetry::target_span;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::fmt::Display;
-use std::sync::Arc;
-use tokio::sync::mpsc::{self, UnboundedSender};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, Receiver, UnboundedSender};
 use tokio::task::JoinHandle;
+use tracing::Instrument;
 
 pub type ScanError = ExecutionError;
 
 #[derive(Debug, Clone)]
 pub struct ScanOutput {
+    /// Position of the target in the list handed to the scanner.
+    pub index: usize,
     pub target: String,
     pub output: String,
+    /// Why the task failed, if it did. Its output is empty then.
+    pub error: Option<String>,
+    /// Variant name of the error, e.g. `Timeout`, which the error counts are grouped by.
+    pub error_kind: Option<String>,
+    /// When the task for this target finished.
+    pub completed_at: DateTime<Utc>,
 }
 
 pub type ScanResult = Result<Vec<ScanOutput>, ScanError>;
@@ -21,11 +43,117 @@ pub type ScanResult = Result<Vec<ScanOutput>, ScanError>;
 #[derive(Default)]
 pub struct ScanOptions {
     pub recorder: Option<RecorderConfig>,
+    /// Stop scheduling new targets after this much time has elapsed.
+    pub max_runtime: Option<Duration>,
+    /// Lower concurrency while task errors spike, within these bounds.
+    pub adaptive: Option<AdaptiveConcurrency>,
+    /// Hide the progress bar. It is also hidden when stderr isn't a terminal.
+    pub quiet: bool,
+    /// Replace the bar with JSON progress events on stderr.
+    pub progress_format: ProgressFormat,
+    /// Timestamp recorded findings and JSON finding events.
+    pub timestamps: bool,
+    /// Filled with finding and error counts as the scan runs.
+    pub stats: Option<Arc<ScanStats>>,
+    /// Targets of the list already scanned by an earlier run. The bar starts there and counts
+    /// them in its length, so a resumed scan shows progress through the whole list.
+    pub completed_before: usize,
+    /// Serve the scan counters to Prometheus while the scan runs.
+    pub metrics: Option<MetricsEndpoint>,
+    /// End the scan with an error on the first failed target instead of recording the failure
+    /// and moving on.
+    pub abort_on_error: bool,
+    /// Least time between the starts of two targets.
+    pub target_interval: Option<Duration>,
+    /// Stop scheduling new targets once this many have produced findings.
+    pub stop_after_findings: Option<usize>,
+}
+
+/// Counters collected while a scan runs.
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    /// Targets of the whole list, including those done by an earlier run.
+    targets: AtomicUsize,
+    done: AtomicUsize,
+    in_flight: AtomicUsize,
+    findings: AtomicUsize,
+    /// Failed targets by error kind, e.g. `Timeout`.
+    errors: Mutex<BTreeMap<String, usize>>,
+    /// How long each finished target took.
+    latencies: Mutex<Vec<(String, Duration)>>,
+}
+
+impl ScanStats {
+    pub fn targets(&self) -> usize {
+        self.targets.load(Ordering::Relaxed)
+    }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// Targets whose task is running right now.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Targets that produced output.
+    pub fn findings(&self) -> usize {
+        self.findings.load(Ordering::Relaxed)
+    }
+
+    pub fn errors(&self) -> BTreeMap<String, usize> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    /// Percentiles, histogram and slowest targets of the targets finished so far.
+    pub fn latency_summary(&self) -> Option<LatencySummary> {
+        LatencySummary::new(self.latencies.lock().unwrap().clone(), SLOWEST_TARGETS)
+    }
+
+    fn record_latency(&self, target: &str, elapsed: Duration) {
+        self.latencies
+            .lock()
+            .unwrap()
+            .push((target.to_string(), elapsed));
+    }
+
+    fn record_finding(&self) -> usize {
+        self.findings.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Counts `error` under its variant name, so messages that embed the target don't split
+    /// the tally.
+    fn record_error<E: Debug>(&self, error: &E) {
+        *self
+            .errors
+            .lock()
+            .unwrap()
+            .entry(error_kind(error))
+            .or_default() += 1;
+    }
+}
+
+/// Index in the full target list of each scanned target.
+#[derive(Clone)]
+enum TargetIndices {
+    /// Targets from this index on, in order.
+    From(usize),
+    /// The targets left once an earlier run's out-of-order completions were taken out.
+    Listed(Arc<[usize]>),
+}
+
+impl TargetIndices {
+    fn absolute(&self, position: usize) -> usize {
+        match self {
+            TargetIndices::From(base_index) => base_index + position,
+            TargetIndices::Listed(indices) => indices[position],
+        }
+    }
 }
 
 struct RecorderRuntime {
-    sender: UnboundedSender<(usize, String, String)>,
-    forward_handle: JoinHandle<Result<(), RecorderError>>,
+    indices: TargetIndices,
     recorder_task: JoinHandle<Result<(), RecorderError>>,
     handle: RecorderHandle,
 }
@@ -48,17 +176,17 @@ impl TargetScanner {
         Self::new(threads)
     }
 
-    #[allow(dead_code)]
     pub async fn scan<I, T>(&self, targets: I, task: Arc<T>) -> ScanResult
     where
         I: IntoIterator<Item = String>,
-        T: Task + 'static,
-        T::Error: Display,
+        T: Task + ?Sized + 'static,
+        T::Error: Debug + Display,
     {
         self.scan_with_options(targets, task, ScanOptions::default())
             .await
     }
 
+    /// Scans every target and returns the outputs in target order.
     pub async fn scan_with_options<I, T>(
         &self,
         targets: I,
@@ -67,70 +195,250 @@ impl TargetScanner {
     ) -> ScanResult
     where
         I: IntoIterator<Item = String>,
-        T: Task + 'static,
-        T::Error: Display,
+        T: Task + ?Sized + 'static,
+        T::Error: Debug + Display,
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        self.scan_streaming(targets, task, options, sender).await?;
+
+        let mut results = Vec::new();
+        while let Some(output) = receiver.recv().await {
+            results.push(output);
+        }
+        results.sort_by_key(|output| output.index);
+        Ok(results)
+    }
+
+    /// Scans every target, sending each [`ScanOutput`] to `output` as soon as its target completes.
+    ///
+    /// Outputs arrive in completion order; use [`ScanOutput::index`] to restore target order. The
+    /// sender is dropped when the scan finishes, so the receiving side can drain it concurrently
+    /// (e.g. with `tokio::join!`) and stop on `None`. A dropped receiver doesn't stop the scan.
+    pub async fn scan_streaming<I, T>(
+        &self,
+        targets: I,
+        task: Arc<T>,
+        options: ScanOptions,
+        output: UnboundedSender<ScanOutput>,
+    ) -> Result<(), ScanError>
+    where
+        I: IntoIterator<Item = String>,
+        T: Task + ?Sized + 'static,
+        T::Error: Debug + Display,
     {
-        let ScanOptions { recorder } = options;
+        let ScanOptions {
+            recorder,
+            max_runtime,
+            adaptive,
+            quiet,
+            progress_format,
+            timestamps,
+            stats,
+            completed_before,
+            metrics,
+            abort_on_error,
+            target_interval,
+            stop_after_findings,
+        } = options;
+
+        let limiter = adaptive.map(|bounds| {
+            Arc::new(AdaptiveLimiter::new(AdaptiveConcurrency {
+                min: bounds.min,
+                max: bounds.max.min(self.concurrency),
+            }))
+        });
+        let monitor = limiter.as_ref().map(|limiter| limiter.spawn_monitor());
+
+        let pause = Arc::new(PauseSwitch::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let executor_options = ExecutorOptions {
+            deadline: max_runtime.map(|runtime| Instant::now() + runtime),
+            limiter: limiter.clone(),
+            pause: Some(Arc::clone(&pause)),
+            abort_on_error,
+            target_interval,
+            stop: stop_after_findings.map(|_| Arc::clone(&stop)),
+        };
 
         let targets_vec: Vec<String> = targets.into_iter().collect();
 
-        let total = targets_vec.len() as u64;
+        let completed_before = completed_before as u64;
+        let total = targets_vec.len() as u64 + completed_before;
+
+        let json_progress = (progress_format == ProgressFormat::Json)
+            .then(|| Arc::new(JsonProgress::new(targets_vec.len(), timestamps)));
+        let ticker = json_progress
+            .as_ref()
+            .map(|progress| progress.spawn_ticker());
+
+        let tui = progress_format == ProgressFormat::Tui && !quiet && io::stderr().is_terminal();
+        let progress_bar = if quiet || tui || json_progress.is_some() || !io::stderr().is_terminal()
+        {
+            // A hidden bar still counts progress but never draws; findings go straight to stdout.
+            let bar = ProgressBar::hidden();
+            bar.set_length(total);
+            bar
+        } else {
+            let bar = ProgressBar::new(total);
+            let template = if crate::use_color() {
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({per_sec} targets/s) | {msg}"
+            } else {
+                "{spinner} [{elapsed_precise}] [{wide_bar}] {pos}/{len} ({per_sec} targets/s) | {msg}"
+            };
+            bar.set_style(
+                ProgressStyle::with_template(template)
+                    .unwrap()
+                    .progress_chars("##-"),
+            );
+            bar.set_message("0 findings");
+            bar
+        };
+        if completed_before > 0 {
+            // Record the starting position, then restart the rate estimate so {per_sec} only
+            // measures this run.
+            progress_bar.set_position(completed_before);
+            progress_bar.tick();
+            progress_bar.reset_eta();
+        }
 
-        let progress_bar = ProgressBar::new(total);
-        progress_bar.set_style(
-            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({per_sec} targets/s)")
-                .unwrap()
-                .progress_chars("##-"),
-        );
+        set_active_bar(Some(progress_bar.clone()));
+
+        let stats = stats.unwrap_or_default();
+        stats.targets.store(total as usize, Ordering::Relaxed);
+        stats
+            .done
+            .store(completed_before as usize, Ordering::Relaxed);
+        let metrics = metrics.map(|endpoint| endpoint.spawn(Arc::clone(&stats)));
+        let stats_signal =
+            spawn_stats_signal(progress_bar.clone(), Arc::clone(&stats), completed_before);
+        let pause_signal =
+            spawn_pause_signal(progress_bar.clone(), Arc::clone(&stats), Arc::clone(&pause));
+        let dashboard = tui.then(|| {
+            Arc::new(Dashboard::new(
+                Arc::clone(&stats),
+                Arc::clone(&pause),
+                completed_before as usize,
+            ))
+        });
+        let dashboard_handle = match &dashboard {
+            Some(dashboard) => match dashboard.open() {
+                Ok(handle) => Some(handle),
+                Err(err) => {
+                    eprintln!("Failed to open the dashboard: {}", err);
+                    None
+                }
+            },
+            None => None,
+        };
 
         let progress_bar_clone = progress_bar.clone();
         let task = Arc::new(ProgressTask {
             inner: Arc::clone(&task),
             progress: progress_bar_clone,
+            limiter,
+            events: json_progress.clone(),
+            dashboard: dashboard_handle.as_ref().and(dashboard),
+            stats: Arc::clone(&stats),
+            pause,
+            stop_after_findings: stop_after_findings.map(|limit| (limit, stop)),
         });
 
-        let mut recorder_runtime = recorder.map(|recorder_cfg| self.spawn_recorder(recorder_cfg));
-
-        let result_sender = recorder_runtime.as_ref().map(|runtime| &runtime.sender);
+        let buffer = recorder
+            .as_ref()
+            .map_or(DEFAULT_RECORDER_BUFFER, |recorder_cfg| recorder_cfg.buffer)
+            .max(1);
+        let recorder_runtime = recorder.map(|recorder_cfg| {
+            self.spawn_recorder(RecorderConfig {
+                timestamps,
+                ..recorder_cfg
+            })
+        });
 
-        let execution_outcome =
-            executor::execute(targets_vec, self.concurrency, task, result_sender).await;
+        let (sender, receiver) = mpsc::channel::<ScanOutput>(buffer);
+        let forward_handle = tokio::spawn(Self::forward_results(
+            receiver,
+            recorder_runtime
+                .as_ref()
+                .map(|runtime| (runtime.indices.clone(), runtime.handle.clone())),
+            output,
+        ));
+
+        let execution_outcome = executor::execute(
+            targets_vec,
+            self.concurrency,
+            task,
+            &sender,
+            &executor_options,
+        )
+        .await;
         progress_bar.finish_and_clear();
+        set_active_bar(None);
+        if let Some(handle) = dashboard_handle {
+            handle.close();
+        }
+        if let Some(monitor) = monitor {
+            monitor.abort();
+        }
+        for signal_task in [stats_signal, pause_signal, metrics].into_iter().flatten() {
+            signal_task.abort();
+        }
+        if let (Some(ticker), Some(progress)) = (ticker, &json_progress) {
+            ticker.abort();
+            progress.emit_progress();
+        }
+
+        // Drop the producer side so the forwarding task can exit.
+        drop(sender);
+        let forward_outcome = match forward_handle.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => Err(ExecutionError::persistence(err)),
+            Err(join_err) => Err(ExecutionError::internal(join_err)),
+        };
+
+        let recorder_outcome = self.finalize_recorder(recorder_runtime).await;
 
-        let recorder_outcome = self.finalize_recorder(recorder_runtime.take()).await;
+        execution_outcome.and(forward_outcome).and(recorder_outcome)
+    }
+
+    async fn forward_results(
+        mut receiver: Receiver<ScanOutput>,
+        recorder: Option<(TargetIndices, RecorderHandle)>,
+        output: UnboundedSender<ScanOutput>,
+    ) -> Result<(), RecorderError> {
+        while let Some(result) = receiver.recv().await {
+            if let Some((indices, handle)) = &recorder {
+                handle
+                    .record(
+                        indices.absolute(result.index),
+                        result.target.clone(),
+                        result.output.clone(),
+                        result.error.clone(),
+                        result.completed_at,
+                    )
+                    .await?;
+            }
 
-        match (execution_outcome, recorder_outcome) {
-            (Err(err), _) => Err(err),
-            (Ok(_), Err(err)) => Err(err),
-            (Ok(records), Ok(())) => Ok(records
-                .into_iter()
-                .map(|(target, output)| ScanOutput { target, output })
-                .collect()),
+            let _ = output.send(result);
         }
+        Ok(())
     }
 
     fn spawn_recorder(&self, recorder_cfg: RecorderConfig) -> RecorderRuntime {
         let base_index = recorder_cfg.base_index;
+        let indices = match &recorder_cfg.completed {
+            Some(completed) => TargetIndices::Listed(
+                (base_index..base_index + recorder_cfg.total_targets)
+                    .filter(|&index| !completed.contains(index))
+                    .collect(),
+            ),
+            None => TargetIndices::From(base_index),
+        };
         let (recorder, handle, receiver) = ScanRecorder::new(recorder_cfg);
 
-        let recorder_handle = handle.clone();
         let recorder_task = tokio::spawn(async move { recorder.run(receiver).await });
 
-        let (sender, receiver) = mpsc::unbounded_channel::<(usize, String, String)>();
-        let forward_handle = tokio::spawn(async move {
-            let mut receiver = receiver;
-            while let Some((index, target, output)) = receiver.recv().await {
-                let absolute_index = base_index + index;
-                if let Err(err) = recorder_handle.record(absolute_index, target, output) {
-                    return Err(err);
-                }
-            }
-            Ok::<_, RecorderError>(())
-        });
-
         RecorderRuntime {
-            sender,
-            forward_handle,
+            indices,
             recorder_task,
             handle,
         }
@@ -142,26 +450,16 @@ impl TargetScanner {
         };
 
         let RecorderRuntime {
-            sender,
-            forward_handle,
             recorder_task,
             handle,
+            ..
         } = runtime;
 
-        // Request a final flush and drop the producer side so the forwarding task can exit.
-        let _ = handle.request_flush();
-        drop(sender);
-
-        let forward_result = forward_handle.await;
-        let recorder_result = recorder_task.await;
-
-        match forward_result {
-            Ok(Ok(())) => {}
-            Ok(Err(err)) => return Err(ExecutionError::persistence(err)),
-            Err(join_err) => return Err(ExecutionError::internal(join_err)),
-        }
+        // Request a final flush and drop the last handle so the recorder can finish.
+        let _ = handle.request_flush().await;
+        drop(handle);
 
-        match recorder_result {
+        match recorder_task.await {
             Ok(Ok(())) => Ok(()),
             Ok(Err(err)) => Err(ExecutionError::persistence(err)),
             Err(join_err) => Err(ExecutionError::internal(join_err)),
@@ -175,35 +473,221 @@ impl Default for TargetScanner {
     }
 }
 
-struct ProgressTask<T: Task> {
+/// On Unix, prints where the scan stands to stderr every time the process gets SIGUSR1, e.g. from
+/// `kill -USR1 <pid>` on a backgrounded run.
+#[cfg(unix)]
+fn spawn_stats_signal(
+    progress: ProgressBar,
+    stats: Arc<ScanStats>,
+    completed_before: u64,
+) -> Option<JoinHandle<()>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut signals = match signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(err) => {
+            eprintln!("Failed to listen for SIGUSR1: {}", err);
+            return None;
+        }
+    };
+    Some(tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            let report = stats_report(&progress, &stats, completed_before);
+            progress.suspend(|| eprintln!("{}", report));
+        }
+    }))
+}
+
+#[cfg(not(unix))]
+fn spawn_stats_signal(
+    _progress: ProgressBar,
+    _stats: Arc<ScanStats>,
+    _completed_before: u64,
+) -> Option<JoinHandle<()>> {
+    None
+}
+
+/// On Unix, SIGUSR2 pauses scheduling of new targets and the next one resumes it.
+#[cfg(unix)]
+fn spawn_pause_signal(
+    progress: ProgressBar,
+    stats: Arc<ScanStats>,
+    pause: Arc<PauseSwitch>,
+) -> Option<JoinHandle<()>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut signals = match signal(SignalKind::user_defined2()) {
+        Ok(signals) => signals,
+        Err(err) => {
+            eprintln!("Failed to listen for SIGUSR2: {}", err);
+            return None;
+        }
+    };
+    Some(tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            let paused = pause.toggle();
+            progress.set_message(progress_message(stats.findings(), paused));
+            progress.suspend(|| {
+                if paused {
+                    eprintln!(
+                        "Scan paused, running targets will finish; send SIGUSR2 again to resume"
+                    );
+                } else {
+                    eprintln!("Scan resumed");
+                }
+            });
+        }
+    }))
+}
+
+#[cfg(not(unix))]
+fn spawn_pause_signal(
+    _progress: ProgressBar,
+    _stats: Arc<ScanStats>,
+    _pause: Arc<PauseSwitch>,
+) -> Option<JoinHandle<()>> {
+    None
+}
+
+fn progress_message(findings: usize, paused: bool) -> String {
+    if paused {
+        format!("paused | {} findings", findings)
+    } else {
+        format!("{} findings", findings)
+    }
+}
+
+/// `completed_before` targets were done by an earlier run, so they don't count toward this
+/// run's rate.
+fn stats_report(progress: &ProgressBar, stats: &ScanStats, completed_before: u64) -> String {
+    let done = progress.position();
+    let total = progress.length().unwrap_or(done);
+    let elapsed = progress.elapsed();
+    let done_now = done.saturating_sub(completed_before);
+    let eta = if done_now == 0 {
+        "unknown".to_string()
+    } else {
+        let remaining = elapsed.mul_f64(total.saturating_sub(done) as f64 / done_now as f64);
+        format!("{}s", remaining.as_secs())
+    };
+    let errors = stats.errors();
+    let errors = if errors.is_empty() {
+        "none".to_string()
+    } else {
+        errors
+            .iter()
+            .map(|(kind, count)| format!("{} {}", kind, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "[stats] {}/{} targets, {} findings, errors: {}, elapsed {}s, eta {}",
+        done,
+        total,
+        stats.findings(),
+        errors,
+        elapsed.as_secs(),
+        eta
+    )
+}
+
+struct ProgressTask<T: Task + ?Sized> {
     inner: Arc<T>,
     progress: ProgressBar,
+    limiter: Option<Arc<AdaptiveLimiter>>,
+    events: Option<Arc<JsonProgress>>,
+    dashboard: Option<Arc<Dashboard>>,
+    /// Findings so far are shown next to the bar.
+    stats: Arc<ScanStats>,
+    pause: Arc<PauseSwitch>,
+    /// Raises the flag once this many targets have produced findings.
+    stop_after_findings: Option<(usize, Arc<AtomicBool>)>,
 }
 
 #[async_trait(?Send)]
 impl<T> Task for ProgressTask<T>
 where
-    T: Task + Send + Sync + 'static,
-    T::Error: Display,
+    T: Task + Send + Sync + ?Sized + 'static,
+    T::Error: Debug + Display,
 {
     type Error = T::Error;
 
     async fn execute(&self, target: String) -> Result<String, Self::Error> {
         let progress = self.progress.clone();
 
-        match self.inner.execute(target.clone()).await {
+        self.stats.in_flight.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(target = %target, "target started");
+        if let Some(dashboard) = &self.dashboard {
+            dashboard.start(&target);
+        }
+        let started = Instant::now();
+        let result = self
+            .inner
+            .execute(target.clone())
+            .instrument(target_span(&target))
+            .await;
+        let elapsed = started.elapsed();
+        self.stats.record_latency(&target, elapsed);
+        self.stats.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.stats.done.fetch_add(1, Ordering::Relaxed);
+        if let Some(limiter) = &self.limiter {
+            limiter.record(result.is_ok());
+        }
+
+        match result {
             Ok(output) => {
+                tracing::info!(
+                    target = %target,
+                    findings = output.lines().filter(|line| !line.trim().is_empty()).count(),
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    "target finished"
+                );
                 if !output.trim().is_empty() {
-                    progress.println(output.clone());
+                    let findings = self.stats.record_finding();
+                    progress.set_message(progress_message(findings, self.pause.is_paused()));
+                    if let Some((limit, stop)) = &self.stop_after_findings
+                        && findings >= *limit
+                        && !stop.swap(true, Ordering::Relaxed)
+                    {
+                        tracing::info!(findings, "finding limit reached, stopping");
+                    }
+                    if crate::stdout_reserved() || self.dashboard.is_some() {
+                        // The recorder writes it to stdout, in target order, or the dashboard
+                        // lists it and prints it once it closes.
+                    } else if progress.is_hidden() {
+                        println!("{}", output);
+                    } else {
+                        progress.println(output.clone());
+                    }
                 }
                 progress.inc(1);
+                if let Some(events) = &self.events {
+                    events.complete(&target, &output);
+                }
+                if let Some(dashboard) = &self.dashboard {
+                    dashboard.finish(&target, &output);
+                }
                 Ok(output)
             }
-            Err(_) => {
-                // let message = format!("[-] {}: {}", target, err);
-                // progress.println(message);
+            Err(err) => {
+                // Passed on either way: the executor ends the scan on it under abort_on_error,
+                // and otherwise hands it to the recorder with the target's empty output.
+                tracing::warn!(
+                    target = %target,
+                    kind = %error_kind(&err),
+                    error = %err,
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    "target failed"
+                );
+                self.stats.record_error(&err);
                 progress.inc(1);
-                Ok(String::new())
+                if let Some(events) = &self.events {
+                    events.complete(&target, "");
+                }
+                if let Some(dashboard) = &self.dashboard {
+                    dashboard.finish(&target, "");
+                }
+                Err(err)
             }
         }
     }
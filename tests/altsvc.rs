@@ -0,0 +1,41 @@
+use riphttp::core::altsvc::extract_alt_svc_port;
+
+#[test]
+fn extracts_the_h3_port_from_alt_svc() {
+    assert_eq!(extract_alt_svc_port(r#"h3=":443""#), Some(443));
+    assert_eq!(extract_alt_svc_port(r#"h3=":8443"; ma=86400"#), Some(8443));
+    assert_eq!(
+        extract_alt_svc_port(r#"h3=":443"; ma=2592000; persist=1"#),
+        Some(443)
+    );
+    assert_eq!(extract_alt_svc_port("h3=:4433"), Some(4433));
+}
+
+#[test]
+fn prefers_final_h3_over_drafts_in_multiple_entries() {
+    assert_eq!(
+        extract_alt_svc_port(r#"h3-29=":8443"; ma=86400, h3=":443"; ma=86400"#),
+        Some(443)
+    );
+    assert_eq!(
+        extract_alt_svc_port(r#"h2=":443", h3-29=":8443"; ma=86400"#),
+        Some(8443)
+    );
+    assert_eq!(
+        extract_alt_svc_port(r#"h2=":8080",h3=":9443";ma=60"#),
+        Some(9443)
+    );
+}
+
+#[test]
+fn ignores_other_hosts_and_malformed_entries() {
+    assert_eq!(extract_alt_svc_port("clear"), None);
+    assert_eq!(extract_alt_svc_port(r#"h2=":443"; ma=86400"#), None);
+    assert_eq!(extract_alt_svc_port(r#"h3="alt.example.com:443""#), None);
+    assert_eq!(
+        extract_alt_svc_port(r#"h3="alt.example.com:443", h3-29=":8443""#),
+        Some(8443)
+    );
+    assert_eq!(extract_alt_svc_port(r#"h3=":notaport", h3=":0""#), None);
+    assert_eq!(extract_alt_svc_port(""), None);
+}
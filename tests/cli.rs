@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A target that refuses connections, so client runs fail fast after printing what they send.
+const CLOSED_TARGET: &str = "http://127.0.0.1:9/";
+
+fn riphttp(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_riphttp"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// A fresh directory holding a one-target `targets.txt`.
+fn scan_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("riphttp-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("targets.txt"), "https://example.com\n").unwrap();
+    dir
+}
+
+#[test]
+fn verbose_is_accepted_before_and_after_the_subcommand() {
+    let dir = scan_dir("verbose");
+    let scan = ["-t", "targets.txt", "--mode", "detect", "--dry-run"];
+    let before = [&["-v", "scan"][..], &scan].concat();
+    let after = [&["scan", "-v"][..], &scan].concat();
+
+    for args in [before, after] {
+        let output = riphttp(&dir, &args);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{:?}: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(
+            stdout.contains("Running trailers scan in verbose mode"),
+            "{:?}: {}",
+            args,
+            stdout
+        );
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn verbose_is_accepted_around_the_top_level_client_args() {
+    let dir = std::env::temp_dir();
+    for args in [["-v", CLOSED_TARGET], [CLOSED_TARGET, "-v"]] {
+        let output = riphttp(&dir, &args);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains(&format!("Sending request to: {}", CLOSED_TARGET)),
+            "{:?}: {}",
+            args,
+            stdout
+        );
+    }
+
+    let output = riphttp(&dir, &[CLOSED_TARGET]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Sending request to"));
+}
+
+#[test]
+fn top_level_client_args_conflict_with_subcommands() {
+    let dir = scan_dir("conflict");
+    let output = riphttp(&dir, &["-m", "POST", "scan", "-t", "targets.txt"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    // clap stops looking for a subcommand after a client arg, so `scan` is taken for the URL.
+    assert!(
+        stderr.contains("unexpected argument '-t' found"),
+        "{}",
+        stderr
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn seed_repeats_a_scans_random_choices() {
+    let dir = scan_dir("seed");
+    let scan = |seed: &[&str]| {
+        let args = [
+            &["scan", "-t", "targets.txt", "--mode", "te-cl"][..],
+            &["--random-smuggle-path", "--dry-run"],
+            seed,
+        ]
+        .concat();
+        String::from_utf8_lossy(&riphttp(&dir, &args).stdout).into_owned()
+    };
+
+    let first = scan(&["--seed", "7"]);
+    assert!(first.contains("Using seed 7"), "{}", first);
+    assert_eq!(scan(&["--seed", "7"]), first);
+    assert_ne!(scan(&["--seed", "8"]), first);
+
+    let random = scan(&[]);
+    let seed = random
+        .lines()
+        .find_map(|line| line.strip_prefix("Using seed "))
+        .expect("the generated seed is printed");
+    assert_eq!(scan(&["--seed", seed]), random);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn direct_connection_modes_refuse_a_proxy() {
+    let dir = scan_dir("proxy");
+    let scan = |mode: &str| {
+        riphttp(
+            &dir,
+            &[
+                "scan",
+                "-t",
+                "targets.txt",
+                "--mode",
+                mode,
+                "--proxy",
+                "127.0.0.1:8080",
+                "--dry-run",
+            ],
+        )
+    };
+
+    for mode in ["te-cl", "queue-poison", "all"] {
+        let output = scan(mode);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{}", mode);
+        assert!(
+            stderr.contains("sends payloads that can't go through a proxy"),
+            "{}: {}",
+            mode,
+            stderr
+        );
+    }
+    for mode in ["trail-merge", "h2-smug", "detect"] {
+        let output = scan(mode);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{}", mode);
+        assert!(
+            stderr.contains("detects the target's protocols over a direct connection"),
+            "{}: {}",
+            mode,
+            stderr
+        );
+    }
+    let output = riphttp(
+        &dir,
+        &[
+            "scan",
+            "-t",
+            "targets.txt",
+            "--head-only",
+            "--proxy",
+            "127.0.0.1:8080",
+            "--dry-run",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,263 @@
+//! A tiny HTTP/1.1 origin for exercising the scan modules end to end.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How long a stalled request waits for the rest of its body before the server answers 504,
+/// the way a front end gives up on a back end.
+pub const STALL: Duration = Duration::from_millis(300);
+
+/// How the server treats each request. Every connection carries one request and is closed
+/// after the response.
+#[derive(Debug, Clone, Copy)]
+pub enum Behavior {
+    /// Answers every request with this status.
+    Status(u16),
+    /// Waits for as many body bytes as the Content-Length header promises, answering 504
+    /// after `STALL` when they don't come. Chunked trailers are ignored.
+    StallOnContentLength,
+    /// Merges chunked trailers into the headers, so a trailer Content-Length larger than the
+    /// body makes it stall like `StallOnContentLength`.
+    MergeTrailers,
+    /// Answers a POST as soon as its head arrives, with a second status line inside the body
+    /// the way a back end that split the request answers twice on one read. Other requests get
+    /// 200.
+    SplitOnPost,
+    /// Reads chunked bodies like a back end behind a CL.TE front end: a complete request after
+    /// the last chunk is answered too, and that response goes to the next request on any
+    /// connection.
+    PoisonQueue,
+    /// Never answers a POST, holding the connection until the client gives up, like a back end
+    /// waiting for a body that isn't coming. Once a POST was seen, other requests get 500.
+    HangOnPost,
+}
+
+/// What one server remembers across its connections.
+#[derive(Default)]
+struct State {
+    /// Whether a `HangOnPost` server has been sent a POST.
+    hung: AtomicBool,
+    /// Path of the request smuggled to a `PoisonQueue` server, whose response is still waiting
+    /// for the next client.
+    queued: Mutex<Option<String>>,
+}
+
+/// Starts a server on a free local port and returns its base URL.
+pub async fn spawn(behavior: Behavior) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let state = Arc::new(State::default());
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            tokio::spawn(serve(stream, behavior, state.clone()));
+        }
+    });
+    format!("http://{}/", addr)
+}
+
+async fn serve(mut stream: TcpStream, behavior: Behavior, state: Arc<State>) {
+    let mut reader = Reader::default();
+    let Some(head) = reader.line_block(&mut stream).await else {
+        return;
+    };
+    if matches!(behavior, Behavior::SplitOnPost)
+        && head.first().is_some_and(|line| line.starts_with("POST "))
+    {
+        let second = "HTTP/1.1 405 Method Not Allowed\r\ncontent-length: 0\r\n\r\n";
+        let response = format!(
+            "HTTP/1.1 200 Test\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            second.len(),
+            second
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+        return;
+    }
+    if matches!(behavior, Behavior::HangOnPost)
+        && head.first().is_some_and(|line| line.starts_with("POST "))
+    {
+        state.hung.store(true, Ordering::SeqCst);
+        while reader.read_more(&mut stream).await {}
+        return;
+    }
+    let content_length = header(&head, "content-length").and_then(|value| value.parse().ok());
+    let chunked = header(&head, "transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+
+    let queued = match behavior {
+        Behavior::PoisonQueue => state.queued.lock().unwrap().take(),
+        _ => None,
+    };
+    if let Some(path) = queued {
+        let body = format!("Not found: {}", path);
+        let response = format!(
+            "HTTP/1.1 404 Test\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+        return;
+    }
+
+    let (received, trailers) = if chunked {
+        let Some((body, trailers)) = reader.chunked_body(&mut stream).await else {
+            return;
+        };
+        (body, trailers)
+    } else {
+        (reader.take(content_length.unwrap_or(0)), Vec::new())
+    };
+    if matches!(behavior, Behavior::PoisonQueue) && chunked {
+        let leftover = String::from_utf8_lossy(&reader.buf).into_owned();
+        if let Some(path) = leftover
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+        {
+            *state.queued.lock().unwrap() = Some(path.to_string());
+        }
+    }
+
+    let status = match behavior {
+        Behavior::Status(status) => status,
+        Behavior::StallOnContentLength if !chunked => {
+            stall(
+                &mut stream,
+                &mut reader,
+                content_length.unwrap_or(0),
+                received,
+            )
+            .await
+        }
+        Behavior::HangOnPost if state.hung.load(Ordering::SeqCst) => 500,
+        Behavior::StallOnContentLength
+        | Behavior::SplitOnPost
+        | Behavior::PoisonQueue
+        | Behavior::HangOnPost => 200,
+        Behavior::MergeTrailers => {
+            match header(&trailers, "content-length").and_then(|value| value.parse().ok()) {
+                Some(declared) => stall(&mut stream, &mut reader, declared, received).await,
+                None => 200,
+            }
+        }
+    };
+
+    let response = format!(
+        "HTTP/1.1 {} Test\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+        status
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Waits for `declared` body bytes of which `received` arrived, returning 504 if they don't.
+async fn stall(
+    stream: &mut TcpStream,
+    reader: &mut Reader,
+    declared: usize,
+    received: usize,
+) -> u16 {
+    if declared <= received {
+        return 200;
+    }
+    let missing = declared - received;
+    match tokio::time::timeout(STALL, reader.fill(stream, missing)).await {
+        Ok(true) => 200,
+        _ => 504,
+    }
+}
+
+fn header<'a>(lines: &'a [String], name: &str) -> Option<&'a str> {
+    lines.iter().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+#[derive(Default)]
+struct Reader {
+    buf: Vec<u8>,
+}
+
+impl Reader {
+    /// Reads more bytes into the buffer; false once the peer closed.
+    async fn read_more(&mut self, stream: &mut TcpStream) -> bool {
+        let mut chunk = [0u8; 4096];
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => false,
+            Ok(read) => {
+                self.buf.extend_from_slice(&chunk[..read]);
+                true
+            }
+        }
+    }
+
+    async fn line(&mut self, stream: &mut TcpStream) -> Option<String> {
+        loop {
+            if let Some(end) = self.buf.windows(2).position(|window| window == b"\r\n") {
+                let line = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+                self.buf.drain(..end + 2);
+                return Some(line);
+            }
+            if !self.read_more(stream).await {
+                return None;
+            }
+        }
+    }
+
+    /// Lines up to the next empty one, e.g. a request head or chunked trailers.
+    async fn line_block(&mut self, stream: &mut TcpStream) -> Option<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let line = self.line(stream).await?;
+            if line.is_empty() {
+                return Some(lines);
+            }
+            lines.push(line);
+        }
+    }
+
+    /// Reads a chunked body, returning its length and its trailers.
+    async fn chunked_body(&mut self, stream: &mut TcpStream) -> Option<(usize, Vec<String>)> {
+        let mut length = 0;
+        loop {
+            let size = self.line(stream).await?;
+            let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+            if size == 0 {
+                return Some((length, self.line_block(stream).await?));
+            }
+            if !self.fill(stream, size + 2).await {
+                return None;
+            }
+            self.take(size + 2);
+            length += size;
+        }
+    }
+
+    /// Waits until `count` bytes are buffered; false if the peer closed first.
+    async fn fill(&mut self, stream: &mut TcpStream, count: usize) -> bool {
+        while self.buf.len() < count {
+            if !self.read_more(stream).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Drops up to `count` buffered bytes and returns how many there were.
+    fn take(&mut self, count: usize) -> usize {
+        let count = count.min(self.buf.len());
+        self.buf.drain(..count);
+        count
+    }
+}
@@ -0,0 +1,238 @@
+mod common;
+
+use common::{Behavior, spawn};
+use riphttp::modules::census::CensusTask;
+use riphttp::modules::options::ModuleOptions;
+use riphttp::modules::queuepoison::QueuePoisonTask;
+use riphttp::modules::teclsmug::TeClTask;
+use riphttp::modules::template::{RequestTemplate, TemplateTask};
+use riphttp::modules::trailmerge::TrailMergeTask;
+use riphttp::modules::trailsmug::TrailSmugTask;
+use riphttp::scanner::task::Task;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Short timeouts for every module under test; each test sets the flags it exercises itself.
+fn module_options() -> ModuleOptions {
+    ModuleOptions {
+        connect_timeout: Some(Duration::from_secs(1)),
+        read_timeout: Some(Duration::from_secs(2)),
+        ..ModuleOptions::default()
+    }
+}
+
+fn trailmerge() -> TrailMergeTask {
+    TrailMergeTask::with_options(module_options())
+}
+
+#[tokio::test]
+async fn trailmerge_reports_a_server_that_merges_trailers() {
+    let target = spawn(Behavior::MergeTrailers).await;
+    let output = trailmerge().execute(target).await.unwrap();
+    assert!(output.contains("gateway timeout"), "{:?}", output);
+}
+
+#[tokio::test]
+async fn trailmerge_confirms_the_trailer_content_length_was_merged() {
+    let target = spawn(Behavior::MergeTrailers).await;
+    let output = trailmerge().execute(target).await.unwrap();
+    assert!(
+        output.contains("merged trailer content-length: waited for exactly 27 extra bytes"),
+        "{:?}",
+        output
+    );
+}
+
+#[tokio::test]
+async fn trailmerge_sends_custom_trailers_in_place_of_the_built_in_ones() {
+    // Without the built-in trailer Content-Length there is nothing for the server to stall on.
+    let target = spawn(Behavior::MergeTrailers).await;
+    let task = TrailMergeTask::with_options(ModuleOptions {
+        trailers: Arc::new(vec!["x-filler: 1".to_string()]),
+        ..module_options()
+    });
+    let output = task.execute(target).await.unwrap();
+    assert_eq!(output, "");
+}
+
+#[tokio::test]
+async fn trailmerge_ignores_a_clean_server() {
+    let target = spawn(Behavior::Status(200)).await;
+    let output = trailmerge().execute(target).await.unwrap();
+    assert_eq!(output, "");
+}
+
+#[tokio::test]
+async fn trailmerge_ignores_a_server_that_drops_trailers() {
+    // Stalls on a short Content-Length body, but never reads one from the trailers.
+    let target = spawn(Behavior::StallOnContentLength).await;
+    let output = trailmerge().execute(target).await.unwrap();
+    assert_eq!(output, "");
+}
+
+#[tokio::test]
+async fn trailmerge_skips_targets_whose_baseline_already_fails() {
+    let target = spawn(Behavior::Status(504)).await;
+    let output = trailmerge().execute(target).await.unwrap();
+    assert_eq!(output, "");
+}
+
+#[tokio::test]
+async fn trailmerge_classifies_a_server_that_rejects_trailers() {
+    let target = spawn(Behavior::Status(400)).await;
+    let task = TrailMergeTask::with_options(ModuleOptions {
+        classify_trailers: true,
+        ..module_options()
+    });
+    let output = task.execute(target).await.unwrap();
+    assert!(output.starts_with("[-] trailers rejected"), "{:?}", output);
+    assert!(output.ends_with("status 400"), "{:?}", output);
+}
+
+#[tokio::test]
+async fn trailmerge_classifies_a_clean_server() {
+    let target = spawn(Behavior::Status(200)).await;
+    let task = TrailMergeTask::with_options(ModuleOptions {
+        classify_trailers: true,
+        ..module_options()
+    });
+    let output = task.execute(target).await.unwrap();
+    assert!(
+        output.starts_with("[-] trailers accepted, no desync"),
+        "{:?}",
+        output
+    );
+}
+
+#[tokio::test]
+async fn trailsmug_reports_a_split_response() {
+    let target = spawn(Behavior::SplitOnPost).await;
+    let output = TrailSmugTask::with_options(module_options())
+        .execute(target)
+        .await
+        .unwrap();
+    assert!(
+        output.contains("split response: \"HTTP/1.1 405 Method Not Allowed\""),
+        "{:?}",
+        output
+    );
+}
+
+#[tokio::test]
+async fn teclsmug_keeps_going_when_every_attack_times_out() {
+    let target = spawn(Behavior::HangOnPost).await;
+    let output = TeClTask::with_options(ModuleOptions {
+        read_timeout: Some(Duration::from_millis(500)),
+        probes: 1,
+        max_consecutive_errors: 1,
+        ..module_options()
+    })
+    .execute(target)
+    .await
+    .unwrap();
+    assert!(output.contains("TE.CL resp difference"), "{:?}", output);
+}
+
+#[tokio::test]
+async fn teclsmug_reports_an_attack_that_stalls_while_the_follow_up_does_not() {
+    let target = spawn(Behavior::HangOnPost).await;
+    let output = TeClTask::with_options(ModuleOptions {
+        read_timeout: Some(Duration::from_millis(500)),
+        probes: 1,
+        ..module_options()
+    })
+    .execute(target)
+    .await
+    .unwrap();
+    assert!(
+        output.contains("TE.CL timing difference: attack"),
+        "{:?}",
+        output
+    );
+}
+
+#[tokio::test]
+async fn queue_poison_reports_a_marker_in_the_next_response() {
+    let target = spawn(Behavior::PoisonQueue).await;
+    let output = QueuePoisonTask::with_options(module_options())
+        .execute(target)
+        .await
+        .unwrap();
+    assert!(output.contains("response queue poisoned"), "{:?}", output);
+    assert!(
+        output.contains("(status 404, baseline 200)"),
+        "{:?}",
+        output
+    );
+}
+
+#[tokio::test]
+async fn census_reports_the_baseline_status() {
+    let target = spawn(Behavior::Status(503)).await;
+    let output = CensusTask::new().execute(target).await.unwrap();
+    assert!(output.starts_with("status 503 HTTP/1.1"), "{:?}", output);
+}
+
+#[tokio::test]
+async fn template_reports_an_attack_a_detect_rule_matches() {
+    let target = spawn(Behavior::SplitOnPost).await;
+    let template = RequestTemplate::parse(
+        "name: split\n\
+         detect: marker 405 Method Not Allowed\n\
+         \n\
+         [baseline]\n\
+         GET {{path}} HTTP/1.1\n\
+         Host: {{host}}\n\
+         \n\
+         [attack post]\n\
+         POST {{path}} HTTP/1.1\n\
+         Host: {{host}}\n\
+         Content-Length: {{length}}\n\
+         \n\
+         x=1\n",
+    )
+    .unwrap();
+    let output = TemplateTask::with_options(module_options())
+        .with_template(template)
+        .execute(target)
+        .await
+        .unwrap();
+    assert!(
+        output.contains("template split post: reflected \"405 Method Not Allowed\""),
+        "{:?}",
+        output
+    );
+}
+
+#[test]
+fn template_renders_escapes_and_the_body_length() {
+    let template = RequestTemplate::parse(
+        "detect: status\n\
+         [baseline]\n\
+         GET {{path}} HTTP/1.1\n\
+         [attack]\n\
+         POST {{path}} HTTP/1.1\n\
+         Content-Length: {{length}}\n\
+         \n\
+         0\\r\\n\n\
+         GET /{{marker}} HTTP/1.1\n\
+         X: \\\n",
+    )
+    .unwrap();
+    let task = TemplateTask::new();
+    let target = "http://example.com/app";
+
+    assert_eq!(
+        task.render(&template.baseline, target, "m").unwrap(),
+        "GET /app HTTP/1.1\r\n\r\n"
+    );
+    assert_eq!(
+        task.render(&template.attacks[0], target, "m").unwrap(),
+        "POST /app HTTP/1.1\r\nContent-Length: 25\r\n\r\n0\r\n\r\nGET /m HTTP/1.1\r\nX: "
+    );
+    let err = RequestTemplate::parse(
+        "detect: status\n[baseline]\nGET {{url}} HTTP/1.1\n[attack]\nGET / HTTP/1.1\n",
+    )
+    .unwrap_err();
+    assert!(err.contains("unknown placeholder '{{url}}'"), "{}", err);
+}
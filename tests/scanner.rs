@@ -0,0 +1,496 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use riphttp::core::finding::Finding;
+use riphttp::core::sqlite::payload_signature;
+use riphttp::scanner::checkpoint::{
+    Checkpoint, CheckpointFormat, CompletedSet, read_checkpoint, write_checkpoint,
+};
+use riphttp::scanner::compare::diff_outputs;
+use riphttp::scanner::executor::{ExecutorOptions, execute};
+use riphttp::scanner::recorder::{RecorderConfig, ScanRecorder, default_recorder_config};
+use riphttp::scanner::scanner::{ScanOptions, ScanStats, TargetScanner};
+use riphttp::scanner::task::Task;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Answers `found <target>` for every target, after the target's delay, or fails the targets
+/// marked as failing.
+#[derive(Default)]
+struct MockTask {
+    delays: HashMap<String, Duration>,
+    failing: HashSet<String>,
+}
+
+impl MockTask {
+    fn delay(mut self, target: &str, millis: u64) -> Self {
+        self.delays
+            .insert(target.to_string(), Duration::from_millis(millis));
+        self
+    }
+
+    fn fail(mut self, target: &str) -> Self {
+        self.failing.insert(target.to_string());
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl Task for MockTask {
+    type Error = String;
+
+    async fn execute(&self, target: String) -> Result<String, Self::Error> {
+        if let Some(delay) = self.delays.get(&target) {
+            tokio::time::sleep(*delay).await;
+        }
+        if self.failing.contains(&target) {
+            return Err(format!("{} failed", target));
+        }
+        Ok(format!("found {}", target))
+    }
+}
+
+fn targets(count: usize) -> Vec<String> {
+    (0..count).map(|index| format!("t{}", index)).collect()
+}
+
+/// A fresh directory for the output and checkpoint of one test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("riphttp-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn recorder_config(dir: &Path, base_index: usize, total_targets: usize) -> RecorderConfig {
+    RecorderConfig {
+        checkpoint_path: dir.join("checkpoint"),
+        ..default_recorder_config(
+            dir.join("output.txt"),
+            "targets.txt",
+            "mock",
+            base_index,
+            total_targets,
+            base_index == 0,
+        )
+    }
+}
+
+#[tokio::test]
+async fn results_come_back_in_target_order() {
+    // Earlier targets finish last.
+    let task = MockTask::default()
+        .delay("t0", 60)
+        .delay("t1", 40)
+        .delay("t2", 20);
+    let results = TargetScanner::new(4)
+        .scan(targets(4), Arc::new(task))
+        .await
+        .unwrap();
+
+    let order: Vec<(usize, &str)> = results
+        .iter()
+        .map(|result| (result.index, result.target.as_str()))
+        .collect();
+    assert_eq!(order, [(0, "t0"), (1, "t1"), (2, "t2"), (3, "t3")]);
+    assert_eq!(results[0].output, "found t0");
+}
+
+#[tokio::test]
+async fn failed_targets_keep_their_place() {
+    let task = MockTask::default().delay("t0", 30).fail("t1");
+    let stats = Arc::new(ScanStats::default());
+    let options = ScanOptions {
+        quiet: true,
+        stats: Some(Arc::clone(&stats)),
+        ..ScanOptions::default()
+    };
+    let results = TargetScanner::new(3)
+        .scan_with_options(targets(3), Arc::new(task), options)
+        .await
+        .unwrap();
+
+    let outputs: Vec<&str> = results
+        .iter()
+        .map(|result| result.output.as_str())
+        .collect();
+    assert_eq!(outputs, ["found t0", "", "found t2"]);
+    assert_eq!(results[1].error.as_deref(), Some("t1 failed"));
+    assert!(results[0].error.is_none() && results[2].error.is_none());
+    assert_eq!(stats.errors().values().sum::<usize>(), 1);
+    assert_eq!(stats.findings(), 2);
+}
+
+#[tokio::test]
+async fn recorder_commits_contiguously() {
+    let dir = scratch_dir("contiguous");
+    let (recorder, handle, receiver) = ScanRecorder::new(recorder_config(&dir, 0, 4));
+    let run = tokio::spawn(recorder.run(receiver));
+
+    for index in [2, 1] {
+        handle
+            .record(
+                index,
+                format!("t{}", index),
+                format!("found t{}", index),
+                None,
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+    }
+    drop(handle);
+    run.await.unwrap().unwrap();
+
+    // Nothing is committed past the missing first target; once the scan stops, the held
+    // results go out after a marker and the checkpoint lists them as done.
+    let output = std::fs::read_to_string(dir.join("output.txt")).unwrap();
+    assert_eq!(
+        output,
+        "# stopped with the target at index 0 unfinished; 2 later result(s) written out of order\nt1\tfound t1\nt2\tfound t2\n"
+    );
+    let checkpoint = read_checkpoint(dir.join("checkpoint"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(checkpoint.next_index, 0);
+    let completed = checkpoint.completed.unwrap();
+    assert_eq!(completed.len(), 4);
+    assert!(completed.contains(1) && completed.contains(2));
+    assert_eq!(completed.count(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn recorder_truncates_long_lines() {
+    let dir = scratch_dir("max-line-length");
+    let cfg = RecorderConfig {
+        max_line_length: Some(40),
+        ..recorder_config(&dir, 0, 1)
+    };
+    let (recorder, handle, receiver) = ScanRecorder::new(cfg);
+    let run = tokio::spawn(recorder.run(receiver));
+
+    let finding = format!("short\npayload {}", "x".repeat(100));
+    handle
+        .record(0, "t0".to_string(), finding, None, Utc::now())
+        .await
+        .unwrap();
+    drop(handle);
+    run.await.unwrap().unwrap();
+
+    let output = std::fs::read_to_string(dir.join("output.txt")).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "t0\tshort");
+    assert_eq!(lines[1].len(), 40);
+    assert!(lines[1].starts_with("payload xxx"));
+    assert!(lines[1].ends_with("…[truncated 91 bytes]"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn recorder_advances_next_index_and_keeps_an_unfinished_checkpoint() {
+    let dir = scratch_dir("next-index");
+    let (recorder, handle, receiver) = ScanRecorder::new(recorder_config(&dir, 0, 4));
+    let run = tokio::spawn(recorder.run(receiver));
+
+    for index in [2, 0, 1] {
+        handle
+            .record(
+                index,
+                format!("t{}", index),
+                format!("found t{}", index),
+                None,
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+    }
+    drop(handle);
+    run.await.unwrap().unwrap();
+
+    let output = std::fs::read_to_string(dir.join("output.txt")).unwrap();
+    assert_eq!(output, "t0\tfound t0\nt1\tfound t1\nt2\tfound t2\n");
+    let checkpoint = read_checkpoint(dir.join("checkpoint"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(checkpoint.next_index, 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn binary_checkpoint_round_trips_a_million_target_completed_set() {
+    let dir = scratch_dir("binary-checkpoint");
+    let mut completed = CompletedSet::new(1_000_000);
+    for index in (0..1_000_000).step_by(3) {
+        completed.insert(index);
+    }
+    let checkpoint = Checkpoint::new(42, "targets.txt", "output.txt", "mock")
+        .with_seed(Some(3))
+        .with_probe_seed(Some(7))
+        .with_conditions(50, Some("http://127.0.0.1:8080".to_string()))
+        .with_completed(Some(completed))
+        .with_format(CheckpointFormat::Binary);
+    write_checkpoint(dir.join("checkpoint"), &checkpoint)
+        .await
+        .unwrap();
+
+    // One bit per target plus a short header.
+    let size = std::fs::metadata(dir.join("checkpoint")).unwrap().len();
+    assert!(size < 1_000_000 / 8 + 256, "{} bytes", size);
+    let restored = read_checkpoint(dir.join("checkpoint"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(restored, checkpoint);
+    let completed = restored.completed.unwrap();
+    assert_eq!(completed.count(), 333_334);
+    assert!(completed.contains(999_999) && !completed.contains(999_998));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn checkpoint_is_removed_once_every_target_is_recorded() {
+    let dir = scratch_dir("complete");
+    let task = MockTask::default().delay("t0", 30).fail("t2");
+    let options = ScanOptions {
+        recorder: Some(recorder_config(&dir, 0, 4)),
+        quiet: true,
+        ..ScanOptions::default()
+    };
+    TargetScanner::new(4)
+        .scan_with_options(targets(4), Arc::new(task), options)
+        .await
+        .unwrap();
+
+    let output = std::fs::read_to_string(dir.join("output.txt")).unwrap();
+    assert_eq!(output, "t0\tfound t0\nt1\tfound t1\nt3\tfound t3\n");
+    assert!(!dir.join("checkpoint").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn resume_skips_targets_before_next_index() {
+    let dir = scratch_dir("resume");
+    std::fs::write(dir.join("output.txt"), "t0\tfound t0\nt1\tfound t1\n").unwrap();
+
+    let (recorder, handle, receiver) = ScanRecorder::new(recorder_config(&dir, 2, 2));
+    let run = tokio::spawn(recorder.run(receiver));
+    // A late result for a target the earlier run already recorded is dropped.
+    handle
+        .record(
+            1,
+            "t1".to_string(),
+            "found t1 again".to_string(),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    for index in [3, 2] {
+        handle
+            .record(
+                index,
+                format!("t{}", index),
+                format!("found t{}", index),
+                None,
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+    }
+    drop(handle);
+    run.await.unwrap().unwrap();
+
+    let output = std::fs::read_to_string(dir.join("output.txt")).unwrap();
+    assert_eq!(
+        output,
+        "t0\tfound t0\nt1\tfound t1\nt2\tfound t2\nt3\tfound t3\n"
+    );
+    assert!(!dir.join("checkpoint").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn resumed_scan_records_at_absolute_indices() {
+    let dir = scratch_dir("resume-scan");
+    std::fs::write(dir.join("output.txt"), "t0\tfound t0\n").unwrap();
+
+    // Like `scan --resume`: the targets before the checkpoint are left out of the scan.
+    let task = MockTask::default().delay("t1", 30);
+    let options = ScanOptions {
+        recorder: Some(recorder_config(&dir, 1, 3)),
+        quiet: true,
+        completed_before: 1,
+        ..ScanOptions::default()
+    };
+    let results = TargetScanner::new(3)
+        .scan_with_options(targets(4).into_iter().skip(1), Arc::new(task), options)
+        .await
+        .unwrap();
+
+    assert_eq!(results.first().map(|result| result.index), Some(0));
+    let output = std::fs::read_to_string(dir.join("output.txt")).unwrap();
+    assert_eq!(
+        output,
+        "t0\tfound t0\nt1\tfound t1\nt2\tfound t2\nt3\tfound t3\n"
+    );
+    assert!(!dir.join("checkpoint").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn resumed_scan_skips_targets_finished_out_of_order() {
+    let dir = scratch_dir("resume-out-of-order");
+    std::fs::write(dir.join("output.txt"), "t0\tfound t0\nt2\tfound t2\n").unwrap();
+
+    let mut completed = CompletedSet::new(4);
+    completed.insert(2);
+    let options = ScanOptions {
+        recorder: Some(RecorderConfig {
+            completed: Some(completed.clone()),
+            ..recorder_config(&dir, 1, 3)
+        }),
+        quiet: true,
+        completed_before: 2,
+        ..ScanOptions::default()
+    };
+    let remaining = ["t1", "t3"].map(String::from);
+    TargetScanner::new(2)
+        .scan_with_options(
+            remaining,
+            Arc::new(MockTask::default().delay("t1", 30)),
+            options,
+        )
+        .await
+        .unwrap();
+
+    let output = std::fs::read_to_string(dir.join("output.txt")).unwrap();
+    assert_eq!(
+        output,
+        "t0\tfound t0\nt2\tfound t2\nt1\tfound t1\nt3\tfound t3\n"
+    );
+    assert!(!dir.join("checkpoint").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn delay_between_targets_spaces_out_starts() {
+    let options = ScanOptions {
+        quiet: true,
+        target_interval: Some(Duration::from_millis(50)),
+        ..ScanOptions::default()
+    };
+    let started = std::time::Instant::now();
+    let results = TargetScanner::new(4)
+        .scan_with_options(targets(3), Arc::new(MockTask::default()), options)
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert!(started.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn slow_consumers_dont_stall_targets_in_flight() {
+    let task = MockTask::default().delay("t2", 50);
+    let (result_tx, mut result_rx) = tokio::sync::mpsc::channel(1);
+    let options = ExecutorOptions::default();
+    let started = Utc::now();
+    let scan = execute(targets(3), 3, Arc::new(task), &result_tx, &options);
+    // Nothing is read until well after every task should have finished.
+    let consumer = async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let mut outputs = Vec::new();
+        while let Some(output) = result_rx.recv().await {
+            outputs.push(output);
+            if outputs.len() == 3 {
+                break;
+            }
+        }
+        outputs
+    };
+    let (scanned, outputs) = tokio::join!(scan, consumer);
+
+    scanned.unwrap();
+    let slow = outputs.iter().find(|output| output.target == "t2").unwrap();
+    assert!((slow.completed_at - started).num_milliseconds() < 200);
+}
+
+#[test]
+fn diff_matches_findings_across_runs() {
+    let previous = "\
+https://a.com\t[!] https://a.com follow-up echoed the smuggled marker rpm3kd81xq0zpa7 (status 404)
+https://b.com\t[!] https://b.com TE.CL resp difference: baseline 200 curr 502 payload POST / HTTP/1.1
+Host: b.com
+# 1 targets skipped
+https://c.com\t[!] https://c.com stalled after 9981ms
+";
+    let current = "\
+2026-10-16T09:00:00.000Z\thttps://a.com\t[!] https://a.com follow-up echoed the smuggled marker rpm9wq2m1c4bs0e (status 404)
+2026-10-16T09:00:01.000Z\thttps://c.com\t[!] https://c.com stalled after 10012ms
+2026-10-16T09:00:02.000Z\thttps://d.com\t[!] https://d.com CL.TE resp difference: baseline 200 curr 400
+";
+    let diff = diff_outputs(previous, current);
+
+    let targets = |entries: &[riphttp::scanner::compare::OutputEntry]| -> Vec<String> {
+        entries.iter().map(|entry| entry.target.clone()).collect()
+    };
+    assert_eq!(targets(&diff.new), ["https://d.com"]);
+    assert_eq!(targets(&diff.resolved), ["https://b.com"]);
+    assert_eq!(targets(&diff.unchanged), ["https://a.com", "https://c.com"]);
+    assert!(diff.resolved[0].finding.ends_with("HTTP/1.1\nHost: b.com"));
+}
+
+#[tokio::test]
+async fn stop_after_findings_stops_scheduling() {
+    let options = ScanOptions {
+        quiet: true,
+        stop_after_findings: Some(2),
+        ..ScanOptions::default()
+    };
+    let results = TargetScanner::new(1)
+        .scan_with_options(targets(10), Arc::new(MockTask::default()), options)
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 2);
+}
+
+#[test]
+fn payload_signatures_ignore_what_changes_between_runs() {
+    let finding = |marker: &str, path: &str, user_agent: &str, te: &str| {
+        let payload = format!(
+            "POST /?cb=1 HTTP/1.1\r\nHost: a.com\r\nUser-Agent: {}\r\nX-Scan-Id: {}\r\n{}\r\n\r\n5\r\nGET {}/{} HTTP/1.1\r\n",
+            user_agent, path, te, path, marker
+        );
+        Finding::new("https://a.com", "TeCl", String::new())
+            .with_payload(payload)
+            .with_marker(Some(marker))
+    };
+    let signature = |finding: &Finding| payload_signature(finding, Some("x-scan-id"));
+
+    let first = finding(
+        "rpm3kd81xq",
+        "/vcm",
+        "curl/8.0",
+        "Transfer-Encoding: chunked",
+    );
+    let again = finding(
+        "rpm9wq2m1c",
+        "/x7q",
+        "Mozilla/5.0",
+        "Transfer-Encoding: chunked",
+    );
+    let other = finding(
+        "rpm3kd81xq",
+        "/vcm",
+        "curl/8.0",
+        "Transfer-Encoding : chunked",
+    );
+    assert_eq!(signature(&first), signature(&again));
+    assert_ne!(signature(&first), signature(&other));
+    assert!(!signature(&first).contains("curl"), "{}", signature(&first));
+}
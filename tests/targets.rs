@@ -0,0 +1,294 @@
+use riphttp::core::dns::{ResolveOverrides, parse_connect_to, parse_resolve};
+use riphttp::core::scope::{HostList, filter_scope};
+use riphttp::core::utils::{
+    TargetsFormat, TargetsLayout, canonicalize_target, canonicalize_targets, expand_cidr,
+    expand_ports, load_targets, normalize_target,
+};
+use std::net::IpAddr;
+
+#[test]
+fn normalizes_ipv6_literals() {
+    assert_eq!(normalize_target("[::1]"), "http://[::1]");
+    assert_eq!(normalize_target("[::1]:8080"), "http://[::1]:8080");
+    assert_eq!(normalize_target("2001:db8::1"), "http://[2001:db8::1]");
+    assert_eq!(
+        normalize_target("https://[2001:db8::1]:8443/"),
+        "https://[2001:db8::1]:8443/"
+    );
+    assert_eq!(normalize_target("example.com"), "http://example.com");
+}
+
+#[test]
+fn expands_ipv4_and_ipv6_cidr_blocks() {
+    let v4 = expand_cidr("192.0.2.5/30").unwrap().unwrap();
+    assert_eq!(
+        v4,
+        [
+            "http://192.0.2.4",
+            "http://192.0.2.5",
+            "http://192.0.2.6",
+            "http://192.0.2.7"
+        ]
+    );
+
+    let v6 = expand_cidr("[2001:db8::]/127").unwrap().unwrap();
+    assert_eq!(v6, ["http://[2001:db8::]", "http://[2001:db8::1]"]);
+
+    let single = expand_cidr("2001:db8::1/128").unwrap().unwrap();
+    assert_eq!(single, ["http://[2001:db8::1]"]);
+}
+
+#[test]
+fn rejects_oversized_cidr_blocks() {
+    assert!(expand_cidr("2001:db8::/64").unwrap().is_err());
+    assert!(expand_cidr("::/0").unwrap().is_err());
+    assert!(expand_cidr("10.0.0.0/8").unwrap().is_err());
+    assert!(expand_cidr("10.0.0.0/33").unwrap().is_err());
+}
+
+#[test]
+fn ignores_entries_that_are_not_cidr() {
+    assert!(expand_cidr("http://10.0.0.1/24").is_none());
+    assert!(expand_cidr("example.com/24").is_none());
+    assert!(expand_cidr("[::1]").is_none());
+}
+
+#[test]
+fn adds_ports_to_bracketed_hosts_without_one() {
+    let targets = vec![
+        "http://[2001:db8::1]/".to_string(),
+        "http://[2001:db8::1]:8443/".to_string(),
+    ];
+    assert_eq!(
+        expand_ports(targets, &[80, 8080]),
+        [
+            "http://[2001:db8::1]/",
+            "http://[2001:db8::1]:8080/",
+            "http://[2001:db8::1]:8443/"
+        ]
+    );
+}
+
+#[test]
+fn parses_resolve_entries_with_ipv6_addresses() {
+    let (host, port, ip) = parse_resolve("Example.com:443:[2001:db8::1]").unwrap();
+    assert_eq!(host, "example.com");
+    assert_eq!(port, 443);
+    assert_eq!(ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+}
+
+#[test]
+fn connect_to_keeps_the_original_authority() {
+    let entry = parse_connect_to("Example.com:80:edge.example.net:8443").unwrap();
+    let tls = parse_connect_to("example.com:443:edge.example.net:8443").unwrap();
+    let overrides = ResolveOverrides::default().with_connect_to(vec![entry, tls]);
+    assert_eq!(
+        overrides.apply("http://example.com/login"),
+        Ok(Some((
+            "http://edge.example.net:8443/login".to_string(),
+            "example.com".to_string()
+        )))
+    );
+    assert_eq!(overrides.apply("http://example.com:8080/"), Ok(None));
+    let err = overrides.apply("https://example.com/").unwrap_err();
+    assert!(
+        err.contains("--connect-to can't be used with https target"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn resolve_rejects_https_targets() {
+    let entry = parse_resolve("example.com:443:203.0.113.7").unwrap();
+    let http = parse_resolve("example.com:80:203.0.113.7").unwrap();
+    let overrides = ResolveOverrides::new(vec![entry, http]);
+    assert_eq!(
+        overrides.apply("http://example.com/login"),
+        Ok(Some((
+            "http://203.0.113.7/login".to_string(),
+            "example.com".to_string()
+        )))
+    );
+    let err = overrides.apply("https://example.com/login").unwrap_err();
+    assert!(
+        err.contains("--resolve can't be used with https target"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn loads_mixed_target_lists() {
+    let path = std::env::temp_dir().join(format!("riphttp-targets-{}.txt", std::process::id()));
+    std::fs::write(
+        &path,
+        "https://[2001:db8::1]:8443/\n[::1]\n\n198.51.100.0/31#prod\nexample.com\tteam:payments\n",
+    )
+    .unwrap();
+
+    let targets = load_targets(path.to_str().unwrap(), &TargetsLayout::default())
+        .await
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let tagged: Vec<(&str, &str)> = targets
+        .iter()
+        .map(|(target, tag)| (target.as_str(), tag.as_str()))
+        .collect();
+    assert_eq!(
+        tagged,
+        [
+            ("https://[2001:db8::1]:8443/", ""),
+            ("http://[::1]", ""),
+            ("http://198.51.100.0", "prod"),
+            ("http://198.51.100.1", "prod"),
+            ("http://example.com", "team:payments")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn loads_csv_and_json_target_lists() {
+    let path = std::env::temp_dir().join(format!("riphttp-targets-{}.list", std::process::id()));
+    let load = async |content: &str, format: TargetsFormat, column: Option<&str>| {
+        std::fs::write(&path, content).unwrap();
+        let layout = TargetsLayout {
+            format,
+            column: column.map(str::to_string),
+        };
+        let targets = load_targets(path.to_str().unwrap(), &layout).await;
+        std::fs::remove_file(&path).unwrap();
+        targets.map(|targets| {
+            targets
+                .into_iter()
+                .map(|(target, _)| target)
+                .collect::<Vec<_>>()
+        })
+    };
+    let csv = "name,URL\nshop, https://shop.example\n\n\"a, b\",198.51.100.0/31\nempty,\n";
+    let expected = [
+        "https://shop.example",
+        "http://198.51.100.0",
+        "http://198.51.100.1",
+    ];
+
+    assert_eq!(load(csv, TargetsFormat::Csv, None).await.unwrap(), expected);
+    assert_eq!(
+        load(csv, TargetsFormat::Csv, Some("url")).await.unwrap(),
+        expected
+    );
+    assert_eq!(
+        load("a.test,x\nb.test,y\n", TargetsFormat::Csv, Some("2"))
+            .await
+            .unwrap(),
+        ["http://x", "http://y"]
+    );
+    assert!(load(csv, TargetsFormat::Csv, Some("host")).await.is_err());
+
+    let array = r#"[" https://shop.example ", "198.51.100.0/31", ""]"#;
+    assert_eq!(
+        load(array, TargetsFormat::Json, None).await.unwrap(),
+        expected
+    );
+    let object = r#"{"targets": [{"url": "https://shop.example"}, "198.51.100.0/31"]}"#;
+    assert_eq!(
+        load(object, TargetsFormat::Json, None).await.unwrap(),
+        expected
+    );
+    let lines = "{\"input\": \"shop\", \"url\": \"https://shop.example\"}\n\n{\"input\": \"net\", \"url\": \"198.51.100.0/31\"}\n";
+    assert_eq!(
+        load(lines, TargetsFormat::Json, None).await.unwrap(),
+        expected
+    );
+    assert_eq!(
+        load(lines, TargetsFormat::Json, Some("input"))
+            .await
+            .unwrap(),
+        ["http://shop", "http://net"]
+    );
+    assert!(load("[1]", TargetsFormat::Json, None).await.is_err());
+}
+
+#[test]
+fn canonicalizes_targets_without_touching_significant_ports() {
+    assert_eq!(
+        canonicalize_target("http://Example.COM:80/a/./b/../c/#top"),
+        "http://example.com/a/c"
+    );
+    assert_eq!(
+        canonicalize_target("https://example.com:443/"),
+        "https://example.com/"
+    );
+    assert_eq!(
+        canonicalize_target("http://example.com:443/"),
+        "http://example.com:443/"
+    );
+    assert_eq!(
+        canonicalize_target("https://example.com:8443/x?q=1"),
+        "https://example.com:8443/x?q=1"
+    );
+
+    let (targets, merged) = canonicalize_targets(vec![
+        "http://example.com".to_string(),
+        "https://example.com/app/".to_string(),
+        "HTTP://EXAMPLE.com:80/".to_string(),
+        "https://example.com:443/app".to_string(),
+        "http://example.com:8080".to_string(),
+    ]);
+    assert_eq!(
+        targets,
+        [
+            "http://example.com/",
+            "https://example.com/app",
+            "http://example.com:8080/"
+        ]
+    );
+    assert_eq!(merged, 2);
+}
+
+#[test]
+fn scope_matches_subdomains_and_networks() {
+    let scope = HostList::parse(
+        "# in scope\nexample.com\n*.corp.test\n10.0.0.0/8\n2001:db8::/32\nhttps://192.0.2.7/\n",
+    )
+    .unwrap();
+    assert!(scope.contains("http://example.com"));
+    assert!(scope.contains("https://API.Example.com:8443/x"));
+    assert!(scope.contains("http://a.b.corp.test"));
+    assert!(scope.contains("http://corp.test"));
+    assert!(scope.contains("http://10.20.30.40:8080"));
+    assert!(scope.contains("http://[2001:db8::1]"));
+    assert!(scope.contains("http://192.0.2.7"));
+    assert!(!scope.contains("http://badexample.com"));
+    assert!(!scope.contains("http://example.com.evil.test"));
+    assert!(!scope.contains("http://11.0.0.1"));
+    assert!(!scope.contains("http://192.0.2.8"));
+    assert!(HostList::parse("10.0.0.0/33").is_err());
+}
+
+#[test]
+fn scope_entries_with_a_port_cover_the_host() {
+    let scope = HostList::parse("example.com:8443\n[2001:db8::1]:443\n192.0.2.7:80\n").unwrap();
+
+    assert!(scope.contains("https://example.com:8443/"));
+    assert!(scope.contains("http://api.example.com"));
+    assert!(scope.contains("https://[2001:db8::1]:8443"));
+    assert!(scope.contains("http://192.0.2.7:8080"));
+    assert!(!scope.contains("http://192.0.2.8:80"));
+    assert!(HostList::parse("example.com:https").is_err());
+}
+
+#[test]
+fn exclusions_win_over_the_scope() {
+    let scope = HostList::parse("example.com\n").unwrap();
+    let exclude = HostList::parse("admin.example.com\n").unwrap();
+    let targets = vec![
+        "http://www.example.com".to_string(),
+        "http://admin.example.com".to_string(),
+        "http://other.test".to_string(),
+    ];
+    let (kept, dropped) = filter_scope(targets, Some(&scope), Some(&exclude));
+    assert_eq!(kept, ["http://www.example.com"]);
+    assert_eq!(dropped, 2);
+}